@@ -0,0 +1,375 @@
+use alef_backend::{c, cranelift, llvm, qbe};
+use alef_ir::{iface, link, module::Module, read, verify};
+use alef_parser::{
+    diagnostic::{self, codes, err::Diagnostic, err::LabeledSpan},
+    module::{self as modules, Resolver, SOURCE_EXT},
+    parse,
+    source::loc::{DefaultLocation, Location},
+    source::sman::{sman, sman_mut, FileId},
+    source::MemoryBuffer,
+};
+use anyhow::{anyhow, bail};
+use clap::{AppSettings, ArgEnum, Parser};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+/// The kinds of output `build` can produce.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emit {
+    /// QBE's SSA text, to be turned into assembly by `qbe`.
+    Ssa,
+
+    /// A native object file, compiled with Cranelift (x86-64 only).
+    Obj,
+
+    /// Portable C99, to be compiled with any C compiler.
+    C,
+
+    /// LLVM's textual IR, to be compiled with `llc` or `clang`.
+    Ll,
+}
+
+impl Emit {
+    /// The extension of the output file when none is given.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Emit::Ssa => "ssa",
+            Emit::Obj => "o",
+            Emit::C => "c",
+            Emit::Ll => "ll",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[clap(about = "Compile an Alef program", version, author)]
+#[clap(setting(AppSettings::ArgRequiredElseHelp))]
+pub struct BuildCommand {
+    /// Enable tracing
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Enable debug messages
+    #[clap(long)]
+    pub debug: bool,
+
+    /// What to produce
+    #[clap(long, arg_enum, default_value = "ssa")]
+    pub emit: Emit,
+
+    /// Input file, the main IR module (.air) of the program
+    #[clap(parse(from_os_str))]
+    pub input: PathBuf,
+
+    /// Add a directory to search for imported modules
+    #[clap(short = 'M', long = "module-path", parse(from_os_str))]
+    pub module_path: Vec<PathBuf>,
+
+    /// Output file, the input file with the extension of the output by default
+    #[clap(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+
+    /// Compile the input module alone, reading the interfaces (.airi) of its imports
+    #[clap(short = 'c', long)]
+    pub separate: bool,
+
+    /// Also write the interface of the input module (.airi) next to it
+    #[clap(long)]
+    pub interface: bool,
+}
+
+/// Publish `errs`, found in `path`, and turn them into a single error.
+fn report<E: Display>(path: &Path, errs: &[E], fault: IrFault) -> anyhow::Error {
+    for e in errs {
+        fail(path, None, fault, e);
+    }
+    anyhow!("{}: {} errors", path.display(), errs.len())
+}
+
+/// Publish `e`, found in `path` at `at` if known, and turn it into an error.
+fn fail<E: Display>(
+    path: &Path,
+    at: Option<(usize, usize)>,
+    fault: IrFault,
+    e: E,
+) -> anyhow::Error {
+    let err = IrError::new(fault, path, at, e.to_string());
+    let what = err.to_string();
+    diagnostic::diag(Box::new(err));
+    anyhow!("{}: {}: {}", path.display(), what, e)
+}
+
+/// What failed on the IR modules of a program, which decides the code of the error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IrFault {
+    Read,
+    Verify,
+    Link,
+    Interface,
+    Backend,
+}
+
+/// An error of the IR modules of a program, published as a diagnostic. Read errors point at
+/// where reading stopped; the others, which name the function or module they are about, are
+/// located at the start of the file.
+#[derive(Debug)]
+pub struct IrError {
+    pub fault: IrFault,
+    pub loc: Option<DefaultLocation>,
+    pub msg: String,
+}
+
+impl IrError {
+    /// An error in the file `path`, at `line` and `col` if known.
+    pub fn new(fault: IrFault, path: &Path, at: Option<(usize, usize)>, msg: String) -> IrError {
+        let loc = source(path).map(|file| {
+            let index = match at {
+                // The reader counts columns in characters.
+                Some((line, col)) => sman().offset(file, line, col).unwrap_or(0),
+                None => 0,
+            };
+            DefaultLocation { file, index }
+        });
+        IrError { fault, loc, msg }
+    }
+}
+
+/// The source of `path` in the SourceManager, added the first time.
+fn source(path: &Path) -> Option<FileId> {
+    let name = path.display().to_string();
+    let found = sman().lookup(&name);
+    found.or_else(|| sman_mut().add_file(name).ok())
+}
+
+impl Display for IrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self.fault {
+            IrFault::Read => "cannot read IR module",
+            IrFault::Verify => "malformed IR module",
+            IrFault::Link => "cannot link the program",
+            IrFault::Interface => "cannot use the interface of a module",
+            IrFault::Backend => "cannot translate the program",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::error::Error for IrError {}
+
+impl Diagnostic for IrError {
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        let code = match self.fault {
+            IrFault::Read => codes::E0601,
+            IrFault::Verify => codes::E0602,
+            IrFault::Link => codes::E0603,
+            IrFault::Interface => codes::E0604,
+            IrFault::Backend => codes::E0605,
+        };
+        Some(Box::new(code))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        self.loc.map(|l| Box::new(l) as Box<dyn Location>)
+    }
+
+    fn context(&self) -> Option<String> {
+        if self.fault != IrFault::Read {
+            return None;
+        }
+        let loc = self.loc.as_ref()?;
+        let sm = sman();
+        let (line, _) = sm.line_col(loc.file, loc.index);
+        sm.line(loc.file, line).map(|l| l.to_string())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+        if self.fault != IrFault::Read {
+            return None;
+        }
+        let col = self.loc.as_ref()?.get_col();
+        Some(Box::new(
+            vec![LabeledSpan {
+                msg: Some(self.msg.clone()),
+                start: col,
+                end: col,
+                span: None,
+            }]
+            .into_iter(),
+        ))
+    }
+}
+
+/// Read the IR module in `path` and return it with its text, without verifying it.
+pub fn read_module(path: &Path) -> anyhow::Result<(Module, String)> {
+    if path.extension().is_none_or(|e| e != "air") {
+        bail!(
+            "{}: Alef sources cannot be lowered to the IR yet, pass an IR module (.air)",
+            path.display()
+        );
+    }
+
+    let text = std::fs::read_to_string(path)?;
+    let module =
+        read::read(&text).map_err(|e| fail(path, Some((e.line, e.col)), IrFault::Read, &e.msg))?;
+    Ok((module, text))
+}
+
+/// Read and verify the IR module in `path`.
+fn load_module(path: &Path) -> anyhow::Result<Module> {
+    let (module, _) = read_module(path)?;
+    verify::verify(&module).map_err(|errs| report(path, &errs, IrFault::Verify))?;
+    Ok(module)
+}
+
+/// Read the module in `path` alone, declaring what it imports from the interface files of its
+/// imports, searched in the directories of `module_path`.
+pub fn load_separate(path: &Path, module_path: &[PathBuf]) -> anyhow::Result<Module> {
+    let mut resolver = Resolver::new(iface::EXT);
+    for dir in module_path {
+        resolver.path(dir.clone());
+    }
+
+    let (mut module, _) = read_module(path)?;
+    for name in module.imports.clone() {
+        let file = resolver.resolve(&name, Some(path)).ok_or_else(|| {
+            let msg = format!(
+                "cannot find the interface of module {}, generate it with `build --interface`",
+                name
+            );
+            fail(path, None, IrFault::Interface, msg)
+        })?;
+        let text = std::fs::read_to_string(&file)?;
+        let interface = iface::read(&text).map_err(|e| {
+            let at = match &e {
+                iface::InterfaceError::Read(r) => Some((r.line, r.col)),
+                _ => None,
+            };
+            fail(&file, at, IrFault::Interface, e)
+        })?;
+
+        // The interface must describe the module next to it, if there is one.
+        let source = file.with_extension("air");
+        if source.is_file() {
+            interface
+                .check(&std::fs::read_to_string(&source)?)
+                .map_err(|e| fail(&file, None, IrFault::Interface, e))?;
+        }
+        interface
+            .import(&mut module)
+            .map_err(|errs| report(path, &errs, IrFault::Interface))?;
+    }
+    verify::verify(&module).map_err(|errs| report(path, &errs, IrFault::Verify))?;
+    Ok(module)
+}
+
+/// Read the program whose main module is in `path`: the module and everything it imports,
+/// searched in the directories of `module_path`, linked together.
+pub fn load_program(path: &Path, module_path: &[PathBuf]) -> anyhow::Result<Module> {
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    let mut resolver = Resolver::new(&ext);
+    for dir in module_path {
+        resolver.path(dir.clone());
+    }
+
+    if ext == SOURCE_EXT {
+        // Check the imports of the sources, even if they cannot be compiled further.
+        modules::load(path, &resolver, |p| {
+            let mbuf = MemoryBuffer::from_file(p.display().to_string())?;
+            let (_, imports) = parse::Parser::new(Box::new(mbuf), None).header();
+            Ok(imports.into_iter().map(|i| i.name).collect())
+        })?;
+        return load_module(path);
+    }
+
+    let mut loaded = HashMap::new();
+    let units = modules::load(path, &resolver, |p| {
+        let (module, text) = read_module(p)?;
+        let imports = module.imports.clone();
+        loaded.insert(p.to_path_buf(), (module, text));
+        Ok(imports)
+    })
+    .map_err(|e| match e {
+        // Modules that cannot be read are reported already.
+        modules::ModuleError::Load(_, e) => e,
+        e => fail(path, None, IrFault::Link, e),
+    })?;
+
+    // Every module sees the interfaces of the modules it imports.
+    let mut modules = vec![];
+    for u in &units {
+        let mut module = loaded[&u.path].0.clone();
+        for (name, file) in u.imports.iter().zip(&u.files) {
+            let (m, text) = &loaded[file];
+            iface::Interface::new(name, m, text)
+                .import(&mut module)
+                .map_err(|errs| report(&u.path, &errs, IrFault::Interface))?;
+        }
+        verify::verify(&module).map_err(|errs| report(&u.path, &errs, IrFault::Verify))?;
+        modules.push((u.name.clone(), module));
+    }
+    if modules.len() == 1 {
+        return Ok(modules.pop().unwrap().1);
+    }
+
+    let module = link::link(modules).map_err(|errs| report(path, &errs, IrFault::Link))?;
+    verify::verify(&module).map_err(|errs| report(path, &errs, IrFault::Verify))?;
+    Ok(module)
+}
+
+impl BuildCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        if self.trace {
+            let sl = SimpleLogger::new();
+            let sl = SimpleLogger::with_level(sl, LevelFilter::max());
+            sl.init()?;
+        }
+
+        if self.debug & !self.trace {
+            let sl = SimpleLogger::new();
+            sl.init()?;
+        }
+
+        let in_path = self.input.as_path();
+        let out_path = match self.output {
+            Some(ref op) => op.clone(),
+            None => in_path.with_extension(self.emit.extension()),
+        };
+
+        let module = if self.separate {
+            load_separate(in_path, &self.module_path)?
+        } else {
+            load_program(in_path, &self.module_path)?
+        };
+        if self.interface {
+            let (m, text) = read_module(in_path)?;
+            let name = in_path.file_stem().unwrap_or_default().to_string_lossy();
+            let iface_path = in_path.with_extension(iface::EXT);
+            std::fs::write(
+                &iface_path,
+                iface::Interface::new(&name, &m, &text).to_string(),
+            )?;
+            log::debug!("wrote {}", iface_path.display());
+        }
+        let out = match self.emit {
+            Emit::Ssa => qbe::emit(&module).into_bytes(),
+            Emit::Obj => {
+                let name = in_path.file_stem().unwrap_or_default().to_string_lossy();
+                cranelift::emit(&module, &name)
+                    .map_err(|e| fail(in_path, None, IrFault::Backend, e))?
+            }
+            Emit::C => c::emit(&module)
+                .map_err(|e| fail(in_path, None, IrFault::Backend, e))?
+                .into_bytes(),
+            Emit::Ll => llvm::emit(&module).into_bytes(),
+        };
+        std::fs::write(&out_path, out)?;
+        log::debug!("wrote {}", out_path.display());
+        Ok(())
+    }
+}
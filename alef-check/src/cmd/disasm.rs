@@ -0,0 +1,47 @@
+use crate::cmd::build::load_program;
+use alef_vm::compile;
+use clap::{AppSettings, Parser};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[clap(about = "Print the bytecode of an Alef program", version, author)]
+#[clap(setting(AppSettings::ArgRequiredElseHelp))]
+pub struct DisasmCommand {
+    /// Enable tracing
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Enable debug messages
+    #[clap(long)]
+    pub debug: bool,
+
+    /// Input file, the main IR module (.air) of the program
+    #[clap(parse(from_os_str))]
+    pub input: PathBuf,
+
+    /// Add a directory to search for imported modules
+    #[clap(short = 'M', long = "module-path", parse(from_os_str))]
+    pub module_path: Vec<PathBuf>,
+}
+
+impl DisasmCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        if self.trace {
+            let sl = SimpleLogger::new();
+            let sl = SimpleLogger::with_level(sl, LevelFilter::max());
+            sl.init()?;
+        }
+
+        if self.debug & !self.trace {
+            let sl = SimpleLogger::new();
+            sl.init()?;
+        }
+
+        let module = load_program(self.input.as_path(), &self.module_path)?;
+        let program = compile::compile(&module)?;
+        print!("{}", program);
+        Ok(())
+    }
+}
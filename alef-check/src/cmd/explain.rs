@@ -0,0 +1,48 @@
+use alef_parser::diagnostic::codes;
+use anyhow::bail;
+use clap::Parser;
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+
+#[derive(Parser, Debug)]
+#[clap(about = "Explain a diagnostic code, or list them all", version, author)]
+pub struct ExplainCommand {
+    /// Enable tracing
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Enable debug messages
+    #[clap(long)]
+    pub debug: bool,
+
+    /// The code to explain, e.g. E0102; every code is listed without one
+    pub code: Option<String>,
+}
+
+impl ExplainCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        if self.trace {
+            let sl = SimpleLogger::new();
+            let sl = SimpleLogger::with_level(sl, LevelFilter::max());
+            sl.init()?;
+        }
+
+        if self.debug & !self.trace {
+            let sl = SimpleLogger::new();
+            sl.init()?;
+        }
+
+        match &self.code {
+            Some(code) => match codes::lookup(code) {
+                Some(c) => print!("{}", c.explanation),
+                None => bail!("{} is not a diagnostic code, see `alef-check explain`", code),
+            },
+            None => {
+                for c in codes::CODES {
+                    println!("{}  {}", c.id, c.summary);
+                }
+            }
+        }
+        Ok(())
+    }
+}
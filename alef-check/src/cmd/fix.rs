@@ -0,0 +1,84 @@
+use crate::cmd::lex::preprocess;
+use alef_parser::diagnostic::{self, fix};
+use alef_parser::lex::scan::Scanner;
+use alef_parser::parse::module::ModuleParser;
+use alef_parser::source::sman::sman;
+use clap::{AppSettings, Parser};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[clap(about = "Apply the machine-applicable fixes of diagnostics to Alef source files", version, author)]
+#[clap(setting(AppSettings::ArgRequiredElseHelp))]
+pub struct FixCommand {
+    /// Enable tracing
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Enable debug messages
+    #[clap(long)]
+    pub debug: bool,
+
+    /// Print the fixed sources instead of writing them
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Add a directory to search for included files
+    #[clap(short = 'I', long = "include-dir", parse(from_os_str))]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Define a macro, as NAME or NAME=VALUE
+    #[clap(short = 'D', long = "define")]
+    pub defines: Vec<String>,
+
+    /// Input files, which are fixed in place
+    #[clap(parse(from_os_str), required = true)]
+    pub inputs: Vec<PathBuf>,
+}
+
+impl FixCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        if self.trace {
+            let sl = SimpleLogger::new();
+            let sl = SimpleLogger::with_level(sl, LevelFilter::max());
+            sl.init()?;
+        }
+
+        if self.debug & !self.trace {
+            let sl = SimpleLogger::new();
+            sl.init()?;
+        }
+
+        for input in &self.inputs {
+            // Only the module header and the tokens are checked until the parser is complete.
+            let mbuf = preprocess(input, &self.include_dirs, &self.defines)?;
+            let mut scanner = Scanner::new(Box::new(mbuf), None);
+            ModuleParser::new(&mut scanner).header();
+            while !scanner.tok().is_end() && !diagnostic::context().should_stop() {}
+
+            // Files included by the input are left alone, they may be shared with other programs.
+            let name = input.to_string_lossy();
+            let edits = diagnostic::context().take_fixes();
+            let edits = fix::by_file(&edits)
+                .remove(name.as_ref())
+                .unwrap_or_default();
+            let content = {
+                let sm = sman();
+                sm.lookup(&name).map(|f| sm.content(f)).unwrap_or_default()
+            };
+            let (fixed, applied) = fix::apply(&content, &edits);
+            if self.dry_run {
+                print!("{}", fixed);
+            } else {
+                // An untouched input keeps its modification time, so what is built from it stays
+                // up to date.
+                if applied > 0 {
+                    std::fs::write(input, fixed)?;
+                }
+                println!("{}: {} fixes applied", name, applied);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use alef_parser::{cimport, lex::pp::Preprocessor};
+use anyhow::bail;
+use std::path::PathBuf;
+use clap::{Parser, AppSettings};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+
+/// The macros predefined for headers, those of a C99 compiler for x86-64 Linux.
+const PREDEFINED: &[&str] = &[
+    "__STDC__=1",
+    "__STDC_VERSION__=199901L",
+    "__x86_64__=1",
+    "__LP64__=1",
+    "__linux__=1",
+];
+
+#[derive(Parser, Debug)]
+#[clap(about = "Translate a C header to Alef declarations", version, author)]
+#[clap(setting(AppSettings::ArgRequiredElseHelp))]
+pub struct ImportCCommand {
+    /// Enable tracing
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Enable debug messages
+    #[clap(long)]
+    pub debug: bool,
+
+    /// Add a directory to search for included headers
+    #[clap(short = 'I', long = "include-dir", parse(from_os_str))]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Define a macro, as NAME or NAME=VALUE
+    #[clap(short = 'D', long = "define")]
+    pub defines: Vec<String>,
+
+    /// Output file, the standard output if missing
+    #[clap(short, long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+
+    /// Input header
+    #[clap(parse(from_os_str))]
+    pub input: PathBuf,
+}
+
+impl ImportCCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        if self.trace {
+            let sl = SimpleLogger::new();
+            let sl = SimpleLogger::with_level(sl, LevelFilter::max());
+            sl.init()?;
+        }
+
+        if self.debug & !self.trace {
+            let sl = SimpleLogger::new();
+            sl.init()?;
+        }
+
+        let mut pp = Preprocessor::new();
+        for dir in &self.include_dirs {
+            pp.include_dir(dir.clone());
+        }
+        for def in PREDEFINED.iter().copied().chain(self.defines.iter().map(|d| d.as_str())) {
+            pp.define(def);
+        }
+
+        let (header, errors) = cimport::import(&mut pp, self.input.to_string_lossy().into())?;
+        if errors > 0 {
+            bail!("{}: {} errors", self.input.display(), errors);
+        }
+        match self.output {
+            Some(ref out) => std::fs::write(out, header.to_string())?,
+            None => print!("{}", header),
+        }
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use alef_parser::{lex, source::MemoryBuffer};
+use alef_parser::{diagnostic, lex, lex::pp::Preprocessor, source::MemoryBuffer};
+use std::path::{Path, PathBuf};
 use clap::{Parser, AppSettings};
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
@@ -19,6 +20,18 @@ pub struct LexCommand {
     #[clap(short, long)]
     pub suppress_output: bool,
 
+    /// Print the preprocessed source instead of the tokens
+    #[clap(short = 'E', long)]
+    pub preprocess: bool,
+
+    /// Add a directory to search for included files
+    #[clap(short = 'I', long = "include-dir", parse(from_os_str))]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Define a macro, as NAME or NAME=VALUE
+    #[clap(short = 'D', long = "define")]
+    pub defines: Vec<String>,
+
     /// Input file
     #[clap(parse(from_os_str))]
     pub input: std::path::PathBuf,
@@ -39,11 +52,18 @@ impl LexCommand {
 
         let in_path = self.input.as_path();
 
-        let mbuf = MemoryBuffer::from_file(in_path.to_string_lossy().into())?;
+        let mut mbuf = preprocess(in_path, &self.include_dirs, &self.defines)?;
+        if self.preprocess {
+            if !self.suppress_output {
+                print!("{}", mbuf.get_content());
+            }
+            return Ok(());
+        }
+
         let mut lex = lex::scan::Scanner::new(Box::new(mbuf), None);
         let mut tok = lex.tok();
         
-        while !tok.is_end() {
+        while !tok.is_end() && !diagnostic::context().should_stop() {
             if !self.suppress_output {
                 println!("{}", tok);
             }
@@ -52,3 +72,16 @@ impl LexCommand {
         Ok(())
     }
 }
+
+/// Preprocess the Alef source in `path`, searching included files in `include_dirs` and with
+/// the macros of `defines` (`NAME` or `NAME=VALUE`) defined.
+pub fn preprocess(path: &Path, include_dirs: &[PathBuf], defines: &[String]) -> anyhow::Result<MemoryBuffer> {
+    let mut pp = Preprocessor::new();
+    for dir in include_dirs {
+        pp.include_dir(dir.clone());
+    }
+    for def in defines {
+        pp.define(def);
+    }
+    pp.preprocess_file(path.to_string_lossy().into())
+}
@@ -0,0 +1,97 @@
+use crate::cmd::build::load_program;
+use alef_ir::lint::{self, Lint, Warning};
+use alef_parser::diagnostic::{self, category::Category, codes, err::Diagnostic, err::Severity};
+use clap::{AppSettings, Parser};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[clap(about = "Warn about likely deadlocks in an Alef program", version, author)]
+#[clap(setting(AppSettings::ArgRequiredElseHelp))]
+pub struct LintCommand {
+    /// Enable tracing
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Enable debug messages
+    #[clap(long)]
+    pub debug: bool,
+
+    /// Input file, the main IR module (.air) of the program
+    #[clap(parse(from_os_str))]
+    pub input: PathBuf,
+
+    /// Add a directory to search for imported modules
+    #[clap(short = 'M', long = "module-path", parse(from_os_str))]
+    pub module_path: Vec<PathBuf>,
+}
+
+impl LintCommand {
+    pub fn execute(&self) -> anyhow::Result<()> {
+        if self.trace {
+            let sl = SimpleLogger::new();
+            let sl = SimpleLogger::with_level(sl, LevelFilter::max());
+            sl.init()?;
+        }
+
+        if self.debug & !self.trace {
+            let sl = SimpleLogger::new();
+            sl.init()?;
+        }
+
+        let module = load_program(self.input.as_path(), &self.module_path)?;
+        let warnings = lint::lint(&module);
+        log::debug!("{} warnings", warnings.len());
+        for warning in warnings {
+            diagnostic::diag(Box::new(LintWarning {
+                file: self.input.display().to_string(),
+                warning,
+            }));
+        }
+        Ok(())
+    }
+}
+
+/// A warning of the IR lints, published as a diagnostic. IR modules carry no source locations,
+/// so the warning names the function and block instead.
+#[derive(Debug)]
+struct LintWarning {
+    file: String,
+    warning: Warning,
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.warning.msg)
+    }
+}
+
+impl std::error::Error for LintWarning {}
+
+impl Diagnostic for LintWarning {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+
+    fn category(&self) -> Option<Category> {
+        Category::from_name(self.warning.lint.name())
+    }
+
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        let code = match self.warning.lint {
+            Lint::RecvNeverSent => codes::E0501,
+            Lint::SendSingleTask => codes::E0502,
+            Lint::AltNeverReady => codes::E0503,
+        };
+        Some(Box::new(code))
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(format!(
+            "in ${} at @{} of {}",
+            self.warning.func, self.warning.block, self.file
+        )))
+    }
+}
@@ -1,24 +1,86 @@
-use clap::{Parser, Subcommand};
+use clap::{ArgEnum, Parser, Subcommand};
 #[derive(Parser, Debug)]
 #[clap(about, version, author)]
 pub struct Cli {
     #[clap(subcommand)]
     pub command: Command,
+
+    /// How to print diagnostics
+    #[clap(long, arg_enum, global = true, default_value = "human")]
+    pub message_format: MessageFormat,
+
+    /// Configure warnings: -W<category> and -Wno-<category> enable and disable a category,
+    /// -Wall enables every category and -Werror makes warnings errors
+    #[clap(short = 'W', global = true, multiple_occurrences = true, number_of_values = 1)]
+    pub warnings: Vec<String>,
+
+    /// Stop reporting errors after this many
+    #[clap(long, global = true)]
+    pub max_errors: Option<usize>,
+
+    /// When to color diagnostics
+    #[clap(long, arg_enum, global = true, default_value = "auto")]
+    pub color: ColorChoice,
+}
+
+/// When diagnostics are colored.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// If stderr is a terminal and NO_COLOR is not set.
+    Auto,
+
+    Always,
+
+    Never,
+}
+
+/// The formats diagnostics can be printed in, all on stderr.
+#[derive(ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageFormat {
+    /// Text for people to read.
+    Human,
+
+    /// One JSON object per diagnostic and line.
+    Json,
+
+    /// A single SARIF 2.1.0 log, printed when the command is done.
+    Sarif,
 }
 
 
 pub mod parse; 
 pub mod generate; 
 pub mod lex; 
+pub mod build;
+pub mod disasm;
+pub mod run;
+pub mod lint;
+pub mod import_c;
+pub mod explain;
+pub mod fix;
 use parse::ParseCommand;
 use generate::GenerateCommand;
 use lex::LexCommand; 
+use build::BuildCommand;
+use disasm::DisasmCommand;
+use run::RunCommand;
+use lint::LintCommand;
+use import_c::ImportCCommand;
+use explain::ExplainCommand;
+use fix::FixCommand;
 
 #[derive(Subcommand, Debug)]
 pub enum Command {
     Parse(ParseCommand),
     Generate(GenerateCommand),
     Lex(LexCommand),
+    Build(BuildCommand),
+    Disasm(DisasmCommand),
+    Run(RunCommand),
+    Lint(LintCommand),
+    ImportC(ImportCCommand),
+    Explain(ExplainCommand),
+    Fix(FixCommand),
 }
 
 
@@ -1,4 +1,6 @@
-use alef_parser::{parse, source::MemoryBuffer};
+use crate::cmd::lex::preprocess;
+use alef_parser::parse;
+use std::path::PathBuf;
 use clap::{Parser, AppSettings};
 use log::LevelFilter;
 use simple_logger::SimpleLogger;
@@ -26,6 +28,14 @@ pub struct ParseCommand {
     /// Output file
     #[clap(short, long, parse(from_os_str))]
     pub output: Option<std::path::PathBuf>,
+
+    /// Add a directory to search for included files
+    #[clap(short = 'I', long = "include-dir", parse(from_os_str))]
+    pub include_dirs: Vec<PathBuf>,
+
+    /// Define a macro, as NAME or NAME=VALUE
+    #[clap(short = 'D', long = "define")]
+    pub defines: Vec<String>,
 }
 
 impl ParseCommand {
@@ -50,7 +60,7 @@ impl ParseCommand {
             out_path = in_path.with_extension(".o");
         }
 
-        let mbuf = MemoryBuffer::from_file(in_path.to_string_lossy().into())?;
+        let mbuf = preprocess(in_path, &self.include_dirs, &self.defines)?;
         let mut parser = parse::Parser::new(Box::new(mbuf), None);
         let program = parser.parse();
         println!("{:?}", program);
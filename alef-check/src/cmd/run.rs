@@ -0,0 +1,68 @@
+use crate::cmd::build::load_program;
+use alef_vm::{compile, err::VmError, machine};
+use anyhow::anyhow;
+use clap::{AppSettings, Parser};
+use log::LevelFilter;
+use simple_logger::SimpleLogger;
+use std::io::Write;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[clap(about = "Run an Alef program on the bytecode machine", version, author)]
+#[clap(setting(AppSettings::ArgRequiredElseHelp))]
+pub struct RunCommand {
+    /// Enable tracing
+    #[clap(long)]
+    pub trace: bool,
+
+    /// Enable debug messages
+    #[clap(long)]
+    pub debug: bool,
+
+    /// Input file, the main IR module (.air) of the program
+    #[clap(parse(from_os_str))]
+    pub input: PathBuf,
+
+    /// Add a directory to search for imported modules
+    #[clap(short = 'M', long = "module-path", parse(from_os_str))]
+    pub module_path: Vec<PathBuf>,
+}
+
+impl RunCommand {
+    /// Run the program and return its exit status.
+    pub fn execute(&self) -> anyhow::Result<i32> {
+        if self.trace {
+            let sl = SimpleLogger::new();
+            let sl = SimpleLogger::with_level(sl, LevelFilter::max());
+            sl.init()?;
+        }
+
+        if self.debug & !self.trace {
+            let sl = SimpleLogger::new();
+            sl.init()?;
+        }
+
+        let module = load_program(self.input.as_path(), &self.module_path)?;
+        let program = compile::compile(&module)?;
+        let stdout = std::io::stdout();
+        let mut out = stdout.lock();
+        let status = match machine::run(&program, &mut out) {
+            Ok(status) => status,
+            Err(VmError::Deadlock(blocked)) => {
+                out.flush()?;
+                eprintln!(
+                    "{}: deadlock, every thread is blocked",
+                    self.input.display()
+                );
+                for b in blocked {
+                    eprintln!("  {}", b);
+                }
+                machine::EXIT_DEADLOCK
+            }
+            Err(e) => return Err(anyhow!("{}: {}", self.input.display(), e)),
+        };
+        out.flush()?;
+        log::debug!("exit status {}", status);
+        Ok(status)
+    }
+}
@@ -1,16 +1,92 @@
 #![allow(unused_assignments, dead_code, unused_imports, unused_variables)]
 mod cmd;
+use anyhow::bail;
 use clap::Parser;
-use crate::cmd::{Cli, Command, parse::ParseCommand, generate::GenerateCommand, lex::LexCommand};
+use alef_parser::diagnostic::{category::Category, dman::WARNING_LEVEL, err::Severity};
+use std::io::{self, Write};
+use std::process;
+use alef_parser::diagnostic::{self, dman::{DefaultDiagnosticsManager, Theme}, json::JsonDiagnosticsManager, sarif::SarifDiagnosticsManager};
+use crate::cmd::{Cli, ColorChoice, Command, MessageFormat, parse::ParseCommand, generate::GenerateCommand, lex::LexCommand, build::BuildCommand, disasm::DisasmCommand, run::RunCommand, lint::LintCommand, import_c::ImportCCommand, explain::ExplainCommand, fix::FixCommand};
+
+/// The exit status when diagnostics reported errors.
+const EXIT_ERRORS: i32 = 1;
 
 fn main() -> anyhow::Result<()> {
     let cmd = Cli::parse();
-    
-    match cmd.command {
+
+    match cmd.message_format {
+        MessageFormat::Human => {
+            let colors = match cmd.color {
+                ColorChoice::Auto => Theme::colors(),
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+            };
+            diagnostic::set_manager(Box::new(DefaultDiagnosticsManager::new(Theme::terminal(colors))));
+        }
+        MessageFormat::Json => diagnostic::set_manager(Box::new(JsonDiagnosticsManager {})),
+        MessageFormat::Sarif => diagnostic::set_manager(Box::new(SarifDiagnosticsManager::new(
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION"),
+        ))),
+    }
+
+    for w in &cmd.warnings {
+        match w.as_str() {
+            "error" => diagnostic::context().werror = true,
+            "all" => *WARNING_LEVEL.lock().unwrap() = 1,
+            _ => {
+                let (name, enabled) = match w.strip_prefix("no-") {
+                    Some(name) => (name, false),
+                    None => (w.as_str(), true),
+                };
+                match Category::from_name(name) {
+                    Some(c) => diagnostic::context().enable(c, enabled),
+                    None => bail!("unknown warning option -W{}", w),
+                }
+            }
+        }
+    }
+    diagnostic::context().max_errors = cmd.max_errors;
+
+    let res = run(cmd.command);
+    diagnostic::finish();
+    let status = match res {
+        Ok(status) => status,
+        // The diagnostics tell what failed already.
+        Err(_) if diagnostic::context().errors() > 0 => EXIT_ERRORS,
+        Err(e) => return Err(e),
+    };
+
+    let (errors, warnings) = {
+        let ctx = diagnostic::context();
+        (ctx.errors(), ctx.count(Severity::Warning))
+    };
+    if cmd.message_format == MessageFormat::Human && errors + warnings > 0 {
+        eprintln!("{} errors and {} warnings reported", errors, warnings);
+    }
+    let status = if errors > 0 { EXIT_ERRORS } else { status };
+    if status != 0 {
+        io::stdout().flush()?;
+        process::exit(status);
+    }
+    Ok(())
+}
+
+/// Run a command, returning the status to exit with: that of the program for `run`, 0 for the
+/// others.
+fn run(command: Command) -> anyhow::Result<i32> {
+    match command {
         Command::Parse(p) => p.execute()?, 
         Command::Generate(g) => g.execute()?,
         Command::Lex(l) => l.execute()?,
+        Command::Build(b) => b.execute()?,
+        Command::Disasm(d) => d.execute()?,
+        Command::Run(r) => return r.execute(),
+        Command::Lint(l) => l.execute()?,
+        Command::ImportC(i) => i.execute()?,
+        Command::Explain(e) => e.execute()?,
+        Command::Fix(f) => f.execute()?,
     }
 
-    Ok(())
+    Ok(0)
 }
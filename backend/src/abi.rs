@@ -0,0 +1,234 @@
+//! The System V x86-64 calling convention for aggregates.
+//!
+//! Scalars map onto registers directly, but aggregates passed or returned by value have to be
+//! classified like a C compiler does, or calls between generated code, the runtime and C
+//! libraries disagree about where the bytes are. An aggregate of at most 16 bytes is split in
+//! eightbytes, each passed in a general purpose register if it contains any integer or pointer
+//! and in a vector register otherwise; larger aggregates, and aggregates that do not fit in the
+//! registers that are left, are passed in memory. Aggregates returned in memory are written to a
+//! buffer whose address is passed by the caller as a hidden first argument.
+//!
+//! Backends that emit assembly through a C-compatible tool (QBE, C) get this for free; the
+//! others use this module to lower signatures.
+
+use alef_ir::func::Signature;
+use alef_ir::module::Module;
+use alef_ir::ty::{AggrKind, Type};
+
+/// The number of general purpose registers used for arguments.
+const INT_REGS: usize = 6;
+
+/// The number of vector registers used for arguments.
+const SSE_REGS: usize = 8;
+
+/// The register class of an eightbyte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    /// A general purpose register, the eightbyte is passed as an `i64`.
+    Int,
+
+    /// A vector register, the eightbyte is passed as an `f64`.
+    Sse,
+}
+
+/// How an argument is passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    /// A scalar, passed as it is.
+    Direct(Type),
+
+    /// An aggregate passed in registers, one per eightbyte.
+    Split(Vec<Class>),
+
+    /// An aggregate copied on the stack; the size is rounded up to a multiple of 8.
+    Memory(u64),
+}
+
+/// How a value is returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ret {
+    /// Nothing is returned.
+    Void,
+
+    /// A scalar, returned as it is.
+    Direct(Type),
+
+    /// An aggregate returned in registers, one per eightbyte.
+    Split(Vec<Class>),
+
+    /// An aggregate written to the buffer passed as hidden first argument.
+    Memory(u64),
+}
+
+/// A signature lowered to the calling convention.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lowered {
+    /// How each parameter is passed.
+    pub params: Vec<Arg>,
+
+    /// How the result is returned.
+    pub ret: Ret,
+}
+
+/// Collect the scalars of `ty` with their offsets.
+fn scalars(module: &Module, ty: &Type, offset: u64, out: &mut Vec<(u64, Type)>) {
+    match ty {
+        Type::Named(name) => {
+            let def = module.typedef(name).expect("unknown aggregate");
+            for (i, field) in def.fields.iter().enumerate() {
+                let off = match def.kind {
+                    AggrKind::Struct => module.field_offset(name, i).unwrap(),
+                    AggrKind::Union => 0,
+                };
+                scalars(module, field, offset + off, out);
+            }
+        }
+        Type::Array(of, n) => {
+            let size = module.layout(of).unwrap().size;
+            for i in 0..*n {
+                scalars(module, of, offset + i * size, out);
+            }
+        }
+        ty => out.push((offset, ty.clone())),
+    }
+}
+
+/// Classify an aggregate; None if it is passed in memory.
+pub fn classify(module: &Module, ty: &Type) -> Option<Vec<Class>> {
+    let size = module.layout(ty)?.size;
+    if size > 16 || size == 0 {
+        return None;
+    }
+
+    let mut fields = vec![];
+    scalars(module, ty, 0, &mut fields);
+    let classes = (0..size.div_ceil(8))
+        .map(|i| {
+            let int = fields
+                .iter()
+                .filter(|(off, _)| off / 8 == i)
+                .any(|(_, ty)| !ty.is_float());
+            if int {
+                Class::Int
+            } else {
+                Class::Sse
+            }
+        })
+        .collect();
+    Some(classes)
+}
+
+/// Lower a signature, keeping track of the registers used so that an aggregate that does not
+/// fit in the remaining ones goes in memory as a whole.
+pub fn lower(module: &Module, sig: &Signature) -> Lowered {
+    let mut int = 0;
+    let mut sse = 0;
+
+    let ret = match &sig.ret {
+        Type::Void => Ret::Void,
+        ty if ty.is_aggregate() => match classify(module, ty) {
+            Some(classes) => Ret::Split(classes),
+            None => {
+                int += 1;
+                Ret::Memory(module.layout(ty).unwrap().size)
+            }
+        },
+        ty => Ret::Direct(ty.clone()),
+    };
+
+    let params = sig
+        .params
+        .iter()
+        .map(|ty| {
+            if !ty.is_aggregate() {
+                if ty.is_float() {
+                    sse += 1;
+                } else {
+                    int += 1;
+                }
+                return Arg::Direct(ty.clone());
+            }
+
+            let size = module.layout(ty).unwrap().size;
+            match classify(module, ty) {
+                Some(classes) => {
+                    let need_int = classes.iter().filter(|c| **c == Class::Int).count();
+                    let need_sse = classes.len() - need_int;
+                    if int + need_int <= INT_REGS && sse + need_sse <= SSE_REGS {
+                        int += need_int;
+                        sse += need_sse;
+                        Arg::Split(classes)
+                    } else {
+                        Arg::Memory(size.div_ceil(8) * 8)
+                    }
+                }
+                None => Arg::Memory(size.div_ceil(8) * 8),
+            }
+        })
+        .collect();
+
+    Lowered { params, ret }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alef_ir::func::CallConv;
+    use alef_ir::read::read;
+
+    fn module() -> Module {
+        read(
+            "
+            type %II = { i32, i32 }
+            type %IF = { i32, f64 }
+            type %FF = { f64, f64 }
+            type %Big = { i64, i64, i64 }
+            type %U = union { i64, f64 }
+            type %Bytes = { [3 x i8] }
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn classes() {
+        let m = module();
+        let named = |n: &str| Type::Named(n.into());
+        assert_eq!(classify(&m, &named("II")), Some(vec![Class::Int]));
+        assert_eq!(
+            classify(&m, &named("IF")),
+            Some(vec![Class::Int, Class::Sse])
+        );
+        assert_eq!(
+            classify(&m, &named("FF")),
+            Some(vec![Class::Sse, Class::Sse])
+        );
+        assert_eq!(classify(&m, &named("Big")), None);
+        assert_eq!(classify(&m, &named("U")), Some(vec![Class::Int]));
+        assert_eq!(classify(&m, &named("Bytes")), Some(vec![Class::Int]));
+    }
+
+    #[test]
+    fn register_exhaustion() {
+        let m = module();
+        let sig = Signature {
+            conv: CallConv::C,
+            params: vec![
+                Type::I64,
+                Type::I64,
+                Type::I64,
+                Type::I64,
+                Type::I64,
+                Type::Named("II".into()),
+                Type::Named("IF".into()),
+            ],
+            variadic: false,
+            ret: Type::Named("Big".into()),
+        };
+        let l = lower(&m, &sig);
+        assert_eq!(l.ret, Ret::Memory(24));
+        // The hidden return pointer takes the sixth register, so neither aggregate fits.
+        assert_eq!(l.params[5], Arg::Memory(8));
+        assert_eq!(l.params[6], Arg::Memory(16));
+    }
+}
@@ -0,0 +1,904 @@
+//! Translate the IR into portable C99.
+//!
+//! The C output is meant for bootstrapping on machines the native backends do not support and
+//! for debugging: every IR instruction becomes one statement, so the generated code can be read
+//! next to the IR dump and diffed between compiler versions.
+//!
+//! - Aggregates become `struct` and `union` definitions and are passed and returned by value;
+//!   the IR refers to aggregates by address, so aggregate temporaries are `char *`;
+//! - temporaries are declared at the top of the function as `t_<name>`, blocks become labels
+//!   `l_<name>` and `alloca` slots are locals `s_<name>`;
+//! - integer arithmetic is done on unsigned types and converted back, so that it wraps like it
+//!   does in the other backends instead of being undefined on overflow;
+//! - data definitions are structures with one member per item; C cannot express an item that is
+//!   not at an offset multiple of its alignment, so such data is rejected.
+//!
+//! Names are emitted unchanged when they are valid C identifiers; `.` becomes `__` and any other
+//! character is replaced by `_` followed by its code point in hexadecimal.
+
+use crate::err::BackendError;
+use crate::expand::expand;
+use alef_ir::func::{CallConv, Function, Linkage, Param, Signature};
+use alef_ir::inst::{BinOp, CmpOp, ConvOp, Inst, Terminator, UnOp, Value};
+use alef_ir::module::{Data, DataItem, Module};
+use alef_ir::ty::{AggrKind, Type, TypeDef};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Translate a verified module into a C99 translation unit.
+pub fn emit(module: &Module) -> Result<String, BackendError> {
+    let module = expand(module);
+    let mut e = Emitter {
+        module: &module,
+        out: String::new(),
+        blit: false,
+    };
+    e.module()?;
+    Ok(e.out)
+}
+
+/// Make `name` a valid C identifier.
+fn ident(name: &str) -> String {
+    let mut s = String::new();
+    for c in name.chars() {
+        match c {
+            c if c.is_ascii_alphanumeric() || c == '_' => s.push(c),
+            '.' => s.push_str("__"),
+            c => write!(s, "_{:x}", c as u32).unwrap(),
+        }
+    }
+    s
+}
+
+/// Return true if the function uses `vastart`.
+fn reads_varargs(f: &Function) -> bool {
+    f.blocks
+        .iter()
+        .any(|b| b.insts.iter().any(|i| matches!(i, Inst::VaStart { .. })))
+}
+
+/// The C name of a parameter; aggregates are copied to a local by the callee.
+fn param_name(p: &Param) -> String {
+    match p.ty {
+        Type::Named(_) => format!("p_{}", ident(&p.name)),
+        _ => FuncEmitter::temp(&p.name),
+    }
+}
+
+/// The C type of a scalar, aggregates are referred to by address.
+fn ctype(ty: &Type) -> &'static str {
+    match ty {
+        Type::Void => "void",
+        Type::I8 => "int8_t",
+        Type::I16 => "int16_t",
+        Type::I32 => "int32_t",
+        Type::I64 => "int64_t",
+        Type::F64 => "double",
+        _ => "char *",
+    }
+}
+
+/// The unsigned type of the same width as an integer type.
+fn utype(ty: &Type) -> &'static str {
+    match ty {
+        Type::I8 => "uint8_t",
+        Type::I16 => "uint16_t",
+        Type::I32 => "uint32_t",
+        Type::I64 => "uint64_t",
+        _ => "uintptr_t",
+    }
+}
+
+/// The unsigned type arithmetic on `ty` is done in; sub-word types would otherwise be promoted
+/// to `int`, where overflow is undefined.
+fn wtype(ty: &Type) -> &'static str {
+    match ty {
+        Type::I8 | Type::I16 | Type::I32 => "uint32_t",
+        ty => utype(ty),
+    }
+}
+
+/// The mask applied to shift amounts, as shifting by the width of the type is undefined.
+fn shift_mask(ty: &Type) -> u32 {
+    match ty {
+        Type::I64 | Type::Ptr => 63,
+        _ => 31,
+    }
+}
+
+fn int_lit(v: i64) -> String {
+    match v {
+        i64::MIN => "(-9223372036854775807 - 1)".into(),
+        v => v.to_string(),
+    }
+}
+
+fn float_lit(x: f64) -> String {
+    if x.is_nan() {
+        "(0.0 / 0.0)".into()
+    } else if x.is_infinite() {
+        format!("({}1.0 / 0.0)", if x < 0.0 { "-" } else { "" })
+    } else {
+        format!("{:?}", x)
+    }
+}
+
+/// Sign extend the constant `v` from the width of `ty`.
+fn sext_const(ty: &Type, v: i64) -> i64 {
+    match ty {
+        Type::I8 => v as i8 as i64,
+        Type::I16 => v as i16 as i64,
+        Type::I32 => v as i32 as i64,
+        _ => v,
+    }
+}
+
+/// A C string literal for `s`, with other non printable bytes as octal escapes.
+fn str_lit(s: &str) -> String {
+    let mut out = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' | b'\\' | b'?' => write!(out, "\\{}", b as char).unwrap(),
+            b'\n' => out.push_str("\\n"),
+            b'\t' => out.push_str("\\t"),
+            b' '..=b'~' => out.push(b as char),
+            b => write!(out, "\\{:03o}", b).unwrap(),
+        }
+    }
+    out.push('"');
+    out
+}
+
+struct Emitter<'a> {
+    module: &'a Module,
+    out: String,
+    blit: bool,
+}
+
+impl<'a> Emitter<'a> {
+    /// The tag of an aggregate, `struct name` or `union name`.
+    fn tag(&self, name: &str) -> String {
+        match self.module.typedef(name).map(|t| t.kind) {
+            Some(AggrKind::Union) => format!("union {}", ident(name)),
+            _ => format!("struct {}", ident(name)),
+        }
+    }
+
+    /// Declare `name` with type `ty`.
+    fn decl(&self, ty: &Type, name: &str) -> String {
+        match ty {
+            Type::Named(t) => format!("{} {}", self.tag(t), name),
+            Type::Array(of, n) => self.decl(of, &format!("{}[{}]", name, n)),
+            Type::Ptr => format!("char *{}", name),
+            ty => format!("{} {}", ctype(ty), name),
+        }
+    }
+
+    /// The type of a parameter or result: named aggregates by value, arrays by address.
+    fn value_type(&self, ty: &Type) -> String {
+        match ty {
+            Type::Named(t) => self.tag(t),
+            ty => ctype(ty).into(),
+        }
+    }
+
+    /// The declaration of a function `declarator`, with parameter names if given.
+    fn prototype(&self, declarator: &str, sig: &Signature, names: Option<Vec<String>>) -> String {
+        let mut params: Vec<String> = sig
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, ty)| match &names {
+                Some(names) => match ty {
+                    Type::Named(_) => format!("{} {}", self.value_type(ty), names[i]),
+                    Type::Ptr | Type::Array(..) => format!("char *{}", names[i]),
+                    ty => format!("{} {}", ctype(ty), names[i]),
+                },
+                None => self.value_type(ty),
+            })
+            .collect();
+        if sig.variadic {
+            params.push("...".into());
+        }
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+        let ret = self.value_type(&sig.ret);
+        let sep = if ret.ends_with('*') { "" } else { " " };
+        format!("{}{}{}({})", ret, sep, declarator, params)
+    }
+
+    fn module(&mut self) -> Result<(), BackendError> {
+        // `va_start` names the last fixed parameter.
+        let varargs: Vec<&Function> = self
+            .module
+            .funcs
+            .iter()
+            .filter(|f| reads_varargs(f))
+            .collect();
+        if let Some(f) = varargs.iter().find(|f| f.params.is_empty()) {
+            return Err(BackendError::Unsupported(format!(
+                "${} reads its variadic arguments but has no fixed parameter, C99 needs one",
+                f.name
+            )));
+        }
+
+        let mut body = String::new();
+        let mut fns = vec![];
+        for f in &self.module.funcs {
+            let mut fe = FuncEmitter::new(self, f);
+            fe.function(f);
+            let (blit, out) = (fe.blit, fe.out);
+            self.blit |= blit;
+            fns.push(out);
+        }
+        for (i, f) in fns.iter().enumerate() {
+            if i > 0 {
+                body.push('\n');
+            }
+            body.push_str(f);
+        }
+
+        self.out.push_str("#include <stdint.h>\n");
+        if !varargs.is_empty() {
+            self.out.push_str("#include <stdarg.h>\n");
+        }
+        self.out.push('\n');
+
+        let mut done = HashSet::new();
+        for t in &self.module.types {
+            self.typedef(t, &mut done)?;
+        }
+
+        for d in &self.module.data {
+            self.data_type(d)?;
+        }
+        if !self.module.types.is_empty() || !self.module.data.is_empty() {
+            self.out.push('\n');
+        }
+
+        if self.blit {
+            self.out.push_str(
+                "static void alef_c_blit(char *dst, const char *src, int64_t n) {\n\
+                 \twhile (n-- > 0)\n\
+                 \t\t*dst++ = *src++;\n\
+                 }\n\n",
+            );
+        }
+
+        let halts = self
+            .module
+            .funcs
+            .iter()
+            .any(|f| f.blocks.iter().any(|b| b.term == Terminator::Hlt));
+        if halts && self.module.signature("abort").is_none() {
+            self.out.push_str("void abort(void);\n");
+        }
+        for e in &self.module.externs {
+            writeln!(
+                self.out,
+                "{};",
+                self.prototype(&ident(&e.name), &e.sig, None)
+            )
+            .unwrap();
+        }
+        for f in &self.module.funcs {
+            let storage = match f.linkage {
+                Linkage::Local => "static ",
+                Linkage::Export => "",
+            };
+            let proto = self.prototype(&ident(&f.name), &f.signature(), None);
+            writeln!(self.out, "{}{};", storage, proto).unwrap();
+        }
+        if !self.module.externs.is_empty() || !self.module.funcs.is_empty() || halts {
+            self.out.push('\n');
+        }
+
+        for d in &self.module.data {
+            self.data(d);
+        }
+        if !self.module.data.is_empty() {
+            self.out.push('\n');
+        }
+
+        self.out.push_str(&body);
+        Ok(())
+    }
+
+    /// Emit a definition after the ones of the aggregates it contains, as C requires.
+    fn typedef(&mut self, t: &'a TypeDef, done: &mut HashSet<&'a str>) -> Result<(), BackendError> {
+        if !done.insert(&t.name) {
+            return Ok(());
+        }
+        if t.fields.is_empty() {
+            return Err(BackendError::Unsupported(format!(
+                "empty aggregate %{}",
+                t.name
+            )));
+        }
+        for field in &t.fields {
+            let mut elem = field;
+            while let Type::Array(of, _) = elem {
+                elem = of;
+            }
+            if let Type::Named(name) = elem {
+                if let Some(dep) = self.module.typedef(name) {
+                    self.typedef(dep, done)?;
+                }
+            }
+        }
+
+        writeln!(self.out, "{} {{", self.tag(&t.name)).unwrap();
+        for (i, field) in t.fields.iter().enumerate() {
+            writeln!(self.out, "\t{};", self.decl(field, &format!("f{}", i))).unwrap();
+        }
+        self.out.push_str("};\n");
+        Ok(())
+    }
+
+    /// The structure holding a data definition, one member per non-empty item.
+    fn data_type(&mut self, d: &Data) -> Result<(), BackendError> {
+        let mut offset = 0;
+        let mut members = vec![];
+        for item in &d.items {
+            if offset % item.align() != 0 {
+                return Err(BackendError::Unsupported(format!(
+                    "data ${} has a misaligned item at offset {}",
+                    d.name, offset
+                )));
+            }
+            offset += item.size();
+
+            let name = format!("f{}", members.len());
+            let member = match item {
+                DataItem::Str(_) | DataItem::Zero(_) => format!("char {}[{}]", name, item.size()),
+                DataItem::Runestr(_) => format!("int32_t {}[{}]", name, item.size() / 4),
+                DataItem::Int(ty, _) => format!("{} {}", ctype(ty), name),
+                DataItem::Float(_) => format!("double {}", name),
+                DataItem::Addr(_) => format!("void *{}", name),
+            };
+            if item.size() > 0 {
+                members.push(member);
+            }
+        }
+        if members.is_empty() {
+            members.push("char f0".into());
+        }
+
+        writeln!(
+            self.out,
+            "struct d_{} {{ {}; }};",
+            ident(&d.name),
+            members.join("; ")
+        )
+        .unwrap();
+        Ok(())
+    }
+
+    fn data(&mut self, d: &Data) {
+        let mut inits = vec![];
+        for item in &d.items {
+            if item.size() == 0 {
+                continue;
+            }
+            inits.push(match item {
+                DataItem::Str(s) => str_lit(s),
+                DataItem::Runestr(s) => {
+                    let runes: Vec<String> = s
+                        .chars()
+                        .chain(std::iter::once('\0'))
+                        .map(|c| (c as u32).to_string())
+                        .collect();
+                    format!("{{ {} }}", runes.join(", "))
+                }
+                DataItem::Int(ty, v) => int_lit(sext_const(ty, *v)),
+                DataItem::Float(x) => float_lit(*x),
+                DataItem::Addr(name) => format!("(void *)&{}", ident(name)),
+                DataItem::Zero(_) => "{ 0 }".into(),
+            });
+        }
+        if inits.is_empty() {
+            inits.push("0".into());
+        }
+
+        let storage = match d.linkage {
+            Linkage::Local => "static ",
+            Linkage::Export => "",
+        };
+        writeln!(
+            self.out,
+            "{}struct d_{} {} = {{ {} }};",
+            storage,
+            ident(&d.name),
+            ident(&d.name),
+            inits.join(", ")
+        )
+        .unwrap();
+    }
+}
+
+/// Emission state of a single function.
+struct FuncEmitter<'a, 'e> {
+    e: &'e Emitter<'a>,
+    out: String,
+    types: HashMap<&'a str, Type>,
+    blit: bool,
+
+    /// The C name of the last fixed parameter, for `va_start`.
+    last: Option<String>,
+}
+
+impl<'a, 'e> FuncEmitter<'a, 'e> {
+    fn new(e: &'e Emitter<'a>, f: &'a Function) -> FuncEmitter<'a, 'e> {
+        let mut types = HashMap::new();
+        for p in &f.params {
+            types.insert(p.name.as_str(), p.ty.clone());
+        }
+        for b in &f.blocks {
+            for inst in &b.insts {
+                if let (Some(dst), Some(ty)) = (inst.dst(), inst.dst_type()) {
+                    types.insert(dst, ty);
+                }
+            }
+        }
+        FuncEmitter {
+            e,
+            out: String::new(),
+            types,
+            blit: false,
+            last: f.params.last().map(param_name),
+        }
+    }
+
+    fn line(&mut self, s: String) {
+        writeln!(self.out, "\t{}", s).unwrap();
+    }
+
+    fn temp(name: &str) -> String {
+        format!("t_{}", ident(name))
+    }
+
+    fn label(name: &str) -> String {
+        format!("l_{}", ident(name))
+    }
+
+    /// The width of the value of a temporary, 64 for constants.
+    fn type_of(&self, v: &Value) -> Type {
+        match v {
+            Value::Temp(t) => self.types.get(t.as_str()).cloned().unwrap_or(Type::Ptr),
+            Value::Global(_) => Type::Ptr,
+            Value::Int(_) => Type::I64,
+            Value::Float(_) => Type::F64,
+        }
+    }
+
+    /// An operand of type `ty`.
+    fn operand(&self, ty: &Type, v: &Value) -> String {
+        match v {
+            Value::Temp(name) => FuncEmitter::temp(name),
+            Value::Global(name) => format!("(char *)&{}", ident(name)),
+            Value::Int(i) if ty.is_float() => float_lit(*i as f64),
+            Value::Int(0) if !ty.is_scalar() || *ty == Type::Ptr => "(char *)0".into(),
+            Value::Int(i) if !ty.is_scalar() || *ty == Type::Ptr => {
+                format!("(char *)(intptr_t){}", int_lit(*i))
+            }
+            Value::Int(i) => int_lit(sext_const(ty, *i)),
+            Value::Float(x) => float_lit(*x),
+        }
+    }
+
+    /// An operand reinterpreted as the unsigned type `u`.
+    fn unsigned(&self, u: &str, ty: &Type, v: &Value) -> String {
+        format!("({}){}", u, self.operand(ty, v))
+    }
+
+    fn function(&mut self, f: &Function) {
+        let names = f.params.iter().map(param_name).collect();
+        let storage = match f.linkage {
+            Linkage::Local => "static ",
+            Linkage::Export => "",
+        };
+        let proto = self
+            .e
+            .prototype(&ident(&f.name), &f.signature(), Some(names));
+        writeln!(self.out, "{}{} {{", storage, proto).unwrap();
+
+        // Aggregates passed by value are referred to by the address of the parameter.
+        for p in &f.params {
+            if let Type::Named(_) = p.ty {
+                let name = ident(&p.name);
+                self.line(format!("char *t_{} = (char *)&p_{};", name, name));
+            }
+        }
+        for b in &f.blocks {
+            for inst in &b.insts {
+                let (Some(dst), Some(ty)) = (inst.dst(), inst.dst_type()) else {
+                    continue;
+                };
+                let t = FuncEmitter::temp(dst);
+                match (inst, &ty) {
+                    (Inst::Alloca { ty, .. }, _) => {
+                        let slot = self.e.decl(ty, &format!("s_{}", ident(dst)));
+                        self.line(format!("{};", slot));
+                        self.line(format!("char *{};", t));
+                    }
+                    (
+                        Inst::Call {
+                            ret: ret @ Type::Named(_),
+                            ..
+                        },
+                        _,
+                    ) => {
+                        let result = self.e.decl(ret, &format!("r_{}", ident(dst)));
+                        self.line(format!("{};", result));
+                        self.line(format!("char *{};", t));
+                    }
+                    (_, ty) if ty.is_aggregate() => self.line(format!("char *{};", t)),
+                    (_, ty) => self.line(format!("{};", self.e.decl(ty, &t))),
+                }
+            }
+        }
+
+        for (i, b) in f.blocks.iter().enumerate() {
+            if i > 0 || self.jumped_to(f, &b.label) {
+                writeln!(self.out, "{}:", FuncEmitter::label(&b.label)).unwrap();
+            }
+            for inst in &b.insts {
+                self.inst(inst);
+            }
+            self.term(&b.term);
+        }
+        self.out.push_str("}\n");
+    }
+
+    /// Return true if some block jumps to `label`, unused labels are warned about.
+    fn jumped_to(&self, f: &Function, label: &str) -> bool {
+        f.blocks
+            .iter()
+            .any(|b| b.term.successors().contains(&label))
+    }
+
+    /// Emit `dst = (T)((W)lhs op (W)rhs)`, computing in the wrapping type `W` of `ty`.
+    fn wrapping(&mut self, dst: &str, ty: &Type, op: &str, lhs: &Value, rhs: &Value) {
+        let w = wtype(ty);
+        let (l, r) = (self.unsigned(w, ty, lhs), self.unsigned(w, ty, rhs));
+        self.line(format!("{} = ({})({} {} {});", dst, ctype(ty), l, op, r));
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Bin {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let d = FuncEmitter::temp(dst);
+                if ty.is_float() {
+                    let op = match op {
+                        BinOp::Add => "+",
+                        BinOp::Sub => "-",
+                        BinOp::Mul => "*",
+                        _ => "/",
+                    };
+                    let (l, r) = (self.operand(ty, lhs), self.operand(ty, rhs));
+                    self.line(format!("{} = {} {} {};", d, l, op, r));
+                    return;
+                }
+
+                let t = ctype(ty);
+                let u = utype(ty);
+                let (l, r) = (self.operand(ty, lhs), self.operand(ty, rhs));
+                let m = shift_mask(ty);
+                let line = match op {
+                    BinOp::Add => return self.wrapping(&d, ty, "+", lhs, rhs),
+                    BinOp::Sub => return self.wrapping(&d, ty, "-", lhs, rhs),
+                    BinOp::Mul => return self.wrapping(&d, ty, "*", lhs, rhs),
+                    BinOp::And => return self.wrapping(&d, ty, "&", lhs, rhs),
+                    BinOp::Or => return self.wrapping(&d, ty, "|", lhs, rhs),
+                    BinOp::Xor => return self.wrapping(&d, ty, "^", lhs, rhs),
+                    BinOp::Div => format!("{} = ({})({} / {});", d, t, l, r),
+                    BinOp::Rem => format!("{} = ({})({} % {});", d, t, l, r),
+                    BinOp::UDiv => format!("{} = ({})(({}){} / ({}){});", d, t, u, l, u, r),
+                    BinOp::URem => format!("{} = ({})(({}){} % ({}){});", d, t, u, l, u, r),
+                    BinOp::Shl => {
+                        format!("{} = ({})(({}){} << ({} & {}));", d, t, wtype(ty), l, r, m)
+                    }
+                    BinOp::Shr => format!("{} = ({})({} >> ({} & {}));", d, t, l, r, m),
+                    BinOp::UShr => {
+                        format!("{} = ({})(({}){} >> ({} & {}));", d, t, u, l, r, m)
+                    }
+                };
+                self.line(line);
+            }
+            Inst::Cmp {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let c = match op {
+                    CmpOp::Eq => "==",
+                    CmpOp::Ne => "!=",
+                    CmpOp::Lt | CmpOp::ULt => "<",
+                    CmpOp::Le | CmpOp::ULe => "<=",
+                    CmpOp::Gt | CmpOp::UGt => ">",
+                    CmpOp::Ge | CmpOp::UGe => ">=",
+                };
+                let unsigned = matches!(op, CmpOp::ULt | CmpOp::ULe | CmpOp::UGt | CmpOp::UGe);
+                let (l, r) = if unsigned && !ty.is_float() {
+                    let u = utype(ty);
+                    (self.unsigned(u, ty, lhs), self.unsigned(u, ty, rhs))
+                } else {
+                    (self.operand(ty, lhs), self.operand(ty, rhs))
+                };
+                self.line(format!("{} = {} {} {};", FuncEmitter::temp(dst), l, c, r));
+            }
+            Inst::Un { dst, op, ty, arg } => {
+                let d = FuncEmitter::temp(dst);
+                let line = match op {
+                    UnOp::Neg if ty.is_float() => format!("{} = -{};", d, self.operand(ty, arg)),
+                    UnOp::Neg => format!(
+                        "{} = ({})(0 - {});",
+                        d,
+                        ctype(ty),
+                        self.unsigned(wtype(ty), ty, arg)
+                    ),
+                    UnOp::Not => format!(
+                        "{} = ({})~{};",
+                        d,
+                        ctype(ty),
+                        self.unsigned(wtype(ty), ty, arg)
+                    ),
+                };
+                self.line(line);
+            }
+            Inst::Conv {
+                dst,
+                op,
+                from,
+                arg,
+                to,
+            } => {
+                let v = self.conv(*op, from, arg, to);
+                self.line(format!("{} = {};", FuncEmitter::temp(dst), v));
+            }
+            Inst::Copy { dst, ty, arg } => {
+                let v = self.operand(ty, arg);
+                self.line(format!("{} = {};", FuncEmitter::temp(dst), v));
+            }
+            Inst::Alloca { dst, .. } => {
+                let name = ident(dst);
+                self.line(format!("t_{} = (char *)&s_{};", name, name));
+            }
+            Inst::Load { dst, ty, addr } => {
+                let a = self.operand(&Type::Ptr, addr);
+                let d = FuncEmitter::temp(dst);
+                self.line(format!("{} = *({} *){};", d, ctype(ty).trim_end(), a));
+            }
+            Inst::Store { ty, value, addr } => {
+                let v = self.operand(ty, value);
+                let a = self.operand(&Type::Ptr, addr);
+                self.line(format!("*({} *){} = {};", ctype(ty).trim_end(), a, v));
+            }
+            Inst::Field {
+                dst,
+                aggr,
+                base,
+                index,
+            } => {
+                let offset = self.e.module.field_offset(aggr, *index as usize).unwrap();
+                let b = self.operand(&Type::Ptr, base);
+                self.line(format!("{} = {} + {};", FuncEmitter::temp(dst), b, offset));
+            }
+            Inst::Index {
+                dst,
+                elem,
+                base,
+                index,
+            } => {
+                let size = self.e.module.layout(elem).unwrap().size as i64;
+                let b = self.operand(&Type::Ptr, base);
+                let d = FuncEmitter::temp(dst);
+                let line = match index {
+                    Value::Int(i) => format!("{} = {} + {};", d, b, int_lit(i * size)),
+                    index => {
+                        let i = self.operand(&self.type_of(index), index);
+                        format!("{} = {} + (int64_t){} * {};", d, b, i, size)
+                    }
+                };
+                self.line(line);
+            }
+            Inst::Blit { ty, dst, src } => {
+                let d = self.operand(&Type::Ptr, dst);
+                let s = self.operand(&Type::Ptr, src);
+                let line = match ty {
+                    Type::Named(name) => {
+                        let tag = self.e.tag(name);
+                        format!("*({} *){} = *({} *){};", tag, d, tag, s)
+                    }
+                    ty => {
+                        self.blit = true;
+                        let size = self.e.module.layout(ty).unwrap().size;
+                        format!("alef_c_blit({}, {}, {});", d, s, size)
+                    }
+                };
+                self.line(line);
+            }
+            Inst::Call {
+                dst,
+                ret,
+                callee,
+                args,
+                fixed,
+            } => {
+                let call = self.call(ret, callee, args, *fixed);
+                match (dst, ret) {
+                    (Some(dst), Type::Named(_)) => {
+                        let name = ident(dst);
+                        self.line(format!("r_{} = {};", name, call));
+                        self.line(format!("t_{} = (char *)&r_{};", name, name));
+                    }
+                    (Some(dst), _) => self.line(format!("{} = {};", FuncEmitter::temp(dst), call)),
+                    (None, _) => self.line(format!("{};", call)),
+                }
+            }
+            Inst::VaStart { ap } => {
+                let a = self.operand(&Type::Ptr, ap);
+                let last = self.last.clone().unwrap_or_default();
+                self.line(format!("va_start(*(va_list *){}, {});", a, last));
+            }
+            Inst::VaArg { dst, ty, ap } => {
+                let a = self.operand(&Type::Ptr, ap);
+                let d = FuncEmitter::temp(dst);
+                self.line(format!(
+                    "{} = va_arg(*(va_list *){}, {});",
+                    d,
+                    a,
+                    ctype(ty).trim_end()
+                ));
+            }
+            inst => unreachable!("{} should have been expanded", inst),
+        }
+    }
+
+    fn call(
+        &self,
+        ret: &Type,
+        callee: &Value,
+        args: &[(Type, Value)],
+        fixed: Option<usize>,
+    ) -> String {
+        let values: Vec<String> = args
+            .iter()
+            .map(|(ty, v)| match ty {
+                Type::Named(name) => format!("*({} *){}", self.e.tag(name), self.operand(ty, v)),
+                ty => self.operand(ty, v),
+            })
+            .collect();
+        let values = values.join(", ");
+
+        if let Value::Global(name) = callee {
+            if self.e.module.signature(name).is_some() {
+                return format!("{}({})", ident(name), values);
+            }
+        }
+
+        // Calls through pointers cast the pointer to the type made from the arguments.
+        let sig = Signature {
+            conv: CallConv::C,
+            params: args[..fixed.unwrap_or(args.len())]
+                .iter()
+                .map(|(ty, _)| ty.clone())
+                .collect(),
+            variadic: fixed.is_some(),
+            ret: ret.clone(),
+        };
+        let ty = self.e.prototype("(*)", &sig, None);
+        format!("(({}){})({})", ty, self.operand(&Type::Ptr, callee), values)
+    }
+
+    fn conv(&self, op: ConvOp, from: &Type, arg: &Value, to: &Type) -> String {
+        let a = self.operand(from, arg);
+        let t = ctype(to);
+        match op {
+            ConvOp::Sext | ConvOp::Trunc | ConvOp::SiToF => format!("({}){}", t, a),
+            ConvOp::Zext | ConvOp::UiToF => format!("({})({}){}", t, utype(from), a),
+            ConvOp::FToSi => format!("({}){}", t, a),
+            ConvOp::FToUi => format!("({})({}){}", t, utype(to), a),
+            ConvOp::Bitcast if from == to => a,
+            ConvOp::Bitcast if from.is_float() || to.is_float() => {
+                // Reinterpret the bits through a union, which C99 allows.
+                let (f, i) = if from.is_float() {
+                    ("f", "i")
+                } else {
+                    ("i", "f")
+                };
+                let int = if from.is_float() {
+                    ctype(to)
+                } else {
+                    ctype(from)
+                };
+                format!(
+                    "((union {{ double f; {} i; }}){{ .{} = {} }}).{}",
+                    int, f, a, i
+                )
+            }
+            ConvOp::Bitcast if *from == Type::Ptr || *to == Type::Ptr => {
+                format!("({})(intptr_t){}", t, a)
+            }
+            ConvOp::Bitcast => format!("({}){}", t, a),
+        }
+    }
+
+    fn term(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Jmp(l) => self.line(format!("goto {};", FuncEmitter::label(l))),
+            Terminator::Br { cond, then, else_ } => {
+                let c = self.operand(&Type::I32, cond);
+                self.line(format!("if ({})", c));
+                self.line(format!("\tgoto {};", FuncEmitter::label(then)));
+                self.line(format!("goto {};", FuncEmitter::label(else_)));
+            }
+            Terminator::Switch {
+                ty,
+                value,
+                default,
+                cases,
+            } => {
+                let v = self.operand(ty, value);
+                self.line(format!("switch ({}) {{", v));
+                let mut seen = HashSet::new();
+                for (n, target) in cases {
+                    let n = sext_const(ty, *n);
+                    if seen.insert(n) {
+                        self.line(format!(
+                            "case {}: goto {};",
+                            int_lit(n),
+                            FuncEmitter::label(target)
+                        ));
+                    }
+                }
+                self.line(format!("default: goto {};", FuncEmitter::label(default)));
+                self.line("}".into());
+            }
+            Terminator::Ret(None) => self.line("return;".into()),
+            Terminator::Ret(Some((ty, v))) => {
+                let v = match ty {
+                    Type::Named(name) => {
+                        format!("*({} *){}", self.e.tag(name), self.operand(ty, v))
+                    }
+                    ty => self.operand(ty, v),
+                };
+                self.line(format!("return {};", v))
+            }
+            Terminator::Hlt => self.line("abort();".into()),
+            term => unreachable!("{} should have been expanded", term),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(ident("main"), "main");
+        assert_eq!(ident("alef.thunk.0"), "alef__thunk__0");
+        assert_eq!(ident("alef_alloc"), "alef_alloc");
+        assert_eq!(ident("αβ"), "_3b1_3b2");
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(str_lit("a\"b\\c?\n\r"), "\"a\\\"b\\\\c\\?\\n\\015\"");
+        assert_eq!(int_lit(i64::MIN), "(-9223372036854775807 - 1)");
+        assert_eq!(float_lit(2.0), "2.0");
+        assert_eq!(float_lit(f64::NEG_INFINITY), "(-1.0 / 0.0)");
+    }
+}
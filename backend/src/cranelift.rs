@@ -0,0 +1,849 @@
+//! Compile the IR to a native object file with Cranelift.
+//!
+//! Only x86-64 is supported, since aggregates passed by value follow the System V rules
+//! implemented in `abi`. Temporaries become Cranelift variables, so that the frontend takes care
+//! of building SSA form across blocks; `alloca` slots are static stack slots.
+//!
+//! Cranelift has no support for variadic calls, which on x86-64 need `%al` set to an upper bound
+//! of the number of vector registers used. Variadic calls therefore go through a small
+//! trampoline per callee, `alef.vcall.<name>`, that sets `%al` to 8 and jumps to the callee.
+//! Variadic functions can be defined, but cannot read their variadic arguments (`vastart`).
+
+use crate::abi::{self, Arg, Class, Lowered, Ret};
+use crate::err::BackendError;
+use crate::expand::expand;
+use alef_ir::func::{CallConv, Function, Linkage, Signature};
+use alef_ir::inst::{BinOp, CmpOp, ConvOp, Inst, Terminator, UnOp, Value};
+use alef_ir::module::{DataItem, Module};
+use alef_ir::ty::Type;
+use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::{
+    self as cl, types, AbiParam, ArgumentPurpose, ExternalName, InstBuilder, MemFlags,
+    StackSlotData, StackSlotKind, TrapCode, UserExternalName, UserFuncName,
+};
+use cranelift_codegen::isa::{OwnedTargetIsa, TargetFrontendConfig};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_codegen::{FinalizedMachReloc, FinalizedRelocTarget};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Switch, Variable};
+use cranelift_module::{DataDescription, DataId, FuncId, Module as _};
+use cranelift_object::{ObjectBuilder, ObjectModule};
+use std::collections::HashMap;
+use target_lexicon::Architecture;
+
+/// `mov $8, %al; jmp rel32`, the relocation of the jump target starts at offset 3.
+const TRAMPOLINE: [u8; 7] = [0xb0, 0x08, 0xe9, 0, 0, 0, 0];
+
+/// Compile a verified module into an object file for the host, named `name`.
+pub fn emit(module: &Module, name: &str) -> Result<Vec<u8>, BackendError> {
+    let module = expand(module);
+    let isa = isa()?;
+    let builder = ObjectBuilder::new(isa, name, cranelift_module::default_libcall_names())
+        .map_err(|e| module_error("", e))?;
+    let mut c = Compiler {
+        m: &module,
+        obj: ObjectModule::new(builder),
+        funcs: HashMap::new(),
+        data: HashMap::new(),
+        trampolines: HashMap::new(),
+    };
+    c.declare()?;
+    c.define_data()?;
+    for f in &module.funcs {
+        c.function(f)?;
+    }
+
+    let product = c.obj.finish();
+    product.emit().map_err(|e| module_error("", e))
+}
+
+fn isa() -> Result<OwnedTargetIsa, BackendError> {
+    let builder = cranelift_native::builder().map_err(|e| BackendError::Target(e.to_string()))?;
+    if builder.triple().architecture != Architecture::X86_64 {
+        return Err(BackendError::Target(builder.triple().to_string()));
+    }
+
+    let mut flags = settings::builder();
+    flags.set("is_pic", "true").unwrap();
+    flags.set("opt_level", "speed").unwrap();
+    builder
+        .finish(settings::Flags::new(flags))
+        .map_err(|e| BackendError::Target(e.to_string()))
+}
+
+fn module_error(func: &str, e: impl std::fmt::Debug) -> BackendError {
+    BackendError::Codegen {
+        func: func.to_string(),
+        msg: format!("{:?}", e),
+    }
+}
+
+/// The Cranelift type of a scalar; aggregates are handled by address.
+fn cl_type(ty: &Type) -> cl::Type {
+    match ty {
+        Type::I8 => types::I8,
+        Type::I16 => types::I16,
+        Type::I32 => types::I32,
+        Type::F64 => types::F64,
+        _ => types::I64,
+    }
+}
+
+fn class_type(c: &Class) -> cl::Type {
+    match c {
+        Class::Int => types::I64,
+        Class::Sse => types::F64,
+    }
+}
+
+/// A scalar parameter or result; narrow integers are sign extended as C compilers expect.
+fn scalar_param(ty: &Type) -> AbiParam {
+    let p = AbiParam::new(cl_type(ty));
+    match ty {
+        Type::I8 | Type::I16 => p.sext(),
+        _ => p,
+    }
+}
+
+/// The mask of the significant bits of a constant of the given type.
+fn mask(ty: cl::Type) -> u64 {
+    match ty.bits() {
+        64 => u64::MAX,
+        bits => (1 << bits) - 1,
+    }
+}
+
+struct Compiler<'a> {
+    m: &'a Module,
+    obj: ObjectModule,
+    funcs: HashMap<String, FuncId>,
+    data: HashMap<String, DataId>,
+    trampolines: HashMap<String, FuncId>,
+}
+
+impl<'a> Compiler<'a> {
+    fn signature(&self, lowered: &Lowered) -> cl::Signature {
+        let mut sig = self.obj.make_signature();
+        if let Ret::Memory(_) = lowered.ret {
+            sig.params
+                .push(AbiParam::special(types::I64, ArgumentPurpose::StructReturn));
+        }
+        for p in &lowered.params {
+            match p {
+                Arg::Direct(ty) => sig.params.push(scalar_param(ty)),
+                Arg::Split(classes) => sig
+                    .params
+                    .extend(classes.iter().map(|c| AbiParam::new(class_type(c)))),
+                Arg::Memory(size) => sig.params.push(AbiParam::special(
+                    types::I64,
+                    ArgumentPurpose::StructArgument(*size as u32),
+                )),
+            }
+        }
+        match &lowered.ret {
+            Ret::Direct(ty) => sig.returns.push(scalar_param(ty)),
+            Ret::Split(classes) => sig
+                .returns
+                .extend(classes.iter().map(|c| AbiParam::new(class_type(c)))),
+            Ret::Void | Ret::Memory(_) => {}
+        }
+        sig
+    }
+
+    fn declare(&mut self) -> Result<(), BackendError> {
+        for e in &self.m.externs {
+            let sig = self.signature(&abi::lower(self.m, &e.sig));
+            let id = self
+                .obj
+                .declare_function(&e.name, cranelift_module::Linkage::Import, &sig)
+                .map_err(|err| module_error(&e.name, err))?;
+            self.funcs.insert(e.name.clone(), id);
+        }
+        for f in &self.m.funcs {
+            let sig = self.signature(&abi::lower(self.m, &f.signature()));
+            let id = self
+                .obj
+                .declare_function(&f.name, linkage(f.linkage), &sig)
+                .map_err(|err| module_error(&f.name, err))?;
+            self.funcs.insert(f.name.clone(), id);
+        }
+        for d in &self.m.data {
+            let id = self
+                .obj
+                .declare_data(&d.name, linkage(d.linkage), true, false)
+                .map_err(|err| module_error("", err))?;
+            self.data.insert(d.name.clone(), id);
+        }
+        Ok(())
+    }
+
+    fn define_data(&mut self) -> Result<(), BackendError> {
+        for d in &self.m.data {
+            let mut desc = DataDescription::new();
+            let mut bytes = vec![];
+            let mut relocs = vec![];
+            for item in &d.items {
+                match item {
+                    DataItem::Str(s) => {
+                        bytes.extend(s.as_bytes());
+                        bytes.push(0);
+                    }
+                    DataItem::Runestr(s) => {
+                        for c in s.chars().chain(std::iter::once('\0')) {
+                            bytes.extend((c as u32).to_le_bytes());
+                        }
+                    }
+                    DataItem::Int(_, v) => {
+                        bytes.extend(&v.to_le_bytes()[..item.size() as usize]);
+                    }
+                    DataItem::Float(x) => bytes.extend(x.to_le_bytes()),
+                    DataItem::Addr(name) => {
+                        relocs.push((bytes.len() as u32, name));
+                        bytes.extend([0; 8]);
+                    }
+                    DataItem::Zero(n) => bytes.resize(bytes.len() + *n as usize, 0),
+                }
+            }
+            desc.define(bytes.into_boxed_slice());
+            desc.set_align(d.align());
+            for (offset, name) in relocs {
+                if let Some(id) = self.funcs.get(name) {
+                    let f = self.obj.declare_func_in_data(*id, &mut desc);
+                    desc.write_function_addr(offset, f);
+                } else {
+                    let gv = self.obj.declare_data_in_data(self.data[name], &mut desc);
+                    desc.write_data_addr(offset, gv, 0);
+                }
+            }
+            self.obj
+                .define_data(self.data[&d.name], &desc)
+                .map_err(|e| module_error("", e))?;
+        }
+        Ok(())
+    }
+
+    /// The trampoline used for variadic calls to `callee`, created on first use.
+    fn trampoline(&mut self, callee: &str) -> Result<FuncId, BackendError> {
+        if let Some(id) = self.trampolines.get(callee) {
+            return Ok(*id);
+        }
+
+        let target = *self.funcs.get(callee).ok_or_else(|| {
+            BackendError::Unsupported(format!("variadic call to ${} through a pointer", callee))
+        })?;
+        let name = format!("alef.vcall.{}", callee);
+        let sig = self.obj.make_signature();
+        let id = self
+            .obj
+            .declare_function(&name, cranelift_module::Linkage::Local, &sig)
+            .map_err(|e| module_error(&name, e))?;
+
+        let mut func = cl::Function::new();
+        let target = func.declare_imported_user_function(UserExternalName::new(0, target.as_u32()));
+        let reloc = FinalizedMachReloc {
+            offset: 3,
+            kind: Reloc::X86CallPLTRel4,
+            target: FinalizedRelocTarget::ExternalName(ExternalName::User(target)),
+            addend: -4,
+        };
+        self.obj
+            .define_function_bytes(id, &func, 16, &TRAMPOLINE, &[reloc])
+            .map_err(|e| module_error(&name, e))?;
+        self.trampolines.insert(callee.to_string(), id);
+        Ok(id)
+    }
+
+    fn function(&mut self, f: &Function) -> Result<(), BackendError> {
+        let lowered = abi::lower(self.m, &f.signature());
+        let mut ctx = self.obj.make_context();
+        ctx.func.signature = self.signature(&lowered);
+        ctx.func.name = UserFuncName::user(0, self.funcs[&f.name].as_u32());
+
+        let mut fctx = FunctionBuilderContext::new();
+        let config = self.obj.target_config();
+        let b = FunctionBuilder::new(&mut ctx.func, &mut fctx);
+        let mut t = Translator {
+            c: self,
+            b,
+            config,
+            func: f,
+            lowered,
+            vars: HashMap::new(),
+            blocks: HashMap::new(),
+            sret: None,
+        };
+        t.function()?;
+        t.b.seal_all_blocks();
+        t.b.finalize();
+
+        let id = self.funcs[&f.name];
+        self.obj
+            .define_function(id, &mut ctx)
+            .map_err(|e| module_error(&f.name, e))?;
+        Ok(())
+    }
+}
+
+fn linkage(l: Linkage) -> cranelift_module::Linkage {
+    match l {
+        Linkage::Local => cranelift_module::Linkage::Local,
+        Linkage::Export => cranelift_module::Linkage::Export,
+    }
+}
+
+/// Translation state of a single function.
+struct Translator<'a, 'b, 'c> {
+    c: &'c mut Compiler<'a>,
+    b: FunctionBuilder<'b>,
+    config: TargetFrontendConfig,
+    func: &'c Function,
+    lowered: Lowered,
+    vars: HashMap<&'c str, (Variable, cl::Type)>,
+    blocks: HashMap<&'c str, cl::Block>,
+    sret: Option<cl::Value>,
+}
+
+impl<'a, 'b, 'c> Translator<'a, 'b, 'c> {
+    fn error(&self, msg: String) -> BackendError {
+        BackendError::Codegen {
+            func: self.func.name.clone(),
+            msg,
+        }
+    }
+
+    fn var(&mut self, name: &'c str, ty: &Type) {
+        let ty = if ty.is_aggregate() {
+            types::I64
+        } else {
+            cl_type(ty)
+        };
+        let var = Variable::from_u32(self.vars.len() as u32);
+        self.b.declare_var(var, ty);
+        self.vars.insert(name, (var, ty));
+    }
+
+    fn def(&mut self, name: &str, v: cl::Value) {
+        let (var, _) = self.vars[name];
+        self.b.def_var(var, v);
+    }
+
+    fn slot(&mut self, size: u64, align: u64) -> cl::Value {
+        let slot = self.b.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            size as u32,
+            align.max(1).trailing_zeros() as u8,
+        ));
+        self.b.ins().stack_addr(types::I64, slot, 0)
+    }
+
+    fn copy(&mut self, dst: cl::Value, src: cl::Value, size: u64, align: u64) {
+        let align = align.clamp(1, 8) as u8;
+        self.b.emit_small_memory_copy(
+            self.config,
+            dst,
+            src,
+            size,
+            align,
+            align,
+            true,
+            MemFlags::new(),
+        );
+    }
+
+    /// The address of a global.
+    fn global(&mut self, name: &str) -> Result<cl::Value, BackendError> {
+        let v = if let Some(id) = self.c.funcs.get(name) {
+            let f = self.c.obj.declare_func_in_func(*id, self.b.func);
+            self.b.ins().func_addr(types::I64, f)
+        } else if let Some(id) = self.c.data.get(name) {
+            let gv = self.c.obj.declare_data_in_func(*id, self.b.func);
+            self.b.ins().symbol_value(types::I64, gv)
+        } else {
+            return Err(self.error(format!("unknown global ${}", name)));
+        };
+        Ok(v)
+    }
+
+    /// Translate an operand expected to be of type `ty`.
+    fn operand(&mut self, ty: &Type, v: &Value) -> Result<cl::Value, BackendError> {
+        let v = match v {
+            Value::Temp(name) => {
+                let (var, _) = self.vars[name.as_str()];
+                self.b.use_var(var)
+            }
+            Value::Global(name) => self.global(name)?,
+            Value::Int(i) if ty.is_float() => self.b.ins().f64const(*i as f64),
+            Value::Int(i) => {
+                let t = if ty.is_aggregate() {
+                    types::I64
+                } else {
+                    cl_type(ty)
+                };
+                self.b.ins().iconst(t, (*i as u64 & mask(t)) as i64)
+            }
+            Value::Float(x) => self.b.ins().f64const(*x),
+        };
+        Ok(v)
+    }
+
+    /// The width of the value of a temporary, 64 for constants.
+    fn width(&self, v: &Value) -> u32 {
+        match v {
+            Value::Temp(name) => self.vars[name.as_str()].1.bits(),
+            _ => 64,
+        }
+    }
+
+    fn function(&mut self) -> Result<(), BackendError> {
+        let f = self.func;
+        for p in &f.params {
+            self.var(&p.name, &p.ty);
+        }
+        for block in &f.blocks {
+            for inst in &block.insts {
+                if let (Some(dst), Some(ty)) = (inst.dst(), inst.dst_type()) {
+                    self.var(dst, &ty);
+                }
+            }
+            let cb = self.b.create_block();
+            self.blocks.insert(&block.label, cb);
+        }
+
+        // The entry block receives the parameters as the calling convention passes them and
+        // rebuilds the IR parameters, then jumps to the first block of the function.
+        let entry = self.b.create_block();
+        self.b.append_block_params_for_function_params(entry);
+        self.b.switch_to_block(entry);
+        let params = self.b.block_params(entry).to_vec();
+        let mut next = params.iter();
+        if let Ret::Memory(_) = self.lowered.ret {
+            self.sret = next.next().copied();
+        }
+        for (p, arg) in f.params.iter().zip(self.lowered.params.clone()) {
+            match arg {
+                Arg::Direct(_) | Arg::Memory(_) => {
+                    let v = *next.next().unwrap();
+                    self.def(&p.name, v);
+                }
+                Arg::Split(classes) => {
+                    let slot = self.slot(16, 8);
+                    for (i, _) in classes.iter().enumerate() {
+                        let v = *next.next().unwrap();
+                        self.b.ins().store(MemFlags::new(), v, slot, 8 * i as i32);
+                    }
+                    self.def(&p.name, slot);
+                }
+            }
+        }
+        let first = self.blocks[f.blocks[0].label.as_str()];
+        self.b.ins().jump(first, &[]);
+
+        for block in &f.blocks {
+            let cb = self.blocks[block.label.as_str()];
+            self.b.switch_to_block(cb);
+            for inst in &block.insts {
+                self.inst(inst)?;
+            }
+            self.term(&block.term)?;
+        }
+        Ok(())
+    }
+
+    fn inst(&mut self, inst: &'c Inst) -> Result<(), BackendError> {
+        match inst {
+            Inst::Bin {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let l = self.operand(ty, lhs)?;
+                let r = self.operand(ty, rhs)?;
+                let ins = self.b.ins();
+                let v = if ty.is_float() {
+                    match op {
+                        BinOp::Add => ins.fadd(l, r),
+                        BinOp::Sub => ins.fsub(l, r),
+                        BinOp::Mul => ins.fmul(l, r),
+                        _ => ins.fdiv(l, r),
+                    }
+                } else {
+                    match op {
+                        BinOp::Add => ins.iadd(l, r),
+                        BinOp::Sub => ins.isub(l, r),
+                        BinOp::Mul => ins.imul(l, r),
+                        BinOp::Div => ins.sdiv(l, r),
+                        BinOp::UDiv => ins.udiv(l, r),
+                        BinOp::Rem => ins.srem(l, r),
+                        BinOp::URem => ins.urem(l, r),
+                        BinOp::And => ins.band(l, r),
+                        BinOp::Or => ins.bor(l, r),
+                        BinOp::Xor => ins.bxor(l, r),
+                        BinOp::Shl => ins.ishl(l, r),
+                        BinOp::Shr => ins.sshr(l, r),
+                        BinOp::UShr => ins.ushr(l, r),
+                    }
+                };
+                self.def(dst, v);
+            }
+            Inst::Cmp {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let l = self.operand(ty, lhs)?;
+                let r = self.operand(ty, rhs)?;
+                let c = if ty.is_float() {
+                    let cc = match op {
+                        CmpOp::Eq => FloatCC::Equal,
+                        CmpOp::Ne => FloatCC::NotEqual,
+                        CmpOp::Lt | CmpOp::ULt => FloatCC::LessThan,
+                        CmpOp::Le | CmpOp::ULe => FloatCC::LessThanOrEqual,
+                        CmpOp::Gt | CmpOp::UGt => FloatCC::GreaterThan,
+                        CmpOp::Ge | CmpOp::UGe => FloatCC::GreaterThanOrEqual,
+                    };
+                    self.b.ins().fcmp(cc, l, r)
+                } else {
+                    let cc = match op {
+                        CmpOp::Eq => IntCC::Equal,
+                        CmpOp::Ne => IntCC::NotEqual,
+                        CmpOp::Lt => IntCC::SignedLessThan,
+                        CmpOp::Le => IntCC::SignedLessThanOrEqual,
+                        CmpOp::Gt => IntCC::SignedGreaterThan,
+                        CmpOp::Ge => IntCC::SignedGreaterThanOrEqual,
+                        CmpOp::ULt => IntCC::UnsignedLessThan,
+                        CmpOp::ULe => IntCC::UnsignedLessThanOrEqual,
+                        CmpOp::UGt => IntCC::UnsignedGreaterThan,
+                        CmpOp::UGe => IntCC::UnsignedGreaterThanOrEqual,
+                    };
+                    self.b.ins().icmp(cc, l, r)
+                };
+                let v = self.b.ins().uextend(types::I32, c);
+                self.def(dst, v);
+            }
+            Inst::Un { dst, op, ty, arg } => {
+                let a = self.operand(ty, arg)?;
+                let v = match op {
+                    UnOp::Neg if ty.is_float() => self.b.ins().fneg(a),
+                    UnOp::Neg => self.b.ins().ineg(a),
+                    UnOp::Not => self.b.ins().bnot(a),
+                };
+                self.def(dst, v);
+            }
+            Inst::Conv {
+                dst,
+                op,
+                from,
+                arg,
+                to,
+            } => {
+                let a = self.operand(from, arg)?;
+                let v = self.conv(*op, from, a, to);
+                self.def(dst, v);
+            }
+            Inst::Copy { dst, ty, arg } => {
+                let v = self.operand(ty, arg)?;
+                self.def(dst, v);
+            }
+            Inst::Alloca { dst, ty } => {
+                let layout = self.c.m.layout(ty).unwrap();
+                let v = self.slot(layout.size, layout.align);
+                self.def(dst, v);
+            }
+            Inst::Load { dst, ty, addr } => {
+                let a = self.operand(&Type::Ptr, addr)?;
+                let v = self.b.ins().load(cl_type(ty), MemFlags::new(), a, 0);
+                self.def(dst, v);
+            }
+            Inst::Store { ty, value, addr } => {
+                let v = self.operand(ty, value)?;
+                let a = self.operand(&Type::Ptr, addr)?;
+                self.b.ins().store(MemFlags::new(), v, a, 0);
+            }
+            Inst::Field {
+                dst,
+                aggr,
+                base,
+                index,
+            } => {
+                let offset = self.c.m.field_offset(aggr, *index as usize).unwrap();
+                let b = self.operand(&Type::Ptr, base)?;
+                let v = self.b.ins().iadd_imm(b, offset as i64);
+                self.def(dst, v);
+            }
+            Inst::Index {
+                dst,
+                elem,
+                base,
+                index,
+            } => {
+                let size = self.c.m.layout(elem).unwrap().size as i64;
+                let b = self.operand(&Type::Ptr, base)?;
+                let v = match index {
+                    Value::Int(i) => self.b.ins().iadd_imm(b, i * size),
+                    index => {
+                        let mut i = self.operand(&Type::I64, index)?;
+                        if self.width(index) < 64 {
+                            i = self.b.ins().sextend(types::I64, i);
+                        }
+                        let off = self.b.ins().imul_imm(i, size);
+                        self.b.ins().iadd(b, off)
+                    }
+                };
+                self.def(dst, v);
+            }
+            Inst::Blit { ty, dst, src } => {
+                let layout = self.c.m.layout(ty).unwrap();
+                let d = self.operand(&Type::Ptr, dst)?;
+                let s = self.operand(&Type::Ptr, src)?;
+                self.copy(d, s, layout.size, layout.align);
+            }
+            Inst::Call {
+                dst,
+                ret,
+                callee,
+                args,
+                fixed,
+            } => self.call(dst.as_deref(), ret, callee, args, *fixed)?,
+            Inst::VaStart { .. } | Inst::VaArg { .. } => {
+                return Err(BackendError::Unsupported(
+                    "vastart and vaarg, Cranelift cannot read variadic arguments".into(),
+                ))
+            }
+            inst => unreachable!("{} should have been expanded", inst),
+        }
+        Ok(())
+    }
+
+    fn conv(&mut self, op: ConvOp, from: &Type, a: cl::Value, to: &Type) -> cl::Value {
+        let ft = cl_type(from);
+        let tt = cl_type(to);
+        let ins = self.b.ins();
+        match op {
+            ConvOp::Sext | ConvOp::Zext | ConvOp::Trunc if ft == tt => a,
+            ConvOp::Sext if ft.bits() < tt.bits() => ins.sextend(tt, a),
+            ConvOp::Zext if ft.bits() < tt.bits() => ins.uextend(tt, a),
+            ConvOp::Sext | ConvOp::Zext | ConvOp::Trunc if ft.bits() > tt.bits() => {
+                ins.ireduce(tt, a)
+            }
+            ConvOp::Trunc => ins.uextend(tt, a),
+            ConvOp::SiToF => {
+                let a = if ft.bits() < 32 {
+                    ins.sextend(types::I32, a)
+                } else {
+                    a
+                };
+                self.b.ins().fcvt_from_sint(types::F64, a)
+            }
+            ConvOp::UiToF => {
+                let a = if ft.bits() < 32 {
+                    ins.uextend(types::I32, a)
+                } else {
+                    a
+                };
+                self.b.ins().fcvt_from_uint(types::F64, a)
+            }
+            ConvOp::FToSi | ConvOp::FToUi => {
+                let wide = if tt.bits() < 32 { types::I32 } else { tt };
+                let v = if op == ConvOp::FToSi {
+                    ins.fcvt_to_sint_sat(wide, a)
+                } else {
+                    ins.fcvt_to_uint_sat(wide, a)
+                };
+                if wide == tt {
+                    v
+                } else {
+                    self.b.ins().ireduce(tt, v)
+                }
+            }
+            ConvOp::Bitcast if ft == tt => a,
+            ConvOp::Bitcast if ft.is_float() != tt.is_float() && ft.bits() == tt.bits() => {
+                ins.bitcast(tt, MemFlags::new(), a)
+            }
+            ConvOp::Bitcast if ft.bits() < tt.bits() => ins.uextend(tt, a),
+            ConvOp::Bitcast => ins.ireduce(tt, a),
+            // Sext and Zext to a narrower type are not valid IR, but reduce anyway.
+            ConvOp::Sext | ConvOp::Zext => ins.ireduce(tt, a),
+        }
+    }
+
+    fn call(
+        &mut self,
+        dst: Option<&str>,
+        ret: &Type,
+        callee: &Value,
+        args: &[(Type, Value)],
+        fixed: Option<usize>,
+    ) -> Result<(), BackendError> {
+        // Calls through pointers and variadic calls use a signature made from the arguments.
+        let call_sig = Signature {
+            conv: CallConv::C,
+            params: args.iter().map(|(ty, _)| ty.clone()).collect(),
+            variadic: false,
+            ret: ret.clone(),
+        };
+        let direct = match callee {
+            Value::Global(name) if fixed.is_none() && self.c.funcs.contains_key(name) => Some(name),
+            _ => None,
+        };
+        let sig = match direct {
+            Some(name) => self.c.m.signature(name).unwrap(),
+            None => call_sig,
+        };
+        let lowered = abi::lower(self.c.m, &sig);
+
+        let mut values = vec![];
+        let mut sret = None;
+        if let Ret::Memory(size) = lowered.ret {
+            let align = self.c.m.layout(ret).unwrap().align;
+            let slot = self.slot(size, align);
+            sret = Some(slot);
+            values.push(slot);
+        }
+        for ((ty, v), arg) in args.iter().zip(&lowered.params) {
+            match arg {
+                Arg::Direct(_) => values.push(self.operand(ty, v)?),
+                Arg::Split(classes) => {
+                    let layout = self.c.m.layout(ty).unwrap();
+                    let p = self.operand(&Type::Ptr, v)?;
+                    let tmp = self.slot(16, 8);
+                    self.copy(tmp, p, layout.size, layout.align);
+                    for (i, c) in classes.iter().enumerate() {
+                        let part =
+                            self.b
+                                .ins()
+                                .load(class_type(c), MemFlags::new(), tmp, 8 * i as i32);
+                        values.push(part);
+                    }
+                }
+                Arg::Memory(size) => {
+                    let layout = self.c.m.layout(ty).unwrap();
+                    let p = self.operand(&Type::Ptr, v)?;
+                    let tmp = self.slot(*size, layout.align.max(8));
+                    self.copy(tmp, p, layout.size, layout.align);
+                    values.push(tmp);
+                }
+            }
+        }
+
+        let call = match (direct, callee) {
+            (Some(name), _) => {
+                let f = self.c.obj.declare_func_in_func(self.c.funcs[name], self.b.func);
+                self.b.ins().call(f, &values)
+            }
+            (None, Value::Global(name)) if fixed.is_some() => {
+                let id = self.c.trampoline(name).map_err(|e| match e {
+                    BackendError::Unsupported(msg) => self.error(msg),
+                    e => e,
+                })?;
+                let f = self.c.obj.declare_func_in_func(id, self.b.func);
+                let addr = self.b.ins().func_addr(types::I64, f);
+                let sig = self.c.signature(&lowered);
+                let sig = self.b.import_signature(sig);
+                self.b.ins().call_indirect(sig, addr, &values)
+            }
+            (None, _) if fixed.is_some() => {
+                return Err(self.error("variadic calls through pointers are not supported".into()))
+            }
+            (None, callee) => {
+                let addr = self.operand(&Type::Ptr, callee)?;
+                let sig = self.c.signature(&lowered);
+                let sig = self.b.import_signature(sig);
+                self.b.ins().call_indirect(sig, addr, &values)
+            }
+        };
+
+        let results = self.b.inst_results(call).to_vec();
+        if let Some(dst) = dst {
+            let v = match &lowered.ret {
+                Ret::Direct(_) => results[0],
+                Ret::Split(_) => {
+                    let tmp = self.slot(16, 8);
+                    for (i, r) in results.iter().enumerate() {
+                        self.b.ins().store(MemFlags::new(), *r, tmp, 8 * i as i32);
+                    }
+                    tmp
+                }
+                Ret::Memory(_) => sret.unwrap(),
+                Ret::Void => return Ok(()),
+            };
+            self.def(dst, v);
+        }
+        Ok(())
+    }
+
+    fn term(&mut self, term: &Terminator) -> Result<(), BackendError> {
+        match term {
+            Terminator::Jmp(l) => {
+                let target = self.blocks[l.as_str()];
+                self.b.ins().jump(target, &[]);
+            }
+            Terminator::Br { cond, then, else_ } => {
+                let c = self.operand(&Type::I32, cond)?;
+                let (t, e) = (self.blocks[then.as_str()], self.blocks[else_.as_str()]);
+                self.b.ins().brif(c, t, &[], e, &[]);
+            }
+            Terminator::Switch {
+                ty,
+                value,
+                default,
+                cases,
+            } => {
+                let v = self.operand(ty, value)?;
+                let m = mask(cl_type(ty));
+                let mut switch = Switch::new();
+                let mut seen = std::collections::HashSet::new();
+                for (n, l) in cases {
+                    let n = *n as u64 & m;
+                    if seen.insert(n) {
+                        switch.set_entry(n as u128, self.blocks[l.as_str()]);
+                    }
+                }
+                switch.emit(&mut self.b, v, self.blocks[default.as_str()]);
+            }
+            Terminator::Ret(None) => {
+                self.b.ins().return_(&[]);
+            }
+            Terminator::Ret(Some((ty, v))) => match self.lowered.ret.clone() {
+                Ret::Direct(_) => {
+                    let v = self.operand(ty, v)?;
+                    self.b.ins().return_(&[v]);
+                }
+                Ret::Split(classes) => {
+                    let layout = self.c.m.layout(ty).unwrap();
+                    let p = self.operand(&Type::Ptr, v)?;
+                    let tmp = self.slot(16, 8);
+                    self.copy(tmp, p, layout.size, layout.align);
+                    let parts: Vec<_> = classes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, c)| {
+                            self.b
+                                .ins()
+                                .load(class_type(c), MemFlags::new(), tmp, 8 * i as i32)
+                        })
+                        .collect();
+                    self.b.ins().return_(&parts);
+                }
+                Ret::Memory(size) => {
+                    let layout = self.c.m.layout(ty).unwrap();
+                    let p = self.operand(&Type::Ptr, v)?;
+                    let sret = self.sret.unwrap();
+                    self.copy(sret, p, size, layout.align);
+                    self.b.ins().return_(&[]);
+                }
+                Ret::Void => {
+                    self.b.ins().return_(&[]);
+                }
+            },
+            Terminator::Hlt => {
+                self.b.ins().trap(TrapCode::unwrap_user(1));
+            }
+            term => unreachable!("{} should have been expanded", term),
+        }
+        Ok(())
+    }
+}
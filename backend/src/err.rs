@@ -0,0 +1,23 @@
+use thiserror::Error;
+
+/// Error returned when a backend cannot translate a module.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BackendError {
+    /// The backend does not support the machine it would generate code for.
+    #[error("unsupported target: {0}")]
+    Target(String),
+
+    /// The module uses something the backend cannot translate.
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+
+    /// The code generator failed.
+    #[error("in ${func}: {msg}")]
+    Codegen {
+        /// The function being translated, empty for module-level failures.
+        func: String,
+
+        /// A message describing the failure.
+        msg: String,
+    },
+}
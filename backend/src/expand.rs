@@ -0,0 +1,866 @@
+//! Rewrite the Alef-specific parts of the IR in terms of its core and of calls to the runtime.
+//!
+//! After expansion a module only contains arithmetic, memory and call instructions and the
+//! `jmp`, `br`, `switch`, `ret` and `hlt` terminators, which is all a backend has to translate:
+//!
+//! - `alloc`, `unalloc`, `box` and the channel instructions become runtime calls, with stack
+//!   slots to pass elements by address;
+//! - `alt` fills an array of `alef.altcase` and switches on the result of `alef_alt`;
+//! - `proc`, `task` and `spawn` pack their arguments in a heap allocated environment and start a
+//!   generated thunk, which unpacks them and calls the target;
+//! - `rescue` and `unrescue` push and pop handler numbers on a per-function stack, and `raise`
+//!   pops the innermost handler and jumps to it, or calls `alef_raise` when there is none.
+//!
+//! The handler stack has room for one entry per `rescue` instruction of the function, which is
+//! enough as long as every path pops what it pushes, as the lowering of `rescue` blocks does.
+
+use crate::runtime;
+use alef_ir::func::{Block, CallConv, Function, Linkage, Param, Signature};
+use alef_ir::inst::{AltCase, BinOp, CmpOp, Inst, Terminator, Value};
+use alef_ir::module::{Extern, Module};
+use alef_ir::ty::{AggrKind, Type, TypeDef};
+use std::collections::{BTreeSet, HashSet};
+
+struct Expander<'a> {
+    module: &'a Module,
+    /// The aggregates of the module and the ones added by the expansion.
+    types: Module,
+    thunks: Vec<Function>,
+    used: BTreeSet<&'static str>,
+    globals: HashSet<String>,
+    nthunk: usize,
+}
+
+/// Expansion state of a single function.
+struct FuncExpander<'a, 'b> {
+    x: &'b mut Expander<'a>,
+    names: HashSet<String>,
+    ntemp: usize,
+    entry: Vec<Inst>,
+    blocks: Vec<Block>,
+    insts: Vec<Inst>,
+    handlers: Vec<String>,
+    rescue: Option<(Value, Value)>,
+    raise_block: Option<String>,
+}
+
+impl<'a> Expander<'a> {
+    fn size(&self, ty: &Type) -> i64 {
+        let layout = self
+            .types
+            .layout(ty)
+            .unwrap_or_else(|| panic!("no layout for {}, verify the module first", ty));
+        layout.size as i64
+    }
+
+    /// Fresh names for a thunk and its environment type.
+    fn fresh_thunk(&mut self) -> (String, String) {
+        loop {
+            let thunk = format!("alef.thunk.{}", self.nthunk);
+            let env = format!("alef.env.{}", self.nthunk);
+            self.nthunk += 1;
+            if !self.globals.contains(&thunk) && self.types.typedef(&env).is_none() {
+                self.globals.insert(thunk.clone());
+                return (thunk, env);
+            }
+        }
+    }
+
+    fn call(&mut self, dst: Option<String>, name: &'static str, args: Vec<Value>) -> Inst {
+        self.used.insert(name);
+        let sig = runtime::signature(name).unwrap();
+        Inst::Call {
+            dst,
+            ret: sig.ret,
+            callee: Value::global(name),
+            args: sig.params.into_iter().zip(args).collect(),
+            fixed: None,
+        }
+    }
+
+    /// Create the environment type and the thunk for a `proc`, `task` or `spawn` of `callee`
+    /// with `args`. Return the thunk name and the environment type, None if there is nothing to
+    /// pass.
+    fn thunk(&mut self, callee: &Value, args: &[(Type, Value)]) -> (String, Option<String>) {
+        let mut fields = vec![];
+        if let Value::Temp(_) = callee {
+            fields.push(Type::Ptr);
+        }
+        fields.extend(args.iter().map(|(ty, _)| ty.clone()));
+
+        let ret = match callee {
+            Value::Global(name) => self
+                .module
+                .signature(name)
+                .map(|s| s.ret)
+                .unwrap_or(Type::Void),
+            _ => Type::Void,
+        };
+
+        let (name, env_name) = self.fresh_thunk();
+        let env = Value::temp("env");
+        let mut insts = vec![];
+        let env_ty = if fields.is_empty() {
+            None
+        } else {
+            self.types.types.push(TypeDef {
+                name: env_name.clone(),
+                kind: AggrKind::Struct,
+                fields: fields.clone(),
+            });
+            Some(env_name)
+        };
+
+        let mut target = callee.clone();
+        let mut call_args = vec![];
+        if let Some(env_ty) = &env_ty {
+            for (i, ty) in fields.iter().enumerate() {
+                let p = format!("p{}", i);
+                insts.push(Inst::Field {
+                    dst: p.clone(),
+                    aggr: env_ty.clone(),
+                    base: env.clone(),
+                    index: i as u32,
+                });
+                let v = if ty.is_aggregate() {
+                    Value::Temp(p)
+                } else {
+                    let a = format!("a{}", i);
+                    insts.push(Inst::Load {
+                        dst: a.clone(),
+                        ty: ty.clone(),
+                        addr: Value::Temp(p),
+                    });
+                    Value::Temp(a)
+                };
+                if i == 0 && matches!(callee, Value::Temp(_)) {
+                    target = v;
+                } else {
+                    call_args.push((ty.clone(), v));
+                }
+            }
+        }
+        insts.push(Inst::Call {
+            dst: None,
+            ret,
+            callee: target,
+            args: call_args,
+            fixed: None,
+        });
+        if env_ty.is_some() {
+            insts.push(self.call(None, runtime::UNALLOC, vec![env]));
+        }
+
+        self.thunks.push(Function {
+            name: name.clone(),
+            linkage: Linkage::Local,
+            conv: CallConv::C,
+            params: vec![Param {
+                ty: Type::Ptr,
+                name: "env".into(),
+            }],
+            variadic: false,
+            ret: Type::Void,
+            blocks: vec![Block {
+                label: "start".into(),
+                insts,
+                term: Terminator::Ret(None),
+            }],
+        });
+        (name, env_ty)
+    }
+}
+
+impl<'a, 'b> FuncExpander<'a, 'b> {
+    fn new(x: &'b mut Expander<'a>, func: &Function) -> FuncExpander<'a, 'b> {
+        let mut names: HashSet<String> = func.params.iter().map(|p| p.name.clone()).collect();
+        let mut handlers = vec![];
+        for b in &func.blocks {
+            names.insert(b.label.clone());
+            for inst in &b.insts {
+                if let Some(dst) = inst.dst() {
+                    names.insert(dst.to_string());
+                }
+                if let Inst::Rescue { label } = inst {
+                    if !handlers.contains(label) {
+                        handlers.push(label.clone());
+                    }
+                }
+            }
+        }
+
+        FuncExpander {
+            x,
+            names,
+            ntemp: 0,
+            entry: vec![],
+            blocks: vec![],
+            insts: vec![],
+            handlers,
+            rescue: None,
+            raise_block: None,
+        }
+    }
+
+    /// A name that is not used for any temporary or label of the function.
+    fn fresh(&mut self) -> String {
+        loop {
+            let name = format!("rt.{}", self.ntemp);
+            self.ntemp += 1;
+            if self.names.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+
+    /// Reserve a stack slot in the entry block, so that loops do not grow the stack.
+    fn slot(&mut self, ty: Type) -> Value {
+        let dst = self.fresh();
+        self.entry.push(Inst::Alloca {
+            dst: dst.clone(),
+            ty,
+        });
+        Value::Temp(dst)
+    }
+
+    fn push(&mut self, inst: Inst) {
+        self.insts.push(inst);
+    }
+
+    fn call(&mut self, dst: Option<String>, name: &'static str, args: Vec<Value>) {
+        let inst = self.x.call(dst, name, args);
+        self.push(inst);
+    }
+
+    /// The address of a buffer holding `value`, an element of type `elem`.
+    fn buffer(&mut self, elem: &Type, value: &Value) -> Value {
+        if elem.is_aggregate() {
+            value.clone()
+        } else {
+            let slot = self.slot(elem.clone());
+            self.push(Inst::Store {
+                ty: elem.clone(),
+                value: value.clone(),
+                addr: slot.clone(),
+            });
+            slot
+        }
+    }
+
+    fn spawn(&mut self, start: &'static str, group: Option<&Value>, callee: &Value, args: &[(Type, Value)]) {
+        let (thunk, env_ty) = self.x.thunk(callee, args);
+        let env = match env_ty {
+            None => Value::Int(0),
+            Some(env_ty) => {
+                let env = self.fresh();
+                let size = self.x.size(&Type::Named(env_ty.clone()));
+                self.call(Some(env.clone()), runtime::ALLOC, vec![Value::Int(size)]);
+                let values = match callee {
+                    Value::Temp(_) => Some((Type::Ptr, callee.clone())),
+                    _ => None,
+                };
+                for (i, (ty, v)) in values.iter().chain(args).enumerate() {
+                    let p = self.fresh();
+                    self.push(Inst::Field {
+                        dst: p.clone(),
+                        aggr: env_ty.clone(),
+                        base: Value::Temp(env.clone()),
+                        index: i as u32,
+                    });
+                    if ty.is_aggregate() {
+                        self.push(Inst::Blit {
+                            ty: ty.clone(),
+                            dst: Value::Temp(p),
+                            src: v.clone(),
+                        });
+                    } else {
+                        self.push(Inst::Store {
+                            ty: ty.clone(),
+                            value: v.clone(),
+                            addr: Value::Temp(p),
+                        });
+                    }
+                }
+                Value::Temp(env)
+            }
+        };
+
+        let mut call_args = vec![];
+        call_args.extend(group.cloned());
+        call_args.push(Value::Global(thunk));
+        call_args.push(env);
+        self.call(None, start, call_args);
+    }
+
+    /// The handler stack and depth slots, created on first use.
+    fn rescue_slots(&mut self) -> (Value, Value) {
+        if let Some(slots) = &self.rescue {
+            return slots.clone();
+        }
+        let stack = self.slot(Type::Array(Box::new(Type::I32), 0));
+        let depth = self.slot(Type::I32);
+        self.rescue = Some((stack, depth));
+        self.rescue.clone().unwrap()
+    }
+
+    fn depth_add(&mut self, delta: i64) -> Value {
+        let (_, depth) = self.rescue_slots();
+        let d = self.fresh();
+        let d1 = self.fresh();
+        self.push(Inst::Load {
+            dst: d.clone(),
+            ty: Type::I32,
+            addr: depth.clone(),
+        });
+        self.push(Inst::Bin {
+            dst: d1.clone(),
+            op: BinOp::Add,
+            ty: Type::I32,
+            lhs: Value::Temp(d.clone()),
+            rhs: Value::Int(delta),
+        });
+        self.push(Inst::Store {
+            ty: Type::I32,
+            value: Value::Temp(d1.clone()),
+            addr: depth,
+        });
+        if delta > 0 {
+            Value::Temp(d)
+        } else {
+            Value::Temp(d1)
+        }
+    }
+
+    fn stack_entry(&mut self, index: Value) -> Value {
+        let (stack, _) = self.rescue_slots();
+        let p = self.fresh();
+        self.push(Inst::Index {
+            dst: p.clone(),
+            elem: Type::I32,
+            base: stack,
+            index,
+        });
+        Value::Temp(p)
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Alloc { dst, ty } => {
+                let size = self.x.size(ty);
+                self.call(Some(dst.clone()), runtime::ALLOC, vec![Value::Int(size)]);
+            }
+            Inst::Unalloc { ptr } => self.call(None, runtime::UNALLOC, vec![ptr.clone()]),
+            Inst::ChanNew { dst, elem, cap } => {
+                let size = self.x.size(elem);
+                self.call(
+                    Some(dst.clone()),
+                    runtime::CHAN_NEW,
+                    vec![Value::Int(size), cap.clone()],
+                );
+            }
+            Inst::Send { elem, chan, value } => {
+                let buf = self.buffer(elem, value);
+                self.call(None, runtime::CHAN_SEND, vec![chan.clone(), buf]);
+            }
+            Inst::Recv { dst, elem, chan } => {
+                let slot = self.slot(elem.clone());
+                self.call(None, runtime::CHAN_RECV, vec![chan.clone(), slot.clone()]);
+                if elem.is_aggregate() {
+                    self.push(Inst::Copy {
+                        dst: dst.clone(),
+                        ty: Type::Ptr,
+                        arg: slot,
+                    });
+                } else {
+                    self.push(Inst::Load {
+                        dst: dst.clone(),
+                        ty: elem.clone(),
+                        addr: slot,
+                    });
+                }
+            }
+            Inst::CanSend { dst, chan } => {
+                self.call(Some(dst.clone()), runtime::CHAN_CANSEND, vec![chan.clone()])
+            }
+            Inst::CanRecv { dst, chan } => {
+                self.call(Some(dst.clone()), runtime::CHAN_CANRECV, vec![chan.clone()])
+            }
+            Inst::Proc { callee, args } => self.spawn(runtime::PROC, None, callee, args),
+            Inst::Task { callee, args } => self.spawn(runtime::TASK, None, callee, args),
+            Inst::ParBegin { dst } => self.call(Some(dst.clone()), runtime::PAR_BEGIN, vec![]),
+            Inst::ParSpawn {
+                group,
+                callee,
+                args,
+            } => self.spawn(runtime::PAR_SPAWN, Some(group), callee, args),
+            Inst::ParJoin { group } => self.call(None, runtime::PAR_JOIN, vec![group.clone()]),
+            Inst::Rescue { label } => {
+                let id = self.handlers.iter().position(|h| h == label).unwrap();
+                let d = self.depth_add(1);
+                let p = self.stack_entry(d);
+                self.push(Inst::Store {
+                    ty: Type::I32,
+                    value: Value::Int(id as i64),
+                    addr: p,
+                });
+            }
+            Inst::Unrescue => {
+                self.depth_add(-1);
+            }
+            Inst::Box { dst, ty, value } => {
+                let size = self.x.size(ty);
+                self.call(Some(dst.clone()), runtime::ALLOC, vec![Value::Int(size)]);
+                let inst = if ty.is_aggregate() {
+                    Inst::Blit {
+                        ty: ty.clone(),
+                        dst: Value::temp(dst),
+                        src: value.clone(),
+                    }
+                } else {
+                    Inst::Store {
+                        ty: ty.clone(),
+                        value: value.clone(),
+                        addr: Value::temp(dst),
+                    }
+                };
+                self.push(inst);
+            }
+            Inst::Unbox { dst, ty, poly } => {
+                let inst = if ty.is_aggregate() {
+                    Inst::Copy {
+                        dst: dst.clone(),
+                        ty: Type::Ptr,
+                        arg: poly.clone(),
+                    }
+                } else {
+                    Inst::Load {
+                        dst: dst.clone(),
+                        ty: ty.clone(),
+                        addr: poly.clone(),
+                    }
+                };
+                self.push(inst);
+            }
+            inst => self.push(inst.clone()),
+        }
+    }
+
+    fn finish_block(&mut self, label: String, term: Terminator) {
+        self.blocks.push(Block {
+            label,
+            insts: std::mem::take(&mut self.insts),
+            term,
+        });
+    }
+
+    /// The block every `raise` of the function jumps to when there are handlers.
+    fn raise_block(&mut self) -> String {
+        if let Some(label) = &self.raise_block {
+            return label.clone();
+        }
+
+        let label = self.fresh();
+        let unhandled = self.fresh();
+        let dispatch = self.fresh();
+        self.raise_block = Some(label.clone());
+
+        let saved = std::mem::take(&mut self.insts);
+        let (_, depth) = self.rescue_slots();
+        let d = self.fresh();
+        let empty = self.fresh();
+        self.push(Inst::Load {
+            dst: d.clone(),
+            ty: Type::I32,
+            addr: depth,
+        });
+        self.push(Inst::Cmp {
+            dst: empty.clone(),
+            op: CmpOp::Eq,
+            ty: Type::I32,
+            lhs: Value::Temp(d),
+            rhs: Value::Int(0),
+        });
+        self.finish_block(
+            label.clone(),
+            Terminator::Br {
+                cond: Value::Temp(empty),
+                then: unhandled.clone(),
+                else_: dispatch.clone(),
+            },
+        );
+
+        self.call(None, runtime::RAISE, vec![]);
+        self.finish_block(unhandled, Terminator::Hlt);
+
+        let d1 = self.depth_add(-1);
+        let p = self.stack_entry(d1);
+        let id = self.fresh();
+        self.push(Inst::Load {
+            dst: id.clone(),
+            ty: Type::I32,
+            addr: p,
+        });
+        let mut cases: Vec<(i64, String)> = self
+            .handlers
+            .iter()
+            .enumerate()
+            .map(|(i, h)| (i as i64, h.clone()))
+            .collect();
+        let (_, default) = cases.pop().unwrap();
+        self.finish_block(
+            dispatch,
+            Terminator::Switch {
+                ty: Type::I32,
+                value: Value::Temp(id),
+                default,
+                cases,
+            },
+        );
+
+        self.insts = saved;
+        label
+    }
+
+    fn alt(&mut self, cases: &[AltCase]) -> Terminator {
+        let n = cases.len();
+        if self.x.types.typedef(runtime::ALT_CASE).is_none() {
+            self.x.types.types.push(runtime::alt_case());
+        }
+        let case_ty = Type::Named(runtime::ALT_CASE.to_string());
+        let array = self.slot(Type::Array(Box::new(case_ty), n as u64));
+
+        for (i, c) in cases.iter().enumerate() {
+            let (dir, buf) = match c {
+                AltCase::Recv { slot, .. } => {
+                    (runtime::ALT_RECV, slot.clone().unwrap_or(Value::Int(0)))
+                }
+                AltCase::Send { elem, value, .. } => (runtime::ALT_SEND, self.buffer(elem, value)),
+            };
+            let entry = self.fresh();
+            self.push(Inst::Index {
+                dst: entry.clone(),
+                elem: Type::Named(runtime::ALT_CASE.to_string()),
+                base: array.clone(),
+                index: Value::Int(i as i64),
+            });
+            let fields = [
+                (Type::Ptr, c.chan().clone()),
+                (Type::I64, Value::Int(dir)),
+                (Type::Ptr, buf),
+            ];
+            for (index, (ty, value)) in fields.into_iter().enumerate() {
+                let p = self.fresh();
+                self.push(Inst::Field {
+                    dst: p.clone(),
+                    aggr: runtime::ALT_CASE.to_string(),
+                    base: Value::Temp(entry.clone()),
+                    index: index as u32,
+                });
+                self.push(Inst::Store {
+                    ty,
+                    value,
+                    addr: Value::Temp(p),
+                });
+            }
+        }
+
+        let k = self.fresh();
+        self.call(
+            Some(k.clone()),
+            runtime::ALT,
+            vec![array, Value::Int(n as i64)],
+        );
+        let mut targets: Vec<(i64, String)> = cases
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (i as i64, c.target().to_string()))
+            .collect();
+        let (_, default) = targets.pop().unwrap();
+        if targets.is_empty() {
+            Terminator::Jmp(default)
+        } else {
+            Terminator::Switch {
+                ty: Type::I32,
+                value: Value::Temp(k),
+                default,
+                cases: targets,
+            }
+        }
+    }
+
+    fn run(mut self, func: &Function) -> Function {
+        for b in &func.blocks {
+            let at = self.blocks.len();
+            for inst in &b.insts {
+                self.inst(inst);
+            }
+            let term = match &b.term {
+                Terminator::Alt(cases) => self.alt(cases),
+                Terminator::Raise if self.handlers.is_empty() => {
+                    self.call(None, runtime::RAISE, vec![]);
+                    Terminator::Hlt
+                }
+                Terminator::Raise => Terminator::Jmp(self.raise_block()),
+                term => term.clone(),
+            };
+            // The raise block is created while another block is being filled: keep the blocks
+            // in their original order, so that the entry block stays first.
+            self.finish_block(b.label.clone(), term);
+            let block = self.blocks.pop().unwrap();
+            self.blocks.insert(at, block);
+        }
+
+        // The handler stack needs a size, known only now.
+        let nrescue = func
+            .blocks
+            .iter()
+            .flat_map(|b| &b.insts)
+            .filter(|i| matches!(i, Inst::Rescue { .. }))
+            .count() as u64;
+        let mut entry = std::mem::take(&mut self.entry);
+        if let Some((Value::Temp(stack), depth)) = &self.rescue {
+            for inst in entry.iter_mut() {
+                if let Inst::Alloca { dst, ty } = inst {
+                    if dst == stack {
+                        *ty = Type::Array(Box::new(Type::I32), nrescue.max(1));
+                    }
+                }
+            }
+            entry.push(Inst::Store {
+                ty: Type::I32,
+                value: Value::Int(0),
+                addr: depth.clone(),
+            });
+        }
+        entry.append(&mut self.blocks[0].insts);
+        self.blocks[0].insts = entry;
+
+        Function {
+            name: func.name.clone(),
+            linkage: func.linkage,
+            conv: func.conv,
+            params: func.params.clone(),
+            variadic: func.variadic,
+            ret: func.ret.clone(),
+            blocks: self.blocks,
+        }
+    }
+}
+
+/// Expand the Alef-specific instructions and terminators of a verified module.
+///
+/// The runtime functions that are used are declared in the result, unless the module already
+/// declares them.
+pub fn expand(module: &Module) -> Module {
+    let mut x = Expander {
+        module,
+        types: Module {
+            types: module.types.clone(),
+            ..Module::default()
+        },
+        thunks: vec![],
+        used: BTreeSet::new(),
+        globals: module
+            .data
+            .iter()
+            .map(|d| d.name.clone())
+            .chain(module.externs.iter().map(|e| e.name.clone()))
+            .chain(module.funcs.iter().map(|f| f.name.clone()))
+            .collect(),
+        nthunk: 0,
+    };
+
+    let mut funcs = vec![];
+    for func in &module.funcs {
+        let fx = FuncExpander::new(&mut x, func);
+        funcs.push(fx.run(func));
+    }
+    funcs.append(&mut x.thunks);
+
+    let mut externs = module.externs.clone();
+    for name in &x.used {
+        if module.signature(name).is_none() {
+            externs.push(Extern {
+                name: name.to_string(),
+                sig: runtime::signature(name).unwrap(),
+            });
+        }
+    }
+
+    Module {
+        imports: module.imports.clone(),
+        types: x.types.types,
+        data: module.data.clone(),
+        externs,
+        funcs,
+    }
+}
+
+/// The signature of the thunks generated for `proc`, `task` and `spawn`, which the runtime calls.
+pub fn thunk_signature() -> Signature {
+    Signature {
+        conv: CallConv::C,
+        params: vec![Type::Ptr],
+        variadic: false,
+        ret: Type::Void,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alef_ir::read::read;
+    use alef_ir::verify::verify;
+
+    fn expanded(src: &str) -> Module {
+        let m = read(src).unwrap();
+        verify(&m).unwrap();
+        let x = expand(&m);
+        if let Err(errs) = verify(&x) {
+            panic!("{}\n{:?}", x, errs);
+        }
+        x
+    }
+
+    fn is_core(m: &Module) -> bool {
+        m.funcs.iter().flat_map(|f| &f.blocks).all(|b| {
+            !matches!(b.term, Terminator::Alt(_) | Terminator::Raise)
+                && b.insts.iter().all(|i| {
+                    matches!(
+                        i,
+                        Inst::Bin { .. }
+                            | Inst::Cmp { .. }
+                            | Inst::Un { .. }
+                            | Inst::Conv { .. }
+                            | Inst::Copy { .. }
+                            | Inst::Alloca { .. }
+                            | Inst::Load { .. }
+                            | Inst::Store { .. }
+                            | Inst::Field { .. }
+                            | Inst::Index { .. }
+                            | Inst::Blit { .. }
+                            | Inst::Call { .. }
+                    )
+                })
+        })
+    }
+
+    #[test]
+    fn channels() {
+        let m = expanded(
+            "
+            type %P = { i32, f64 }
+            fn $f(ptr %p) -> i32 {
+            @start:
+                %c = chan i32, 0
+                %d = chan %P, 2
+                send i32 %c, 1
+                send %P %d, %p
+                %v = recv i32 %c
+                %q = recv %P %d
+                %k = canrecv %c
+                alt {
+                    recv i32 %c -> @a
+                    send %P %d, %q -> @b
+                }
+            @a:
+                ret i32 %v
+            @b:
+                ret i32 %k
+            }",
+        );
+        assert!(is_core(&m));
+        assert!(m.typedef(runtime::ALT_CASE).is_some());
+        for name in [
+            runtime::CHAN_NEW,
+            runtime::CHAN_SEND,
+            runtime::CHAN_RECV,
+            runtime::CHAN_CANRECV,
+            runtime::ALT,
+        ] {
+            assert!(m.externs.iter().any(|e| e.name == name), "{} not declared", name);
+        }
+        // Stack slots are all in the entry block.
+        let f = m.func("f").unwrap();
+        assert!(f.blocks[1..]
+            .iter()
+            .flat_map(|b| &b.insts)
+            .all(|i| !matches!(i, Inst::Alloca { .. })));
+    }
+
+    #[test]
+    fn thunks() {
+        let m = expanded(
+            "
+            type %P = { i32, f64 }
+            fn $g(i32 %a, %P %p) -> i32 {
+            @start:
+                ret i32 %a
+            }
+            fn $h() -> void {
+            @start:
+                ret
+            }
+            fn $f(ptr %p, ptr %fp) -> void {
+            @start:
+                proc $g(i32 1, %P %p)
+                task $h()
+                %g = par
+                spawn %g, %fp(i64 2)
+                join %g
+                ret
+            }",
+        );
+        assert!(is_core(&m));
+        let thunks: Vec<_> = m
+            .funcs
+            .iter()
+            .filter(|f| f.name.starts_with("alef.thunk"))
+            .collect();
+        assert_eq!(thunks.len(), 3);
+        assert!(thunks.iter().all(|t| t.signature() == thunk_signature()));
+        // `task $h()` has nothing to pass.
+        assert_eq!(
+            m.types
+                .iter()
+                .filter(|t| t.name.starts_with("alef.env"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn rescue() {
+        let m = expanded(
+            "
+            fn $f(i32 %x) -> i32 {
+            @start:
+                rescue @h1
+                rescue @h2
+                br %x, @r, @ok
+            @r:
+                raise
+            @ok:
+                unrescue
+                unrescue
+                ret i32 0
+            @h1:
+                ret i32 1
+            @h2:
+                raise
+            }
+            fn $g() -> void {
+            @start:
+                raise
+            }",
+        );
+        assert!(is_core(&m));
+        let f = m.func("f").unwrap();
+        assert_eq!(f.blocks[0].label, "start");
+        assert!(f.blocks[0].insts.iter().any(
+            |i| matches!(i, Inst::Alloca { ty: Type::Array(_, 2), .. })
+        ));
+        let g = m.func("g").unwrap();
+        assert_eq!(g.blocks[0].term, Terminator::Hlt);
+    }
+}
@@ -0,0 +1,28 @@
+//! Code generation from the Alef IR.
+//!
+//! Every backend takes a verified `alef_ir::module::Module`, expands the Alef-specific
+//! instructions into calls to the runtime with `expand::expand` and translates the result.
+
+/// The interface with the runtime library.
+pub mod runtime;
+
+/// Expansion of the Alef-specific instructions into the core of the IR.
+pub mod expand;
+
+/// QBE's SSA text.
+pub mod qbe;
+
+/// The System V x86-64 calling convention.
+pub mod abi;
+
+/// Errors reported by the backends.
+pub mod err;
+
+/// Native object files through Cranelift.
+pub mod cranelift;
+
+/// Portable C99.
+pub mod c;
+
+/// LLVM's textual IR.
+pub mod llvm;
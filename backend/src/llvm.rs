@@ -0,0 +1,1011 @@
+//! Translate the IR into LLVM's textual IR, to be compiled with `llc` or `clang`.
+//!
+//! The output uses opaque pointers (the default from LLVM 15; LLVM 14 needs `-opaque-pointers`)
+//! and no LLVM library is linked. Integer types map onto LLVM's by width: `byte` is `i8`, `sint`
+//! and `usint` are `i16`, `int` and `uint` are `i32`, `lint` and `ulint` are `i64`; signedness
+//! is carried by the operations, as in LLVM. Narrow parameters and results are marked `signext`.
+//!
+//! LLVM does not apply the C calling convention to aggregates passed by value, so signatures are
+//! lowered with `abi` (System V x86-64): aggregates are split in `i64` and `double` eightbytes,
+//! passed `byval` or returned through an `sret` pointer, the way `clang` does.
+//!
+//! IR temporaries are assigned once but their definitions need not dominate their uses, which
+//! LLVM requires; temporaries used outside the part of the block following their definition are
+//! stored to a stack slot and reloaded where they are used, and `mem2reg` removes the slots.
+//! All stack slots, including `alloca`s, are created in an entry block added to each function.
+
+use crate::abi::{self, Arg, Class, Lowered, Ret};
+use crate::expand::expand;
+use alef_ir::func::{CallConv, Function, Linkage, Signature};
+use alef_ir::inst::{BinOp, CmpOp, ConvOp, Inst, Terminator, UnOp, Value};
+use alef_ir::module::{Data, DataItem, Module};
+use alef_ir::ty::{AggrKind, Type, TypeDef};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// The declaration of the intrinsic used for `blit`.
+const MEMCPY: &str = "declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)";
+
+/// The declaration of the intrinsic used for `hlt`.
+const TRAP: &str = "declare void @llvm.trap()";
+
+/// The declaration of the intrinsic used for `vastart`.
+const VA_START: &str = "declare void @llvm.va_start(ptr)";
+
+/// Translate a verified module into LLVM IR text.
+pub fn emit(module: &Module) -> String {
+    let module = expand(module);
+    let mut e = Emitter {
+        module: &module,
+        out: String::new(),
+        memcpy: false,
+        trap: false,
+        va_start: false,
+    };
+    e.module();
+    e.out
+}
+
+/// Make `name` a valid LLVM identifier, quoting it if needed.
+fn ident(name: &str) -> String {
+    let plain = name.chars().enumerate().all(|(i, c)| {
+        c.is_ascii_alphabetic()
+            || matches!(c, '-' | '$' | '.' | '_')
+            || (i > 0 && c.is_ascii_digit())
+    });
+    if plain && !name.is_empty() {
+        return name.to_string();
+    }
+
+    let mut s = String::from("\"");
+    for b in name.bytes() {
+        match b {
+            b'"' | b'\\' => write!(s, "\\{:02X}", b).unwrap(),
+            b' '..=b'~' => s.push(b as char),
+            b => write!(s, "\\{:02X}", b).unwrap(),
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// The LLVM type of a value of type `ty`; aggregates are referred to by address.
+fn scalar(ty: &Type) -> &'static str {
+    match ty {
+        Type::Void => "void",
+        Type::I8 => "i8",
+        Type::I16 => "i16",
+        Type::I32 => "i32",
+        Type::I64 => "i64",
+        Type::F64 => "double",
+        _ => "ptr",
+    }
+}
+
+/// The parameter attributes C compilers use for narrow integers.
+fn ext_attr(ty: &Type) -> &'static str {
+    match ty {
+        Type::I8 | Type::I16 => " signext",
+        _ => "",
+    }
+}
+
+fn class(c: &Class) -> &'static str {
+    match c {
+        Class::Int => "i64",
+        Class::Sse => "double",
+    }
+}
+
+/// The type of an aggregate returned in registers.
+fn split(classes: &[Class]) -> String {
+    match classes {
+        [c] => class(c).into(),
+        cs => {
+            let cs: Vec<&str> = cs.iter().map(class).collect();
+            format!("{{ {} }}", cs.join(", "))
+        }
+    }
+}
+
+/// Sign extend the constant `v` from the width of `ty`.
+fn sext_const(ty: &Type, v: i64) -> i64 {
+    match ty {
+        Type::I8 => v as i8 as i64,
+        Type::I16 => v as i16 as i64,
+        Type::I32 => v as i32 as i64,
+        _ => v,
+    }
+}
+
+/// A float constant: decimal when LLVM accepts it, i.e. when it is exact, hexadecimal otherwise.
+fn float_lit(x: f64) -> String {
+    if x.is_finite() && (x * 1024.0).fract() == 0.0 && x.abs() < 1e15 {
+        format!("{:?}", x)
+    } else {
+        format!("0x{:016X}", x.to_bits())
+    }
+}
+
+struct Emitter<'a> {
+    module: &'a Module,
+    out: String,
+    memcpy: bool,
+    trap: bool,
+    va_start: bool,
+}
+
+impl<'a> Emitter<'a> {
+    /// The LLVM type of `ty` as stored in memory.
+    fn mem_type(&self, ty: &Type) -> String {
+        match ty {
+            Type::Named(name) => format!("%{}", ident(name)),
+            Type::Array(of, n) => format!("[{} x {}]", n, self.mem_type(of)),
+            ty => scalar(ty).into(),
+        }
+    }
+
+    /// The return type and parameter list of a lowered signature; names are given to the
+    /// parameters in definitions.
+    fn signature(
+        &self,
+        sig: &Signature,
+        lowered: &Lowered,
+        names: Option<&[String]>,
+    ) -> (String, String) {
+        let mut params = vec![];
+        if let Ret::Memory(_) = lowered.ret {
+            let ty = self.mem_type(&sig.ret);
+            params.push(match names {
+                Some(_) => format!("ptr sret({}) %.sret", ty),
+                None => format!("ptr sret({})", ty),
+            });
+        }
+        for (i, (ty, arg)) in sig.params.iter().zip(&lowered.params).enumerate() {
+            let name = |suffix: &str| match names {
+                Some(names) => format!(" %{}", ident(&format!("{}{}", names[i], suffix))),
+                None => String::new(),
+            };
+            match arg {
+                Arg::Direct(ty) => {
+                    params.push(format!("{}{}{}", scalar(ty), ext_attr(ty), name("")))
+                }
+                Arg::Split(classes) => {
+                    for (j, c) in classes.iter().enumerate() {
+                        params.push(format!("{}{}", class(c), name(&format!(".{}", j))));
+                    }
+                }
+                Arg::Memory(_) => {
+                    let align = self.module.layout(ty).unwrap().align.max(8);
+                    params.push(format!(
+                        "ptr byval({}) align {}{}",
+                        self.mem_type(ty),
+                        align,
+                        name("")
+                    ));
+                }
+            }
+        }
+        if sig.variadic {
+            params.push("...".into());
+        }
+
+        let ret = match &lowered.ret {
+            Ret::Void | Ret::Memory(_) => "void".into(),
+            Ret::Direct(ty) => format!("{} {}", ext_attr(ty), scalar(ty))
+                .trim_start()
+                .to_string(),
+            Ret::Split(classes) => split(classes),
+        };
+        (ret, format!("({})", params.join(", ")))
+    }
+
+    fn module(&mut self) {
+        for t in &self.module.types {
+            self.typedef(t);
+        }
+        if !self.module.types.is_empty() {
+            self.out.push('\n');
+        }
+
+        for d in &self.module.data {
+            self.data(d);
+        }
+        if !self.module.data.is_empty() {
+            self.out.push('\n');
+        }
+
+        for e in &self.module.externs {
+            let lowered = abi::lower(self.module, &e.sig);
+            let (ret, params) = self.signature(&e.sig, &lowered, None);
+            writeln!(self.out, "declare {} @{}{}", ret, ident(&e.name), params).unwrap();
+        }
+        if !self.module.externs.is_empty() {
+            self.out.push('\n');
+        }
+
+        let mut fns = vec![];
+        for f in &self.module.funcs {
+            let mut fe = FuncEmitter::new(self, f);
+            fe.function(f);
+            let (memcpy, trap, va_start, out) = (fe.memcpy, fe.trap, fe.va_start, fe.out);
+            self.memcpy |= memcpy;
+            self.trap |= trap;
+            self.va_start |= va_start;
+            fns.push(out);
+        }
+        self.out.push_str(&fns.join("\n"));
+
+        if self.memcpy || self.trap || self.va_start {
+            self.out.push('\n');
+        }
+        if self.memcpy {
+            writeln!(self.out, "{}", MEMCPY).unwrap();
+        }
+        if self.trap {
+            writeln!(self.out, "{}", TRAP).unwrap();
+        }
+        if self.va_start {
+            writeln!(self.out, "{}", VA_START).unwrap();
+        }
+    }
+
+    fn typedef(&mut self, t: &TypeDef) {
+        let body = match t.kind {
+            AggrKind::Struct => {
+                let fields: Vec<String> = t.fields.iter().map(|f| self.mem_type(f)).collect();
+                format!("{{ {} }}", fields.join(", "))
+            }
+            // LLVM has no unions: use the most aligned member, padded to the size of the union.
+            AggrKind::Union => {
+                let layout = self.module.layout(&Type::Named(t.name.clone())).unwrap();
+                let widest = t
+                    .fields
+                    .iter()
+                    .max_by_key(|f| self.module.layout(f).unwrap().align)
+                    .unwrap();
+                let size = self.module.layout(widest).unwrap().size;
+                match layout.size - size {
+                    0 => format!("{{ {} }}", self.mem_type(widest)),
+                    pad => format!("{{ {}, [{} x i8] }}", self.mem_type(widest), pad),
+                }
+            }
+        };
+        writeln!(self.out, "%{} = type {}", ident(&t.name), body).unwrap();
+    }
+
+    fn data(&mut self, d: &Data) {
+        let mut types = vec![];
+        let mut inits = vec![];
+        for item in &d.items {
+            let (ty, init) = match item {
+                DataItem::Str(s) => {
+                    let mut init = String::from("c\"");
+                    for b in s.bytes().chain(std::iter::once(0)) {
+                        match b {
+                            b'"' | b'\\' => write!(init, "\\{:02X}", b).unwrap(),
+                            b' '..=b'~' => init.push(b as char),
+                            b => write!(init, "\\{:02X}", b).unwrap(),
+                        }
+                    }
+                    init.push('"');
+                    (format!("[{} x i8]", item.size()), init)
+                }
+                DataItem::Runestr(s) => {
+                    let runes: Vec<String> = s
+                        .chars()
+                        .chain(std::iter::once('\0'))
+                        .map(|c| format!("i32 {}", c as u32))
+                        .collect();
+                    let n = runes.len();
+                    (format!("[{} x i32]", n), format!("[{}]", runes.join(", ")))
+                }
+                DataItem::Int(ty, v) => (scalar(ty).into(), sext_const(ty, *v).to_string()),
+                DataItem::Float(x) => ("double".into(), float_lit(*x)),
+                DataItem::Addr(name) => ("ptr".into(), format!("@{}", ident(name))),
+                DataItem::Zero(n) => (format!("[{} x i8]", n), "zeroinitializer".into()),
+            };
+            inits.push(format!("{} {}", ty, init));
+            types.push(ty);
+        }
+
+        let linkage = match d.linkage {
+            Linkage::Local => "internal ",
+            Linkage::Export => "",
+        };
+        writeln!(
+            self.out,
+            "@{} = {}global <{{ {} }}> <{{ {} }}>, align {}",
+            ident(&d.name),
+            linkage,
+            types.join(", "),
+            inits.join(", "),
+            d.align()
+        )
+        .unwrap();
+    }
+}
+
+/// Emission state of a single function.
+struct FuncEmitter<'a, 'e> {
+    e: &'e Emitter<'a>,
+    out: String,
+    /// Instructions of the entry block: stack slots and parameter setup.
+    entry: Vec<String>,
+    types: HashMap<&'a str, Type>,
+    /// Temporaries kept in a stack slot, with the name of the slot.
+    slots: HashMap<&'a str, String>,
+    /// The name of the LLVM block of each IR block.
+    labels: HashMap<&'a str, String>,
+    names: HashSet<String>,
+    ntemp: usize,
+    /// The temporaries defined so far in the current block.
+    local: HashSet<&'a str>,
+    lowered: Lowered,
+    memcpy: bool,
+    trap: bool,
+    va_start: bool,
+}
+
+impl<'a, 'e> FuncEmitter<'a, 'e> {
+    fn new(e: &'e Emitter<'a>, f: &'a Function) -> FuncEmitter<'a, 'e> {
+        let mut types = HashMap::new();
+        let mut names = HashSet::new();
+        let mut def_block = HashMap::new();
+        for p in &f.params {
+            let ty = if p.ty.is_aggregate() {
+                Type::Ptr
+            } else {
+                p.ty.clone()
+            };
+            types.insert(p.name.as_str(), ty);
+            names.insert(p.name.clone());
+        }
+        for (i, b) in f.blocks.iter().enumerate() {
+            for inst in &b.insts {
+                if let (Some(dst), Some(ty)) = (inst.dst(), inst.dst_type()) {
+                    types.insert(dst, ty);
+                    names.insert(dst.to_string());
+                    // Stack slots are all created in the entry block, which dominates every use.
+                    if !matches!(inst, Inst::Alloca { .. }) {
+                        def_block.insert(dst, i);
+                    }
+                }
+            }
+        }
+
+        // A temporary needs a slot if it is used in another block, or in its own block before
+        // its definition (through a loop).
+        let mut slotted = HashSet::new();
+        for (i, b) in f.blocks.iter().enumerate() {
+            let mut seen = HashSet::new();
+            let all_uses = b
+                .insts
+                .iter()
+                .map(|inst| (inst.uses(), inst.dst()))
+                .chain(std::iter::once((b.term.uses(), None)));
+            for (uses, dst) in all_uses {
+                for v in uses {
+                    if let Value::Temp(t) = v {
+                        let outside = def_block.get(t.as_str()).is_some_and(|d| *d != i);
+                        let early =
+                            def_block.get(t.as_str()) == Some(&i) && !seen.contains(t.as_str());
+                        if outside || early {
+                            slotted.insert(t.as_str());
+                        }
+                    }
+                }
+                if let Some(dst) = dst {
+                    seen.insert(dst);
+                }
+            }
+        }
+
+        let mut fe = FuncEmitter {
+            e,
+            out: String::new(),
+            entry: vec![],
+            types,
+            slots: HashMap::new(),
+            labels: HashMap::new(),
+            names,
+            ntemp: 0,
+            local: HashSet::new(),
+            lowered: abi::lower(e.module, &f.signature()),
+            memcpy: false,
+            trap: false,
+            va_start: false,
+        };
+        for b in &f.blocks {
+            let label = if fe.names.insert(b.label.clone()) {
+                b.label.clone()
+            } else {
+                fe.fresh(&b.label)
+            };
+            fe.labels.insert(&b.label, label);
+        }
+        let mut slotted: Vec<&str> = slotted.into_iter().collect();
+        slotted.sort_by_key(|t| {
+            f.blocks
+                .iter()
+                .position(|b| b.insts.iter().any(|i| i.dst() == Some(t)))
+        });
+        for t in slotted {
+            let slot = fe.fresh(&format!("{}.addr", t));
+            let ty = scalar(&fe.types[t]);
+            fe.entry.push(format!("%{} = alloca {}", ident(&slot), ty));
+            fe.slots.insert(t, slot);
+        }
+        fe
+    }
+
+    /// A local name not used by the function, made from `hint`.
+    fn fresh(&mut self, hint: &str) -> String {
+        if self.names.insert(hint.to_string()) {
+            return hint.to_string();
+        }
+        loop {
+            let name = format!("{}.{}", hint, self.ntemp);
+            self.ntemp += 1;
+            if self.names.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+
+    /// A fresh temporary for intermediate results.
+    fn tmp(&mut self) -> String {
+        let name = self.fresh(".t");
+        format!("%{}", ident(&name))
+    }
+
+    fn line(&mut self, s: String) {
+        writeln!(self.out, "  {}", s).unwrap();
+    }
+
+    /// A stack slot in the entry block.
+    fn slot(&mut self, ty: &str, align: u64) -> String {
+        let name = self.tmp();
+        self.entry
+            .push(format!("{} = alloca {}, align {}", name, ty, align));
+        name
+    }
+
+    /// The type of a value, `ptr` for globals and the given default for constants.
+    fn type_of(&self, v: &Value, default: &Type) -> Type {
+        match v {
+            Value::Temp(t) => self.types.get(t.as_str()).cloned().unwrap_or(Type::Ptr),
+            Value::Global(_) => Type::Ptr,
+            _ => default.clone(),
+        }
+    }
+
+    /// An operand of type `ty`, reloading temporaries kept in a slot.
+    fn operand(&mut self, ty: &Type, v: &Value) -> String {
+        match v {
+            Value::Temp(t) => {
+                if let Some(slot) = self.slots.get(t.as_str()).cloned() {
+                    if !self.local.contains(t.as_str()) {
+                        let r = self.tmp();
+                        let ty = scalar(&self.types[t.as_str()]);
+                        self.line(format!("{} = load {}, ptr %{}", r, ty, ident(&slot)));
+                        return r;
+                    }
+                }
+                format!("%{}", ident(t))
+            }
+            Value::Global(name) => format!("@{}", ident(name)),
+            Value::Int(i) if ty.is_float() => float_lit(*i as f64),
+            Value::Int(0) if !ty.is_int() => "null".into(),
+            Value::Int(i) if !ty.is_int() => format!("inttoptr (i64 {} to ptr)", i),
+            Value::Int(i) => sext_const(ty, *i).to_string(),
+            Value::Float(x) => float_lit(*x),
+        }
+    }
+
+    /// Record the definition of `dst`, storing it to its slot if it has one.
+    fn defined(&mut self, dst: &'a str) {
+        self.local.insert(dst);
+        if let Some(slot) = self.slots.get(dst).cloned() {
+            let ty = scalar(&self.types[dst]);
+            self.line(format!(
+                "store {} %{}, ptr %{}",
+                ty,
+                ident(dst),
+                ident(&slot)
+            ));
+        }
+    }
+
+    fn label(&self, l: &str) -> String {
+        format!("%{}", ident(&self.labels[l]))
+    }
+
+    fn function(&mut self, f: &'a Function) {
+        let linkage = match f.linkage {
+            Linkage::Local => "internal ",
+            Linkage::Export => "",
+        };
+        let names: Vec<String> = f.params.iter().map(|p| p.name.clone()).collect();
+        let lowered = self.lowered.clone();
+        let (ret, params) = self.e.signature(&f.signature(), &lowered, Some(&names));
+        let head = format!("define {}{} @{}{} {{", linkage, ret, ident(&f.name), params);
+
+        // Aggregates split in registers are stored back to memory.
+        for (p, arg) in f.params.iter().zip(&lowered.params) {
+            if let Arg::Split(classes) = arg {
+                let slot = format!("%{}", ident(&p.name));
+                self.entry
+                    .push(format!("{} = alloca {{ i64, i64 }}, align 8", slot));
+                for (j, c) in classes.iter().enumerate() {
+                    let part = format!("%{}", ident(&format!("{}.{}", p.name, j)));
+                    let addr = self.tmp();
+                    self.entry.push(format!(
+                        "{} = getelementptr inbounds i8, ptr {}, i64 {}",
+                        addr,
+                        slot,
+                        8 * j
+                    ));
+                    self.entry
+                        .push(format!("store {} {}, ptr {}", class(c), part, addr));
+                }
+            }
+        }
+
+        for b in &f.blocks {
+            writeln!(self.out, "{}:", ident(&self.labels[b.label.as_str()])).unwrap();
+            self.local.clear();
+            for inst in &b.insts {
+                self.inst(inst);
+                if let Some(dst) = inst.dst() {
+                    self.defined(dst);
+                }
+            }
+            self.term(&b.term);
+        }
+
+        let body = std::mem::take(&mut self.out);
+        let entry = self.fresh("entry");
+        writeln!(self.out, "{}", head).unwrap();
+        writeln!(self.out, "{}:", ident(&entry)).unwrap();
+        for line in std::mem::take(&mut self.entry) {
+            self.line(line);
+        }
+        let first = self.label(&f.blocks[0].label);
+        self.line(format!("br label {}", first));
+        self.out.push_str(&body);
+        self.out.push_str("}\n");
+    }
+
+    fn inst(&mut self, inst: &'a Inst) {
+        match inst {
+            Inst::Bin {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let name = match (op, ty.is_float()) {
+                    (BinOp::Add, false) => "add",
+                    (BinOp::Add, true) => "fadd",
+                    (BinOp::Sub, false) => "sub",
+                    (BinOp::Sub, true) => "fsub",
+                    (BinOp::Mul, false) => "mul",
+                    (BinOp::Mul, true) => "fmul",
+                    (BinOp::Div, false) => "sdiv",
+                    (_, true) => "fdiv",
+                    (BinOp::UDiv, _) => "udiv",
+                    (BinOp::Rem, _) => "srem",
+                    (BinOp::URem, _) => "urem",
+                    (BinOp::And, _) => "and",
+                    (BinOp::Or, _) => "or",
+                    (BinOp::Xor, _) => "xor",
+                    (BinOp::Shl, _) => "shl",
+                    (BinOp::Shr, _) => "ashr",
+                    (BinOp::UShr, _) => "lshr",
+                };
+                let l = self.operand(ty, lhs);
+                let mut r = self.operand(ty, rhs);
+                // Shifting by the width of the type or more is poison: mask the amount.
+                if matches!(op, BinOp::Shl | BinOp::Shr | BinOp::UShr) {
+                    let bits = ty.int_bits().unwrap_or(64) as i64;
+                    r = match rhs {
+                        Value::Int(i) => (i & (bits - 1)).to_string(),
+                        _ => {
+                            let m = self.tmp();
+                            self.line(format!("{} = and {} {}, {}", m, scalar(ty), r, bits - 1));
+                            m
+                        }
+                    };
+                }
+                self.line(format!(
+                    "%{} = {} {} {}, {}",
+                    ident(dst),
+                    name,
+                    scalar(ty),
+                    l,
+                    r
+                ));
+            }
+            Inst::Cmp {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let cmp = if ty.is_float() {
+                    let cc = match op {
+                        CmpOp::Eq => "oeq",
+                        CmpOp::Ne => "une",
+                        CmpOp::Lt | CmpOp::ULt => "olt",
+                        CmpOp::Le | CmpOp::ULe => "ole",
+                        CmpOp::Gt | CmpOp::UGt => "ogt",
+                        CmpOp::Ge | CmpOp::UGe => "oge",
+                    };
+                    format!("fcmp {}", cc)
+                } else {
+                    let cc = match op {
+                        CmpOp::Eq => "eq",
+                        CmpOp::Ne => "ne",
+                        CmpOp::Lt => "slt",
+                        CmpOp::Le => "sle",
+                        CmpOp::Gt => "sgt",
+                        CmpOp::Ge => "sge",
+                        CmpOp::ULt => "ult",
+                        CmpOp::ULe => "ule",
+                        CmpOp::UGt => "ugt",
+                        CmpOp::UGe => "uge",
+                    };
+                    format!("icmp {}", cc)
+                };
+                let l = self.operand(ty, lhs);
+                let r = self.operand(ty, rhs);
+                let c = self.tmp();
+                self.line(format!("{} = {} {} {}, {}", c, cmp, scalar(ty), l, r));
+                self.line(format!("%{} = zext i1 {} to i32", ident(dst), c));
+            }
+            Inst::Un { dst, op, ty, arg } => {
+                let a = self.operand(ty, arg);
+                let t = scalar(ty);
+                let line = match op {
+                    UnOp::Neg if ty.is_float() => format!("%{} = fneg {} {}", ident(dst), t, a),
+                    UnOp::Neg => format!("%{} = sub {} 0, {}", ident(dst), t, a),
+                    UnOp::Not => format!("%{} = xor {} {}, -1", ident(dst), t, a),
+                };
+                self.line(line);
+            }
+            Inst::Conv {
+                dst,
+                op,
+                from,
+                arg,
+                to,
+            } => {
+                let a = self.operand(from, arg);
+                let (f, t) = (scalar(from), scalar(to));
+                let (fb, tb) = (from.int_bits(), to.int_bits());
+                let conv = match op {
+                    _ if from == to => "bitcast",
+                    ConvOp::Sext | ConvOp::Zext | ConvOp::Trunc | ConvOp::Bitcast
+                        if fb.is_some() && tb.is_some() =>
+                    {
+                        match fb.cmp(&tb) {
+                            std::cmp::Ordering::Less if *op == ConvOp::Sext => "sext",
+                            std::cmp::Ordering::Less => "zext",
+                            _ => "trunc",
+                        }
+                    }
+                    ConvOp::SiToF => "sitofp",
+                    ConvOp::UiToF => "uitofp",
+                    ConvOp::FToSi => "fptosi",
+                    ConvOp::FToUi => "fptoui",
+                    _ if *from == Type::Ptr => "ptrtoint",
+                    _ if *to == Type::Ptr => "inttoptr",
+                    _ => "bitcast",
+                };
+                self.line(format!("%{} = {} {} {} to {}", ident(dst), conv, f, a, t));
+            }
+            Inst::Copy { dst, ty, arg } => {
+                let a = self.operand(ty, arg);
+                let t = scalar(ty);
+                self.line(format!("%{} = bitcast {} {} to {}", ident(dst), t, a, t));
+            }
+            Inst::Alloca { dst, ty } => {
+                let align = self.e.module.layout(ty).unwrap().align;
+                let t = self.e.mem_type(ty);
+                self.entry
+                    .push(format!("%{} = alloca {}, align {}", ident(dst), t, align));
+            }
+            Inst::Load { dst, ty, addr } => {
+                let a = self.operand(&Type::Ptr, addr);
+                self.line(format!("%{} = load {}, ptr {}", ident(dst), scalar(ty), a));
+            }
+            Inst::Store { ty, value, addr } => {
+                let v = self.operand(ty, value);
+                let a = self.operand(&Type::Ptr, addr);
+                self.line(format!("store {} {}, ptr {}", scalar(ty), v, a));
+            }
+            Inst::Field {
+                dst,
+                aggr,
+                base,
+                index,
+            } => {
+                let b = self.operand(&Type::Ptr, base);
+                let kind = self.e.module.typedef(aggr).unwrap().kind;
+                let line = match kind {
+                    AggrKind::Struct => format!(
+                        "%{} = getelementptr inbounds %{}, ptr {}, i32 0, i32 {}",
+                        ident(dst),
+                        ident(aggr),
+                        b,
+                        index
+                    ),
+                    AggrKind::Union => format!(
+                        "%{} = getelementptr inbounds i8, ptr {}, i64 0",
+                        ident(dst),
+                        b
+                    ),
+                };
+                self.line(line);
+            }
+            Inst::Index {
+                dst,
+                elem,
+                base,
+                index,
+            } => {
+                let b = self.operand(&Type::Ptr, base);
+                let ity = match self.type_of(index, &Type::I64) {
+                    ty if ty.is_int() => ty,
+                    _ => Type::I64,
+                };
+                let i = self.operand(&ity, index);
+                self.line(format!(
+                    "%{} = getelementptr inbounds {}, ptr {}, {} {}",
+                    ident(dst),
+                    self.e.mem_type(elem),
+                    b,
+                    scalar(&ity),
+                    i
+                ));
+            }
+            Inst::Blit { ty, dst, src } => {
+                let size = self.e.module.layout(ty).unwrap().size;
+                let d = self.operand(&Type::Ptr, dst);
+                let s = self.operand(&Type::Ptr, src);
+                self.memcpy(&d, &s, size);
+            }
+            Inst::Call {
+                dst,
+                ret,
+                callee,
+                args,
+                fixed,
+            } => self.call(dst.as_deref(), ret, callee, args, *fixed),
+            Inst::VaStart { ap } => {
+                self.va_start = true;
+                let a = self.operand(&Type::Ptr, ap);
+                self.line(format!("call void @llvm.va_start(ptr {})", a));
+            }
+            Inst::VaArg { dst, ty, ap } => {
+                let a = self.operand(&Type::Ptr, ap);
+                self.line(format!(
+                    "%{} = va_arg ptr {}, {}",
+                    ident(dst),
+                    a,
+                    scalar(ty)
+                ));
+            }
+            inst => unreachable!("{} should have been expanded", inst),
+        }
+    }
+
+    fn memcpy(&mut self, dst: &str, src: &str, size: u64) {
+        self.memcpy = true;
+        self.line(format!(
+            "call void @llvm.memcpy.p0.p0.i64(ptr {}, ptr {}, i64 {}, i1 false)",
+            dst, src, size
+        ));
+    }
+
+    fn call(
+        &mut self,
+        dst: Option<&str>,
+        ret: &Type,
+        callee: &Value,
+        args: &[(Type, Value)],
+        fixed: Option<usize>,
+    ) {
+        // The signature of the callee if it is known, one made from the arguments otherwise.
+        let sig = match callee {
+            Value::Global(name) => self.e.module.signature(name),
+            _ => None,
+        }
+        .unwrap_or_else(|| Signature {
+            conv: CallConv::C,
+            params: args[..fixed.unwrap_or(args.len())]
+                .iter()
+                .map(|(ty, _)| ty.clone())
+                .collect(),
+            variadic: fixed.is_some(),
+            ret: ret.clone(),
+        });
+        let call_sig = Signature {
+            conv: CallConv::C,
+            params: args.iter().map(|(ty, _)| ty.clone()).collect(),
+            variadic: false,
+            ret: ret.clone(),
+        };
+        let lowered = abi::lower(self.e.module, &call_sig);
+
+        let mut values = vec![];
+        let mut sret = None;
+        if let Ret::Memory(_) = lowered.ret {
+            let layout = self.e.module.layout(ret).unwrap();
+            let ty = self.e.mem_type(ret);
+            let slot = self.slot(&ty, layout.align);
+            values.push(format!("ptr sret({}) {}", ty, slot));
+            sret = Some(slot);
+        }
+        for ((ty, v), arg) in args.iter().zip(&lowered.params) {
+            match arg {
+                Arg::Direct(ty) => {
+                    let v = self.operand(ty, v);
+                    values.push(format!("{}{} {}", scalar(ty), ext_attr(ty), v));
+                }
+                Arg::Split(classes) => {
+                    let size = self.e.module.layout(ty).unwrap().size;
+                    let p = self.operand(&Type::Ptr, v);
+                    let tmp = self.slot("{ i64, i64 }", 8);
+                    self.memcpy(&tmp, &p, size);
+                    for (j, c) in classes.iter().enumerate() {
+                        let addr = self.tmp();
+                        self.line(format!(
+                            "{} = getelementptr inbounds i8, ptr {}, i64 {}",
+                            addr,
+                            tmp,
+                            8 * j
+                        ));
+                        let part = self.tmp();
+                        self.line(format!("{} = load {}, ptr {}", part, class(c), addr));
+                        values.push(format!("{} {}", class(c), part));
+                    }
+                }
+                Arg::Memory(_) => {
+                    let align = self.e.module.layout(ty).unwrap().align.max(8);
+                    let p = self.operand(&Type::Ptr, v);
+                    values.push(format!(
+                        "ptr byval({}) align {} {}",
+                        self.e.mem_type(ty),
+                        align,
+                        p
+                    ));
+                }
+            }
+        }
+
+        // The function type is only needed for variadic callees, but it documents indirect calls.
+        let (ret_ty, params) = self
+            .e
+            .signature(&sig, &abi::lower(self.e.module, &sig), None);
+        let fn_ty = match callee {
+            Value::Global(_) if !sig.variadic => ret_ty,
+            _ => format!("{} {}", ret_ty, params),
+        };
+        let callee = self.operand(&Type::Ptr, callee);
+        let call = format!("call {} {}({})", fn_ty, callee, values.join(", "));
+
+        match (&lowered.ret, dst) {
+            (Ret::Void, _) | (_, None) => self.line(call),
+            (Ret::Direct(_), Some(dst)) => self.line(format!("%{} = {}", ident(dst), call)),
+            (Ret::Split(classes), Some(dst)) => {
+                let r = self.tmp();
+                self.line(format!("{} = {}", r, call));
+                let slot = format!("%{}", ident(dst));
+                self.entry
+                    .push(format!("{} = alloca {{ i64, i64 }}, align 8", slot));
+                self.line(format!("store {} {}, ptr {}", split(classes), r, slot));
+            }
+            (Ret::Memory(_), Some(dst)) => {
+                self.line(call);
+                let slot = sret.unwrap();
+                self.line(format!(
+                    "%{} = getelementptr inbounds i8, ptr {}, i64 0",
+                    ident(dst),
+                    slot
+                ));
+            }
+        }
+    }
+
+    fn term(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Jmp(l) => {
+                let l = self.label(l);
+                self.line(format!("br label {}", l))
+            }
+            Terminator::Br { cond, then, else_ } => {
+                let c = self.operand(&Type::I32, cond);
+                let b = self.tmp();
+                self.line(format!("{} = icmp ne i32 {}, 0", b, c));
+                let (t, e) = (self.label(then), self.label(else_));
+                self.line(format!("br i1 {}, label {}, label {}", b, t, e));
+            }
+            Terminator::Switch {
+                ty,
+                value,
+                default,
+                cases,
+            } => {
+                let v = self.operand(ty, value);
+                let t = scalar(ty);
+                let mut seen = HashSet::new();
+                let mut lines = vec![];
+                for (n, target) in cases {
+                    let n = sext_const(ty, *n);
+                    if seen.insert(n) {
+                        lines.push(format!("    {} {}, label {}", t, n, self.label(target)));
+                    }
+                }
+                let d = self.label(default);
+                self.line(format!("switch {} {}, label {} [", t, v, d));
+                for l in lines {
+                    writeln!(self.out, "{}", l).unwrap();
+                }
+                self.line("]".into());
+            }
+            Terminator::Ret(None) => self.line("ret void".into()),
+            Terminator::Ret(Some((ty, v))) => match self.lowered.ret.clone() {
+                Ret::Direct(_) => {
+                    let v = self.operand(ty, v);
+                    self.line(format!("ret {} {}", scalar(ty), v));
+                }
+                Ret::Split(classes) => {
+                    let size = self.e.module.layout(ty).unwrap().size;
+                    let p = self.operand(&Type::Ptr, v);
+                    let tmp = self.slot("{ i64, i64 }", 8);
+                    self.memcpy(&tmp, &p, size);
+                    let r = self.tmp();
+                    let t = split(&classes);
+                    self.line(format!("{} = load {}, ptr {}", r, t, tmp));
+                    self.line(format!("ret {} {}", t, r));
+                }
+                Ret::Memory(size) => {
+                    let p = self.operand(&Type::Ptr, v);
+                    self.memcpy("%.sret", &p, size);
+                    self.line("ret void".into());
+                }
+                Ret::Void => self.line("ret void".into()),
+            },
+            Terminator::Hlt => {
+                self.trap = true;
+                self.line("call void @llvm.trap()".into());
+                self.line("unreachable".into());
+            }
+            term => unreachable!("{} should have been expanded", term),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(ident("main"), "main");
+        assert_eq!(ident("alef.thunk.0"), "alef.thunk.0");
+        assert_eq!(ident("0"), "\"0\"");
+        assert_eq!(ident("a b"), "\"a b\"");
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(float_lit(2.5), "2.5");
+        assert_eq!(float_lit(0.1), "0x3FB999999999999A");
+        assert_eq!(float_lit(f64::INFINITY), "0x7FF0000000000000");
+    }
+}
@@ -0,0 +1,674 @@
+//! Translate the IR into the SSA text accepted by QBE (version 1.2 or later).
+//!
+//! The mapping is mostly direct, since the IR was designed after QBE's:
+//!
+//! - `i8`, `i16` and `i32` live in `w` temporaries, `i64` and `ptr` in `l`, `f64` in `d`.
+//!   Sub-word values are kept sign extended, so every operation that could leave garbage in the
+//!   upper bits is followed by `extsb` or `extsh`, and unsigned operations zero extend their
+//!   operands first;
+//! - aggregates become `type` definitions and are passed to and returned from functions by value
+//!   with QBE's `:name` ABI types;
+//! - `field` and `index` are address arithmetic with offsets computed from the module layout;
+//! - `switch` is a chain of comparisons, as QBE has no multi-way jump.
+//!
+//! Names are emitted unchanged when they are valid QBE identifiers; any other character is
+//! replaced by `_` followed by its code point in hexadecimal.
+
+use crate::expand::expand;
+use alef_ir::func::{Function, Linkage};
+use alef_ir::inst::{BinOp, CmpOp, ConvOp, Inst, Terminator, UnOp, Value};
+use alef_ir::module::{Data, DataItem, Module};
+use alef_ir::ty::{AggrKind, Type, TypeDef};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+/// Translate a verified module into QBE's SSA text.
+pub fn emit(module: &Module) -> String {
+    let module = expand(module);
+    let mut e = Emitter {
+        module: &module,
+        out: String::new(),
+    };
+    e.module();
+    e.out
+}
+
+/// Make `name` a valid QBE identifier.
+fn ident(name: &str) -> String {
+    let mut s = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+            s.push(c);
+        } else {
+            write!(s, "_{:x}", c as u32).unwrap();
+        }
+    }
+    s
+}
+
+/// The class of the temporaries holding values of type `ty`.
+fn base(ty: &Type) -> &'static str {
+    match ty {
+        Type::I8 | Type::I16 | Type::I32 => "w",
+        Type::F64 => "d",
+        _ => "l",
+    }
+}
+
+/// The extended type used in memory and data definitions.
+fn ext(ty: &Type) -> &'static str {
+    match ty {
+        Type::I8 => "b",
+        Type::I16 => "h",
+        Type::I32 => "w",
+        Type::F64 => "d",
+        _ => "l",
+    }
+}
+
+/// The type used for parameters, arguments and return values.
+fn abi(ty: &Type) -> String {
+    match ty {
+        Type::I8 => "sb".into(),
+        Type::I16 => "sh".into(),
+        Type::Named(name) => format!(":{}", ident(name)),
+        ty => base(ty).into(),
+    }
+}
+
+/// Sign extend the constant `v` from the width of `ty`, the representation of sub-word values.
+fn sext_const(ty: &Type, v: i64) -> i64 {
+    match ty {
+        Type::I8 => v as i8 as i64,
+        Type::I16 => v as i16 as i64,
+        Type::I32 => v as i32 as i64,
+        _ => v,
+    }
+}
+
+fn value(v: &Value) -> String {
+    match v {
+        Value::Temp(name) => format!("%{}", ident(name)),
+        Value::Global(name) => format!("${}", ident(name)),
+        Value::Int(i) => i.to_string(),
+        Value::Float(x) => format!("d_{:?}", x),
+    }
+}
+
+/// The element type and count of an array, flattening nested arrays.
+fn flatten(ty: &Type) -> (&Type, u64) {
+    match ty {
+        Type::Array(of, n) => {
+            let (elem, m) = flatten(of);
+            (elem, n * m)
+        }
+        ty => (ty, 1),
+    }
+}
+
+struct Emitter<'a> {
+    module: &'a Module,
+    out: String,
+}
+
+/// Emission state of a single function.
+struct FuncEmitter<'a> {
+    module: &'a Module,
+    out: &'a mut String,
+    types: HashMap<&'a str, Type>,
+    names: HashSet<String>,
+    ntemp: usize,
+}
+
+impl<'a> Emitter<'a> {
+    fn module(&mut self) {
+        let mut done = HashSet::new();
+        for t in &self.module.types {
+            self.typedef(t, &mut done);
+        }
+        if !self.module.types.is_empty() {
+            self.out.push('\n');
+        }
+
+        for d in &self.module.data {
+            self.data(d);
+        }
+        if !self.module.data.is_empty() {
+            self.out.push('\n');
+        }
+
+        for (i, f) in self.module.funcs.iter().enumerate() {
+            if i > 0 {
+                self.out.push('\n');
+            }
+            let mut fe = FuncEmitter::new(self.module, &mut self.out, f);
+            fe.function(f);
+        }
+    }
+
+    /// Emit a type definition after the ones it depends on, as QBE requires.
+    fn typedef(&mut self, t: &'a TypeDef, done: &mut HashSet<&'a str>) {
+        if !done.insert(&t.name) {
+            return;
+        }
+        for field in &t.fields {
+            if let (Type::Named(name), _) = flatten(field) {
+                if let Some(dep) = self.module.typedef(name) {
+                    self.typedef(dep, done);
+                }
+            }
+        }
+
+        let field = |ty: &Type| {
+            let (elem, n) = flatten(ty);
+            let elem = match elem {
+                Type::Named(name) => format!(":{}", ident(name)),
+                ty => ext(ty).to_string(),
+            };
+            match ty {
+                Type::Array(..) => format!("{} {}", elem, n),
+                _ => elem,
+            }
+        };
+        let fields: Vec<String> = t.fields.iter().map(field).collect();
+        let body = match t.kind {
+            AggrKind::Struct => format!("{{ {} }}", fields.join(", ")),
+            AggrKind::Union => {
+                let variants: Vec<String> = fields.iter().map(|f| format!("{{ {} }}", f)).collect();
+                format!("{{ {} }}", variants.join(" "))
+            }
+        };
+        writeln!(self.out, "type :{} = {}", ident(&t.name), body).unwrap();
+    }
+
+    fn data(&mut self, d: &Data) {
+        let mut items = vec![];
+        for item in &d.items {
+            match item {
+                DataItem::Str(s) => {
+                    let mut run = String::new();
+                    for b in s.bytes() {
+                        if (b' '..=b'~').contains(&b) && b != b'"' && b != b'\\' {
+                            run.push(b as char);
+                            continue;
+                        }
+                        if !run.is_empty() {
+                            items.push(format!("b \"{}\"", run));
+                            run.clear();
+                        }
+                        items.push(format!("b {}", b));
+                    }
+                    if !run.is_empty() {
+                        items.push(format!("b \"{}\"", run));
+                    }
+                    items.push("b 0".into());
+                }
+                DataItem::Runestr(s) => {
+                    items.extend(s.chars().map(|c| format!("w {}", c as u32)));
+                    items.push("w 0".into());
+                }
+                DataItem::Int(ty, v) => items.push(format!("{} {}", ext(ty), v)),
+                DataItem::Float(x) => items.push(format!("d d_{:?}", x)),
+                DataItem::Addr(name) => items.push(format!("l ${}", ident(name))),
+                DataItem::Zero(n) => items.push(format!("z {}", n)),
+            }
+        }
+
+        if d.linkage == Linkage::Export {
+            self.out.push_str("export ");
+        }
+        writeln!(
+            self.out,
+            "data ${} = align {} {{ {} }}",
+            ident(&d.name),
+            d.align(),
+            items.join(", ")
+        )
+        .unwrap();
+    }
+}
+
+impl<'a> FuncEmitter<'a> {
+    fn new(module: &'a Module, out: &'a mut String, f: &'a Function) -> FuncEmitter<'a> {
+        let mut types = HashMap::new();
+        let mut names = HashSet::new();
+        for p in &f.params {
+            let ty = if p.ty.is_aggregate() {
+                Type::Ptr
+            } else {
+                p.ty.clone()
+            };
+            types.insert(p.name.as_str(), ty);
+            names.insert(ident(&p.name));
+        }
+        for b in &f.blocks {
+            names.insert(ident(&b.label));
+            for inst in &b.insts {
+                if let (Some(dst), Some(ty)) = (inst.dst(), inst.dst_type()) {
+                    types.insert(dst, ty);
+                    names.insert(ident(dst));
+                }
+            }
+        }
+        FuncEmitter {
+            module,
+            out,
+            types,
+            names,
+            ntemp: 0,
+        }
+    }
+
+    /// A temporary or label name not used by the function.
+    fn fresh(&mut self) -> String {
+        loop {
+            let name = format!("q.{}", self.ntemp);
+            self.ntemp += 1;
+            if self.names.insert(name.clone()) {
+                return name;
+            }
+        }
+    }
+
+    fn line(&mut self, s: String) {
+        writeln!(self.out, "\t{}", s).unwrap();
+    }
+
+    /// The type of a value, `l` for globals and the given default for constants.
+    fn type_of(&self, v: &Value, default: &Type) -> Type {
+        match v {
+            Value::Temp(t) => self.types.get(t.as_str()).cloned().unwrap_or(Type::Ptr),
+            Value::Global(_) => Type::Ptr,
+            _ => default.clone(),
+        }
+    }
+
+    /// Emit `%dst =k op args`, followed by a sign extension if `ty` is a sub-word integer.
+    fn op(&mut self, dst: &str, ty: &Type, op: &str, args: &[String]) {
+        let args = args.join(", ");
+        match ty {
+            Type::I8 | Type::I16 => {
+                let raw = self.fresh();
+                self.line(format!("%{} =w {} {}", raw, op, args));
+                let ext = if *ty == Type::I8 { "extsb" } else { "extsh" };
+                self.line(format!("%{} =w {} %{}", ident(dst), ext, raw));
+            }
+            ty => self.line(format!("%{} ={} {} {}", ident(dst), base(ty), op, args)),
+        }
+    }
+
+    /// A sub-word operand zero extended, for unsigned operations.
+    fn zext_operand(&mut self, ty: &Type, v: &Value) -> String {
+        let (mask, ext) = match ty {
+            Type::I8 => (0xff, "extub"),
+            Type::I16 => (0xffff, "extuh"),
+            _ => return self.operand(ty, v),
+        };
+        match v {
+            Value::Int(i) => (i & mask).to_string(),
+            v => {
+                let t = self.fresh();
+                self.line(format!("%{} =w {} {}", t, ext, value(v)));
+                format!("%{}", t)
+            }
+        }
+    }
+
+    /// An operand of type `ty`, with constants in their canonical form.
+    fn operand(&self, ty: &Type, v: &Value) -> String {
+        match v {
+            Value::Int(i) if ty.is_int() => sext_const(ty, *i).to_string(),
+            Value::Int(i) if ty.is_float() => format!("d_{:?}", *i as f64),
+            v => value(v),
+        }
+    }
+
+    fn args(&self, args: &[(Type, Value)], fixed: Option<usize>) -> String {
+        let mut out = vec![];
+        for (i, (ty, v)) in args.iter().enumerate() {
+            if fixed == Some(i) {
+                out.push("...".to_string());
+            }
+            let ty = match ty {
+                Type::Array(..) => &Type::Ptr,
+                ty => ty,
+            };
+            out.push(format!("{} {}", abi(ty), self.operand(ty, v)));
+        }
+        if fixed == Some(args.len()) {
+            out.push("...".into());
+        }
+        out.join(", ")
+    }
+
+    fn function(&mut self, f: &Function) {
+        if f.linkage == Linkage::Export {
+            self.out.push_str("export ");
+        }
+        self.out.push_str("function ");
+        match &f.ret {
+            Type::Void => {}
+            Type::Array(..) => self.out.push_str("l "),
+            ty => write!(self.out, "{} ", abi(ty)).unwrap(),
+        }
+
+        let mut params: Vec<String> = f
+            .params
+            .iter()
+            .map(|p| {
+                let ty = match &p.ty {
+                    Type::Array(..) => Type::Ptr,
+                    ty => ty.clone(),
+                };
+                format!("{} %{}", abi(&ty), ident(&p.name))
+            })
+            .collect();
+        if f.variadic {
+            params.push("...".into());
+        }
+        writeln!(self.out, "${}({}) {{", ident(&f.name), params.join(", ")).unwrap();
+
+        for b in &f.blocks {
+            writeln!(self.out, "@{}", ident(&b.label)).unwrap();
+            for inst in &b.insts {
+                self.inst(inst);
+            }
+            self.term(&b.term);
+        }
+        self.out.push_str("}\n");
+    }
+
+    fn inst(&mut self, inst: &Inst) {
+        match inst {
+            Inst::Bin {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let name = match (op, ty.is_float()) {
+                    (BinOp::Add, _) => "add",
+                    (BinOp::Sub, _) => "sub",
+                    (BinOp::Mul, _) => "mul",
+                    (BinOp::Div, _) => "div",
+                    (BinOp::UDiv, _) => "udiv",
+                    (BinOp::Rem, _) => "rem",
+                    (BinOp::URem, _) => "urem",
+                    (BinOp::And, _) => "and",
+                    (BinOp::Or, _) => "or",
+                    (BinOp::Xor, _) => "xor",
+                    (BinOp::Shl, _) => "shl",
+                    (BinOp::Shr, _) => "sar",
+                    (BinOp::UShr, _) => "shr",
+                };
+                let unsigned = matches!(op, BinOp::UDiv | BinOp::URem | BinOp::UShr);
+                let (l, r) = if unsigned {
+                    (self.zext_operand(ty, lhs), self.zext_operand(ty, rhs))
+                } else {
+                    (self.operand(ty, lhs), self.operand(ty, rhs))
+                };
+                self.op(dst, ty, name, &[l, r]);
+            }
+            Inst::Cmp {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let name = if ty.is_float() {
+                    match op {
+                        CmpOp::Eq => "eq",
+                        CmpOp::Ne => "ne",
+                        CmpOp::Lt | CmpOp::ULt => "lt",
+                        CmpOp::Le | CmpOp::ULe => "le",
+                        CmpOp::Gt | CmpOp::UGt => "gt",
+                        CmpOp::Ge | CmpOp::UGe => "ge",
+                    }
+                } else {
+                    match op {
+                        CmpOp::Eq => "eq",
+                        CmpOp::Ne => "ne",
+                        CmpOp::Lt => "slt",
+                        CmpOp::Le => "sle",
+                        CmpOp::Gt => "sgt",
+                        CmpOp::Ge => "sge",
+                        CmpOp::ULt => "ult",
+                        CmpOp::ULe => "ule",
+                        CmpOp::UGt => "ugt",
+                        CmpOp::UGe => "uge",
+                    }
+                };
+                let (l, r) = (self.operand(ty, lhs), self.operand(ty, rhs));
+                self.line(format!(
+                    "%{} =w c{}{} {}, {}",
+                    ident(dst),
+                    name,
+                    base(ty),
+                    l,
+                    r
+                ));
+            }
+            Inst::Un { dst, op, ty, arg } => {
+                let a = self.operand(ty, arg);
+                match op {
+                    UnOp::Neg => self.op(dst, ty, "neg", &[a]),
+                    UnOp::Not => self.op(dst, ty, "xor", &[a, "-1".into()]),
+                }
+            }
+            Inst::Conv {
+                dst,
+                op,
+                from,
+                arg,
+                to,
+            } => self.conv(dst, *op, from, arg, to),
+            Inst::Copy { dst, ty, arg } => {
+                let a = self.operand(ty, arg);
+                self.line(format!("%{} ={} copy {}", ident(dst), base(ty), a));
+            }
+            Inst::Alloca { dst, ty } => {
+                let layout = self.module.layout(ty).unwrap();
+                let align = match layout.align {
+                    0..=4 => 4,
+                    5..=8 => 8,
+                    _ => 16,
+                };
+                self.line(format!(
+                    "%{} =l alloc{} {}",
+                    ident(dst),
+                    align,
+                    layout.size
+                ));
+            }
+            Inst::Load { dst, ty, addr } => {
+                let load = match ty {
+                    Type::I8 => "loadsb",
+                    Type::I16 => "loadsh",
+                    Type::I32 => "loadw",
+                    Type::F64 => "loadd",
+                    _ => "loadl",
+                };
+                self.line(format!(
+                    "%{} ={} {} {}",
+                    ident(dst),
+                    base(ty),
+                    load,
+                    value(addr)
+                ));
+            }
+            Inst::Store { ty, value: v, addr } => {
+                let v = self.operand(ty, v);
+                self.line(format!("store{} {}, {}", ext(ty), v, value(addr)));
+            }
+            Inst::Field {
+                dst,
+                aggr,
+                base: b,
+                index,
+            } => {
+                let offset = self.module.field_offset(aggr, *index as usize).unwrap();
+                self.line(format!("%{} =l add {}, {}", ident(dst), value(b), offset));
+            }
+            Inst::Index {
+                dst,
+                elem,
+                base: b,
+                index,
+            } => {
+                let size = self.module.layout(elem).unwrap().size as i64;
+                match index {
+                    Value::Int(i) => {
+                        self.line(format!("%{} =l add {}, {}", ident(dst), value(b), i * size))
+                    }
+                    index => {
+                        let mut i = value(index);
+                        if base(&self.type_of(index, &Type::I64)) == "w" {
+                            let t = self.fresh();
+                            self.line(format!("%{} =l extsw {}", t, i));
+                            i = format!("%{}", t);
+                        }
+                        let off = self.fresh();
+                        self.line(format!("%{} =l mul {}, {}", off, i, size));
+                        self.line(format!("%{} =l add {}, %{}", ident(dst), value(b), off));
+                    }
+                }
+            }
+            Inst::Blit { ty, dst, src } => {
+                let size = self.module.layout(ty).unwrap().size;
+                self.line(format!("blit {}, {}, {}", value(src), value(dst), size));
+            }
+            Inst::Call {
+                dst,
+                ret,
+                callee,
+                args,
+                fixed,
+            } => {
+                let args = self.args(args, *fixed);
+                let call = format!("call {}({})", value(callee), args);
+                match dst {
+                    Some(dst) => {
+                        let ret = match ret {
+                            Type::Array(..) => "l".into(),
+                            ty => abi(ty),
+                        };
+                        self.line(format!("%{} ={} {}", ident(dst), ret, call))
+                    }
+                    None => self.line(call),
+                }
+            }
+            Inst::VaStart { ap } => self.line(format!("vastart {}", value(ap))),
+            Inst::VaArg { dst, ty, ap } => {
+                self.line(format!("%{} ={} vaarg {}", ident(dst), base(ty), value(ap)))
+            }
+            inst => unreachable!("{} should have been expanded", inst),
+        }
+    }
+
+    fn conv(&mut self, dst: &str, op: ConvOp, from: &Type, arg: &Value, to: &Type) {
+        let a = self.operand(from, arg);
+        let d = ident(dst);
+        let k = base(to);
+        let same = base(from) == k;
+        let line = match op {
+            ConvOp::Sext if same => format!("%{} ={} copy {}", d, k, a),
+            ConvOp::Sext => format!("%{} =l extsw {}", d, a),
+            ConvOp::Zext => {
+                let ext = match from {
+                    Type::I8 => "extub",
+                    Type::I16 => "extuh",
+                    Type::I32 if k == "l" => "extuw",
+                    _ => "copy",
+                };
+                format!("%{} ={} {} {}", d, k, ext, a)
+            }
+            ConvOp::Trunc => match to {
+                Type::I8 => format!("%{} =w extsb {}", d, a),
+                Type::I16 => format!("%{} =w extsh {}", d, a),
+                _ => format!("%{} ={} copy {}", d, k, a),
+            },
+            ConvOp::SiToF => {
+                let conv = if base(from) == "l" { "sltof" } else { "swtof" };
+                format!("%{} =d {} {}", d, conv, a)
+            }
+            ConvOp::UiToF => {
+                let a = self.zext_operand(from, arg);
+                let conv = if base(from) == "l" { "ultof" } else { "uwtof" };
+                format!("%{} =d {} {}", d, conv, a)
+            }
+            ConvOp::FToSi => return self.op(dst, to, "dtosi", &[a]),
+            ConvOp::FToUi => return self.op(dst, to, "dtoui", &[a]),
+            ConvOp::Bitcast if same => format!("%{} ={} copy {}", d, k, a),
+            ConvOp::Bitcast => format!("%{} ={} cast {}", d, k, a),
+        };
+        self.line(line);
+    }
+
+    fn term(&mut self, term: &Terminator) {
+        match term {
+            Terminator::Jmp(l) => self.line(format!("jmp @{}", ident(l))),
+            Terminator::Br { cond, then, else_ } => self.line(format!(
+                "jnz {}, @{}, @{}",
+                value(cond),
+                ident(then),
+                ident(else_)
+            )),
+            Terminator::Switch {
+                ty,
+                value: v,
+                default,
+                cases,
+            } => {
+                let v = value(v);
+                for (i, (n, target)) in cases.iter().enumerate() {
+                    let c = self.fresh();
+                    self.line(format!(
+                        "%{} =w ceq{} {}, {}",
+                        c,
+                        base(ty),
+                        v,
+                        sext_const(ty, *n)
+                    ));
+                    if i + 1 == cases.len() {
+                        self.line(format!("jnz %{}, @{}, @{}", c, ident(target), ident(default)));
+                    } else {
+                        let next = self.fresh();
+                        self.line(format!("jnz %{}, @{}, @{}", c, ident(target), next));
+                        writeln!(self.out, "@{}", next).unwrap();
+                    }
+                }
+                if cases.is_empty() {
+                    self.line(format!("jmp @{}", ident(default)));
+                }
+            }
+            Terminator::Ret(None) => self.line("ret".into()),
+            Terminator::Ret(Some((ty, v))) => {
+                let v = self.operand(ty, v);
+                self.line(format!("ret {}", v))
+            }
+            Terminator::Hlt => self.line("hlt".into()),
+            term => unreachable!("{} should have been expanded", term),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(ident("main"), "main");
+        assert_eq!(ident("alef.thunk.0"), "alef.thunk.0");
+        assert_eq!(ident("alef_alloc"), "alef_alloc");
+        assert_eq!(ident("αβ"), "_3b1_3b2");
+    }
+
+    #[test]
+    fn flattened_arrays() {
+        let ty = Type::Array(Box::new(Type::Array(Box::new(Type::I32), 3)), 2);
+        assert_eq!(flatten(&ty), (&Type::I32, 6));
+    }
+}
@@ -0,0 +1,121 @@
+//! The interface between generated code and the Alef runtime.
+//!
+//! Everything that needs more than a few machine instructions (allocation, channels, `alt`,
+//! processes, tasks and `par` blocks, unhandled exceptions) is a call to one of the C functions
+//! declared here. The runtime library implements them; the backends only ever see ordinary
+//! calls, because `expand` rewrites the Alef-specific instructions before code generation.
+
+use alef_ir::func::{CallConv, Signature};
+use alef_ir::ty::{AggrKind, Type, TypeDef};
+
+/// `ptr alef_alloc(i64 size)`: allocate `size` zeroed bytes, raising an error when out of memory.
+pub const ALLOC: &str = "alef_alloc";
+
+/// `void alef_unalloc(ptr p)`: free memory returned by `alef_alloc`.
+pub const UNALLOC: &str = "alef_unalloc";
+
+/// `ptr alef_chan_new(i64 elem_size, i64 cap)`: create a channel, synchronous if `cap` is 0.
+pub const CHAN_NEW: &str = "alef_chan_new";
+
+/// `void alef_chan_send(ptr chan, ptr src)`: send the element stored at `src`.
+pub const CHAN_SEND: &str = "alef_chan_send";
+
+/// `void alef_chan_recv(ptr chan, ptr dst)`: receive an element and store it at `dst`.
+pub const CHAN_RECV: &str = "alef_chan_recv";
+
+/// `i32 alef_chan_cansend(ptr chan)`: return non-zero if a send would not block.
+pub const CHAN_CANSEND: &str = "alef_chan_cansend";
+
+/// `i32 alef_chan_canrecv(ptr chan)`: return non-zero if a receive would not block.
+pub const CHAN_CANRECV: &str = "alef_chan_canrecv";
+
+/// `i32 alef_alt(ptr cases, i32 n)`: wait until one of the `n` cases (an array of `ALT_CASE`)
+/// can proceed, perform it and return its index.
+pub const ALT: &str = "alef_alt";
+
+/// `void alef_proc(ptr fn, ptr env)`: run `fn(env)` in a new process.
+pub const PROC: &str = "alef_proc";
+
+/// `void alef_task(ptr fn, ptr env)`: run `fn(env)` in a new task of the current process.
+pub const TASK: &str = "alef_task";
+
+/// `void alef_yield()`: let the other tasks of the current process run.
+pub const YIELD: &str = "alef_yield";
+
+/// `ptr alef_par_begin()`: start a group of processes for a `par` block.
+pub const PAR_BEGIN: &str = "alef_par_begin";
+
+/// `void alef_par_spawn(ptr group, ptr fn, ptr env)`: run `fn(env)` as a member of the group.
+pub const PAR_SPAWN: &str = "alef_par_spawn";
+
+/// `void alef_par_join(ptr group)`: wait for every member of the group and release it.
+pub const PAR_JOIN: &str = "alef_par_join";
+
+/// `void alef_raise()`: report an exception raised outside of any `rescue` block; never returns.
+pub const RAISE: &str = "alef_raise";
+
+/// The name of the aggregate describing an `alt` case: `{ ptr chan, i64 dir, ptr buf }`.
+///
+/// `dir` is `ALT_RECV` or `ALT_SEND`; `buf` is where the received element is stored or where
+/// the element to send is read from, and can be null for receives whose value is discarded.
+pub const ALT_CASE: &str = "alef.altcase";
+
+/// The direction of an `alt` case that receives.
+pub const ALT_RECV: i64 = 0;
+
+/// The direction of an `alt` case that sends.
+pub const ALT_SEND: i64 = 1;
+
+/// Every runtime function, in the order they are documented above.
+pub const FUNCTIONS: &[&str] = &[
+    ALLOC,
+    UNALLOC,
+    CHAN_NEW,
+    CHAN_SEND,
+    CHAN_RECV,
+    CHAN_CANSEND,
+    CHAN_CANRECV,
+    ALT,
+    PROC,
+    TASK,
+    YIELD,
+    PAR_BEGIN,
+    PAR_SPAWN,
+    PAR_JOIN,
+    RAISE,
+];
+
+/// The signature of a runtime function, None if `name` is not part of the runtime interface.
+pub fn signature(name: &str) -> Option<Signature> {
+    use Type::*;
+    let (params, ret) = match name {
+        ALLOC => (vec![I64], Ptr),
+        UNALLOC => (vec![Ptr], Void),
+        CHAN_NEW => (vec![I64, I64], Ptr),
+        CHAN_SEND | CHAN_RECV => (vec![Ptr, Ptr], Void),
+        CHAN_CANSEND | CHAN_CANRECV => (vec![Ptr], I32),
+        ALT => (vec![Ptr, I32], I32),
+        PROC | TASK => (vec![Ptr, Ptr], Void),
+        YIELD => (vec![], Void),
+        PAR_BEGIN => (vec![], Ptr),
+        PAR_SPAWN => (vec![Ptr, Ptr, Ptr], Void),
+        PAR_JOIN => (vec![Ptr], Void),
+        RAISE => (vec![], Void),
+        _ => return None,
+    };
+    Some(Signature {
+        conv: CallConv::C,
+        params,
+        variadic: false,
+        ret,
+    })
+}
+
+/// The definition of `ALT_CASE`.
+pub fn alt_case() -> TypeDef {
+    TypeDef {
+        name: ALT_CASE.to_string(),
+        kind: AggrKind::Struct,
+        fields: vec![Type::Ptr, Type::I64, Type::Ptr],
+    }
+}
@@ -0,0 +1,104 @@
+#include <stdint.h>
+
+struct IF {
+	int32_t f0;
+	double f1;
+};
+struct FF {
+	double f0;
+	double f1;
+};
+struct Big {
+	int64_t f0;
+	int64_t f1;
+	int64_t f2;
+};
+struct d_fmt_if { char f0[9]; };
+struct d_fmt_f { char f0[6]; };
+struct d_fmt_l { char f0[5]; };
+struct d_fmt_d { char f0[4]; };
+
+int32_t printf(char *, ...);
+struct IF mk_if(int32_t, double);
+double sum_ff(struct FF);
+struct Big mk_big(int64_t);
+int64_t sum_big(struct Big);
+int32_t call_scale(void);
+struct IF scale(struct IF, int32_t);
+int32_t main(void);
+
+static struct d_fmt_if fmt_if = { "%d %.1f\n" };
+static struct d_fmt_f fmt_f = { "%.2f\n" };
+static struct d_fmt_l fmt_l = { "%ld\n" };
+static struct d_fmt_d fmt_d = { "%d\n" };
+
+struct IF scale(struct IF p_x, int32_t t_k) {
+	char *t_x = (char *)&p_x;
+	struct IF s_r;
+	char *t_r;
+	char *t_xi__p;
+	char *t_xf__p;
+	int32_t t_xi;
+	double t_xf;
+	int32_t t_ri;
+	double t_kf;
+	double t_rf;
+	char *t_ri__p;
+	char *t_rf__p;
+	t_r = (char *)&s_r;
+	t_xi__p = t_x + 0;
+	t_xf__p = t_x + 8;
+	t_xi = *(int32_t *)t_xi__p;
+	t_xf = *(double *)t_xf__p;
+	t_ri = (int32_t)((uint32_t)t_xi * (uint32_t)t_k);
+	t_kf = (double)t_k;
+	t_rf = t_xf * t_kf;
+	t_ri__p = t_r + 0;
+	t_rf__p = t_r + 8;
+	*(int32_t *)t_ri__p = t_ri;
+	*(double *)t_rf__p = t_rf;
+	return *(struct IF *)t_r;
+}
+
+int32_t main(void) {
+	struct IF r_a;
+	char *t_a;
+	char *t_ai__p;
+	char *t_af__p;
+	int32_t t_ai;
+	double t_af;
+	int32_t t_r0;
+	struct FF s_ff;
+	char *t_ff;
+	char *t_ffa;
+	char *t_ffb;
+	double t_s;
+	int32_t t_r1;
+	struct Big r_b;
+	char *t_b;
+	int64_t t_t;
+	int32_t t_r2;
+	int32_t t_c;
+	int32_t t_r3;
+	r_a = mk_if(3, 1.5);
+	t_a = (char *)&r_a;
+	t_ai__p = t_a + 0;
+	t_af__p = t_a + 8;
+	t_ai = *(int32_t *)t_ai__p;
+	t_af = *(double *)t_af__p;
+	t_r0 = printf((char *)&fmt_if, t_ai, t_af);
+	t_ff = (char *)&s_ff;
+	t_ffa = t_ff + 0;
+	t_ffb = t_ff + 8;
+	*(double *)t_ffa = 1.25;
+	*(double *)t_ffb = 2.5;
+	t_s = sum_ff(*(struct FF *)t_ff);
+	t_r1 = printf((char *)&fmt_f, t_s);
+	r_b = mk_big(5);
+	t_b = (char *)&r_b;
+	t_t = sum_big(*(struct Big *)t_b);
+	t_r2 = printf((char *)&fmt_l, t_t);
+	t_c = call_scale();
+	t_r3 = printf((char *)&fmt_d, t_c);
+	return 0;
+}
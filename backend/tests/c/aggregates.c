@@ -0,0 +1,161 @@
+#include <stdint.h>
+
+struct Point {
+	int32_t f0;
+	int32_t f1;
+};
+struct Line {
+	struct Point f0;
+	struct Point f1;
+};
+struct d_fmt { char f0[7]; };
+struct d_fmt1 { char f0[4]; };
+
+int32_t printf(char *, ...);
+static struct Point add(struct Point, struct Point);
+static int32_t sum(char *, int32_t);
+int32_t main(void);
+
+static struct d_fmt fmt = { "%d %d\n" };
+static struct d_fmt1 fmt1 = { "%d\n" };
+
+static struct Point add(struct Point p_a, struct Point p_b) {
+	char *t_a = (char *)&p_a;
+	char *t_b = (char *)&p_b;
+	struct Point s_r;
+	char *t_r;
+	char *t_ax__p;
+	char *t_bx__p;
+	char *t_rx__p;
+	int32_t t_ax;
+	int32_t t_bx;
+	int32_t t_rx;
+	char *t_ay__p;
+	char *t_by__p;
+	char *t_ry__p;
+	int32_t t_ay;
+	int32_t t_by;
+	int32_t t_ry;
+	t_r = (char *)&s_r;
+	t_ax__p = t_a + 0;
+	t_bx__p = t_b + 0;
+	t_rx__p = t_r + 0;
+	t_ax = *(int32_t *)t_ax__p;
+	t_bx = *(int32_t *)t_bx__p;
+	t_rx = (int32_t)((uint32_t)t_ax + (uint32_t)t_bx);
+	*(int32_t *)t_rx__p = t_rx;
+	t_ay__p = t_a + 4;
+	t_by__p = t_b + 4;
+	t_ry__p = t_r + 4;
+	t_ay = *(int32_t *)t_ay__p;
+	t_by = *(int32_t *)t_by__p;
+	t_ry = (int32_t)((uint32_t)t_ay + (uint32_t)t_by);
+	*(int32_t *)t_ry__p = t_ry;
+	return *(struct Point *)t_r;
+}
+
+static int32_t sum(char *t_v, int32_t t_n) {
+	int32_t s_acc;
+	char *t_acc;
+	int32_t s_i;
+	char *t_i;
+	int32_t t_iv;
+	int32_t t_more;
+	char *t_p;
+	int32_t t_x;
+	int32_t t_av;
+	int32_t t_s;
+	int32_t t_inc;
+	int32_t t_r;
+	t_acc = (char *)&s_acc;
+	t_i = (char *)&s_i;
+	*(int32_t *)t_acc = 0;
+	*(int32_t *)t_i = 0;
+	goto l_cond;
+l_cond:
+	t_iv = *(int32_t *)t_i;
+	t_more = t_iv < t_n;
+	if (t_more)
+		goto l_body;
+	goto l_done;
+l_body:
+	t_p = t_v + (int64_t)t_iv * 4;
+	t_x = *(int32_t *)t_p;
+	t_av = *(int32_t *)t_acc;
+	t_s = (int32_t)((uint32_t)t_av + (uint32_t)t_x);
+	*(int32_t *)t_acc = t_s;
+	t_inc = (int32_t)((uint32_t)t_iv + (uint32_t)1);
+	*(int32_t *)t_i = t_inc;
+	goto l_cond;
+l_done:
+	t_r = *(int32_t *)t_acc;
+	return t_r;
+}
+
+int32_t main(void) {
+	struct Line s_l;
+	char *t_l;
+	char *t_a;
+	char *t_b;
+	char *t_ax;
+	char *t_ay;
+	char *t_bx;
+	char *t_by;
+	struct Point r_c;
+	char *t_c;
+	char *t_cx__p;
+	char *t_cy__p;
+	int32_t t_cx;
+	int32_t t_cy;
+	int32_t t_r0;
+	int32_t s_v[4];
+	char *t_v;
+	struct Point s_t;
+	char *t_t;
+	char *t_tx;
+	char *t_e0;
+	char *t_e1;
+	char *t_e2;
+	char *t_e3;
+	int32_t t_s;
+	int32_t t_r1;
+	int32_t t_w;
+	int32_t t_w8;
+	int32_t t_r2;
+	t_l = (char *)&s_l;
+	t_a = t_l + 0;
+	t_b = t_l + 8;
+	t_ax = t_a + 0;
+	t_ay = t_a + 4;
+	t_bx = t_b + 0;
+	t_by = t_b + 4;
+	*(int32_t *)t_ax = 1;
+	*(int32_t *)t_ay = 2;
+	*(int32_t *)t_bx = 3;
+	*(int32_t *)t_by = 4;
+	r_c = add(*(struct Point *)t_a, *(struct Point *)t_b);
+	t_c = (char *)&r_c;
+	t_cx__p = t_c + 0;
+	t_cy__p = t_c + 4;
+	t_cx = *(int32_t *)t_cx__p;
+	t_cy = *(int32_t *)t_cy__p;
+	t_r0 = printf((char *)&fmt, t_cx, t_cy);
+	t_v = (char *)&s_v;
+	t_t = (char *)&s_t;
+	*(struct Point *)t_t = *(struct Point *)t_a;
+	t_tx = t_t + 0;
+	t_e0 = t_v + 0;
+	t_e1 = t_v + 4;
+	t_e2 = t_v + 8;
+	t_e3 = t_v + 12;
+	*(int32_t *)t_e0 = 1;
+	*(int32_t *)t_e1 = 2;
+	*(int32_t *)t_e2 = 3;
+	*(int32_t *)t_e3 = 4;
+	t_s = sum(t_v, 4);
+	t_r1 = printf((char *)&fmt1, t_s);
+	t_w = *(int32_t *)t_tx;
+	t_w8 = (int32_t)((uint32_t)t_w + (uint32_t)8);
+	t_r2 = printf((char *)&fmt1, t_w8);
+	return 0;
+}
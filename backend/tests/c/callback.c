@@ -0,0 +1,28 @@
+#include <stdint.h>
+
+struct d_fmt { char f0[4]; };
+
+int32_t printf(char *, ...);
+int32_t sort_and_sum(char *);
+static int32_t descending(char *, char *);
+int32_t main(void);
+
+static struct d_fmt fmt = { "%d\n" };
+
+static int32_t descending(char *t_a, char *t_b) {
+	int32_t t_x;
+	int32_t t_y;
+	int32_t t_r;
+	t_x = *(int32_t *)t_a;
+	t_y = *(int32_t *)t_b;
+	t_r = (int32_t)((uint32_t)t_y - (uint32_t)t_x);
+	return t_r;
+}
+
+int32_t main(void) {
+	int32_t t_s;
+	int32_t t_r;
+	t_s = sort_and_sum((char *)&descending);
+	t_r = printf((char *)&fmt, t_s);
+	return 0;
+}
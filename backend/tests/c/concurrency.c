@@ -0,0 +1,307 @@
+#include <stdint.h>
+
+struct Cell {
+	char *f0;
+	int64_t f1;
+};
+struct alef__altcase {
+	char *f0;
+	int64_t f1;
+	char *f2;
+};
+struct alef__env__0 {
+	char *f0;
+	int32_t f1;
+};
+struct alef__env__1 {
+	char *f0;
+	int32_t f1;
+};
+struct alef__env__2 {
+	char *f0;
+	int32_t f1;
+};
+struct alef__env__3 {
+	char *f0;
+	int32_t f1;
+};
+
+void abort(void);
+void work(char *, int32_t);
+char *alef_alloc(int64_t);
+int32_t alef_alt(char *, int32_t);
+int32_t alef_chan_canrecv(char *);
+int32_t alef_chan_cansend(char *);
+char *alef_chan_new(int64_t, int64_t);
+void alef_chan_recv(char *, char *);
+void alef_chan_send(char *, char *);
+char *alef_par_begin(void);
+void alef_par_join(char *);
+void alef_par_spawn(char *, char *, char *);
+void alef_proc(char *, char *);
+void alef_raise(void);
+void alef_task(char *, char *);
+void alef_unalloc(char *);
+static void producer(char *, int32_t);
+static int32_t consumer(char *, char *);
+int32_t main(void);
+static void alef__thunk__0(char *);
+static void alef__thunk__1(char *);
+static void alef__thunk__2(char *);
+static void alef__thunk__3(char *);
+
+static void producer(char *t_c, int32_t t_n) {
+	int32_t s_rt__0;
+	char *t_rt__0;
+	int32_t s_rt__1;
+	char *t_rt__1;
+	int32_t t_ok;
+	t_rt__0 = (char *)&s_rt__0;
+	t_rt__1 = (char *)&s_rt__1;
+	*(int32_t *)t_rt__0 = t_n;
+	alef_chan_send(t_c, t_rt__0);
+	t_ok = alef_chan_cansend(t_c);
+	if (t_ok)
+		goto l_again;
+	goto l_done;
+l_again:
+	*(int32_t *)t_rt__1 = 0;
+	alef_chan_send(t_c, t_rt__1);
+	goto l_done;
+l_done:
+	return;
+}
+
+static int32_t consumer(char *t_c, char *t_d) {
+	struct alef__altcase s_rt__0[3];
+	char *t_rt__0;
+	int32_t s_rt__9;
+	char *t_rt__9;
+	int32_t s_slot;
+	char *t_slot;
+	char *t_rt__1;
+	char *t_rt__2;
+	char *t_rt__3;
+	char *t_rt__4;
+	char *t_rt__5;
+	char *t_rt__6;
+	char *t_rt__7;
+	char *t_rt__8;
+	char *t_rt__10;
+	char *t_rt__11;
+	char *t_rt__12;
+	char *t_rt__13;
+	int32_t t_rt__14;
+	int32_t t_v;
+	int32_t t_ready;
+	t_rt__0 = (char *)&s_rt__0;
+	t_rt__9 = (char *)&s_rt__9;
+	t_slot = (char *)&s_slot;
+	t_rt__1 = t_rt__0 + 0;
+	t_rt__2 = t_rt__1 + 0;
+	*(char * *)t_rt__2 = t_c;
+	t_rt__3 = t_rt__1 + 8;
+	*(int64_t *)t_rt__3 = 0;
+	t_rt__4 = t_rt__1 + 16;
+	*(char * *)t_rt__4 = t_slot;
+	t_rt__5 = t_rt__0 + 24;
+	t_rt__6 = t_rt__5 + 0;
+	*(char * *)t_rt__6 = t_d;
+	t_rt__7 = t_rt__5 + 8;
+	*(int64_t *)t_rt__7 = 0;
+	t_rt__8 = t_rt__5 + 16;
+	*(char * *)t_rt__8 = (char *)0;
+	*(int32_t *)t_rt__9 = 5;
+	t_rt__10 = t_rt__0 + 48;
+	t_rt__11 = t_rt__10 + 0;
+	*(char * *)t_rt__11 = t_d;
+	t_rt__12 = t_rt__10 + 8;
+	*(int64_t *)t_rt__12 = 1;
+	t_rt__13 = t_rt__10 + 16;
+	*(char * *)t_rt__13 = t_rt__9;
+	t_rt__14 = alef_alt(t_rt__0, 3);
+	switch (t_rt__14) {
+	case 0: goto l_got;
+	case 1: goto l_skip;
+	default: goto l_sent;
+	}
+l_got:
+	t_v = *(int32_t *)t_slot;
+	return t_v;
+l_skip:
+	return -1;
+l_sent:
+	t_ready = alef_chan_canrecv(t_c);
+	return t_ready;
+}
+
+int32_t main(void) {
+	int32_t s_rt__12[1];
+	char *t_rt__12;
+	int32_t s_rt__13;
+	char *t_rt__13;
+	int32_t s_rt__17;
+	char *t_rt__17;
+	char *t_c;
+	char *t_d;
+	char *t_rt__0;
+	char *t_rt__1;
+	char *t_rt__2;
+	char *t_rt__3;
+	char *t_rt__4;
+	char *t_rt__5;
+	char *t_g;
+	char *t_rt__6;
+	char *t_rt__7;
+	char *t_rt__8;
+	char *t_rt__9;
+	char *t_rt__10;
+	char *t_rt__11;
+	int32_t t_rt__14;
+	int32_t t_rt__15;
+	char *t_rt__16;
+	int32_t t_v;
+	int32_t t_bad;
+	int32_t t_rt__21;
+	int32_t t_rt__22;
+	int32_t t_rt__23;
+	int32_t t_rt__24;
+	char *t_rt__25;
+	int32_t t_rt__26;
+	int32_t t_rt__27;
+	int32_t t_rt__28;
+	char *t_b;
+	int32_t t_u;
+	char *t_h;
+	t_rt__12 = (char *)&s_rt__12;
+	t_rt__13 = (char *)&s_rt__13;
+	t_rt__17 = (char *)&s_rt__17;
+	*(int32_t *)t_rt__13 = 0;
+	t_c = alef_chan_new(4, 0);
+	t_d = alef_chan_new(4, 4);
+	t_rt__0 = alef_alloc(16);
+	t_rt__1 = t_rt__0 + 0;
+	*(char * *)t_rt__1 = t_c;
+	t_rt__2 = t_rt__0 + 8;
+	*(int32_t *)t_rt__2 = 42;
+	alef_proc((char *)&alef__thunk__0, t_rt__0);
+	t_rt__3 = alef_alloc(16);
+	t_rt__4 = t_rt__3 + 0;
+	*(char * *)t_rt__4 = t_d;
+	t_rt__5 = t_rt__3 + 8;
+	*(int32_t *)t_rt__5 = 1;
+	alef_task((char *)&alef__thunk__1, t_rt__3);
+	t_g = alef_par_begin();
+	t_rt__6 = alef_alloc(16);
+	t_rt__7 = t_rt__6 + 0;
+	*(char * *)t_rt__7 = t_d;
+	t_rt__8 = t_rt__6 + 8;
+	*(int32_t *)t_rt__8 = 2;
+	alef_par_spawn(t_g, (char *)&alef__thunk__2, t_rt__6);
+	t_rt__9 = alef_alloc(16);
+	t_rt__10 = t_rt__9 + 0;
+	*(char * *)t_rt__10 = t_d;
+	t_rt__11 = t_rt__9 + 8;
+	*(int32_t *)t_rt__11 = 3;
+	alef_par_spawn(t_g, (char *)&alef__thunk__3, t_rt__9);
+	alef_par_join(t_g);
+	t_rt__14 = *(int32_t *)t_rt__13;
+	t_rt__15 = (int32_t)((uint32_t)t_rt__14 + (uint32_t)1);
+	*(int32_t *)t_rt__13 = t_rt__15;
+	t_rt__16 = t_rt__12 + (int64_t)t_rt__14 * 4;
+	*(int32_t *)t_rt__16 = 0;
+	alef_chan_recv(t_c, t_rt__17);
+	t_v = *(int32_t *)t_rt__17;
+	t_bad = t_v == 0;
+	if (t_bad)
+		goto l_raise;
+	goto l_ok;
+l_raise:
+	goto l_rt__18;
+l_rt__18:
+	t_rt__21 = *(int32_t *)t_rt__13;
+	t_rt__22 = t_rt__21 == 0;
+	if (t_rt__22)
+		goto l_rt__19;
+	goto l_rt__20;
+l_rt__19:
+	alef_raise();
+	abort();
+l_rt__20:
+	t_rt__23 = *(int32_t *)t_rt__13;
+	t_rt__24 = (int32_t)((uint32_t)t_rt__23 + (uint32_t)-1);
+	*(int32_t *)t_rt__13 = t_rt__24;
+	t_rt__25 = t_rt__12 + (int64_t)t_rt__24 * 4;
+	t_rt__26 = *(int32_t *)t_rt__25;
+	switch (t_rt__26) {
+	default: goto l_failed;
+	}
+l_ok:
+	t_rt__27 = *(int32_t *)t_rt__13;
+	t_rt__28 = (int32_t)((uint32_t)t_rt__27 + (uint32_t)-1);
+	*(int32_t *)t_rt__13 = t_rt__28;
+	t_b = alef_alloc(4);
+	*(int32_t *)t_b = t_v;
+	t_u = *(int32_t *)t_b;
+	t_h = alef_alloc(16);
+	alef_unalloc(t_h);
+	return t_u;
+l_failed:
+	abort();
+}
+
+static void alef__thunk__0(char *t_env) {
+	char *t_p0;
+	char *t_a0;
+	char *t_p1;
+	int32_t t_a1;
+	t_p0 = t_env + 0;
+	t_a0 = *(char * *)t_p0;
+	t_p1 = t_env + 8;
+	t_a1 = *(int32_t *)t_p1;
+	producer(t_a0, t_a1);
+	alef_unalloc(t_env);
+	return;
+}
+
+static void alef__thunk__1(char *t_env) {
+	char *t_p0;
+	char *t_a0;
+	char *t_p1;
+	int32_t t_a1;
+	t_p0 = t_env + 0;
+	t_a0 = *(char * *)t_p0;
+	t_p1 = t_env + 8;
+	t_a1 = *(int32_t *)t_p1;
+	work(t_a0, t_a1);
+	alef_unalloc(t_env);
+	return;
+}
+
+static void alef__thunk__2(char *t_env) {
+	char *t_p0;
+	char *t_a0;
+	char *t_p1;
+	int32_t t_a1;
+	t_p0 = t_env + 0;
+	t_a0 = *(char * *)t_p0;
+	t_p1 = t_env + 8;
+	t_a1 = *(int32_t *)t_p1;
+	work(t_a0, t_a1);
+	alef_unalloc(t_env);
+	return;
+}
+
+static void alef__thunk__3(char *t_env) {
+	char *t_p0;
+	char *t_a0;
+	char *t_p1;
+	int32_t t_a1;
+	t_p0 = t_env + 0;
+	t_a0 = *(char * *)t_p0;
+	t_p1 = t_env + 8;
+	t_a1 = *(int32_t *)t_p1;
+	work(t_a0, t_a1);
+	alef_unalloc(t_env);
+	return;
+}
@@ -0,0 +1,83 @@
+#include <stdint.h>
+
+struct d_fmt { char f0[9]; };
+
+int32_t printf(char *, ...);
+static int64_t fib(int64_t);
+static int64_t fib_iter(int64_t);
+int32_t main(void);
+
+static struct d_fmt fmt = { "%ld %ld\n" };
+
+static int64_t fib(int64_t t_n) {
+	int32_t t_small;
+	int64_t t_n1;
+	int64_t t_n2;
+	int64_t t_a;
+	int64_t t_b;
+	int64_t t_s;
+	t_small = t_n < 2;
+	if (t_small)
+		goto l_base;
+	goto l_rec;
+l_base:
+	return t_n;
+l_rec:
+	t_n1 = (int64_t)((uint64_t)t_n - (uint64_t)1);
+	t_n2 = (int64_t)((uint64_t)t_n - (uint64_t)2);
+	t_a = fib(t_n1);
+	t_b = fib(t_n2);
+	t_s = (int64_t)((uint64_t)t_a + (uint64_t)t_b);
+	return t_s;
+}
+
+static int64_t fib_iter(int64_t t_n) {
+	int64_t s_a;
+	char *t_a;
+	int64_t s_b;
+	char *t_b;
+	int64_t s_i;
+	char *t_i;
+	int64_t t_iv;
+	int32_t t_more;
+	int64_t t_av;
+	int64_t t_bv;
+	int64_t t_sum;
+	int64_t t_inc;
+	int64_t t_r;
+	t_a = (char *)&s_a;
+	t_b = (char *)&s_b;
+	t_i = (char *)&s_i;
+	*(int64_t *)t_a = 0;
+	*(int64_t *)t_b = 1;
+	*(int64_t *)t_i = 0;
+	goto l_cond;
+l_cond:
+	t_iv = *(int64_t *)t_i;
+	t_more = t_iv < t_n;
+	if (t_more)
+		goto l_body;
+	goto l_done;
+l_body:
+	t_av = *(int64_t *)t_a;
+	t_bv = *(int64_t *)t_b;
+	t_sum = (int64_t)((uint64_t)t_av + (uint64_t)t_bv);
+	*(int64_t *)t_a = t_bv;
+	*(int64_t *)t_b = t_sum;
+	t_inc = (int64_t)((uint64_t)t_iv + (uint64_t)1);
+	*(int64_t *)t_i = t_inc;
+	goto l_cond;
+l_done:
+	t_r = *(int64_t *)t_a;
+	return t_r;
+}
+
+int32_t main(void) {
+	int64_t t_x;
+	int64_t t_y;
+	int32_t t_r;
+	t_x = fib(10);
+	t_y = fib_iter(10);
+	t_r = printf((char *)&fmt, t_x, t_y);
+	return 0;
+}
@@ -0,0 +1,14 @@
+#include <stdint.h>
+
+struct d_msg { char f0[14]; };
+
+int32_t printf(char *, ...);
+int32_t main(void);
+
+static struct d_msg msg = { "hello, world\n" };
+
+int32_t main(void) {
+	int32_t t_r;
+	t_r = printf((char *)&msg);
+	return 0;
+}
@@ -0,0 +1,60 @@
+#include <stdint.h>
+
+struct d_fmt { char f0[24]; };
+
+int32_t printf(char *, ...);
+static int32_t classify(int8_t);
+int32_t main(void);
+
+static struct d_fmt fmt = { "%d %d %d %d %d %.1f %d\n" };
+
+static int32_t classify(int8_t t_c) {
+	switch (t_c) {
+	case -1: goto l_minus;
+	case 0: goto l_zero;
+	case 100: goto l_big;
+	default: goto l_other;
+	}
+l_minus:
+	return -2;
+l_zero:
+	return 0;
+l_big:
+	return 100;
+l_other:
+	return 7;
+}
+
+int32_t main(void) {
+	int8_t t_a;
+	int8_t t_b;
+	int32_t t_b32;
+	int8_t t_m;
+	int32_t t_u;
+	int16_t t_h;
+	int32_t t_hu;
+	int32_t t_k;
+	int8_t t_d;
+	int32_t t_d32;
+	double t_f;
+	double t_g;
+	int8_t t_w;
+	int32_t t_x;
+	int32_t t_r;
+	t_a = 127;
+	t_b = (int8_t)((uint32_t)t_a + (uint32_t)1);
+	t_b32 = (int32_t)t_b;
+	t_m = -1;
+	t_u = (int32_t)(uint8_t)t_m;
+	t_h = -1;
+	t_hu = (int32_t)(uint16_t)t_h;
+	t_k = classify(t_m);
+	t_d = (int8_t)((uint8_t)t_m / (uint8_t)85);
+	t_d32 = (int32_t)(uint8_t)t_d;
+	t_f = (double)5;
+	t_g = t_f / 2.0;
+	t_w = (int8_t)263;
+	t_x = classify(t_w);
+	t_r = printf((char *)&fmt, t_b32, t_u, t_hu, t_k, t_d32, t_g, t_x);
+	return 0;
+}
@@ -0,0 +1,81 @@
+#include <stdint.h>
+#include <stdarg.h>
+
+struct d_fmt_l { char f0[5]; };
+struct d_fmt_fi { char f0[9]; };
+
+int32_t printf(char *, ...);
+static int64_t sum(int32_t, ...);
+static int32_t scale(int32_t, ...);
+int32_t main(void);
+
+static struct d_fmt_l fmt_l = { "%ld\n" };
+static struct d_fmt_fi fmt_fi = { "%.1f %d\n" };
+
+static int64_t sum(int32_t t_n, ...) {
+	int64_t s_ap[3];
+	char *t_ap;
+	int64_t s_acc;
+	char *t_acc;
+	int32_t s_i;
+	char *t_i;
+	int32_t t_iv;
+	int32_t t_more;
+	int64_t t_x;
+	int64_t t_av;
+	int64_t t_s;
+	int32_t t_inc;
+	int64_t t_r;
+	t_ap = (char *)&s_ap;
+	t_acc = (char *)&s_acc;
+	t_i = (char *)&s_i;
+	*(int64_t *)t_acc = 0;
+	*(int32_t *)t_i = 0;
+	va_start(*(va_list *)t_ap, t_n);
+	goto l_cond;
+l_cond:
+	t_iv = *(int32_t *)t_i;
+	t_more = t_iv < t_n;
+	if (t_more)
+		goto l_body;
+	goto l_done;
+l_body:
+	t_x = va_arg(*(va_list *)t_ap, int64_t);
+	t_av = *(int64_t *)t_acc;
+	t_s = (int64_t)((uint64_t)t_av + (uint64_t)t_x);
+	*(int64_t *)t_acc = t_s;
+	t_inc = (int32_t)((uint32_t)t_iv + (uint32_t)1);
+	*(int32_t *)t_i = t_inc;
+	goto l_cond;
+l_done:
+	t_r = *(int64_t *)t_acc;
+	return t_r;
+}
+
+static int32_t scale(int32_t t_n, ...) {
+	int64_t s_ap[3];
+	char *t_ap;
+	double t_f;
+	int32_t t_k;
+	double t_kf;
+	double t_g;
+	int32_t t_r;
+	t_ap = (char *)&s_ap;
+	va_start(*(va_list *)t_ap, t_n);
+	t_f = va_arg(*(va_list *)t_ap, double);
+	t_k = va_arg(*(va_list *)t_ap, int32_t);
+	t_kf = (double)t_k;
+	t_g = t_f * t_kf;
+	t_r = printf((char *)&fmt_fi, t_g, t_n);
+	return t_r;
+}
+
+int32_t main(void) {
+	int64_t t_s;
+	int32_t t_r0;
+	int32_t t_r1;
+	t_s = sum(5, 1, 2, 3, 4, 5);
+	t_r0 = printf((char *)&fmt_l, t_s);
+	t_r1 = scale(3, 2.5, 3);
+	return 0;
+}
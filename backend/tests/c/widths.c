@@ -0,0 +1,90 @@
+#include <stdint.h>
+
+struct d_fmt1 { char f0[25]; };
+struct d_fmt2 { char f0[20]; };
+struct d_fmt3 { char f0[7]; };
+struct d_ops { void *f0; void *f1; };
+
+int32_t printf(char *, ...);
+static int32_t inc(int32_t);
+static int32_t dbl(int32_t);
+int32_t main(void);
+
+static struct d_fmt1 fmt1 = { "%ld %ld %ld %ld %ld %ld\n" };
+static struct d_fmt2 fmt2 = { "%lu %d %d %lu %.0f\n" };
+static struct d_fmt3 fmt3 = { "%d %d\n" };
+static struct d_ops ops = { (void *)&inc, (void *)&dbl };
+
+static int32_t inc(int32_t t_x) {
+	int32_t t_r;
+	t_r = (int32_t)((uint32_t)t_x + (uint32_t)1);
+	return t_r;
+}
+
+static int32_t dbl(int32_t t_x) {
+	int32_t t_r;
+	t_r = (int32_t)((uint32_t)t_x * (uint32_t)2);
+	return t_r;
+}
+
+int32_t main(void) {
+	int8_t t_a;
+	int64_t t_za;
+	int8_t t_ad;
+	int64_t t_zad;
+	int8_t t_as;
+	int64_t t_zas;
+	int8_t t_ar;
+	int64_t t_zar;
+	int16_t t_b;
+	int16_t t_bd;
+	int64_t t_zbd;
+	int16_t t_bs;
+	int64_t t_zbs;
+	int32_t t_r1;
+	int32_t t_c;
+	int32_t t_cd;
+	int64_t t_zcd;
+	int32_t t_cl;
+	int32_t t_cs;
+	int64_t t_d;
+	int64_t t_dd;
+	double t_df;
+	int32_t t_r2;
+	char *t_f;
+	char *t_p;
+	char *t_g;
+	int32_t t_x;
+	int32_t t_y;
+	int32_t t_r3;
+	t_a = -56;
+	t_za = (int64_t)(uint8_t)t_a;
+	t_ad = (int8_t)((uint8_t)t_a / (uint8_t)3);
+	t_zad = (int64_t)(uint8_t)t_ad;
+	t_as = (int8_t)((uint8_t)t_a >> (4 & 31));
+	t_zas = (int64_t)(uint8_t)t_as;
+	t_ar = (int8_t)((uint8_t)t_a % (uint8_t)7);
+	t_zar = (int64_t)(uint8_t)t_ar;
+	t_b = -536;
+	t_bd = (int16_t)((uint16_t)t_b / (uint16_t)1000);
+	t_zbd = (int64_t)(uint16_t)t_bd;
+	t_bs = (int16_t)((uint16_t)t_b >> (8 & 31));
+	t_zbs = (int64_t)(uint16_t)t_bs;
+	t_r1 = printf((char *)&fmt1, t_za, t_zad, t_zas, t_zar, t_zbd, t_zbs);
+	t_c = -294967296;
+	t_cd = (int32_t)((uint32_t)t_c / (uint32_t)3);
+	t_zcd = (int64_t)(uint32_t)t_cd;
+	t_cl = (uint32_t)t_c < (uint32_t)5;
+	t_cs = t_c < 5;
+	t_d = -1;
+	t_dd = (int64_t)((uint64_t)t_d / (uint64_t)10);
+	t_df = (double)(uint64_t)t_d;
+	t_r2 = printf((char *)&fmt2, t_zcd, t_cl, t_cs, t_dd, t_df);
+	t_f = *(char * *)(char *)&ops;
+	t_p = (char *)&ops + 8;
+	t_g = *(char * *)t_p;
+	t_x = ((int32_t (*)(int32_t))t_f)(41);
+	t_y = ((int32_t (*)(int32_t))t_g)(21);
+	t_r3 = printf((char *)&fmt3, t_x, t_y);
+	return 0;
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use alef_backend::c;
+use alef_test_support::{check_golden, tests_dir};
+use std::fs;
+use std::process::Command;
+
+#[test]
+fn test_c() {
+    for (name, module) in common::load_programs() {
+        let out = c::emit(&module).unwrap_or_else(|e| panic!("cannot translate {}: {}", name, e));
+        check_golden(&tests_dir!("c"), &name, "c", &out);
+    }
+}
+
+#[test]
+fn test_c_run() {
+    if !alef_test_support::have_cc() {
+        eprintln!("no C compiler, skipping");
+        return;
+    }
+
+    let dir = alef_test_support::scratch_dir("c");
+    for (name, module) in common::load_programs() {
+        let path = dir.join(format!("{}.alef.c", name));
+        fs::write(&path, c::emit(&module).unwrap()).unwrap();
+
+        // Programs that need the runtime can only be compiled.
+        let Some(expected) = common::expected_output(&name) else {
+            let out = Command::new("cc")
+                .args(["-std=c99", "-c", "-o"])
+                .arg(dir.join(format!("{}.o", name)))
+                .arg(&path)
+                .output()
+                .unwrap();
+            assert!(
+                out.status.success(),
+                "cannot compile {}:\n{}",
+                name,
+                String::from_utf8_lossy(&out.stderr)
+            );
+            continue;
+        };
+        assert_eq!(
+            common::link_and_run(&dir, &name, &path),
+            expected,
+            "{} printed the wrong output",
+            name
+        );
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,46 @@
+// Each test crate only uses some of the helpers.
+#![allow(dead_code)]
+
+use alef_ir::module::Module;
+use alef_test_support::tests_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The directory of the test programs shared by the backends.
+pub fn programs() -> PathBuf {
+    tests_dir!("programs")
+}
+
+/// Read and verify every test program, returning their names and modules.
+pub fn load_programs() -> Vec<(String, Module)> {
+    alef_test_support::load_modules(&programs())
+}
+
+/// The expected standard output of a test program, None if it cannot run without the runtime.
+pub fn expected_output(name: &str) -> Option<String> {
+    fs::read_to_string(programs().join(format!("{}.out", name))).ok()
+}
+
+/// Link `input` (an object file or a source file the C compiler understands) together with the
+/// C half of the program, if there is one, run the result and return its standard output.
+pub fn link_and_run(dir: &Path, name: &str, input: &Path) -> String {
+    let exe = dir.join(name);
+    let mut cc = Command::new("cc");
+    cc.arg("-o").arg(&exe).arg(input);
+    let c = programs().join(format!("{}.c", name));
+    if c.exists() {
+        cc.arg(c);
+    }
+    let out = cc.output().unwrap();
+    assert!(
+        out.status.success(),
+        "cannot link {}:\n{}",
+        name,
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let out = Command::new(&exe).output().unwrap();
+    assert!(out.status.success(), "{} exited with {}", name, out.status);
+    String::from_utf8(out.stdout).unwrap()
+}
@@ -0,0 +1,39 @@
+#![cfg(all(target_os = "linux", target_arch = "x86_64"))]
+
+mod common;
+
+use alef_backend::cranelift;
+use alef_backend::err::BackendError;
+use std::fs;
+
+#[test]
+fn test_cranelift() {
+    if !alef_test_support::have_cc() {
+        eprintln!("no C compiler, skipping");
+        return;
+    }
+
+    let dir = alef_test_support::scratch_dir("cranelift");
+    for (name, module) in common::load_programs() {
+        let Some(expected) = common::expected_output(&name) else {
+            continue;
+        };
+        let obj = match cranelift::emit(&module, &name) {
+            Ok(obj) => obj,
+            Err(e @ BackendError::Unsupported(_)) => {
+                eprintln!("{}: {}, skipping", name, e);
+                continue;
+            }
+            Err(e) => panic!("cannot compile {}: {}", name, e),
+        };
+        let path = dir.join(format!("{}.o", name));
+        fs::write(&path, obj).unwrap();
+        assert_eq!(
+            common::link_and_run(&dir, &name, &path),
+            expected,
+            "{} printed the wrong output",
+            name
+        );
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,79 @@
+%IF = type { i32, double }
+%FF = type { double, double }
+%Big = type { i64, i64, i64 }
+
+@fmt_if = internal global <{ [9 x i8] }> <{ [9 x i8] c"%d %.1f\0A\00" }>, align 1
+@fmt_f = internal global <{ [6 x i8] }> <{ [6 x i8] c"%.2f\0A\00" }>, align 1
+@fmt_l = internal global <{ [5 x i8] }> <{ [5 x i8] c"%ld\0A\00" }>, align 1
+@fmt_d = internal global <{ [4 x i8] }> <{ [4 x i8] c"%d\0A\00" }>, align 1
+
+declare i32 @printf(ptr, ...)
+declare { i64, double } @mk_if(i32, double)
+declare double @sum_ff(double, double)
+declare void @mk_big(ptr sret(%Big), i64)
+declare i64 @sum_big(ptr byval(%Big) align 8)
+declare i32 @call_scale()
+
+define { i64, double } @scale(i64 %x.0, double %x.1, i32 %k) {
+entry:
+  %x = alloca { i64, i64 }, align 8
+  %.t = getelementptr inbounds i8, ptr %x, i64 0
+  store i64 %x.0, ptr %.t
+  %.t.0 = getelementptr inbounds i8, ptr %x, i64 8
+  store double %x.1, ptr %.t.0
+  %r = alloca %IF, align 8
+  %.t.1 = alloca { i64, i64 }, align 8
+  br label %start
+start:
+  %xi.p = getelementptr inbounds %IF, ptr %x, i32 0, i32 0
+  %xf.p = getelementptr inbounds %IF, ptr %x, i32 0, i32 1
+  %xi = load i32, ptr %xi.p
+  %xf = load double, ptr %xf.p
+  %ri = mul i32 %xi, %k
+  %kf = sitofp i32 %k to double
+  %rf = fmul double %xf, %kf
+  %ri.p = getelementptr inbounds %IF, ptr %r, i32 0, i32 0
+  %rf.p = getelementptr inbounds %IF, ptr %r, i32 0, i32 1
+  store i32 %ri, ptr %ri.p
+  store double %rf, ptr %rf.p
+  call void @llvm.memcpy.p0.p0.i64(ptr %.t.1, ptr %r, i64 16, i1 false)
+  %.t.2 = load { i64, double }, ptr %.t.1
+  ret { i64, double } %.t.2
+}
+
+define i32 @main() {
+entry:
+  %a = alloca { i64, i64 }, align 8
+  %ff = alloca %FF, align 8
+  %.t.0 = alloca { i64, i64 }, align 8
+  %.t.5 = alloca %Big, align 8
+  br label %start
+start:
+  %.t = call { i64, double } @mk_if(i32 3, double 1.5)
+  store { i64, double } %.t, ptr %a
+  %ai.p = getelementptr inbounds %IF, ptr %a, i32 0, i32 0
+  %af.p = getelementptr inbounds %IF, ptr %a, i32 0, i32 1
+  %ai = load i32, ptr %ai.p
+  %af = load double, ptr %af.p
+  %r0 = call i32 (ptr, ...) @printf(ptr @fmt_if, i32 %ai, double %af)
+  %ffa = getelementptr inbounds %FF, ptr %ff, i32 0, i32 0
+  %ffb = getelementptr inbounds %FF, ptr %ff, i32 0, i32 1
+  store double 1.25, ptr %ffa
+  store double 2.5, ptr %ffb
+  call void @llvm.memcpy.p0.p0.i64(ptr %.t.0, ptr %ff, i64 16, i1 false)
+  %.t.1 = getelementptr inbounds i8, ptr %.t.0, i64 0
+  %.t.2 = load double, ptr %.t.1
+  %.t.3 = getelementptr inbounds i8, ptr %.t.0, i64 8
+  %.t.4 = load double, ptr %.t.3
+  %s = call double @sum_ff(double %.t.2, double %.t.4)
+  %r1 = call i32 (ptr, ...) @printf(ptr @fmt_f, double %s)
+  call void @mk_big(ptr sret(%Big) %.t.5, i64 5)
+  %b = getelementptr inbounds i8, ptr %.t.5, i64 0
+  %t = call i64 @sum_big(ptr byval(%Big) align 8 %b)
+  %r2 = call i32 (ptr, ...) @printf(ptr @fmt_l, i64 %t)
+  %c = call i32 @call_scale()
+  %r3 = call i32 (ptr, ...) @printf(ptr @fmt_d, i32 %c)
+  ret i32 0
+}
+
+declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)
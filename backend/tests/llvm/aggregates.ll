@@ -0,0 +1,124 @@
+%Point = type { i32, i32 }
+%Line = type { %Point, %Point }
+
+@fmt = internal global <{ [7 x i8] }> <{ [7 x i8] c"%d %d\0A\00" }>, align 1
+@fmt1 = internal global <{ [4 x i8] }> <{ [4 x i8] c"%d\0A\00" }>, align 1
+
+declare i32 @printf(ptr, ...)
+
+define internal i64 @add(i64 %a.0, i64 %b.0) {
+entry:
+  %a = alloca { i64, i64 }, align 8
+  %.t = getelementptr inbounds i8, ptr %a, i64 0
+  store i64 %a.0, ptr %.t
+  %b = alloca { i64, i64 }, align 8
+  %.t.0 = getelementptr inbounds i8, ptr %b, i64 0
+  store i64 %b.0, ptr %.t.0
+  %r = alloca %Point, align 4
+  %.t.1 = alloca { i64, i64 }, align 8
+  br label %start
+start:
+  %ax.p = getelementptr inbounds %Point, ptr %a, i32 0, i32 0
+  %bx.p = getelementptr inbounds %Point, ptr %b, i32 0, i32 0
+  %rx.p = getelementptr inbounds %Point, ptr %r, i32 0, i32 0
+  %ax = load i32, ptr %ax.p
+  %bx = load i32, ptr %bx.p
+  %rx = add i32 %ax, %bx
+  store i32 %rx, ptr %rx.p
+  %ay.p = getelementptr inbounds %Point, ptr %a, i32 0, i32 1
+  %by.p = getelementptr inbounds %Point, ptr %b, i32 0, i32 1
+  %ry.p = getelementptr inbounds %Point, ptr %r, i32 0, i32 1
+  %ay = load i32, ptr %ay.p
+  %by = load i32, ptr %by.p
+  %ry = add i32 %ay, %by
+  store i32 %ry, ptr %ry.p
+  call void @llvm.memcpy.p0.p0.i64(ptr %.t.1, ptr %r, i64 8, i1 false)
+  %.t.2 = load i64, ptr %.t.1
+  ret i64 %.t.2
+}
+
+define internal i32 @sum(ptr %v, i32 %n) {
+entry:
+  %iv.addr = alloca i32
+  %acc = alloca i32, align 4
+  %i = alloca i32, align 4
+  br label %start
+start:
+  store i32 0, ptr %acc
+  store i32 0, ptr %i
+  br label %cond
+cond:
+  %iv = load i32, ptr %i
+  store i32 %iv, ptr %iv.addr
+  %.t = icmp slt i32 %iv, %n
+  %more = zext i1 %.t to i32
+  %.t.0 = icmp ne i32 %more, 0
+  br i1 %.t.0, label %body, label %done
+body:
+  %.t.1 = load i32, ptr %iv.addr
+  %p = getelementptr inbounds i32, ptr %v, i32 %.t.1
+  %x = load i32, ptr %p
+  %av = load i32, ptr %acc
+  %s = add i32 %av, %x
+  store i32 %s, ptr %acc
+  %.t.2 = load i32, ptr %iv.addr
+  %inc = add i32 %.t.2, 1
+  store i32 %inc, ptr %i
+  br label %cond
+done:
+  %r = load i32, ptr %acc
+  ret i32 %r
+}
+
+define i32 @main() {
+entry:
+  %l = alloca %Line, align 4
+  %.t = alloca { i64, i64 }, align 8
+  %.t.2 = alloca { i64, i64 }, align 8
+  %c = alloca { i64, i64 }, align 8
+  %v = alloca [4 x i32], align 4
+  %t = alloca %Point, align 4
+  br label %start
+start:
+  %a = getelementptr inbounds %Line, ptr %l, i32 0, i32 0
+  %b = getelementptr inbounds %Line, ptr %l, i32 0, i32 1
+  %ax = getelementptr inbounds %Point, ptr %a, i32 0, i32 0
+  %ay = getelementptr inbounds %Point, ptr %a, i32 0, i32 1
+  %bx = getelementptr inbounds %Point, ptr %b, i32 0, i32 0
+  %by = getelementptr inbounds %Point, ptr %b, i32 0, i32 1
+  store i32 1, ptr %ax
+  store i32 2, ptr %ay
+  store i32 3, ptr %bx
+  store i32 4, ptr %by
+  call void @llvm.memcpy.p0.p0.i64(ptr %.t, ptr %a, i64 8, i1 false)
+  %.t.0 = getelementptr inbounds i8, ptr %.t, i64 0
+  %.t.1 = load i64, ptr %.t.0
+  call void @llvm.memcpy.p0.p0.i64(ptr %.t.2, ptr %b, i64 8, i1 false)
+  %.t.3 = getelementptr inbounds i8, ptr %.t.2, i64 0
+  %.t.4 = load i64, ptr %.t.3
+  %.t.5 = call i64 @add(i64 %.t.1, i64 %.t.4)
+  store i64 %.t.5, ptr %c
+  %cx.p = getelementptr inbounds %Point, ptr %c, i32 0, i32 0
+  %cy.p = getelementptr inbounds %Point, ptr %c, i32 0, i32 1
+  %cx = load i32, ptr %cx.p
+  %cy = load i32, ptr %cy.p
+  %r0 = call i32 (ptr, ...) @printf(ptr @fmt, i32 %cx, i32 %cy)
+  call void @llvm.memcpy.p0.p0.i64(ptr %t, ptr %a, i64 8, i1 false)
+  %tx = getelementptr inbounds %Point, ptr %t, i32 0, i32 0
+  %e0 = getelementptr inbounds i32, ptr %v, i64 0
+  %e1 = getelementptr inbounds i32, ptr %v, i64 1
+  %e2 = getelementptr inbounds i32, ptr %v, i64 2
+  %e3 = getelementptr inbounds i32, ptr %v, i64 3
+  store i32 1, ptr %e0
+  store i32 2, ptr %e1
+  store i32 3, ptr %e2
+  store i32 4, ptr %e3
+  %s = call i32 @sum(ptr %v, i32 4)
+  %r1 = call i32 (ptr, ...) @printf(ptr @fmt1, i32 %s)
+  %w = load i32, ptr %tx
+  %w8 = add i32 %w, 8
+  %r2 = call i32 (ptr, ...) @printf(ptr @fmt1, i32 %w8)
+  ret i32 0
+}
+
+declare void @llvm.memcpy.p0.p0.i64(ptr, ptr, i64, i1)
@@ -0,0 +1,23 @@
+@fmt = internal global <{ [4 x i8] }> <{ [4 x i8] c"%d\0A\00" }>, align 1
+
+declare i32 @printf(ptr, ...)
+declare i32 @sort_and_sum(ptr)
+
+define internal i32 @descending(ptr %a, ptr %b) {
+entry:
+  br label %start
+start:
+  %x = load i32, ptr %a
+  %y = load i32, ptr %b
+  %r = sub i32 %y, %x
+  ret i32 %r
+}
+
+define i32 @main() {
+entry:
+  br label %start
+start:
+  %s = call i32 @sort_and_sum(ptr @descending)
+  %r = call i32 (ptr, ...) @printf(ptr @fmt, i32 %s)
+  ret i32 0
+}
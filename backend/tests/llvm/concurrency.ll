@@ -0,0 +1,224 @@
+%Cell = type { ptr, i64 }
+%alef.altcase = type { ptr, i64, ptr }
+%alef.env.0 = type { ptr, i32 }
+%alef.env.1 = type { ptr, i32 }
+%alef.env.2 = type { ptr, i32 }
+%alef.env.3 = type { ptr, i32 }
+
+declare void @work(ptr, i32)
+declare ptr @alef_alloc(i64)
+declare i32 @alef_alt(ptr, i32)
+declare i32 @alef_chan_canrecv(ptr)
+declare i32 @alef_chan_cansend(ptr)
+declare ptr @alef_chan_new(i64, i64)
+declare void @alef_chan_recv(ptr, ptr)
+declare void @alef_chan_send(ptr, ptr)
+declare ptr @alef_par_begin()
+declare void @alef_par_join(ptr)
+declare void @alef_par_spawn(ptr, ptr, ptr)
+declare void @alef_proc(ptr, ptr)
+declare void @alef_raise()
+declare void @alef_task(ptr, ptr)
+declare void @alef_unalloc(ptr)
+
+define internal void @producer(ptr %c, i32 %n) {
+entry:
+  %rt.0 = alloca i32, align 4
+  %rt.1 = alloca i32, align 4
+  br label %start
+start:
+  store i32 %n, ptr %rt.0
+  call void @alef_chan_send(ptr %c, ptr %rt.0)
+  %ok = call i32 @alef_chan_cansend(ptr %c)
+  %.t = icmp ne i32 %ok, 0
+  br i1 %.t, label %again, label %done
+again:
+  store i32 0, ptr %rt.1
+  call void @alef_chan_send(ptr %c, ptr %rt.1)
+  br label %done
+done:
+  ret void
+}
+
+define internal i32 @consumer(ptr %c, ptr %d) {
+entry:
+  %rt.0 = alloca [3 x %alef.altcase], align 8
+  %rt.9 = alloca i32, align 4
+  %slot = alloca i32, align 4
+  br label %start
+start:
+  %rt.1 = getelementptr inbounds %alef.altcase, ptr %rt.0, i64 0
+  %rt.2 = getelementptr inbounds %alef.altcase, ptr %rt.1, i32 0, i32 0
+  store ptr %c, ptr %rt.2
+  %rt.3 = getelementptr inbounds %alef.altcase, ptr %rt.1, i32 0, i32 1
+  store i64 0, ptr %rt.3
+  %rt.4 = getelementptr inbounds %alef.altcase, ptr %rt.1, i32 0, i32 2
+  store ptr %slot, ptr %rt.4
+  %rt.5 = getelementptr inbounds %alef.altcase, ptr %rt.0, i64 1
+  %rt.6 = getelementptr inbounds %alef.altcase, ptr %rt.5, i32 0, i32 0
+  store ptr %d, ptr %rt.6
+  %rt.7 = getelementptr inbounds %alef.altcase, ptr %rt.5, i32 0, i32 1
+  store i64 0, ptr %rt.7
+  %rt.8 = getelementptr inbounds %alef.altcase, ptr %rt.5, i32 0, i32 2
+  store ptr null, ptr %rt.8
+  store i32 5, ptr %rt.9
+  %rt.10 = getelementptr inbounds %alef.altcase, ptr %rt.0, i64 2
+  %rt.11 = getelementptr inbounds %alef.altcase, ptr %rt.10, i32 0, i32 0
+  store ptr %d, ptr %rt.11
+  %rt.12 = getelementptr inbounds %alef.altcase, ptr %rt.10, i32 0, i32 1
+  store i64 1, ptr %rt.12
+  %rt.13 = getelementptr inbounds %alef.altcase, ptr %rt.10, i32 0, i32 2
+  store ptr %rt.9, ptr %rt.13
+  %rt.14 = call i32 @alef_alt(ptr %rt.0, i32 3)
+  switch i32 %rt.14, label %sent [
+    i32 0, label %got
+    i32 1, label %skip
+  ]
+got:
+  %v = load i32, ptr %slot
+  ret i32 %v
+skip:
+  ret i32 -1
+sent:
+  %ready = call i32 @alef_chan_canrecv(ptr %c)
+  ret i32 %ready
+}
+
+define i32 @main() {
+entry:
+  %v.addr = alloca i32
+  %rt.12 = alloca [1 x i32], align 4
+  %rt.13 = alloca i32, align 4
+  %rt.17 = alloca i32, align 4
+  br label %start
+start:
+  store i32 0, ptr %rt.13
+  %c = call ptr @alef_chan_new(i64 4, i64 0)
+  %d = call ptr @alef_chan_new(i64 4, i64 4)
+  %rt.0 = call ptr @alef_alloc(i64 16)
+  %rt.1 = getelementptr inbounds %alef.env.0, ptr %rt.0, i32 0, i32 0
+  store ptr %c, ptr %rt.1
+  %rt.2 = getelementptr inbounds %alef.env.0, ptr %rt.0, i32 0, i32 1
+  store i32 42, ptr %rt.2
+  call void @alef_proc(ptr @alef.thunk.0, ptr %rt.0)
+  %rt.3 = call ptr @alef_alloc(i64 16)
+  %rt.4 = getelementptr inbounds %alef.env.1, ptr %rt.3, i32 0, i32 0
+  store ptr %d, ptr %rt.4
+  %rt.5 = getelementptr inbounds %alef.env.1, ptr %rt.3, i32 0, i32 1
+  store i32 1, ptr %rt.5
+  call void @alef_task(ptr @alef.thunk.1, ptr %rt.3)
+  %g = call ptr @alef_par_begin()
+  %rt.6 = call ptr @alef_alloc(i64 16)
+  %rt.7 = getelementptr inbounds %alef.env.2, ptr %rt.6, i32 0, i32 0
+  store ptr %d, ptr %rt.7
+  %rt.8 = getelementptr inbounds %alef.env.2, ptr %rt.6, i32 0, i32 1
+  store i32 2, ptr %rt.8
+  call void @alef_par_spawn(ptr %g, ptr @alef.thunk.2, ptr %rt.6)
+  %rt.9 = call ptr @alef_alloc(i64 16)
+  %rt.10 = getelementptr inbounds %alef.env.3, ptr %rt.9, i32 0, i32 0
+  store ptr %d, ptr %rt.10
+  %rt.11 = getelementptr inbounds %alef.env.3, ptr %rt.9, i32 0, i32 1
+  store i32 3, ptr %rt.11
+  call void @alef_par_spawn(ptr %g, ptr @alef.thunk.3, ptr %rt.9)
+  call void @alef_par_join(ptr %g)
+  %rt.14 = load i32, ptr %rt.13
+  %rt.15 = add i32 %rt.14, 1
+  store i32 %rt.15, ptr %rt.13
+  %rt.16 = getelementptr inbounds i32, ptr %rt.12, i32 %rt.14
+  store i32 0, ptr %rt.16
+  call void @alef_chan_recv(ptr %c, ptr %rt.17)
+  %v = load i32, ptr %rt.17
+  store i32 %v, ptr %v.addr
+  %.t = icmp eq i32 %v, 0
+  %bad = zext i1 %.t to i32
+  %.t.0 = icmp ne i32 %bad, 0
+  br i1 %.t.0, label %raise, label %ok
+raise:
+  br label %rt.18
+rt.18:
+  %rt.21 = load i32, ptr %rt.13
+  %.t.1 = icmp eq i32 %rt.21, 0
+  %rt.22 = zext i1 %.t.1 to i32
+  %.t.2 = icmp ne i32 %rt.22, 0
+  br i1 %.t.2, label %rt.19, label %rt.20
+rt.19:
+  call void @alef_raise()
+  call void @llvm.trap()
+  unreachable
+rt.20:
+  %rt.23 = load i32, ptr %rt.13
+  %rt.24 = add i32 %rt.23, -1
+  store i32 %rt.24, ptr %rt.13
+  %rt.25 = getelementptr inbounds i32, ptr %rt.12, i32 %rt.24
+  %rt.26 = load i32, ptr %rt.25
+  switch i32 %rt.26, label %failed [
+  ]
+ok:
+  %rt.27 = load i32, ptr %rt.13
+  %rt.28 = add i32 %rt.27, -1
+  store i32 %rt.28, ptr %rt.13
+  %b = call ptr @alef_alloc(i64 4)
+  %.t.3 = load i32, ptr %v.addr
+  store i32 %.t.3, ptr %b
+  %u = load i32, ptr %b
+  %h = call ptr @alef_alloc(i64 16)
+  call void @alef_unalloc(ptr %h)
+  ret i32 %u
+failed:
+  call void @llvm.trap()
+  unreachable
+}
+
+define internal void @alef.thunk.0(ptr %env) {
+entry:
+  br label %start
+start:
+  %p0 = getelementptr inbounds %alef.env.0, ptr %env, i32 0, i32 0
+  %a0 = load ptr, ptr %p0
+  %p1 = getelementptr inbounds %alef.env.0, ptr %env, i32 0, i32 1
+  %a1 = load i32, ptr %p1
+  call void @producer(ptr %a0, i32 %a1)
+  call void @alef_unalloc(ptr %env)
+  ret void
+}
+
+define internal void @alef.thunk.1(ptr %env) {
+entry:
+  br label %start
+start:
+  %p0 = getelementptr inbounds %alef.env.1, ptr %env, i32 0, i32 0
+  %a0 = load ptr, ptr %p0
+  %p1 = getelementptr inbounds %alef.env.1, ptr %env, i32 0, i32 1
+  %a1 = load i32, ptr %p1
+  call void @work(ptr %a0, i32 %a1)
+  call void @alef_unalloc(ptr %env)
+  ret void
+}
+
+define internal void @alef.thunk.2(ptr %env) {
+entry:
+  br label %start
+start:
+  %p0 = getelementptr inbounds %alef.env.2, ptr %env, i32 0, i32 0
+  %a0 = load ptr, ptr %p0
+  %p1 = getelementptr inbounds %alef.env.2, ptr %env, i32 0, i32 1
+  %a1 = load i32, ptr %p1
+  call void @work(ptr %a0, i32 %a1)
+  call void @alef_unalloc(ptr %env)
+  ret void
+}
+
+define internal void @alef.thunk.3(ptr %env) {
+entry:
+  br label %start
+start:
+  %p0 = getelementptr inbounds %alef.env.3, ptr %env, i32 0, i32 0
+  %a0 = load ptr, ptr %p0
+  %p1 = getelementptr inbounds %alef.env.3, ptr %env, i32 0, i32 1
+  %a1 = load i32, ptr %p1
+  call void @work(ptr %a0, i32 %a1)
+  call void @alef_unalloc(ptr %env)
+  ret void
+}
+
+declare void @llvm.trap()
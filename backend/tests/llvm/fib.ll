@@ -0,0 +1,66 @@
+@fmt = internal global <{ [9 x i8] }> <{ [9 x i8] c"%ld %ld\0A\00" }>, align 1
+
+declare i32 @printf(ptr, ...)
+
+define internal i64 @fib(i64 %n) {
+entry:
+  br label %start
+start:
+  %.t = icmp slt i64 %n, 2
+  %small = zext i1 %.t to i32
+  %.t.0 = icmp ne i32 %small, 0
+  br i1 %.t.0, label %base, label %rec
+base:
+  ret i64 %n
+rec:
+  %n1 = sub i64 %n, 1
+  %n2 = sub i64 %n, 2
+  %a = call i64 @fib(i64 %n1)
+  %b = call i64 @fib(i64 %n2)
+  %s = add i64 %a, %b
+  ret i64 %s
+}
+
+define internal i64 @fib_iter(i64 %n) {
+entry:
+  %iv.addr = alloca i64
+  %a = alloca i64, align 8
+  %b = alloca i64, align 8
+  %i = alloca i64, align 8
+  br label %start
+start:
+  store i64 0, ptr %a
+  store i64 1, ptr %b
+  store i64 0, ptr %i
+  br label %cond
+cond:
+  %iv = load i64, ptr %i
+  store i64 %iv, ptr %iv.addr
+  %.t = icmp slt i64 %iv, %n
+  %more = zext i1 %.t to i32
+  %.t.0 = icmp ne i32 %more, 0
+  br i1 %.t.0, label %body, label %done
+body:
+  %av = load i64, ptr %a
+  %bv = load i64, ptr %b
+  %sum = add i64 %av, %bv
+  store i64 %bv, ptr %a
+  store i64 %sum, ptr %b
+  %.t.1 = load i64, ptr %iv.addr
+  %inc = add i64 %.t.1, 1
+  store i64 %inc, ptr %i
+  br label %cond
+done:
+  %r = load i64, ptr %a
+  ret i64 %r
+}
+
+define i32 @main() {
+entry:
+  br label %start
+start:
+  %x = call i64 @fib(i64 10)
+  %y = call i64 @fib_iter(i64 10)
+  %r = call i32 (ptr, ...) @printf(ptr @fmt, i64 %x, i64 %y)
+  ret i32 0
+}
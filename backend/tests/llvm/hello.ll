@@ -0,0 +1,11 @@
+@msg = internal global <{ [14 x i8] }> <{ [14 x i8] c"hello, world\0A\00" }>, align 1
+
+declare i32 @printf(ptr, ...)
+
+define i32 @main() {
+entry:
+  br label %start
+start:
+  %r = call i32 (ptr, ...) @printf(ptr @msg)
+  ret i32 0
+}
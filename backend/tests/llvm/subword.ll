@@ -0,0 +1,44 @@
+@fmt = internal global <{ [24 x i8] }> <{ [24 x i8] c"%d %d %d %d %d %.1f %d\0A\00" }>, align 1
+
+declare i32 @printf(ptr, ...)
+
+define internal i32 @classify(i8 signext %c) {
+entry:
+  br label %start
+start:
+  switch i8 %c, label %other [
+    i8 -1, label %minus
+    i8 0, label %zero
+    i8 100, label %big
+  ]
+minus:
+  ret i32 -2
+zero:
+  ret i32 0
+big:
+  ret i32 100
+other:
+  ret i32 7
+}
+
+define i32 @main() {
+entry:
+  br label %start
+start:
+  %a = bitcast i8 127 to i8
+  %b = add i8 %a, 1
+  %b32 = sext i8 %b to i32
+  %m = bitcast i8 -1 to i8
+  %u = zext i8 %m to i32
+  %h = bitcast i16 -1 to i16
+  %hu = zext i16 %h to i32
+  %k = call i32 @classify(i8 signext %m)
+  %d = udiv i8 %m, 85
+  %d32 = zext i8 %d to i32
+  %f = sitofp i32 5 to double
+  %g = fdiv double %f, 2.0
+  %w = trunc i32 263 to i8
+  %x = call i32 @classify(i8 signext %w)
+  %r = call i32 (ptr, ...) @printf(ptr @fmt, i32 %b32, i32 %u, i32 %hu, i32 %k, i32 %d32, double %g, i32 %x)
+  ret i32 0
+}
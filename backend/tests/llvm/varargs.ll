@@ -0,0 +1,63 @@
+@fmt_l = internal global <{ [5 x i8] }> <{ [5 x i8] c"%ld\0A\00" }>, align 1
+@fmt_fi = internal global <{ [9 x i8] }> <{ [9 x i8] c"%.1f %d\0A\00" }>, align 1
+
+declare i32 @printf(ptr, ...)
+
+define internal i64 @sum(i32 %n, ...) {
+entry:
+  %iv.addr = alloca i32
+  %ap = alloca [3 x i64], align 8
+  %acc = alloca i64, align 8
+  %i = alloca i32, align 4
+  br label %start
+start:
+  store i64 0, ptr %acc
+  store i32 0, ptr %i
+  call void @llvm.va_start(ptr %ap)
+  br label %cond
+cond:
+  %iv = load i32, ptr %i
+  store i32 %iv, ptr %iv.addr
+  %.t = icmp slt i32 %iv, %n
+  %more = zext i1 %.t to i32
+  %.t.0 = icmp ne i32 %more, 0
+  br i1 %.t.0, label %body, label %done
+body:
+  %x = va_arg ptr %ap, i64
+  %av = load i64, ptr %acc
+  %s = add i64 %av, %x
+  store i64 %s, ptr %acc
+  %.t.1 = load i32, ptr %iv.addr
+  %inc = add i32 %.t.1, 1
+  store i32 %inc, ptr %i
+  br label %cond
+done:
+  %r = load i64, ptr %acc
+  ret i64 %r
+}
+
+define internal i32 @scale(i32 %n, ...) {
+entry:
+  %ap = alloca [3 x i64], align 8
+  br label %start
+start:
+  call void @llvm.va_start(ptr %ap)
+  %f = va_arg ptr %ap, double
+  %k = va_arg ptr %ap, i32
+  %kf = sitofp i32 %k to double
+  %g = fmul double %f, %kf
+  %r = call i32 (ptr, ...) @printf(ptr @fmt_fi, double %g, i32 %n)
+  ret i32 %r
+}
+
+define i32 @main() {
+entry:
+  br label %start
+start:
+  %s = call i64 (i32, ...) @sum(i32 5, i64 1, i64 2, i64 3, i64 4, i64 5)
+  %r0 = call i32 (ptr, ...) @printf(ptr @fmt_l, i64 %s)
+  %r1 = call i32 (i32, ...) @scale(i32 3, double 2.5, i32 3)
+  ret i32 0
+}
+
+declare void @llvm.va_start(ptr)
@@ -0,0 +1,60 @@
+@fmt1 = internal global <{ [25 x i8] }> <{ [25 x i8] c"%ld %ld %ld %ld %ld %ld\0A\00" }>, align 1
+@fmt2 = internal global <{ [20 x i8] }> <{ [20 x i8] c"%lu %d %d %lu %.0f\0A\00" }>, align 1
+@fmt3 = internal global <{ [7 x i8] }> <{ [7 x i8] c"%d %d\0A\00" }>, align 1
+@ops = internal global <{ ptr, ptr }> <{ ptr @inc, ptr @dbl }>, align 8
+
+declare i32 @printf(ptr, ...)
+
+define internal i32 @inc(i32 %x) {
+entry:
+  br label %start
+start:
+  %r = add i32 %x, 1
+  ret i32 %r
+}
+
+define internal i32 @dbl(i32 %x) {
+entry:
+  br label %start
+start:
+  %r = mul i32 %x, 2
+  ret i32 %r
+}
+
+define i32 @main() {
+entry:
+  br label %start
+start:
+  %a = bitcast i8 -56 to i8
+  %za = zext i8 %a to i64
+  %ad = udiv i8 %a, 3
+  %zad = zext i8 %ad to i64
+  %as = lshr i8 %a, 4
+  %zas = zext i8 %as to i64
+  %ar = urem i8 %a, 7
+  %zar = zext i8 %ar to i64
+  %b = bitcast i16 -536 to i16
+  %bd = udiv i16 %b, 1000
+  %zbd = zext i16 %bd to i64
+  %bs = lshr i16 %b, 8
+  %zbs = zext i16 %bs to i64
+  %r1 = call i32 (ptr, ...) @printf(ptr @fmt1, i64 %za, i64 %zad, i64 %zas, i64 %zar, i64 %zbd, i64 %zbs)
+  %c = bitcast i32 -294967296 to i32
+  %cd = udiv i32 %c, 3
+  %zcd = zext i32 %cd to i64
+  %.t = icmp ult i32 %c, 5
+  %cl = zext i1 %.t to i32
+  %.t.0 = icmp slt i32 %c, 5
+  %cs = zext i1 %.t.0 to i32
+  %d = bitcast i64 -1 to i64
+  %dd = udiv i64 %d, 10
+  %df = uitofp i64 %d to double
+  %r2 = call i32 (ptr, ...) @printf(ptr @fmt2, i64 %zcd, i32 %cl, i32 %cs, i64 %dd, double %df)
+  %f = load ptr, ptr @ops
+  %p = getelementptr inbounds ptr, ptr @ops, i64 1
+  %g = load ptr, ptr %p
+  %x = call i32 (i32) %f(i32 41)
+  %y = call i32 (i32) %g(i32 21)
+  %r3 = call i32 (ptr, ...) @printf(ptr @fmt3, i32 %x, i32 %y)
+  ret i32 0
+}
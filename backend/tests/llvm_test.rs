@@ -0,0 +1,73 @@
+mod common;
+
+use alef_backend::llvm;
+use alef_test_support::{check_golden, tests_dir};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_llvm() {
+    for (name, module) in common::load_programs() {
+        check_golden(&tests_dir!("llvm"), &name, "ll", &llvm::emit(&module));
+    }
+}
+
+/// The major version of `llc`, None if it is not installed.
+fn llc_version() -> Option<u32> {
+    let out = Command::new("llc").arg("--version").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let version = text.split("LLVM version ").nth(1)?;
+    version.split('.').next()?.trim().parse().ok()
+}
+
+fn llc(version: u32, input: &Path, output: &Path) {
+    let mut cmd = Command::new("llc");
+    if version < 15 {
+        cmd.arg("-opaque-pointers");
+    }
+    let out = cmd
+        .args(["-filetype=obj", "-relocation-model=pic", "-o"])
+        .arg(output)
+        .arg(input)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "llc rejected {}:\n{}",
+        input.display(),
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+#[test]
+fn test_llvm_run() {
+    let Some(version) = llc_version() else {
+        eprintln!("no llc, skipping");
+        return;
+    };
+    if !alef_test_support::have_cc() {
+        eprintln!("no C compiler, skipping");
+        return;
+    }
+
+    let dir = alef_test_support::scratch_dir("llvm");
+    for (name, module) in common::load_programs() {
+        let ll = dir.join(format!("{}.ll", name));
+        let obj = dir.join(format!("{}.o", name));
+        fs::write(&ll, llvm::emit(&module)).unwrap();
+        llc(version, &ll, &obj);
+
+        // Programs that need the runtime can only be compiled.
+        if let Some(expected) = common::expected_output(&name) {
+            assert_eq!(
+                common::link_and_run(&dir, &name, &obj),
+                expected,
+                "{} printed the wrong output",
+                name
+            );
+        }
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
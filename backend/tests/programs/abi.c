@@ -0,0 +1,21 @@
+/* C half of abi.air: aggregates passed by value across the language boundary. */
+
+struct IF { int i; double f; };
+struct FF { double a, b; };
+struct Big { long a, b, c; };
+
+struct IF scale(struct IF x, int k);
+
+struct IF mk_if(int i, double f) { struct IF r = { i, f }; return r; }
+
+double sum_ff(struct FF x) { return x.a + x.b; }
+
+struct Big mk_big(long a) { struct Big r = { a, a + 1, a + 2 }; return r; }
+
+long sum_big(struct Big b) { return b.a + b.b + b.c; }
+
+int call_scale(void) {
+	struct IF x = { 3, 0.5 };
+	struct IF r = scale(x, 4);
+	return r.i + (int)r.f;
+}
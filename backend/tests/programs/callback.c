@@ -0,0 +1,9 @@
+/* C half of callback.air: C code calling back into Alef through a function pointer. */
+
+#include <stdlib.h>
+
+int sort_and_sum(int (*cmp)(const void *, const void *)) {
+	int xs[] = { 4, 1, 3, 2 };
+	qsort(xs, 4, sizeof xs[0], cmp);
+	return xs[0] * 1000 + xs[1] * 100 + xs[2] * 10 + xs[3];
+}
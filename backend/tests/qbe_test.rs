@@ -0,0 +1,11 @@
+mod common;
+
+use alef_backend::qbe;
+use alef_test_support::{check_golden, tests_dir};
+
+#[test]
+fn test_qbe() {
+    for (name, module) in common::load_programs() {
+        check_golden(&tests_dir!("qbe"), &name, "ssa", &qbe::emit(&module));
+    }
+}
@@ -0,0 +1,261 @@
+//! A small helper to build functions incrementally, as the lowering of statements does: it
+//! hands out fresh temporaries and labels and keeps track of the block being filled.
+
+use crate::func::{Block, CallConv, Function, Linkage, Param};
+use crate::inst::{BinOp, CmpOp, Inst, Terminator, Value};
+use crate::ty::Type;
+
+struct PartialBlock {
+    label: String,
+    insts: Vec<Inst>,
+    term: Option<Terminator>,
+}
+
+/// Builds a `Function` block by block.
+pub struct FunctionBuilder {
+    name: String,
+    linkage: Linkage,
+    conv: CallConv,
+    params: Vec<Param>,
+    variadic: bool,
+    ret: Type,
+    blocks: Vec<PartialBlock>,
+    current: usize,
+    temps: usize,
+    labels: usize,
+}
+
+impl FunctionBuilder {
+    /// Create a builder for a function; an entry block named `start` is created and selected.
+    pub fn new(name: &str, linkage: Linkage, params: Vec<Param>, ret: Type) -> FunctionBuilder {
+        FunctionBuilder {
+            name: name.to_string(),
+            linkage,
+            conv: CallConv::Alef,
+            params,
+            variadic: false,
+            ret,
+            blocks: vec![PartialBlock {
+                label: "start".to_string(),
+                insts: vec![],
+                term: None,
+            }],
+            current: 0,
+            temps: 0,
+            labels: 0,
+        }
+    }
+
+    /// Mark the function as accepting additional arguments.
+    pub fn set_variadic(&mut self, variadic: bool) {
+        self.variadic = variadic;
+    }
+
+    /// Set the calling convention of the function.
+    pub fn set_conv(&mut self, conv: CallConv) {
+        self.conv = conv;
+    }
+
+    /// Return a fresh temporary name.
+    pub fn temp(&mut self) -> String {
+        let t = format!("t{}", self.temps);
+        self.temps += 1;
+        t
+    }
+
+    /// Create a new empty block and return its label; `hint` is used as a prefix.
+    pub fn create_block(&mut self, hint: &str) -> String {
+        let label = format!("{}.{}", hint, self.labels);
+        self.labels += 1;
+        self.blocks.push(PartialBlock {
+            label: label.clone(),
+            insts: vec![],
+            term: None,
+        });
+        label
+    }
+
+    /// Make the block with the given label the one instructions are appended to.
+    pub fn switch_to(&mut self, label: &str) {
+        self.current = self
+            .blocks
+            .iter()
+            .position(|b| b.label == label)
+            .unwrap_or_else(|| panic!("no block with label {}", label));
+    }
+
+    /// The label of the current block.
+    pub fn current(&self) -> &str {
+        &self.blocks[self.current].label
+    }
+
+    /// Return true if the current block already has a terminator.
+    pub fn is_terminated(&self) -> bool {
+        self.blocks[self.current].term.is_some()
+    }
+
+    /// Append an instruction to the current block.
+    pub fn push(&mut self, inst: Inst) {
+        assert!(
+            !self.is_terminated(),
+            "appending to terminated block {}",
+            self.current()
+        );
+        self.blocks[self.current].insts.push(inst);
+    }
+
+    /// Terminate the current block. A block can only be terminated once.
+    pub fn terminate(&mut self, term: Terminator) {
+        assert!(
+            !self.is_terminated(),
+            "block {} terminated twice",
+            self.current()
+        );
+        self.blocks[self.current].term = Some(term);
+    }
+
+    /// Append a binary operation and return its result.
+    pub fn bin(&mut self, op: BinOp, ty: Type, lhs: Value, rhs: Value) -> Value {
+        let dst = self.temp();
+        self.push(Inst::Bin {
+            dst: dst.clone(),
+            op,
+            ty,
+            lhs,
+            rhs,
+        });
+        Value::Temp(dst)
+    }
+
+    /// Append a comparison and return its result.
+    pub fn cmp(&mut self, op: CmpOp, ty: Type, lhs: Value, rhs: Value) -> Value {
+        let dst = self.temp();
+        self.push(Inst::Cmp {
+            dst: dst.clone(),
+            op,
+            ty,
+            lhs,
+            rhs,
+        });
+        Value::Temp(dst)
+    }
+
+    /// Reserve a stack slot and return its address.
+    pub fn alloca(&mut self, ty: Type) -> Value {
+        let dst = self.temp();
+        self.push(Inst::Alloca {
+            dst: dst.clone(),
+            ty,
+        });
+        Value::Temp(dst)
+    }
+
+    /// Load a scalar and return it.
+    pub fn load(&mut self, ty: Type, addr: Value) -> Value {
+        let dst = self.temp();
+        self.push(Inst::Load {
+            dst: dst.clone(),
+            ty,
+            addr,
+        });
+        Value::Temp(dst)
+    }
+
+    /// Store a scalar.
+    pub fn store(&mut self, ty: Type, value: Value, addr: Value) {
+        self.push(Inst::Store { ty, value, addr });
+    }
+
+    /// Append a call; return its result unless `ret` is `void`.
+    pub fn call(&mut self, ret: Type, callee: Value, args: Vec<(Type, Value)>) -> Option<Value> {
+        let dst = if ret == Type::Void {
+            None
+        } else {
+            Some(self.temp())
+        };
+        self.push(Inst::Call {
+            dst: dst.clone(),
+            ret,
+            callee,
+            args,
+            fixed: None,
+        });
+        dst.map(Value::Temp)
+    }
+
+    /// Finish the function. Blocks that were never terminated end with `hlt`.
+    pub fn finish(self) -> Function {
+        Function {
+            name: self.name,
+            linkage: self.linkage,
+            conv: self.conv,
+            params: self.params,
+            variadic: self.variadic,
+            ret: self.ret,
+            blocks: self
+                .blocks
+                .into_iter()
+                .map(|b| Block {
+                    label: b.label,
+                    insts: b.insts,
+                    term: b.term.unwrap_or(Terminator::Hlt),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_loop() {
+        // Sum the integers below %n.
+        let mut b = FunctionBuilder::new(
+            "sum",
+            Linkage::Export,
+            vec![Param {
+                ty: Type::I32,
+                name: "n".into(),
+            }],
+            Type::I32,
+        );
+        let acc = b.alloca(Type::I32);
+        let i = b.alloca(Type::I32);
+        b.store(Type::I32, Value::Int(0), acc.clone());
+        b.store(Type::I32, Value::Int(0), i.clone());
+        let cond = b.create_block("cond");
+        let body = b.create_block("body");
+        let done = b.create_block("done");
+        b.terminate(Terminator::Jmp(cond.clone()));
+
+        b.switch_to(&cond);
+        let iv = b.load(Type::I32, i.clone());
+        let c = b.cmp(CmpOp::Lt, Type::I32, iv, Value::temp("n"));
+        b.terminate(Terminator::Br {
+            cond: c,
+            then: body.clone(),
+            else_: done.clone(),
+        });
+
+        b.switch_to(&body);
+        let iv = b.load(Type::I32, i.clone());
+        let av = b.load(Type::I32, acc.clone());
+        let s = b.bin(BinOp::Add, Type::I32, av, iv.clone());
+        b.store(Type::I32, s, acc.clone());
+        let inc = b.bin(BinOp::Add, Type::I32, iv, Value::Int(1));
+        b.store(Type::I32, inc, i);
+        b.terminate(Terminator::Jmp(cond));
+
+        b.switch_to(&done);
+        let r = b.load(Type::I32, acc);
+        b.terminate(Terminator::Ret(Some((Type::I32, r))));
+
+        let f = b.finish();
+        assert_eq!(f.blocks.len(), 4);
+        assert_eq!(f.blocks[0].label, "start");
+        assert_eq!(f.blocks[1].label, "cond.0");
+        assert!(f.blocks.iter().all(|b| b.term != Terminator::Hlt));
+    }
+}
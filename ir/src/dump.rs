@@ -0,0 +1,463 @@
+//! The textual form of the IR.
+//!
+//! Every IR element implements `Display`; the output of `Module::to_string` is the canonical
+//! text of the module, which `read::read` accepts back. Golden tests of the lowering compare
+//! against this text, so it must stay stable:
+//!
+//! ```text
+//! import io
+//!
+//! type %Pair = { i32, ptr }
+//!
+//! data $greeting = str "hello\n"
+//!
+//! extern c fn $printf(ptr, ...) -> i32
+//!
+//! export fn $main() -> i32 {
+//! @start:
+//!     %t0 = call i32 $printf(ptr $greeting, ...)
+//!     ret i32 0
+//! }
+//! ```
+
+use crate::func::{Block, CallConv, Function, Linkage, Param};
+use crate::inst::{AltCase, BinOp, CmpOp, ConvOp, Inst, Terminator, UnOp, Value};
+use crate::module::{Data, DataItem, Extern, Module};
+use crate::ty::{AggrKind, TypeDef};
+use std::fmt::{Display, Formatter, Result};
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Value::Temp(name) => write!(f, "%{}", name),
+            Value::Global(name) => write!(f, "${}", name),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{:?}", x),
+        }
+    }
+}
+
+impl Display for BinOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let s = match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::Div => "div",
+            BinOp::UDiv => "udiv",
+            BinOp::Rem => "rem",
+            BinOp::URem => "urem",
+            BinOp::And => "and",
+            BinOp::Or => "or",
+            BinOp::Xor => "xor",
+            BinOp::Shl => "shl",
+            BinOp::Shr => "shr",
+            BinOp::UShr => "ushr",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for CmpOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let s = match self {
+            CmpOp::Eq => "eq",
+            CmpOp::Ne => "ne",
+            CmpOp::Lt => "lt",
+            CmpOp::Le => "le",
+            CmpOp::Gt => "gt",
+            CmpOp::Ge => "ge",
+            CmpOp::ULt => "ult",
+            CmpOp::ULe => "ule",
+            CmpOp::UGt => "ugt",
+            CmpOp::UGe => "uge",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for UnOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            UnOp::Neg => write!(f, "neg"),
+            UnOp::Not => write!(f, "not"),
+        }
+    }
+}
+
+impl Display for ConvOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let s = match self {
+            ConvOp::Sext => "sext",
+            ConvOp::Zext => "zext",
+            ConvOp::Trunc => "trunc",
+            ConvOp::SiToF => "sitof",
+            ConvOp::UiToF => "uitof",
+            ConvOp::FToSi => "ftosi",
+            ConvOp::FToUi => "ftoui",
+            ConvOp::Bitcast => "bitcast",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Write a call-like argument list, inserting `...` after the fixed arguments.
+fn write_args(
+    f: &mut Formatter<'_>,
+    callee: &Value,
+    args: &[(crate::ty::Type, Value)],
+    fixed: Option<usize>,
+) -> Result {
+    write!(f, "{}(", callee)?;
+    for (i, (ty, v)) in args.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        if fixed == Some(i) {
+            write!(f, "..., ")?;
+        }
+        write!(f, "{} {}", ty, v)?;
+    }
+    if fixed == Some(args.len()) {
+        if !args.is_empty() {
+            write!(f, ", ")?;
+        }
+        write!(f, "...")?;
+    }
+    write!(f, ")")
+}
+
+impl Display for Inst {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Inst::Bin {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => write!(f, "%{} = {} {} {}, {}", dst, op, ty, lhs, rhs),
+            Inst::Cmp {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => write!(f, "%{} = {} {} {}, {}", dst, op, ty, lhs, rhs),
+            Inst::Un { dst, op, ty, arg } => write!(f, "%{} = {} {} {}", dst, op, ty, arg),
+            Inst::Conv {
+                dst,
+                op,
+                from,
+                arg,
+                to,
+            } => write!(f, "%{} = {} {} {} to {}", dst, op, from, arg, to),
+            Inst::Copy { dst, ty, arg } => write!(f, "%{} = copy {} {}", dst, ty, arg),
+            Inst::Alloca { dst, ty } => write!(f, "%{} = alloca {}", dst, ty),
+            Inst::VaStart { ap } => write!(f, "vastart {}", ap),
+            Inst::VaArg { dst, ty, ap } => write!(f, "%{} = vaarg {} {}", dst, ty, ap),
+            Inst::Load { dst, ty, addr } => write!(f, "%{} = load {} {}", dst, ty, addr),
+            Inst::Store { ty, value, addr } => write!(f, "store {} {}, {}", ty, value, addr),
+            Inst::Field {
+                dst,
+                aggr,
+                base,
+                index,
+            } => write!(f, "%{} = field %{} {}, {}", dst, aggr, base, index),
+            Inst::Index {
+                dst,
+                elem,
+                base,
+                index,
+            } => write!(f, "%{} = index {} {}, {}", dst, elem, base, index),
+            Inst::Blit { ty, dst, src } => write!(f, "blit {} {}, {}", ty, dst, src),
+            Inst::Call {
+                dst,
+                ret,
+                callee,
+                args,
+                fixed,
+            } => {
+                if let Some(dst) = dst {
+                    write!(f, "%{} = ", dst)?;
+                }
+                write!(f, "call {} ", ret)?;
+                write_args(f, callee, args, *fixed)
+            }
+            Inst::Alloc { dst, ty } => write!(f, "%{} = alloc {}", dst, ty),
+            Inst::Unalloc { ptr } => write!(f, "unalloc {}", ptr),
+            Inst::ChanNew { dst, elem, cap } => write!(f, "%{} = chan {}, {}", dst, elem, cap),
+            Inst::Send { elem, chan, value } => write!(f, "send {} {}, {}", elem, chan, value),
+            Inst::Recv { dst, elem, chan } => write!(f, "%{} = recv {} {}", dst, elem, chan),
+            Inst::CanSend { dst, chan } => write!(f, "%{} = cansend {}", dst, chan),
+            Inst::CanRecv { dst, chan } => write!(f, "%{} = canrecv {}", dst, chan),
+            Inst::Proc { callee, args } => {
+                write!(f, "proc ")?;
+                write_args(f, callee, args, None)
+            }
+            Inst::Task { callee, args } => {
+                write!(f, "task ")?;
+                write_args(f, callee, args, None)
+            }
+            Inst::ParBegin { dst } => write!(f, "%{} = par", dst),
+            Inst::ParSpawn {
+                group,
+                callee,
+                args,
+            } => {
+                write!(f, "spawn {}, ", group)?;
+                write_args(f, callee, args, None)
+            }
+            Inst::ParJoin { group } => write!(f, "join {}", group),
+            Inst::Rescue { label } => write!(f, "rescue @{}", label),
+            Inst::Unrescue => write!(f, "unrescue"),
+            Inst::Box { dst, ty, value } => write!(f, "%{} = box {} {}", dst, ty, value),
+            Inst::Unbox { dst, ty, poly } => write!(f, "%{} = unbox {} {}", dst, ty, poly),
+        }
+    }
+}
+
+impl Display for AltCase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            AltCase::Recv {
+                elem,
+                chan,
+                slot,
+                target,
+            } => {
+                write!(f, "recv {} {}", elem, chan)?;
+                if let Some(slot) = slot {
+                    write!(f, ", {}", slot)?;
+                }
+                write!(f, " -> @{}", target)
+            }
+            AltCase::Send {
+                elem,
+                chan,
+                value,
+                target,
+            } => write!(f, "send {} {}, {} -> @{}", elem, chan, value, target),
+        }
+    }
+}
+
+impl Display for Terminator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Terminator::Jmp(l) => write!(f, "jmp @{}", l),
+            Terminator::Br { cond, then, else_ } => {
+                write!(f, "br {}, @{}, @{}", cond, then, else_)
+            }
+            Terminator::Switch {
+                ty,
+                value,
+                default,
+                cases,
+            } => {
+                writeln!(f, "switch {} {}, @{} {{", ty, value, default)?;
+                for (n, l) in cases {
+                    writeln!(f, "        {} -> @{}", n, l)?;
+                }
+                write!(f, "    }}")
+            }
+            Terminator::Ret(None) => write!(f, "ret"),
+            Terminator::Ret(Some((ty, v))) => write!(f, "ret {} {}", ty, v),
+            Terminator::Alt(cases) => {
+                writeln!(f, "alt {{")?;
+                for c in cases {
+                    writeln!(f, "        {}", c)?;
+                }
+                write!(f, "    }}")
+            }
+            Terminator::Raise => write!(f, "raise"),
+            Terminator::Hlt => write!(f, "hlt"),
+        }
+    }
+}
+
+impl Display for Block {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        writeln!(f, "@{}:", self.label)?;
+        for inst in &self.insts {
+            writeln!(f, "    {}", inst)?;
+        }
+        writeln!(f, "    {}", self.term)
+    }
+}
+
+impl Display for Param {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{} %{}", self.ty, self.name)
+    }
+}
+
+impl Display for Linkage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Linkage::Local => Ok(()),
+            Linkage::Export => write!(f, "export "),
+        }
+    }
+}
+
+impl Display for CallConv {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            CallConv::Alef => Ok(()),
+            CallConv::C => write!(f, "c "),
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}{}fn ${}(", self.linkage, self.conv, self.name)?;
+        for (i, p) in self.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", p)?;
+        }
+        if self.variadic {
+            if !self.params.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "...")?;
+        }
+        writeln!(f, ") -> {} {{", self.ret)?;
+        for b in &self.blocks {
+            write!(f, "{}", b)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+impl Display for TypeDef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "type %{} = ", self.name)?;
+        if self.kind == AggrKind::Union {
+            write!(f, "union ")?;
+        }
+        write!(f, "{{ ")?;
+        for (i, ty) in self.fields.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", ty)?;
+        }
+        write!(f, " }}")
+    }
+}
+
+/// Escape a string literal the way `read` expects it.
+pub fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            '\0' => out.push_str("\\0"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            c if c.is_control() => out.push_str(&format!("\\x{:02x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+impl Display for DataItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            DataItem::Str(s) => write!(f, "str \"{}\"", escape(s)),
+            DataItem::Runestr(s) => write!(f, "runestr \"{}\"", escape(s)),
+            DataItem::Int(ty, i) => write!(f, "{} {}", ty, i),
+            DataItem::Float(x) => write!(f, "f64 {:?}", x),
+            DataItem::Addr(name) => write!(f, "ptr ${}", name),
+            DataItem::Zero(n) => write!(f, "zero {}", n),
+        }
+    }
+}
+
+impl Display for Data {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}data ${} = ", self.linkage, self.name)?;
+        match self.items.as_slice() {
+            [item @ (DataItem::Str(_) | DataItem::Runestr(_))] => write!(f, "{}", item),
+            items => {
+                write!(f, "{{ ")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, " }}")
+            }
+        }
+    }
+}
+
+impl Display for Extern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "extern {}fn ${}(", self.sig.conv, self.name)?;
+        for (i, ty) in self.sig.params.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", ty)?;
+        }
+        if self.sig.variadic {
+            if !self.sig.params.is_empty() {
+                write!(f, ", ")?;
+            }
+            write!(f, "...")?;
+        }
+        write!(f, ") -> {}", self.sig.ret)
+    }
+}
+
+impl Display for Module {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let mut first = true;
+        let mut sep = |f: &mut Formatter<'_>| -> Result {
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+            Ok(())
+        };
+
+        if !self.imports.is_empty() {
+            sep(f)?;
+            for i in &self.imports {
+                writeln!(f, "import {}", i)?;
+            }
+        }
+        if !self.types.is_empty() {
+            sep(f)?;
+            for t in &self.types {
+                writeln!(f, "{}", t)?;
+            }
+        }
+        if !self.data.is_empty() {
+            sep(f)?;
+            for d in &self.data {
+                writeln!(f, "{}", d)?;
+            }
+        }
+        if !self.externs.is_empty() {
+            sep(f)?;
+            for e in &self.externs {
+                writeln!(f, "{}", e)?;
+            }
+        }
+        for func in &self.funcs {
+            sep(f)?;
+            write!(f, "{}", func)?;
+        }
+        Ok(())
+    }
+}
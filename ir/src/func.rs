@@ -0,0 +1,116 @@
+//! Functions are lists of basic blocks; the first block is the entry point.
+
+use crate::inst::{Inst, Terminator};
+use crate::ty::Type;
+
+/// Whether a definition is visible outside of the module.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Linkage {
+    /// Visible only in the module (`intern`).
+    Local,
+
+    /// Visible to other modules (the default in Alef, or `extern`).
+    Export,
+}
+
+/// The calling convention of a function.
+///
+/// Alef functions currently follow the platform C convention too, but only functions declared
+/// `c` are guaranteed to: their signatures are restricted to types C can express, and calls to
+/// them promote variadic arguments like C does. Functions called from C, such as callbacks and
+/// the entry points of processes started by the runtime, must be `c`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CallConv {
+    /// The convention of Alef functions, left to the backend.
+    #[default]
+    Alef,
+
+    /// The C calling convention of the target (`c`).
+    C,
+}
+
+/// A formal parameter of a function.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Param {
+    /// The type of the parameter; aggregates are received as the address of a private copy.
+    pub ty: Type,
+
+    /// The name of the temporary holding the parameter, without the leading `%`.
+    pub name: String,
+}
+
+/// A straight-line sequence of instructions ending with a terminator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    /// The label of the block, without the leading `@`.
+    pub label: String,
+
+    /// The instructions of the block.
+    pub insts: Vec<Inst>,
+
+    /// How the block ends.
+    pub term: Terminator,
+}
+
+/// A function definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The name of the function, without the leading `$`.
+    pub name: String,
+
+    /// The visibility of the function.
+    pub linkage: Linkage,
+
+    /// The calling convention of the function.
+    pub conv: CallConv,
+
+    /// The formal parameters.
+    pub params: Vec<Param>,
+
+    /// Whether the function accepts additional arguments (`...`).
+    pub variadic: bool,
+
+    /// The return type.
+    pub ret: Type,
+
+    /// The basic blocks, the first one is the entry block.
+    pub blocks: Vec<Block>,
+}
+
+impl Function {
+    /// Find a block by label.
+    pub fn block(&self, label: &str) -> Option<&Block> {
+        self.blocks.iter().find(|b| b.label == label)
+    }
+
+    /// The position of the block with the given label.
+    pub fn block_index(&self, label: &str) -> Option<usize> {
+        self.blocks.iter().position(|b| b.label == label)
+    }
+
+    /// The signature of the function.
+    pub fn signature(&self) -> Signature {
+        Signature {
+            conv: self.conv,
+            params: self.params.iter().map(|p| p.ty.clone()).collect(),
+            variadic: self.variadic,
+            ret: self.ret.clone(),
+        }
+    }
+}
+
+/// The types a function accepts and returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    /// The calling convention.
+    pub conv: CallConv,
+
+    /// The types of the fixed parameters.
+    pub params: Vec<Type>,
+
+    /// Whether additional arguments are accepted.
+    pub variadic: bool,
+
+    /// The return type.
+    pub ret: Type,
+}
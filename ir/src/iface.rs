@@ -0,0 +1,257 @@
+//! Interfaces describe what a module offers to the modules importing it: its aggregate types and
+//! the declarations of its exported functions. They are written next to the module (`util.airi`
+//! for `util.air`) so that dependents can be compiled without the module itself, and record a
+//! hash of the text they were generated from, to tell when they are stale.
+//!
+//! The textual form is the one of modules, preceded by two directives:
+//!
+//! ```text
+//! #! module util
+//! #! source 9c1e2f4d3a5b6c78
+//! type %Pair = { i32, ptr }
+//!
+//! extern fn $twice(i32) -> i32
+//! ```
+
+use crate::func::Linkage;
+use crate::module::{Extern, Module};
+use crate::read::{self, err::ReadError};
+use std::fmt::{self, Display, Formatter};
+use thiserror::Error;
+
+/// The extension of interface files.
+pub const EXT: &str = "airi";
+
+/// Errors found while reading or importing an interface.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum InterfaceError {
+    /// The text lacks the directives of an interface, or defines more than declarations.
+    #[error("not an interface, {0}")]
+    Malformed(String),
+
+    /// The declarations cannot be read.
+    #[error("{0}")]
+    Read(#[from] ReadError),
+
+    /// The source of the module changed after the interface was generated.
+    #[error("the interface of module {0} is stale, regenerate it from the module")]
+    Stale(String),
+
+    /// A declaration of the interface disagrees with the importing module.
+    #[error("{decl}, imported from module {module}, conflicts with {other}")]
+    Conflict {
+        module: String,
+        decl: String,
+        other: String,
+    },
+}
+
+/// The interface of a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interface {
+    /// The name of the module.
+    pub name: String,
+
+    /// The hash of the text of the module.
+    pub source: u64,
+
+    /// The types and the declarations of the exported functions, without definitions.
+    pub decls: Module,
+}
+
+/// A stable 64 bit FNV-1a hash of the text of a module.
+pub fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl Interface {
+    /// Compute the interface of the module `name`, read from `text`.
+    pub fn new(name: &str, module: &Module, text: &str) -> Interface {
+        let funcs = module
+            .funcs
+            .iter()
+            .filter(|f| f.linkage == Linkage::Export)
+            .map(|f| Extern {
+                name: f.name.clone(),
+                sig: f.signature(),
+            });
+        Interface {
+            name: name.to_string(),
+            source: hash(text),
+            decls: Module {
+                types: module.types.clone(),
+                externs: funcs.collect(),
+                ..Module::default()
+            },
+        }
+    }
+
+    /// Fail if the interface was not generated from `text`.
+    pub fn check(&self, text: &str) -> Result<(), InterfaceError> {
+        if self.source == hash(text) {
+            Ok(())
+        } else {
+            Err(InterfaceError::Stale(self.name.clone()))
+        }
+    }
+
+    /// Add the declarations of the interface to a module importing it; declarations it already
+    /// has must agree with the interface.
+    pub fn import(&self, module: &mut Module) -> Result<(), Vec<InterfaceError>> {
+        let mut errors = vec![];
+        let mut conflict = |decl: &dyn Display, other: &dyn Display| {
+            errors.push(InterfaceError::Conflict {
+                module: self.name.clone(),
+                decl: decl.to_string(),
+                other: other.to_string(),
+            })
+        };
+
+        for t in &self.decls.types {
+            match module.typedef(&t.name) {
+                Some(u) if u != t => conflict(t, u),
+                Some(_) => {}
+                None => module.types.push(t.clone()),
+            }
+        }
+        for e in &self.decls.externs {
+            if module.func(&e.name).is_some() {
+                conflict(e, &format!("the definition of ${}", e.name));
+            } else if module.datum(&e.name).is_some() {
+                conflict(e, &format!("the data ${}", e.name));
+            } else if let Some(d) = module.externs.iter().find(|d| d.name == e.name) {
+                if d.sig != e.sig {
+                    conflict(e, d);
+                }
+            } else {
+                module.externs.push(e.clone());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Display for Interface {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "#! module {}", self.name)?;
+        writeln!(f, "#! source {:016x}", self.source)?;
+        write!(f, "{}", self.decls)
+    }
+}
+
+/// Read an interface from its textual form.
+pub fn read(text: &str) -> Result<Interface, InterfaceError> {
+    let mut lines = text.lines();
+    let mut directive = |what: &str| {
+        lines
+            .next()
+            .and_then(|l| l.strip_prefix("#! "))
+            .and_then(|l| l.strip_prefix(what))
+            .and_then(|l| l.strip_prefix(' '))
+            .map(|l| l.trim().to_string())
+            .ok_or_else(|| InterfaceError::Malformed(format!("expected \"#! {}\"", what)))
+    };
+    let name = directive("module")?;
+    let source = directive("source")?;
+    let source = u64::from_str_radix(&source, 16)
+        .map_err(|_| InterfaceError::Malformed(format!("invalid source hash {}", source)))?;
+
+    let decls = read::read(text)?;
+    if !decls.funcs.is_empty() || !decls.data.is_empty() || !decls.imports.is_empty() {
+        return Err(InterfaceError::Malformed(
+            "only types and extern declarations are allowed".into(),
+        ));
+    }
+    Ok(Interface {
+        name,
+        source,
+        decls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::verify::verify;
+
+    const UTIL: &str = "
+        type %Pair = { i32, i32 }
+        fn $helper(i32 %x) -> i32 {
+        @start:
+            ret i32 %x
+        }
+        export fn $twice(i32 %x) -> i32 {
+        @start:
+            %y = add i32 %x, %x
+            ret i32 %y
+        }
+    ";
+
+    #[test]
+    fn roundtrip() {
+        let m = read::read(UTIL).unwrap();
+        let iface = Interface::new("util", &m, UTIL);
+        let text = iface.to_string();
+        assert!(text.starts_with("#! module util\n#! source "));
+        assert!(text.contains("extern fn $twice(i32) -> i32"));
+        assert!(!text.contains("helper"));
+
+        let back = read(&text).unwrap();
+        assert_eq!(back, iface);
+        assert_eq!(back.check(UTIL), Ok(()));
+        assert_eq!(
+            back.check(&UTIL.replace("add", "mul")),
+            Err(InterfaceError::Stale("util".to_string()))
+        );
+        assert!(matches!(read(UTIL), Err(InterfaceError::Malformed(_))));
+    }
+
+    #[test]
+    fn import() {
+        let iface = Interface::new("util", &read::read(UTIL).unwrap(), UTIL);
+        let mut main = read::read(
+            "
+            export fn $main() -> i32 {
+            @start:
+                %p = alloca %Pair
+                %r = call i32 $twice(i32 21)
+                ret i32 %r
+            }
+        ",
+        )
+        .unwrap();
+        assert!(verify(&main).is_err());
+        iface.import(&mut main).unwrap();
+        verify(&main).unwrap();
+
+        let mut clash = read::read(
+            "
+            type %Pair = { i64 }
+            extern fn $twice(i64) -> i32
+        ",
+        )
+        .unwrap();
+        let errs: Vec<String> = iface
+            .import(&mut clash)
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errs,
+            [
+                "type %Pair = { i32, i32 }, imported from module util, conflicts with \
+                 type %Pair = { i64 }",
+                "extern fn $twice(i32) -> i32, imported from module util, conflicts with \
+                 extern fn $twice(i64) -> i32",
+            ]
+        );
+    }
+}
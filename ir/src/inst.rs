@@ -0,0 +1,568 @@
+//! Instructions and terminators.
+//!
+//! Instructions never nest: every operand is a `Value`, i.e. a temporary, the address of a
+//! global or a constant. Values of aggregate type are always represented by the address of their
+//! storage, so loading or storing an aggregate is expressed with `blit`, and passing one to a
+//! function passes the address of a copy.
+
+use crate::ty::Type;
+
+/// An operand.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A temporary defined by an instruction or a parameter, written `%name`.
+    Temp(String),
+
+    /// The address of a function or data definition, written `$name`.
+    Global(String),
+
+    /// An integer constant, also used for null pointers.
+    Int(i64),
+
+    /// A float constant.
+    Float(f64),
+}
+
+impl Value {
+    /// Create a temporary value.
+    pub fn temp(name: &str) -> Value {
+        Value::Temp(name.to_string())
+    }
+
+    /// Create a global value.
+    pub fn global(name: &str) -> Value {
+        Value::Global(name.to_string())
+    }
+
+    /// Return the name of the temporary if the value is one.
+    pub fn as_temp(&self) -> Option<&str> {
+        if let Value::Temp(name) = self {
+            Some(name)
+        } else {
+            None
+        }
+    }
+}
+
+/// Binary arithmetic and bitwise operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// Signed (or float) division.
+    Div,
+    /// Unsigned division.
+    UDiv,
+    /// Signed remainder.
+    Rem,
+    /// Unsigned remainder.
+    URem,
+    And,
+    Or,
+    Xor,
+    Shl,
+    /// Arithmetic shift right.
+    Shr,
+    /// Logical shift right.
+    UShr,
+}
+
+/// Comparison operators, they always produce an `i32` which is either 0 or 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    /// Signed (or float) less than.
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Unsigned less than.
+    ULt,
+    ULe,
+    UGt,
+    UGe,
+}
+
+/// Unary operators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UnOp {
+    /// Arithmetic negation.
+    Neg,
+    /// Bitwise complement.
+    Not,
+}
+
+/// Conversions between scalar types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConvOp {
+    /// Sign-extend an integer to a wider integer.
+    Sext,
+    /// Zero-extend an integer to a wider integer.
+    Zext,
+    /// Truncate an integer to a narrower integer.
+    Trunc,
+    /// Signed integer to float.
+    SiToF,
+    /// Unsigned integer to float.
+    UiToF,
+    /// Float to signed integer.
+    FToSi,
+    /// Float to unsigned integer.
+    FToUi,
+    /// Reinterpret a pointer as an `i64` or vice versa.
+    Bitcast,
+}
+
+/// A single non-terminating instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    /// `%dst = <op> ty lhs, rhs`
+    Bin {
+        dst: String,
+        op: BinOp,
+        ty: Type,
+        lhs: Value,
+        rhs: Value,
+    },
+
+    /// `%dst = <op> ty lhs, rhs`, the result is an `i32`.
+    Cmp {
+        dst: String,
+        op: CmpOp,
+        ty: Type,
+        lhs: Value,
+        rhs: Value,
+    },
+
+    /// `%dst = <op> ty arg`
+    Un {
+        dst: String,
+        op: UnOp,
+        ty: Type,
+        arg: Value,
+    },
+
+    /// `%dst = <op> from arg to to`
+    Conv {
+        dst: String,
+        op: ConvOp,
+        from: Type,
+        arg: Value,
+        to: Type,
+    },
+
+    /// `%dst = copy ty arg`, mostly used to materialize constants.
+    Copy { dst: String, ty: Type, arg: Value },
+
+    /// `%dst = alloca ty`, reserve a stack slot in the current frame.
+    Alloca { dst: String, ty: Type },
+
+    /// `%dst = load ty addr`, only for scalar types.
+    Load { dst: String, ty: Type, addr: Value },
+
+    /// `store ty value, addr`, only for scalar types.
+    Store { ty: Type, value: Value, addr: Value },
+
+    /// `%dst = field %aggr base, index`, compute the address of a field of an aggregate.
+    Field {
+        dst: String,
+        aggr: String,
+        base: Value,
+        index: u32,
+    },
+
+    /// `%dst = index elem base, index`, compute `base + index * sizeof(elem)`.
+    Index {
+        dst: String,
+        elem: Type,
+        base: Value,
+        index: Value,
+    },
+
+    /// `blit ty dst, src`, copy an aggregate from `src` to `dst`.
+    Blit { ty: Type, dst: Value, src: Value },
+
+    /// `[%dst =] call ret callee(args)`, arguments after `fixed` are passed as varargs.
+    Call {
+        dst: Option<String>,
+        ret: Type,
+        callee: Value,
+        args: Vec<(Type, Value)>,
+        fixed: Option<usize>,
+    },
+
+    /// `%dst = alloc ty`, heap-allocate zeroed storage for a value of type `ty`.
+    Alloc { dst: String, ty: Type },
+
+    /// `unalloc ptr`, release storage obtained with `alloc`.
+    Unalloc { ptr: Value },
+
+    /// `%dst = chan elem, cap`, create a channel carrying `elem` values with `cap` buffered slots.
+    ChanNew { dst: String, elem: Type, cap: Value },
+
+    /// `send elem chan, value`, block until `value` is delivered on `chan`.
+    Send { elem: Type, chan: Value, value: Value },
+
+    /// `%dst = recv elem chan`, block until a value is received from `chan`.
+    Recv {
+        dst: String,
+        elem: Type,
+        chan: Value,
+    },
+
+    /// `%dst = cansend chan`, 1 if a send on `chan` would not block.
+    CanSend { dst: String, chan: Value },
+
+    /// `%dst = canrecv chan`, 1 if a receive on `chan` would not block.
+    CanRecv { dst: String, chan: Value },
+
+    /// `proc callee(args)`, start a new process running the call.
+    Proc {
+        callee: Value,
+        args: Vec<(Type, Value)>,
+    },
+
+    /// `task callee(args)`, start a new task in the current process running the call.
+    Task {
+        callee: Value,
+        args: Vec<(Type, Value)>,
+    },
+
+    /// `%dst = par`, open a group of processes that are joined by `join`.
+    ParBegin { dst: String },
+
+    /// `spawn group, callee(args)`, start a process running the call as part of a `par` group.
+    ParSpawn {
+        group: Value,
+        callee: Value,
+        args: Vec<(Type, Value)>,
+    },
+
+    /// `join group`, wait for every process of the group to terminate.
+    ParJoin { group: Value },
+
+    /// `rescue @label`, make `label` the innermost active rescue block of the function.
+    Rescue { label: String },
+
+    /// `unrescue`, deactivate the innermost active rescue block.
+    Unrescue,
+
+    /// `%dst = box ty value`, wrap a value into a newly allocated poly value.
+    Box { dst: String, ty: Type, value: Value },
+
+    /// `%dst = unbox ty poly`, extract the value of type `ty` stored in a poly value.
+    Unbox { dst: String, ty: Type, poly: Value },
+
+    /// `vastart ap`, make the `va_list` at `ap` refer to the first variadic argument of the
+    /// function; `ap` must point to at least `ty::VA_LIST_SIZE` bytes.
+    VaStart { ap: Value },
+
+    /// `%dst = vaarg ty ap`, fetch the next variadic argument from the `va_list` at `ap`.
+    VaArg { dst: String, ty: Type, ap: Value },
+}
+
+impl Inst {
+    /// The temporary defined by the instruction, if any.
+    pub fn dst(&self) -> Option<&str> {
+        match self {
+            Inst::Bin { dst, .. }
+            | Inst::Cmp { dst, .. }
+            | Inst::Un { dst, .. }
+            | Inst::Conv { dst, .. }
+            | Inst::Copy { dst, .. }
+            | Inst::Alloca { dst, .. }
+            | Inst::Load { dst, .. }
+            | Inst::Field { dst, .. }
+            | Inst::Index { dst, .. }
+            | Inst::Alloc { dst, .. }
+            | Inst::ChanNew { dst, .. }
+            | Inst::Recv { dst, .. }
+            | Inst::CanSend { dst, .. }
+            | Inst::CanRecv { dst, .. }
+            | Inst::ParBegin { dst }
+            | Inst::Box { dst, .. }
+            | Inst::Unbox { dst, .. }
+            | Inst::VaArg { dst, .. } => Some(dst),
+            Inst::Call { dst, .. } => dst.as_deref(),
+            Inst::Store { .. }
+            | Inst::Blit { .. }
+            | Inst::Unalloc { .. }
+            | Inst::Send { .. }
+            | Inst::Proc { .. }
+            | Inst::Task { .. }
+            | Inst::ParSpawn { .. }
+            | Inst::ParJoin { .. }
+            | Inst::Rescue { .. }
+            | Inst::Unrescue
+            | Inst::VaStart { .. } => None,
+        }
+    }
+
+    /// The type of the temporary defined by the instruction, if any.
+    ///
+    /// Aggregate results (calls, receives and unboxes of aggregates) are addresses, so their
+    /// temporary has type `ptr`.
+    pub fn dst_type(&self) -> Option<Type> {
+        let ty = match self {
+            Inst::Bin { ty, .. } | Inst::Un { ty, .. } | Inst::Copy { ty, .. } => ty.clone(),
+            Inst::Load { ty, .. }
+            | Inst::Recv { elem: ty, .. }
+            | Inst::Unbox { ty, .. }
+            | Inst::VaArg { ty, .. } => ty.clone(),
+            Inst::Cmp { .. } | Inst::CanSend { .. } | Inst::CanRecv { .. } => Type::I32,
+            Inst::Conv { to, .. } => to.clone(),
+            Inst::Alloca { .. }
+            | Inst::Field { .. }
+            | Inst::Index { .. }
+            | Inst::Alloc { .. }
+            | Inst::ChanNew { .. }
+            | Inst::ParBegin { .. }
+            | Inst::Box { .. } => Type::Ptr,
+            Inst::Call { dst: Some(_), ret, .. } => ret.clone(),
+            _ => return None,
+        };
+
+        if ty.is_aggregate() {
+            Some(Type::Ptr)
+        } else {
+            Some(ty)
+        }
+    }
+
+    /// The values read by the instruction.
+    pub fn uses(&self) -> Vec<&Value> {
+        match self {
+            Inst::Bin { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Un { arg, .. } | Inst::Conv { arg, .. } | Inst::Copy { arg, .. } => vec![arg],
+            Inst::Alloca { .. } | Inst::Alloc { .. } | Inst::ParBegin { .. } => vec![],
+            Inst::Load { addr, .. } => vec![addr],
+            Inst::Store { value, addr, .. } => vec![value, addr],
+            Inst::Field { base, .. } => vec![base],
+            Inst::Index { base, index, .. } => vec![base, index],
+            Inst::Blit { dst, src, .. } => vec![dst, src],
+            Inst::Call { callee, args, .. }
+            | Inst::Proc { callee, args }
+            | Inst::Task { callee, args } => {
+                let mut v = vec![callee];
+                v.extend(args.iter().map(|(_, a)| a));
+                v
+            }
+            Inst::Unalloc { ptr } => vec![ptr],
+            Inst::ChanNew { cap, .. } => vec![cap],
+            Inst::Send { chan, value, .. } => vec![chan, value],
+            Inst::Recv { chan, .. } | Inst::CanSend { chan, .. } | Inst::CanRecv { chan, .. } => {
+                vec![chan]
+            }
+            Inst::ParSpawn {
+                group,
+                callee,
+                args,
+            } => {
+                let mut v = vec![group, callee];
+                v.extend(args.iter().map(|(_, a)| a));
+                v
+            }
+            Inst::ParJoin { group } => vec![group],
+            Inst::Rescue { .. } | Inst::Unrescue => vec![],
+            Inst::Box { value, .. } => vec![value],
+            Inst::Unbox { poly, .. } => vec![poly],
+            Inst::VaStart { ap } | Inst::VaArg { ap, .. } => vec![ap],
+        }
+    }
+    /// The values read by the instruction, for rewriting them.
+    pub fn uses_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Inst::Bin { lhs, rhs, .. } | Inst::Cmp { lhs, rhs, .. } => vec![lhs, rhs],
+            Inst::Un { arg, .. } | Inst::Conv { arg, .. } | Inst::Copy { arg, .. } => vec![arg],
+            Inst::Alloca { .. } | Inst::Alloc { .. } | Inst::ParBegin { .. } => vec![],
+            Inst::Load { addr, .. } => vec![addr],
+            Inst::Store { value, addr, .. } => vec![value, addr],
+            Inst::Field { base, .. } => vec![base],
+            Inst::Index { base, index, .. } => vec![base, index],
+            Inst::Blit { dst, src, .. } => vec![dst, src],
+            Inst::Call { callee, args, .. }
+            | Inst::Proc { callee, args }
+            | Inst::Task { callee, args } => {
+                let mut v = vec![callee];
+                v.extend(args.iter_mut().map(|(_, a)| a));
+                v
+            }
+            Inst::Unalloc { ptr } => vec![ptr],
+            Inst::ChanNew { cap, .. } => vec![cap],
+            Inst::Send { chan, value, .. } => vec![chan, value],
+            Inst::Recv { chan, .. } | Inst::CanSend { chan, .. } | Inst::CanRecv { chan, .. } => {
+                vec![chan]
+            }
+            Inst::ParSpawn {
+                group,
+                callee,
+                args,
+            } => {
+                let mut v = vec![group, callee];
+                v.extend(args.iter_mut().map(|(_, a)| a));
+                v
+            }
+            Inst::ParJoin { group } => vec![group],
+            Inst::Rescue { .. } | Inst::Unrescue => vec![],
+            Inst::Box { value, .. } => vec![value],
+            Inst::Unbox { poly, .. } => vec![poly],
+            Inst::VaStart { ap } | Inst::VaArg { ap, .. } => vec![ap],
+        }
+    }
+}
+
+/// A case of an `alt` terminator.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AltCase {
+    /// `recv elem chan [, slot] -> @target`: receive from `chan` and store the value in `slot`.
+    Recv {
+        elem: Type,
+        chan: Value,
+        slot: Option<Value>,
+        target: String,
+    },
+
+    /// `send elem chan, value -> @target`: send `value` on `chan`.
+    Send {
+        elem: Type,
+        chan: Value,
+        value: Value,
+        target: String,
+    },
+}
+
+impl AltCase {
+    /// The block reached when this case is selected.
+    pub fn target(&self) -> &str {
+        match self {
+            AltCase::Recv { target, .. } | AltCase::Send { target, .. } => target,
+        }
+    }
+
+    /// The channel this case communicates on.
+    pub fn chan(&self) -> &Value {
+        match self {
+            AltCase::Recv { chan, .. } | AltCase::Send { chan, .. } => chan,
+        }
+    }
+
+    /// The element type of the channel.
+    pub fn elem(&self) -> &Type {
+        match self {
+            AltCase::Recv { elem, .. } | AltCase::Send { elem, .. } => elem,
+        }
+    }
+}
+
+/// The instruction ending a basic block.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    /// `jmp @target`
+    Jmp(String),
+
+    /// `br cond, @then, @else`, `cond` is an `i32` compared against 0.
+    Br {
+        cond: Value,
+        then: String,
+        else_: String,
+    },
+
+    /// `switch ty value, @default { n -> @target ... }`
+    Switch {
+        ty: Type,
+        value: Value,
+        default: String,
+        cases: Vec<(i64, String)>,
+    },
+
+    /// `ret [ty value]`
+    Ret(Option<(Type, Value)>),
+
+    /// `alt { case ... }`, block until one of the cases can proceed and jump to its target.
+    Alt(Vec<AltCase>),
+
+    /// `raise`, jump to the innermost active rescue block of the function.
+    Raise,
+
+    /// `hlt`, the end of the block is never reached.
+    Hlt,
+}
+
+impl Terminator {
+    /// The labels of the blocks that may follow this one.
+    pub fn successors(&self) -> Vec<&str> {
+        match self {
+            Terminator::Jmp(l) => vec![l],
+            Terminator::Br { then, else_, .. } => vec![then, else_],
+            Terminator::Switch { default, cases, .. } => {
+                let mut v = vec![default.as_str()];
+                v.extend(cases.iter().map(|(_, l)| l.as_str()));
+                v
+            }
+            Terminator::Alt(cases) => cases.iter().map(|c| c.target()).collect(),
+            Terminator::Ret(_) | Terminator::Raise | Terminator::Hlt => vec![],
+        }
+    }
+
+    /// The values read by the terminator.
+    pub fn uses(&self) -> Vec<&Value> {
+        match self {
+            Terminator::Br { cond, .. } => vec![cond],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Ret(Some((_, v))) => vec![v],
+            Terminator::Alt(cases) => {
+                let mut v = vec![];
+                for c in cases {
+                    match c {
+                        AltCase::Recv { chan, slot, .. } => {
+                            v.push(chan);
+                            if let Some(slot) = slot {
+                                v.push(slot);
+                            }
+                        }
+                        AltCase::Send { chan, value, .. } => {
+                            v.push(chan);
+                            v.push(value);
+                        }
+                    }
+                }
+                v
+            }
+            Terminator::Jmp(_) | Terminator::Ret(None) | Terminator::Raise | Terminator::Hlt => {
+                vec![]
+            }
+        }
+    }
+
+    /// The values read by the terminator, for rewriting them.
+    pub fn uses_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Br { cond, .. } => vec![cond],
+            Terminator::Switch { value, .. } => vec![value],
+            Terminator::Ret(Some((_, v))) => vec![v],
+            Terminator::Alt(cases) => {
+                let mut v = vec![];
+                for c in cases {
+                    match c {
+                        AltCase::Recv { chan, slot, .. } => {
+                            v.push(chan);
+                            if let Some(slot) = slot {
+                                v.push(slot);
+                            }
+                        }
+                        AltCase::Send { chan, value, .. } => {
+                            v.push(chan);
+                            v.push(value);
+                        }
+                    }
+                }
+                v
+            }
+            Terminator::Jmp(_) | Terminator::Ret(None) | Terminator::Raise | Terminator::Hlt => {
+                vec![]
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! A typed, CFG-based intermediate representation for Alef programs.
+//!
+//! A module is a set of aggregate type definitions, data definitions, external declarations and
+//! functions; a function is a list of basic blocks, each ending with exactly one terminator.
+//! Values are kept in temporaries that are assigned once; mutable variables live in stack slots
+//! created by `alloca`. Aggregates are always handled through their address.
+//!
+//! The textual form produced by `Display` can be read back with `read::read`, which makes the
+//! IR usable as a stable testing format for the backends.
+
+/// IR types and their layout.
+pub mod ty;
+
+/// Values, instructions and terminators.
+pub mod inst;
+
+/// Functions and basic blocks.
+pub mod func;
+
+/// Modules: types, data, external declarations and functions.
+pub mod module;
+
+/// Incremental construction of functions.
+pub mod builder;
+
+/// The textual form of the IR.
+pub mod dump;
+
+/// Reading the textual form of the IR back into a module.
+pub mod read;
+
+/// Well-formedness checks.
+pub mod verify;
+
+/// Warnings about likely deadlocks.
+pub mod lint;
+
+/// Joining the modules of a program.
+pub mod link;
+
+/// Interfaces of modules, for separate compilation.
+pub mod iface;
@@ -0,0 +1,307 @@
+//! Join the modules of a program into a single module that the backends can compile.
+//!
+//! Definitions exported by a module (`export`, from `extern` or default Alef declarations)
+//! satisfy the external declarations of the others; local definitions (`intern`) stay private to
+//! their module and are renamed `module.name` when their name is used elsewhere in the program.
+//! Declarations that no module defines are left to the system linker.
+
+use crate::func::Linkage;
+use crate::inst::Value;
+use crate::module::{DataItem, Extern, Module};
+use crate::ty::TypeDef;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// A conflict between the modules of a program.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("in module {module}: {msg}")]
+pub struct LinkError {
+    /// The module where the conflict was found.
+    pub module: String,
+
+    /// A message describing the conflict.
+    pub msg: String,
+}
+
+/// A definition visible to the whole program.
+struct Export {
+    /// The index of the defining module.
+    unit: usize,
+
+    /// The declaration of the function, None for data.
+    decl: Option<Extern>,
+}
+
+/// Link the named modules of a program, given in dependency order.
+pub fn link(units: Vec<(String, Module)>) -> Result<Module, Vec<LinkError>> {
+    let mut errors = vec![];
+    let mut error = |module: &str, msg: String| {
+        errors.push(LinkError {
+            module: module.to_string(),
+            msg,
+        })
+    };
+
+    // The definitions of every module, and the exported ones.
+    let mut exports: HashMap<&str, Export> = HashMap::new();
+    let mut defined: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (name, m)) in units.iter().enumerate() {
+        let funcs = m.funcs.iter().map(|f| {
+            let decl = Extern {
+                name: f.name.clone(),
+                sig: f.signature(),
+            };
+            (f.name.as_str(), f.linkage, Some(decl))
+        });
+        let data = m.data.iter().map(|d| (d.name.as_str(), d.linkage, None));
+        for (def, linkage, decl) in funcs.chain(data) {
+            defined.entry(def).or_default().push(i);
+            if linkage != Linkage::Export {
+                continue;
+            }
+            if let Some(other) = exports.get(def) {
+                let msg = format!(
+                    "${} is already defined in module {}",
+                    def, units[other.unit].0
+                );
+                error(name, msg);
+            } else {
+                exports.insert(def, Export { unit: i, decl });
+            }
+        }
+    }
+
+    // The external declarations, checked against the definitions.
+    let mut externs: Vec<Extern> = vec![];
+    let mut declared: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (name, m)) in units.iter().enumerate() {
+        for e in &m.externs {
+            declared.entry(e.name.as_str()).or_default().push(i);
+            if let Some(x) = exports.get(e.name.as_str()) {
+                match x.decl {
+                    Some(ref d) if d.sig != e.sig => error(
+                        name,
+                        format!(
+                            "{} does not match the definition in module {}, {}",
+                            e, units[x.unit].0, d
+                        ),
+                    ),
+                    Some(_) => {}
+                    None => error(
+                        name,
+                        format!(
+                            "${} is data in module {}, not a function",
+                            e.name, units[x.unit].0
+                        ),
+                    ),
+                }
+            } else if let Some(&owner) = defined
+                .get(e.name.as_str())
+                .and_then(|d| d.iter().find(|&&d| d != i))
+            {
+                error(
+                    name,
+                    format!("${} is intern to module {}", e.name, units[owner].0),
+                );
+            } else if let Some(d) = externs.iter().find(|d| d.name == e.name) {
+                if d.sig != e.sig {
+                    error(name, format!("{} conflicts with {}", e, d));
+                }
+            } else {
+                externs.push(e.clone());
+            }
+        }
+    }
+
+    // Aggregates are structural: the same name must mean the same type everywhere.
+    let mut types: Vec<TypeDef> = vec![];
+    for (name, m) in &units {
+        for t in &m.types {
+            match types.iter().find(|u| u.name == t.name) {
+                Some(u) if u != t => error(name, format!("{} differs from {}", t, u)),
+                Some(_) => {}
+                None => types.push(t.clone()),
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    // Local names that clash with another module's are qualified with the module's name.
+    let mut taken: HashSet<String> = defined
+        .keys()
+        .chain(declared.keys())
+        .map(|n| n.to_string())
+        .collect();
+    let mut renames: Vec<HashMap<String, String>> = vec![HashMap::new(); units.len()];
+    for (i, (name, m)) in units.iter().enumerate() {
+        let locals = m
+            .funcs
+            .iter()
+            .map(|f| (&f.name, f.linkage))
+            .chain(m.data.iter().map(|d| (&d.name, d.linkage)));
+        for (def, linkage) in locals {
+            let shared = defined[def.as_str()].iter().any(|&d| d != i)
+                || declared
+                    .get(def.as_str())
+                    .is_some_and(|d| d.iter().any(|&d| d != i));
+            if linkage == Linkage::Export || !shared {
+                continue;
+            }
+            let mut new = format!("{}.{}", name, def);
+            let mut n = 1;
+            while taken.contains(&new) {
+                new = format!("{}.{}.{}", name, def, n);
+                n += 1;
+            }
+            taken.insert(new.clone());
+            renames[i].insert(def.clone(), new);
+        }
+    }
+
+    let mut out = Module {
+        types,
+        externs,
+        ..Module::default()
+    };
+    for ((_, mut m), renames) in units.into_iter().zip(renames) {
+        if !renames.is_empty() {
+            rename(&mut m, &renames);
+        }
+        out.data.append(&mut m.data);
+        out.funcs.append(&mut m.funcs);
+    }
+    Ok(out)
+}
+
+/// Rename the globals of a module.
+fn rename(m: &mut Module, renames: &HashMap<String, String>) {
+    let new = |name: &mut String| {
+        if let Some(n) = renames.get(name) {
+            *name = n.clone();
+        }
+    };
+
+    for d in &mut m.data {
+        new(&mut d.name);
+        for item in &mut d.items {
+            if let DataItem::Addr(a) = item {
+                new(a);
+            }
+        }
+    }
+    for f in &mut m.funcs {
+        new(&mut f.name);
+        for b in &mut f.blocks {
+            let uses = b
+                .insts
+                .iter_mut()
+                .flat_map(|i| i.uses_mut())
+                .chain(b.term.uses_mut());
+            for v in uses {
+                if let Value::Global(g) = v {
+                    new(g);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::read;
+    use crate::verify::verify;
+
+    fn units(srcs: &[(&str, &str)]) -> Vec<(String, Module)> {
+        srcs.iter()
+            .map(|(n, s)| (n.to_string(), read(s).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn resolve() {
+        let lib = "
+            data $count = { i32 0 }
+            fn $helper() -> i32 {
+            @start:
+                ret i32 1
+            }
+            export fn $inc() -> i32 {
+            @start:
+                %h = call i32 $helper()
+                ret i32 %h
+            }
+        ";
+        let main = "
+            import lib
+            extern fn $inc() -> i32
+            extern fn $printf(ptr, ...) -> i32
+            fn $helper() -> i32 {
+            @start:
+                ret i32 2
+            }
+            export fn $main() -> i32 {
+            @start:
+                %a = call i32 $inc()
+                %b = call i32 $helper()
+                ret i32 %b
+            }
+        ";
+        let m = link(units(&[("lib", lib), ("main", main)])).unwrap();
+        verify(&m).unwrap();
+        assert!(m.imports.is_empty());
+
+        // $inc is resolved, $printf is left to the system linker, both $helper are renamed.
+        let externs: Vec<&str> = m.externs.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(externs, ["printf"]);
+        let funcs: Vec<&str> = m.funcs.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(funcs, ["lib.helper", "inc", "main.helper", "main"]);
+        assert!(m.to_string().contains("call i32 $lib.helper()"));
+        assert!(m.datum("count").is_some());
+    }
+
+    #[test]
+    fn conflicts() {
+        let lib = "
+            export data $secret = { i32 0 }
+            fn $hidden() -> i32 {
+            @start:
+                ret i32 0
+            }
+            export fn $f(i32 %x) -> i32 {
+            @start:
+                ret i32 %x
+            }
+        ";
+        let main = "
+            extern fn $hidden() -> i32
+            extern fn $f() -> i32
+            extern fn $secret() -> i32
+            export fn $f2() -> i32 {
+            @start:
+                ret i32 0
+            }
+        ";
+        let again = "
+            export fn $f2() -> i32 {
+            @start:
+                ret i32 0
+            }
+        ";
+        let errs = link(units(&[("lib", lib), ("main", main), ("again", again)])).unwrap_err();
+        let msgs: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            msgs,
+            [
+                "in module again: $f2 is already defined in module main",
+                "in module main: $hidden is intern to module lib",
+                "in module main: extern fn $f() -> i32 does not match the definition in \
+                 module lib, extern fn $f(i32) -> i32",
+                "in module main: $secret is data in module lib, not a function",
+            ]
+        );
+    }
+}
@@ -0,0 +1,578 @@
+//! Warnings about communication that can never happen.
+//!
+//! The analysis tracks every channel created by a `chan` instruction through the temporaries and
+//! parameters it flows into, following calls and spawns of functions of the module. A channel
+//! that is stored in memory, sent on another channel, returned, boxed or handed to a function
+//! outside of the module escapes: it may then be used anywhere, and nothing is said about it.
+//! For the channels that do not escape every send and receive is known, which is enough to find
+//! receives that nothing can ever match. The analysis is conservative: it may miss problems, but
+//! a warning always points at an operation that blocks forever when it is reached.
+
+use crate::func::{Function, Linkage};
+use crate::inst::{AltCase, ConvOp, Inst, Terminator, Value};
+use crate::module::Module;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// The kinds of warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+    /// A receive on a channel nothing ever sends to.
+    RecvNeverSent,
+
+    /// A send on an unbuffered channel in a program with a single task.
+    SendSingleTask,
+
+    /// An `alt` none of whose cases can ever become ready.
+    AltNeverReady,
+}
+
+impl Lint {
+    /// The name of the lint, as used on the command line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::RecvNeverSent => "recv-never-sent",
+            Lint::SendSingleTask => "send-single-task",
+            Lint::AltNeverReady => "alt-never-ready",
+        }
+    }
+}
+
+/// A likely deadlock found by the analysis.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    /// The kind of warning.
+    pub lint: Lint,
+
+    /// The function containing the operation.
+    pub func: String,
+
+    /// The block containing the operation.
+    pub block: String,
+
+    /// A message describing the problem.
+    pub msg: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "in ${} at @{}: {} [{}]",
+            self.func,
+            self.block,
+            self.msg,
+            self.lint.name()
+        )
+    }
+}
+
+/// What a temporary may hold: some of the tracked channels, or anything (`unknown`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Flow {
+    chans: BTreeSet<usize>,
+    unknown: bool,
+}
+
+impl Flow {
+    /// Add `other` to the flow and return true if it changed.
+    fn merge(&mut self, other: &Flow) -> bool {
+        let before = (self.chans.len(), self.unknown);
+        self.chans.extend(other.chans.iter().copied());
+        self.unknown |= other.unknown;
+        before != (self.chans.len(), self.unknown)
+    }
+}
+
+/// A channel created by a `chan` instruction.
+struct Chan {
+    func: usize,
+    name: String,
+
+    /// The capacity, if it is a constant.
+    cap: Option<i64>,
+}
+
+struct Analysis<'a> {
+    module: &'a Module,
+    funcs: HashMap<&'a str, usize>,
+    chans: Vec<Chan>,
+
+    /// The flows of the temporaries by function and name.
+    flows: HashMap<(usize, &'a str), Flow>,
+
+    /// The channels that escape.
+    escaped: BTreeSet<usize>,
+    changed: bool,
+}
+
+impl<'a> Analysis<'a> {
+    fn flow(&self, func: usize, v: &Value) -> Flow {
+        match v {
+            Value::Temp(t) => self
+                .flows
+                .get(&(func, t.as_str()))
+                .cloned()
+                .unwrap_or_default(),
+            _ => Flow::default(),
+        }
+    }
+
+    fn add(&mut self, func: usize, temp: &'a str, flow: &Flow) {
+        let changed = self.flows.entry((func, temp)).or_default().merge(flow);
+        self.changed |= changed;
+    }
+
+    fn unknown(&mut self, func: usize, temp: &'a str) {
+        let flow = Flow {
+            chans: BTreeSet::new(),
+            unknown: true,
+        };
+        self.add(func, temp, &flow);
+    }
+
+    fn escape(&mut self, func: usize, v: &Value) {
+        for c in self.flow(func, v).chans {
+            self.changed |= self.escaped.insert(c);
+        }
+    }
+
+    /// Pass the arguments of a call or spawn to the parameters of `callee`.
+    fn call(&mut self, func: usize, callee: &Value, args: &'a [(crate::ty::Type, Value)]) {
+        let target = match callee {
+            Value::Global(name) => self.funcs.get(name.as_str()).copied(),
+            _ => None,
+        };
+        for (i, (_, arg)) in args.iter().enumerate() {
+            let param = target.and_then(|t| Some((t, &self.module.funcs[t].params.get(i)?.name)));
+            match param {
+                Some((t, name)) => {
+                    let flow = self.flow(func, arg);
+                    self.add(t, name, &flow);
+                }
+                None => self.escape(func, arg),
+            }
+        }
+    }
+
+    fn inst(&mut self, func: usize, inst: &'a Inst) {
+        match inst {
+            Inst::ChanNew { .. } => {}
+            Inst::Copy { dst, arg, .. }
+            | Inst::Conv {
+                dst,
+                op: ConvOp::Bitcast,
+                arg,
+                ..
+            } => {
+                let flow = self.flow(func, arg);
+                self.add(func, dst, &flow);
+            }
+            Inst::Send { value, .. } => self.escape(func, value),
+            Inst::Recv { .. } | Inst::CanSend { .. } | Inst::CanRecv { .. } => {}
+            Inst::Cmp { .. } | Inst::Unalloc { .. } => {}
+            Inst::Call {
+                callee, args, dst, ..
+            } => {
+                self.call(func, callee, args);
+                if let Some(dst) = dst {
+                    self.unknown(func, dst);
+                }
+            }
+            Inst::Proc { callee, args }
+            | Inst::Task { callee, args }
+            | Inst::ParSpawn { callee, args, .. } => self.call(func, callee, args),
+            _ => {
+                for v in inst.uses() {
+                    self.escape(func, v);
+                }
+            }
+        }
+        // Whatever is read from memory or received may be an escaped channel.
+        if let Inst::Load { dst, .. } | Inst::Recv { dst, .. } | Inst::Unbox { dst, .. } = inst {
+            self.unknown(func, dst);
+        }
+    }
+
+    fn term(&mut self, func: usize, term: &'a Terminator) {
+        match term {
+            Terminator::Ret(Some((_, v))) => self.escape(func, v),
+            Terminator::Alt(cases) => {
+                for c in cases {
+                    if let AltCase::Send { value, .. } = c {
+                        self.escape(func, value);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn run(&mut self) {
+        // Functions that may be called from outside the module or through a pointer may receive
+        // anything.
+        let mut entries: BTreeSet<usize> = BTreeSet::new();
+        for (i, f) in self.module.funcs.iter().enumerate() {
+            if f.linkage == Linkage::Export {
+                entries.insert(i);
+            }
+            for b in &f.blocks {
+                for inst in &b.insts {
+                    let callee = match inst {
+                        Inst::Call { callee, .. }
+                        | Inst::Proc { callee, .. }
+                        | Inst::Task { callee, .. }
+                        | Inst::ParSpawn { callee, .. } => Some(callee),
+                        _ => None,
+                    };
+                    for v in inst.uses() {
+                        if let Value::Global(name) = v {
+                            if !callee.is_some_and(|c| std::ptr::eq(c, v)) {
+                                if let Some(&t) = self.funcs.get(name.as_str()) {
+                                    entries.insert(t);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        for t in entries {
+            for p in &self.module.funcs[t].params {
+                self.unknown(t, &p.name);
+            }
+        }
+
+        self.changed = true;
+        while self.changed {
+            self.changed = false;
+            for (i, f) in self.module.funcs.iter().enumerate() {
+                for b in &f.blocks {
+                    for inst in &b.insts {
+                        self.inst(i, inst);
+                    }
+                    self.term(i, &b.term);
+                }
+            }
+        }
+    }
+
+    /// The channels `v` may be, if they are all known and do not escape.
+    fn known(&self, func: usize, v: &Value) -> Option<BTreeSet<usize>> {
+        let flow = self.flow(func, v);
+        let known = !flow.unknown
+            && !flow.chans.is_empty()
+            && flow.chans.iter().all(|c| !self.escaped.contains(c));
+        known.then_some(flow.chans)
+    }
+
+    /// A description of channel `c` for messages.
+    fn describe(&self, chans: &BTreeSet<usize>) -> String {
+        let names: Vec<_> = chans
+            .iter()
+            .map(|&c| {
+                let c = &self.chans[c];
+                format!("%{} of ${}", c.name, self.module.funcs[c.func].name)
+            })
+            .collect();
+        names.join(" or ")
+    }
+}
+
+/// The operations performed on the channels that do not escape.
+#[derive(Default)]
+struct Uses {
+    sends: Vec<usize>,
+    recvs: Vec<usize>,
+}
+
+/// Look for communication that can never happen in `module`.
+pub fn lint(module: &Module) -> Vec<Warning> {
+    let mut a = Analysis {
+        module,
+        funcs: module
+            .funcs
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (f.name.as_str(), i))
+            .collect(),
+        chans: vec![],
+        flows: HashMap::new(),
+        escaped: BTreeSet::new(),
+        changed: false,
+    };
+    for (i, f) in module.funcs.iter().enumerate() {
+        for b in &f.blocks {
+            for inst in &b.insts {
+                if let Inst::ChanNew { dst, cap, .. } = inst {
+                    let flow = Flow {
+                        chans: BTreeSet::from([a.chans.len()]),
+                        unknown: false,
+                    };
+                    a.flows.insert((i, dst.as_str()), flow);
+                    a.chans.push(Chan {
+                        func: i,
+                        name: dst.clone(),
+                        cap: match cap {
+                            Value::Int(n) => Some(*n),
+                            _ => None,
+                        },
+                    });
+                }
+            }
+        }
+    }
+    a.run();
+
+    // Count the operations on every channel and whether anything spawns.
+    let mut uses: Vec<Uses> = a.chans.iter().map(|_| Uses::default()).collect();
+    let mut single = true;
+    for (i, f) in module.funcs.iter().enumerate() {
+        for_each_op(f, |_, op| match op {
+            Op::Send(chan, _) | Op::Recv(chan, _) => {
+                for c in a.flow(i, chan).chans {
+                    let u = &mut uses[c];
+                    if matches!(op, Op::Send(..)) {
+                        u.sends.push(i);
+                    } else {
+                        u.recvs.push(i);
+                    }
+                }
+            }
+            Op::Spawn => single = false,
+            Op::Alt(_) => {}
+        });
+    }
+
+    let unbuffered = |c: usize| a.chans[c].cap == Some(0);
+    // A receive is possible if something sends; in a single task, not on an unbuffered channel.
+    let can_recv = |c: usize| !uses[c].sends.is_empty() && !(single && unbuffered(c));
+    // A send only blocks forever on an unbuffered channel nothing receives from.
+    let can_send = |c: usize| !unbuffered(c) || (!uses[c].recvs.is_empty() && !single);
+
+    let mut warnings = vec![];
+    for (i, f) in module.funcs.iter().enumerate() {
+        let mut warn = |block: &str, lint: Lint, msg: String| {
+            warnings.push(Warning {
+                lint,
+                func: f.name.clone(),
+                block: block.to_string(),
+                msg,
+            })
+        };
+        for_each_op(f, |block, op| match op {
+            Op::Recv(chan, false) => {
+                if let Some(cs) = a.known(i, chan) {
+                    if cs.iter().all(|&c| uses[c].sends.is_empty()) {
+                        let msg = format!(
+                            "receiving on {}, which nothing ever sends to",
+                            a.describe(&cs)
+                        );
+                        warn(block, Lint::RecvNeverSent, msg);
+                    }
+                }
+            }
+            Op::Send(chan, false) if single => {
+                if let Some(cs) = a.known(i, chan) {
+                    if cs.iter().all(|&c| unbuffered(c)) {
+                        let msg = format!(
+                            "sending on unbuffered channel {} blocks forever, there is no other \
+                             task to receive",
+                            a.describe(&cs)
+                        );
+                        warn(block, Lint::SendSingleTask, msg);
+                    }
+                }
+            }
+            Op::Alt(cases) => {
+                let ready = cases.iter().any(|case| match a.known(i, case.chan()) {
+                    None => true,
+                    Some(cs) => match case {
+                        AltCase::Recv { .. } => cs.iter().any(|&c| can_recv(c)),
+                        AltCase::Send { .. } => cs.iter().any(|&c| can_send(c)),
+                    },
+                });
+                if !ready {
+                    let msg = "no case of this alt can ever become ready".to_string();
+                    warn(block, Lint::AltNeverReady, msg);
+                }
+            }
+            _ => {}
+        });
+    }
+    warnings
+}
+
+/// A communication operation; sends and receives say whether they are cases of an `alt`.
+enum Op<'a> {
+    Send(&'a Value, bool),
+    Recv(&'a Value, bool),
+    Alt(&'a [AltCase]),
+    Spawn,
+}
+
+/// Call `f` with the label of the block and every operation of `func`; the cases of an `alt`
+/// are reported both one by one and as a whole.
+fn for_each_op<'a>(func: &'a Function, mut f: impl FnMut(&'a str, Op<'a>)) {
+    for b in &func.blocks {
+        for inst in &b.insts {
+            match inst {
+                Inst::Send { chan, .. } => f(&b.label, Op::Send(chan, false)),
+                Inst::Recv { chan, .. } => f(&b.label, Op::Recv(chan, false)),
+                Inst::Proc { .. } | Inst::Task { .. } | Inst::ParSpawn { .. } => {
+                    f(&b.label, Op::Spawn)
+                }
+                _ => {}
+            }
+        }
+        if let Terminator::Alt(cases) = &b.term {
+            for c in cases {
+                match c {
+                    AltCase::Send { chan, .. } => f(&b.label, Op::Send(chan, true)),
+                    AltCase::Recv { chan, .. } => f(&b.label, Op::Recv(chan, true)),
+                }
+            }
+            f(&b.label, Op::Alt(cases));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::read;
+
+    fn warnings(src: &str) -> Vec<String> {
+        lint(&read(src).unwrap())
+            .into_iter()
+            .map(|w| w.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn recv_never_sent() {
+        let src = "
+            fn $wait(ptr %c) -> void {
+            @start:
+                %v = recv i32 %c
+                ret
+            }
+            export fn $main() -> i32 {
+            @start:
+                %c = chan i32, 0
+                %d = chan i32, 1
+                proc $wait(ptr %c)
+                %e = copy ptr %c
+                %v = recv i32 %e
+                send i32 %d, 1
+                %w = recv i32 %d
+                ret i32 %v
+            }";
+        assert_eq!(
+            warnings(src),
+            [
+                "in $wait at @start: receiving on %c of $main, which nothing ever sends to \
+                 [recv-never-sent]",
+                "in $main at @start: receiving on %c of $main, which nothing ever sends to \
+                 [recv-never-sent]",
+            ]
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        // Channels stored, sent, handed to the outside or received from a parameter of an
+        // exported function may be used anywhere.
+        let src = "
+            extern fn $give(ptr) -> void
+            export fn $f(ptr %p) -> i32 {
+            @start:
+                %v = recv i32 %p
+                ret i32 %v
+            }
+            export fn $main() -> i32 {
+            @start:
+                %slot = alloca ptr
+                %a = chan i32, 1
+                store ptr %a, %slot
+                %x = recv i32 %a
+                %b = chan i32, 1
+                call void $give(ptr %b)
+                %y = recv i32 %b
+                %c = chan i32, 1
+                %d = chan ptr, 1
+                send ptr %d, %c
+                %z = recv i32 %c
+                ret i32 0
+            }";
+        assert_eq!(warnings(src), Vec::<String>::new());
+    }
+
+    #[test]
+    fn single_task() {
+        let src = "
+            export fn $main() -> i32 {
+            @start:
+                %c = chan i32, 0
+                %d = chan i32, 2
+                send i32 %d, 1
+                send i32 %c, 1
+                ret i32 0
+            }";
+        assert_eq!(
+            warnings(src),
+            [
+                "in $main at @start: sending on unbuffered channel %c of $main blocks forever, \
+              there is no other task to receive [send-single-task]"
+            ]
+        );
+
+        // Another task may receive.
+        let src = "
+            fn $get(ptr %c) -> void {
+            @start:
+                %v = recv i32 %c
+                ret
+            }
+            export fn $main() -> i32 {
+            @start:
+                %c = chan i32, 0
+                task $get(ptr %c)
+                send i32 %c, 1
+                ret i32 0
+            }";
+        assert_eq!(warnings(src), Vec::<String>::new());
+    }
+
+    #[test]
+    fn alts() {
+        let src = "
+            fn $put(ptr %c) -> void {
+            @start:
+                send i32 %c, 1
+                ret
+            }
+            export fn $main() -> i32 {
+            @start:
+                %a = chan i32, 0
+                %b = chan i32, 0
+                %c = chan i32, 0
+                proc $put(ptr %c)
+                alt {
+                    recv i32 %a -> @second
+                    send i32 %b, 1 -> @second
+                }
+            @second:
+                alt {
+                    recv i32 %a -> @done
+                    recv i32 %c -> @done
+                }
+            @done:
+                ret i32 0
+            }";
+        assert_eq!(
+            warnings(src),
+            ["in $main at @start: no case of this alt can ever become ready [alt-never-ready]"]
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! A module is the unit of compilation: it holds the aggregate definitions, the data, the
+//! external declarations and the functions of a single Alef program or source file.
+
+use crate::func::{Function, Linkage, Signature};
+use crate::ty::{align_to, AggrKind, Layout, Type, TypeDef, PTR_SIZE};
+
+/// A declaration of a function defined elsewhere, e.g. in the runtime or in the C library.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Extern {
+    /// The name of the function, without the leading `$`.
+    pub name: String,
+
+    /// The signature of the function.
+    pub sig: Signature,
+}
+
+/// An element of a data definition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataItem {
+    /// A NUL-terminated byte string (`str "..."`).
+    Str(String),
+
+    /// A NUL-terminated string of 32 bit runes (`runestr "..."`).
+    Runestr(String),
+
+    /// An integer of the given type.
+    Int(Type, i64),
+
+    /// A float.
+    Float(f64),
+
+    /// The address of another global.
+    Addr(String),
+
+    /// The given number of zero bytes.
+    Zero(u64),
+}
+
+impl DataItem {
+    /// The size of the item in bytes.
+    pub fn size(&self) -> u64 {
+        match self {
+            DataItem::Str(s) => s.len() as u64 + 1,
+            DataItem::Runestr(s) => (s.chars().count() as u64 + 1) * 4,
+            DataItem::Int(ty, _) => ty.int_bits().map(|b| b as u64 / 8).unwrap_or(PTR_SIZE),
+            DataItem::Float(_) | DataItem::Addr(_) => 8,
+            DataItem::Zero(n) => *n,
+        }
+    }
+
+    /// The natural alignment of the item.
+    pub fn align(&self) -> u64 {
+        match self {
+            DataItem::Str(_) | DataItem::Zero(_) => 1,
+            DataItem::Runestr(_) => 4,
+            _ => self.size(),
+        }
+    }
+}
+
+/// A global variable or constant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Data {
+    /// The name of the data, without the leading `$`.
+    pub name: String,
+
+    /// The visibility of the data.
+    pub linkage: Linkage,
+
+    /// The initial content, laid out in order without implicit padding.
+    pub items: Vec<DataItem>,
+}
+
+impl Data {
+    /// The total size of the data in bytes.
+    pub fn size(&self) -> u64 {
+        self.items.iter().map(|i| i.size()).sum()
+    }
+
+    /// The alignment of the data, the largest alignment of its items.
+    pub fn align(&self) -> u64 {
+        self.items.iter().map(|i| i.align()).max().unwrap_or(1)
+    }
+}
+
+/// A whole IR program.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    /// The names of the modules this one imports, resolved when a program is linked.
+    pub imports: Vec<String>,
+
+    /// The aggregate definitions.
+    pub types: Vec<TypeDef>,
+
+    /// The global data.
+    pub data: Vec<Data>,
+
+    /// The declarations of functions defined outside of the module.
+    pub externs: Vec<Extern>,
+
+    /// The function definitions.
+    pub funcs: Vec<Function>,
+}
+
+impl Module {
+    /// Create an empty module.
+    pub fn new() -> Module {
+        Module::default()
+    }
+
+    /// Find an aggregate definition by name.
+    pub fn typedef(&self, name: &str) -> Option<&TypeDef> {
+        self.types.iter().find(|t| t.name == name)
+    }
+
+    /// Find a function definition by name.
+    pub fn func(&self, name: &str) -> Option<&Function> {
+        self.funcs.iter().find(|f| f.name == name)
+    }
+
+    /// Find a data definition by name.
+    pub fn datum(&self, name: &str) -> Option<&Data> {
+        self.data.iter().find(|d| d.name == name)
+    }
+
+    /// The signature of a defined or declared function.
+    pub fn signature(&self, name: &str) -> Option<Signature> {
+        if let Some(f) = self.func(name) {
+            Some(f.signature())
+        } else {
+            self.externs
+                .iter()
+                .find(|e| e.name == name)
+                .map(|e| e.sig.clone())
+        }
+    }
+
+    /// Compute the size and alignment of a type on a 64 bit target, following the C rules for
+    /// struct and union layout. Return None for `void`, unknown aggregates and aggregates that
+    /// contain themselves.
+    pub fn layout(&self, ty: &Type) -> Option<Layout> {
+        self.layout_in(ty, &mut vec![])
+    }
+
+    fn layout_in<'a>(&'a self, ty: &'a Type, visiting: &mut Vec<&'a str>) -> Option<Layout> {
+        match ty {
+            Type::Void => None,
+            Type::I8 => Some(Layout::scalar(1)),
+            Type::I16 => Some(Layout::scalar(2)),
+            Type::I32 => Some(Layout::scalar(4)),
+            Type::I64 | Type::F64 => Some(Layout::scalar(8)),
+            Type::Ptr => Some(Layout::scalar(PTR_SIZE)),
+            Type::Array(of, n) => {
+                let l = self.layout_in(of, visiting)?;
+                Some(Layout {
+                    size: l.size * n,
+                    align: l.align,
+                })
+            }
+            Type::Named(name) => {
+                if visiting.contains(&name.as_str()) {
+                    return None;
+                }
+                let def = self.typedef(name)?;
+                visiting.push(name);
+                let mut size = 0;
+                let mut align = 1;
+                for field in &def.fields {
+                    let l = self.layout_in(field, visiting)?;
+                    align = align.max(l.align);
+                    size = match def.kind {
+                        AggrKind::Struct => align_to(size, l.align) + l.size,
+                        AggrKind::Union => size.max(l.size),
+                    };
+                }
+                visiting.pop();
+                Some(Layout {
+                    size: align_to(size, align),
+                    align,
+                })
+            }
+        }
+    }
+
+    /// The byte offset of the `index`-th field of the aggregate `name`.
+    pub fn field_offset(&self, name: &str, index: usize) -> Option<u64> {
+        let def = self.typedef(name)?;
+        if index >= def.fields.len() {
+            return None;
+        }
+        if def.kind == AggrKind::Union {
+            return Some(0);
+        }
+
+        let mut offset = 0;
+        for (i, field) in def.fields.iter().enumerate() {
+            let l = self.layout(field)?;
+            offset = align_to(offset, l.align);
+            if i == index {
+                return Some(offset);
+            }
+            offset += l.size;
+        }
+        None
+    }
+
+    /// The type of the `index`-th field of the aggregate `name`.
+    pub fn field_type(&self, name: &str, index: usize) -> Option<&Type> {
+        self.typedef(name)?.fields.get(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module() -> Module {
+        let mut m = Module::new();
+        m.types.push(TypeDef {
+            name: "Pair".into(),
+            kind: AggrKind::Struct,
+            fields: vec![Type::I8, Type::I64, Type::I16],
+        });
+        m.types.push(TypeDef {
+            name: "U".into(),
+            kind: AggrKind::Union,
+            fields: vec![Type::I32, Type::Array(Box::new(Type::I8), 6)],
+        });
+        m
+    }
+
+    #[test]
+    fn struct_layout() {
+        let m = module();
+        let l = m.layout(&Type::Named("Pair".into())).unwrap();
+        assert_eq!(l, Layout { size: 24, align: 8 });
+        assert_eq!(m.field_offset("Pair", 0), Some(0));
+        assert_eq!(m.field_offset("Pair", 1), Some(8));
+        assert_eq!(m.field_offset("Pair", 2), Some(16));
+        assert_eq!(m.field_offset("Pair", 3), None);
+    }
+
+    #[test]
+    fn union_layout() {
+        let m = module();
+        let l = m.layout(&Type::Named("U".into())).unwrap();
+        assert_eq!(l, Layout { size: 8, align: 4 });
+        assert_eq!(m.field_offset("U", 1), Some(0));
+    }
+
+    #[test]
+    fn data_size() {
+        let d = Data {
+            name: "s".into(),
+            linkage: Linkage::Local,
+            items: vec![DataItem::Str("abc".into()), DataItem::Runestr("é".into())],
+        };
+        assert_eq!(d.size(), 4 + 8);
+        assert_eq!(d.align(), 4);
+    }
+}
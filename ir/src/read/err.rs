@@ -0,0 +1,15 @@
+use thiserror::Error;
+
+/// Error returned when the text of a module cannot be read.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{line}:{col}: {msg}")]
+pub struct ReadError {
+    /// The line where the error was found.
+    pub line: usize,
+
+    /// The column where the error was found.
+    pub col: usize,
+
+    /// A message describing the error.
+    pub msg: String,
+}
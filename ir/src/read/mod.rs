@@ -0,0 +1,1093 @@
+//! Read the textual form of the IR back into a `Module`.
+//!
+//! The reader accepts the output of `dump` and is a bit more lenient about whitespace and
+//! comments (`#` up to the end of the line). It exists so that backends and analyses can be
+//! tested, and driven from `alef-check`, without going through the front end.
+
+pub mod err;
+
+use crate::func::{Block, CallConv, Function, Linkage, Param};
+use crate::inst::{AltCase, BinOp, CmpOp, ConvOp, Inst, Terminator, UnOp, Value};
+use crate::module::{Data, DataItem, Extern, Module};
+use crate::ty::{AggrKind, Type, TypeDef};
+use crate::func::Signature;
+use err::ReadError;
+
+/// A callee, its arguments and the number of fixed arguments of a variadic call.
+type CallArgs = (Value, Vec<(Type, Value)>, Option<usize>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    /// A bare word: keywords, opcodes and scalar types.
+    Word(String),
+    /// `%name`
+    Temp(String),
+    /// `$name`
+    Global(String),
+    /// `@name`
+    Label(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    /// One of `= , ( ) { } [ ] : -> ...`
+    Punct(&'static str),
+    End,
+}
+
+impl std::fmt::Display for Tok {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tok::Word(w) => write!(f, "\"{}\"", w),
+            Tok::Temp(t) => write!(f, "\"%{}\"", t),
+            Tok::Global(g) => write!(f, "\"${}\"", g),
+            Tok::Label(l) => write!(f, "\"@{}\"", l),
+            Tok::Int(i) => write!(f, "integer {}", i),
+            Tok::Float(x) => write!(f, "float {}", x),
+            Tok::Str(_) => write!(f, "string"),
+            Tok::Punct(p) => write!(f, "\"{}\"", p),
+            Tok::End => write!(f, "end of input"),
+        }
+    }
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    col: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(src: &'a str) -> Lexer<'a> {
+        Lexer {
+            chars: src.chars().peekable(),
+            line: 1,
+            col: 1,
+        }
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    fn err(&self, msg: String) -> ReadError {
+        ReadError {
+            line: self.line,
+            col: self.col,
+            msg,
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let mut s = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_alphanumeric() || c == '_' || c == '.' {
+                s.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        s
+    }
+
+    fn string(&mut self) -> Result<String, ReadError> {
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.err("unterminated string".into())),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('0') => s.push('\0'),
+                    Some('\\') => s.push('\\'),
+                    Some('"') => s.push('"'),
+                    Some('x') => {
+                        let mut hex = String::new();
+                        for _ in 0..2 {
+                            if let Some(c) = self.bump() {
+                                hex.push(c);
+                            }
+                        }
+                        let n = u32::from_str_radix(&hex, 16)
+                            .map_err(|_| self.err(format!("invalid escape \\x{}", hex)))?;
+                        s.push(char::from_u32(n).unwrap_or('\u{fffd}'));
+                    }
+                    c => return Err(self.err(format!("invalid escape {:?}", c))),
+                },
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Tok, ReadError> {
+        let mut s = String::new();
+        if self.chars.peek() == Some(&'-') {
+            s.push('-');
+            self.bump();
+        }
+        let mut float = false;
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_digit() {
+                s.push(c);
+            } else if c == '.' || c == 'e' || c == 'E' {
+                float = true;
+                s.push(c);
+            } else if (c == '-' || c == '+') && s.ends_with(['e', 'E']) {
+                s.push(c);
+            } else {
+                break;
+            }
+            self.bump();
+        }
+
+        if float {
+            s.parse::<f64>()
+                .map(Tok::Float)
+                .map_err(|e| self.err(format!("invalid float {:?}: {}", s, e)))
+        } else {
+            s.parse::<i64>()
+                .map(Tok::Int)
+                .map_err(|e| self.err(format!("invalid integer {:?}: {}", s, e)))
+        }
+    }
+
+    /// Return the next token and the position where it starts.
+    fn next(&mut self) -> Result<(Tok, usize, usize), ReadError> {
+        loop {
+            match self.chars.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('#') => {
+                    while let Some(c) = self.bump() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                _ => break,
+            }
+        }
+
+        let (line, col) = (self.line, self.col);
+        let c = match self.chars.peek() {
+            None => return Ok((Tok::End, line, col)),
+            Some(&c) => c,
+        };
+
+        let tok = match c {
+            '%' | '$' | '@' => {
+                self.bump();
+                let name = self.name();
+                if name.is_empty() {
+                    return Err(self.err(format!("expected a name after '{}'", c)));
+                }
+                match c {
+                    '%' => Tok::Temp(name),
+                    '$' => Tok::Global(name),
+                    _ => Tok::Label(name),
+                }
+            }
+            '"' => {
+                self.bump();
+                Tok::Str(self.string()?)
+            }
+            '-' => {
+                self.bump();
+                if self.chars.peek() == Some(&'>') {
+                    self.bump();
+                    Tok::Punct("->")
+                } else {
+                    let tok = self.number()?;
+                    match tok {
+                        Tok::Int(i) => Tok::Int(-i),
+                        Tok::Float(x) => Tok::Float(-x),
+                        t => t,
+                    }
+                }
+            }
+            '.' => {
+                for _ in 0..3 {
+                    if self.bump() != Some('.') {
+                        return Err(self.err("expected \"...\"".into()));
+                    }
+                }
+                Tok::Punct("...")
+            }
+            '=' | ',' | '(' | ')' | '{' | '}' | '[' | ']' | ':' => {
+                self.bump();
+                Tok::Punct(match c {
+                    '=' => "=",
+                    ',' => ",",
+                    '(' => "(",
+                    ')' => ")",
+                    '{' => "{",
+                    '}' => "}",
+                    '[' => "[",
+                    ']' => "]",
+                    _ => ":",
+                })
+            }
+            c if c.is_ascii_digit() => self.number()?,
+            c if c.is_alphabetic() || c == '_' => Tok::Word(self.name()),
+            c => return Err(self.err(format!("unexpected character {:?}", c))),
+        };
+
+        Ok((tok, line, col))
+    }
+}
+
+struct Reader {
+    toks: Vec<(Tok, usize, usize)>,
+    pos: usize,
+}
+
+type Res<T> = Result<T, ReadError>;
+
+impl Reader {
+    fn peek(&self) -> &Tok {
+        &self.toks[self.pos].0
+    }
+
+    fn peek_at(&self, n: usize) -> &Tok {
+        let i = (self.pos + n).min(self.toks.len() - 1);
+        &self.toks[i].0
+    }
+
+    fn next(&mut self) -> Tok {
+        let t = self.toks[self.pos].0.clone();
+        if self.pos < self.toks.len() - 1 {
+            self.pos += 1;
+        }
+        t
+    }
+
+    fn err<T>(&self, msg: String) -> Res<T> {
+        let (_, line, col) = self.toks[self.pos];
+        Err(ReadError { line, col, msg })
+    }
+
+    fn expected<T>(&self, what: &str) -> Res<T> {
+        self.err(format!("expected {}, found {}", what, self.peek()))
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.peek(), Tok::Punct(q) if *q == p)
+    }
+
+    fn is_word(&self, w: &str) -> bool {
+        matches!(self.peek(), Tok::Word(q) if q == w)
+    }
+
+    fn punct(&mut self, p: &str) -> Res<()> {
+        if self.is_punct(p) {
+            self.next();
+            Ok(())
+        } else {
+            self.expected(&format!("\"{}\"", p))
+        }
+    }
+
+    fn word(&mut self, w: &str) -> Res<()> {
+        if self.is_word(w) {
+            self.next();
+            Ok(())
+        } else {
+            self.expected(&format!("\"{}\"", w))
+        }
+    }
+
+    fn module_name(&mut self) -> Res<String> {
+        match self.peek().clone() {
+            Tok::Word(w) => {
+                self.next();
+                Ok(w)
+            }
+            _ => self.expected("a module name"),
+        }
+    }
+
+    fn temp(&mut self) -> Res<String> {
+        match self.peek().clone() {
+            Tok::Temp(t) => {
+                self.next();
+                Ok(t)
+            }
+            _ => self.expected("a temporary"),
+        }
+    }
+
+    fn global(&mut self) -> Res<String> {
+        match self.peek().clone() {
+            Tok::Global(g) => {
+                self.next();
+                Ok(g)
+            }
+            _ => self.expected("a global name"),
+        }
+    }
+
+    fn label(&mut self) -> Res<String> {
+        match self.peek().clone() {
+            Tok::Label(l) => {
+                self.next();
+                Ok(l)
+            }
+            _ => self.expected("a label"),
+        }
+    }
+
+    fn int(&mut self) -> Res<i64> {
+        match self.peek().clone() {
+            Tok::Int(i) => {
+                self.next();
+                Ok(i)
+            }
+            _ => self.expected("an integer"),
+        }
+    }
+
+    fn ty(&mut self) -> Res<Type> {
+        match self.peek().clone() {
+            Tok::Word(w) => {
+                let ty = match w.as_str() {
+                    "void" => Type::Void,
+                    "i8" => Type::I8,
+                    "i16" => Type::I16,
+                    "i32" => Type::I32,
+                    "i64" => Type::I64,
+                    "f64" => Type::F64,
+                    "ptr" => Type::Ptr,
+                    _ => return self.expected("a type"),
+                };
+                self.next();
+                Ok(ty)
+            }
+            Tok::Temp(name) => {
+                self.next();
+                Ok(Type::Named(name))
+            }
+            Tok::Punct("[") => {
+                self.next();
+                let n = self.int()?;
+                if n < 0 {
+                    return self.err("negative array length".into());
+                }
+                self.word("x")?;
+                let of = self.ty()?;
+                self.punct("]")?;
+                Ok(Type::Array(Box::new(of), n as u64))
+            }
+            _ => self.expected("a type"),
+        }
+    }
+
+    fn value(&mut self) -> Res<Value> {
+        let v = match self.peek().clone() {
+            Tok::Temp(t) => Value::Temp(t),
+            Tok::Global(g) => Value::Global(g),
+            Tok::Int(i) => Value::Int(i),
+            Tok::Float(x) => Value::Float(x),
+            _ => return self.expected("a value"),
+        };
+        self.next();
+        Ok(v)
+    }
+
+    fn linkage(&mut self) -> Linkage {
+        if self.is_word("export") {
+            self.next();
+            Linkage::Export
+        } else {
+            Linkage::Local
+        }
+    }
+
+    fn conv(&mut self) -> CallConv {
+        if self.is_word("c") {
+            self.next();
+            CallConv::C
+        } else {
+            CallConv::Alef
+        }
+    }
+
+    fn module(&mut self) -> Res<Module> {
+        let mut m = Module::new();
+        loop {
+            match self.peek() {
+                Tok::End => return Ok(m),
+                Tok::Word(w) if w == "import" => {
+                    self.next();
+                    m.imports.push(self.module_name()?);
+                }
+                Tok::Word(w) if w == "type" => m.types.push(self.typedef()?),
+                Tok::Word(w) if w == "extern" => m.externs.push(self.extern_()?),
+                Tok::Word(_) => {
+                    let linkage = self.linkage();
+                    if self.is_word("data") {
+                        m.data.push(self.data(linkage)?);
+                    } else if self.is_word("fn") || self.is_word("c") {
+                        m.funcs.push(self.function(linkage)?);
+                    } else {
+                        return self.expected("\"data\" or \"fn\"");
+                    }
+                }
+                _ => return self.expected("a definition"),
+            }
+        }
+    }
+
+    fn typedef(&mut self) -> Res<TypeDef> {
+        self.word("type")?;
+        let name = self.temp()?;
+        self.punct("=")?;
+        let kind = if self.is_word("union") {
+            self.next();
+            AggrKind::Union
+        } else {
+            AggrKind::Struct
+        };
+        self.punct("{")?;
+        let mut fields = vec![self.ty()?];
+        while self.is_punct(",") {
+            self.next();
+            fields.push(self.ty()?);
+        }
+        self.punct("}")?;
+        Ok(TypeDef { name, kind, fields })
+    }
+
+    fn data_item(&mut self) -> Res<DataItem> {
+        if self.is_word("zero") {
+            self.next();
+            let n = self.int()?;
+            return Ok(DataItem::Zero(n.max(0) as u64));
+        }
+        let ty = self.ty()?;
+        match (ty, self.next()) {
+            (Type::F64, Tok::Float(x)) => Ok(DataItem::Float(x)),
+            (Type::F64, Tok::Int(i)) => Ok(DataItem::Float(i as f64)),
+            (Type::Ptr, Tok::Global(g)) => Ok(DataItem::Addr(g)),
+            (ty, Tok::Int(i)) if ty.is_int() || ty == Type::Ptr => Ok(DataItem::Int(ty, i)),
+            (ty, _) => self.err(format!("invalid initializer for type {}", ty)),
+        }
+    }
+
+    fn data(&mut self, linkage: Linkage) -> Res<Data> {
+        self.word("data")?;
+        let name = self.global()?;
+        self.punct("=")?;
+        let mut items = vec![];
+        if self.is_word("str") || self.is_word("runestr") {
+            let rune = self.is_word("runestr");
+            self.next();
+            match self.next() {
+                Tok::Str(s) if rune => items.push(DataItem::Runestr(s)),
+                Tok::Str(s) => items.push(DataItem::Str(s)),
+                _ => return self.err("expected a string literal".into()),
+            }
+        } else {
+            self.punct("{")?;
+            items.push(self.data_item()?);
+            while self.is_punct(",") {
+                self.next();
+                items.push(self.data_item()?);
+            }
+            self.punct("}")?;
+        }
+        Ok(Data {
+            name,
+            linkage,
+            items,
+        })
+    }
+
+    fn extern_(&mut self) -> Res<Extern> {
+        self.word("extern")?;
+        let conv = self.conv();
+        self.word("fn")?;
+        let name = self.global()?;
+        self.punct("(")?;
+        let mut params = vec![];
+        let mut variadic = false;
+        while !self.is_punct(")") {
+            if !params.is_empty() || variadic {
+                self.punct(",")?;
+            }
+            if self.is_punct("...") {
+                self.next();
+                variadic = true;
+            } else {
+                params.push(self.ty()?);
+            }
+        }
+        self.punct(")")?;
+        self.punct("->")?;
+        let ret = self.ty()?;
+        Ok(Extern {
+            name,
+            sig: Signature {
+                conv,
+                params,
+                variadic,
+                ret,
+            },
+        })
+    }
+
+    fn function(&mut self, linkage: Linkage) -> Res<Function> {
+        let conv = self.conv();
+        self.word("fn")?;
+        let name = self.global()?;
+        self.punct("(")?;
+        let mut params = vec![];
+        let mut variadic = false;
+        while !self.is_punct(")") {
+            if !params.is_empty() || variadic {
+                self.punct(",")?;
+            }
+            if self.is_punct("...") {
+                self.next();
+                variadic = true;
+            } else {
+                let ty = self.ty()?;
+                let name = self.temp()?;
+                params.push(Param { ty, name });
+            }
+        }
+        self.punct(")")?;
+        self.punct("->")?;
+        let ret = self.ty()?;
+        self.punct("{")?;
+        let mut blocks = vec![];
+        while !self.is_punct("}") {
+            blocks.push(self.block()?);
+        }
+        self.punct("}")?;
+        if blocks.is_empty() {
+            return self.err(format!("function ${} has no blocks", name));
+        }
+        Ok(Function {
+            name,
+            linkage,
+            conv,
+            params,
+            variadic,
+            ret,
+            blocks,
+        })
+    }
+
+    fn block(&mut self) -> Res<Block> {
+        let label = self.label()?;
+        self.punct(":")?;
+        let mut insts = vec![];
+        loop {
+            if let Some(term) = self.terminator()? {
+                return Ok(Block { label, insts, term });
+            }
+            insts.push(self.inst()?);
+        }
+    }
+
+    /// Read `callee(ty v, ...)`, returning the arguments and the number of fixed ones if a
+    /// `...` marker was found.
+    fn call_args(&mut self) -> Res<CallArgs> {
+        let callee = self.value()?;
+        self.punct("(")?;
+        let mut args = vec![];
+        let mut fixed = None;
+        let mut first = true;
+        while !self.is_punct(")") {
+            if !first {
+                self.punct(",")?;
+            }
+            first = false;
+            if self.is_punct("...") {
+                self.next();
+                fixed = Some(args.len());
+                continue;
+            }
+            let ty = self.ty()?;
+            let v = self.value()?;
+            args.push((ty, v));
+        }
+        self.punct(")")?;
+        Ok((callee, args, fixed))
+    }
+
+    fn no_varargs(&self, fixed: Option<usize>) -> Res<()> {
+        if fixed.is_some() {
+            self.err("\"...\" is only allowed in calls".into())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn terminator(&mut self) -> Res<Option<Terminator>> {
+        let w = match self.peek() {
+            Tok::Word(w) => w.clone(),
+            _ => return Ok(None),
+        };
+        let term = match w.as_str() {
+            "jmp" => {
+                self.next();
+                Terminator::Jmp(self.label()?)
+            }
+            "br" => {
+                self.next();
+                let cond = self.value()?;
+                self.punct(",")?;
+                let then = self.label()?;
+                self.punct(",")?;
+                let else_ = self.label()?;
+                Terminator::Br { cond, then, else_ }
+            }
+            "switch" => {
+                self.next();
+                let ty = self.ty()?;
+                let value = self.value()?;
+                self.punct(",")?;
+                let default = self.label()?;
+                self.punct("{")?;
+                let mut cases = vec![];
+                while !self.is_punct("}") {
+                    let n = self.int()?;
+                    self.punct("->")?;
+                    cases.push((n, self.label()?));
+                }
+                self.punct("}")?;
+                Terminator::Switch {
+                    ty,
+                    value,
+                    default,
+                    cases,
+                }
+            }
+            "ret" => {
+                self.next();
+                // A value follows only if the next token starts a type.
+                match self.peek() {
+                    Tok::Word(w)
+                        if matches!(
+                            w.as_str(),
+                            "i8" | "i16" | "i32" | "i64" | "f64" | "ptr"
+                        ) =>
+                    {
+                        let ty = self.ty()?;
+                        Terminator::Ret(Some((ty, self.value()?)))
+                    }
+                    Tok::Temp(_) if !matches!(self.peek_at(1), Tok::Punct("=")) => {
+                        let ty = self.ty()?;
+                        Terminator::Ret(Some((ty, self.value()?)))
+                    }
+                    Tok::Punct("[") => {
+                        let ty = self.ty()?;
+                        Terminator::Ret(Some((ty, self.value()?)))
+                    }
+                    _ => Terminator::Ret(None),
+                }
+            }
+            "alt" => {
+                self.next();
+                self.punct("{")?;
+                let mut cases = vec![];
+                while !self.is_punct("}") {
+                    cases.push(self.alt_case()?);
+                }
+                self.punct("}")?;
+                Terminator::Alt(cases)
+            }
+            "raise" => {
+                self.next();
+                Terminator::Raise
+            }
+            "hlt" => {
+                self.next();
+                Terminator::Hlt
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(term))
+    }
+
+    fn alt_case(&mut self) -> Res<AltCase> {
+        if self.is_word("recv") {
+            self.next();
+            let elem = self.ty()?;
+            let chan = self.value()?;
+            let slot = if self.is_punct(",") {
+                self.next();
+                Some(self.value()?)
+            } else {
+                None
+            };
+            self.punct("->")?;
+            let target = self.label()?;
+            Ok(AltCase::Recv {
+                elem,
+                chan,
+                slot,
+                target,
+            })
+        } else if self.is_word("send") {
+            self.next();
+            let elem = self.ty()?;
+            let chan = self.value()?;
+            self.punct(",")?;
+            let value = self.value()?;
+            self.punct("->")?;
+            let target = self.label()?;
+            Ok(AltCase::Send {
+                elem,
+                chan,
+                value,
+                target,
+            })
+        } else {
+            self.expected("\"recv\" or \"send\"")
+        }
+    }
+
+    fn inst(&mut self) -> Res<Inst> {
+        let dst = if let (Tok::Temp(t), Tok::Punct("=")) = (self.peek(), self.peek_at(1)) {
+            let t = t.clone();
+            self.next();
+            self.next();
+            Some(t)
+        } else {
+            None
+        };
+
+        let op = match self.next() {
+            Tok::Word(w) => w,
+            t => return self.err(format!("expected an instruction, found {}", t)),
+        };
+
+        // Instructions that don't define a temporary.
+        if dst.is_none() {
+            return match op.as_str() {
+                "store" => {
+                    let ty = self.ty()?;
+                    let value = self.value()?;
+                    self.punct(",")?;
+                    let addr = self.value()?;
+                    Ok(Inst::Store { ty, value, addr })
+                }
+                "blit" => {
+                    let ty = self.ty()?;
+                    let dst = self.value()?;
+                    self.punct(",")?;
+                    let src = self.value()?;
+                    Ok(Inst::Blit { ty, dst, src })
+                }
+                "call" => {
+                    let ret = self.ty()?;
+                    let (callee, args, fixed) = self.call_args()?;
+                    Ok(Inst::Call {
+                        dst: None,
+                        ret,
+                        callee,
+                        args,
+                        fixed,
+                    })
+                }
+                "unalloc" => Ok(Inst::Unalloc { ptr: self.value()? }),
+                "send" => {
+                    let elem = self.ty()?;
+                    let chan = self.value()?;
+                    self.punct(",")?;
+                    let value = self.value()?;
+                    Ok(Inst::Send { elem, chan, value })
+                }
+                "proc" | "task" => {
+                    let (callee, args, fixed) = self.call_args()?;
+                    self.no_varargs(fixed)?;
+                    if op == "proc" {
+                        Ok(Inst::Proc { callee, args })
+                    } else {
+                        Ok(Inst::Task { callee, args })
+                    }
+                }
+                "spawn" => {
+                    let group = self.value()?;
+                    self.punct(",")?;
+                    let (callee, args, fixed) = self.call_args()?;
+                    self.no_varargs(fixed)?;
+                    Ok(Inst::ParSpawn {
+                        group,
+                        callee,
+                        args,
+                    })
+                }
+                "join" => Ok(Inst::ParJoin {
+                    group: self.value()?,
+                }),
+                "rescue" => Ok(Inst::Rescue {
+                    label: self.label()?,
+                }),
+                "unrescue" => Ok(Inst::Unrescue),
+                "vastart" => Ok(Inst::VaStart { ap: self.value()? }),
+                _ => self.err(format!("unknown instruction \"{}\"", op)),
+            };
+        }
+
+        let dst = dst.unwrap();
+        let bin = |op: &str| -> Option<BinOp> {
+            Some(match op {
+                "add" => BinOp::Add,
+                "sub" => BinOp::Sub,
+                "mul" => BinOp::Mul,
+                "div" => BinOp::Div,
+                "udiv" => BinOp::UDiv,
+                "rem" => BinOp::Rem,
+                "urem" => BinOp::URem,
+                "and" => BinOp::And,
+                "or" => BinOp::Or,
+                "xor" => BinOp::Xor,
+                "shl" => BinOp::Shl,
+                "shr" => BinOp::Shr,
+                "ushr" => BinOp::UShr,
+                _ => return None,
+            })
+        };
+        let cmp = |op: &str| -> Option<CmpOp> {
+            Some(match op {
+                "eq" => CmpOp::Eq,
+                "ne" => CmpOp::Ne,
+                "lt" => CmpOp::Lt,
+                "le" => CmpOp::Le,
+                "gt" => CmpOp::Gt,
+                "ge" => CmpOp::Ge,
+                "ult" => CmpOp::ULt,
+                "ule" => CmpOp::ULe,
+                "ugt" => CmpOp::UGt,
+                "uge" => CmpOp::UGe,
+                _ => return None,
+            })
+        };
+        let conv = |op: &str| -> Option<ConvOp> {
+            Some(match op {
+                "sext" => ConvOp::Sext,
+                "zext" => ConvOp::Zext,
+                "trunc" => ConvOp::Trunc,
+                "sitof" => ConvOp::SiToF,
+                "uitof" => ConvOp::UiToF,
+                "ftosi" => ConvOp::FToSi,
+                "ftoui" => ConvOp::FToUi,
+                "bitcast" => ConvOp::Bitcast,
+                _ => return None,
+            })
+        };
+
+        if let Some(op) = bin(&op) {
+            let ty = self.ty()?;
+            let lhs = self.value()?;
+            self.punct(",")?;
+            let rhs = self.value()?;
+            return Ok(Inst::Bin {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            });
+        }
+        if let Some(op) = cmp(&op) {
+            let ty = self.ty()?;
+            let lhs = self.value()?;
+            self.punct(",")?;
+            let rhs = self.value()?;
+            return Ok(Inst::Cmp {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            });
+        }
+        if let Some(op) = conv(&op) {
+            let from = self.ty()?;
+            let arg = self.value()?;
+            self.word("to")?;
+            let to = self.ty()?;
+            return Ok(Inst::Conv {
+                dst,
+                op,
+                from,
+                arg,
+                to,
+            });
+        }
+
+        match op.as_str() {
+            "neg" | "not" => {
+                let ty = self.ty()?;
+                let arg = self.value()?;
+                let op = if op == "neg" { UnOp::Neg } else { UnOp::Not };
+                Ok(Inst::Un { dst, op, ty, arg })
+            }
+            "copy" => {
+                let ty = self.ty()?;
+                let arg = self.value()?;
+                Ok(Inst::Copy { dst, ty, arg })
+            }
+            "alloca" => Ok(Inst::Alloca {
+                dst,
+                ty: self.ty()?,
+            }),
+            "vaarg" => {
+                let ty = self.ty()?;
+                let ap = self.value()?;
+                Ok(Inst::VaArg { dst, ty, ap })
+            }
+            "load" => {
+                let ty = self.ty()?;
+                let addr = self.value()?;
+                Ok(Inst::Load { dst, ty, addr })
+            }
+            "field" => {
+                let aggr = self.temp()?;
+                let base = self.value()?;
+                self.punct(",")?;
+                let index = self.int()?;
+                if index < 0 || index > u32::MAX as i64 {
+                    return self.err(format!("invalid field index {}", index));
+                }
+                Ok(Inst::Field {
+                    dst,
+                    aggr,
+                    base,
+                    index: index as u32,
+                })
+            }
+            "index" => {
+                let elem = self.ty()?;
+                let base = self.value()?;
+                self.punct(",")?;
+                let index = self.value()?;
+                Ok(Inst::Index {
+                    dst,
+                    elem,
+                    base,
+                    index,
+                })
+            }
+            "call" => {
+                let ret = self.ty()?;
+                let (callee, args, fixed) = self.call_args()?;
+                Ok(Inst::Call {
+                    dst: Some(dst),
+                    ret,
+                    callee,
+                    args,
+                    fixed,
+                })
+            }
+            "alloc" => Ok(Inst::Alloc {
+                dst,
+                ty: self.ty()?,
+            }),
+            "chan" => {
+                let elem = self.ty()?;
+                self.punct(",")?;
+                let cap = self.value()?;
+                Ok(Inst::ChanNew { dst, elem, cap })
+            }
+            "recv" => {
+                let elem = self.ty()?;
+                let chan = self.value()?;
+                Ok(Inst::Recv { dst, elem, chan })
+            }
+            "cansend" => Ok(Inst::CanSend {
+                dst,
+                chan: self.value()?,
+            }),
+            "canrecv" => Ok(Inst::CanRecv {
+                dst,
+                chan: self.value()?,
+            }),
+            "par" => Ok(Inst::ParBegin { dst }),
+            "box" => {
+                let ty = self.ty()?;
+                let value = self.value()?;
+                Ok(Inst::Box { dst, ty, value })
+            }
+            "unbox" => {
+                let ty = self.ty()?;
+                let poly = self.value()?;
+                Ok(Inst::Unbox { dst, ty, poly })
+            }
+            _ => self.err(format!("unknown instruction \"{}\"", op)),
+        }
+    }
+}
+
+/// Read a module from its textual form.
+pub fn read(src: &str) -> Result<Module, ReadError> {
+    let mut lexer = Lexer::new(src);
+    let mut toks = vec![];
+    loop {
+        let t = lexer.next()?;
+        let end = t.0 == Tok::End;
+        toks.push(t);
+        if end {
+            break;
+        }
+    }
+
+    let mut reader = Reader { toks, pos: 0 };
+    reader.module()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_function() {
+        let src = "
+            # A comment.
+            export fn $id(i32 %x) -> i32 {
+            @start:
+                ret i32 %x
+            }";
+        let m = read(src).unwrap();
+        assert_eq!(m.funcs.len(), 1);
+        let f = &m.funcs[0];
+        assert_eq!(f.name, "id");
+        assert_eq!(f.linkage, Linkage::Export);
+        assert_eq!(
+            f.blocks[0].term,
+            Terminator::Ret(Some((Type::I32, Value::temp("x"))))
+        );
+    }
+
+    #[test]
+    fn read_ret_void_before_inst() {
+        let src = "fn $f() -> void {\n@a:\n    ret\n@b:\n    %x = copy i32 1\n    ret\n}";
+        let m = read(src).unwrap();
+        assert_eq!(m.funcs[0].blocks.len(), 2);
+        assert_eq!(m.funcs[0].blocks[0].term, Terminator::Ret(None));
+    }
+
+    #[test]
+    fn read_errors() {
+        let err = read("fn $f() -> i32 {\n@a:\n    %x = frob i32 1\n    ret i32 %x\n}").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert!(err.msg.contains("frob"));
+
+        let err = read("data $s = str \"abc").unwrap_err();
+        assert!(err.msg.contains("unterminated"));
+    }
+}
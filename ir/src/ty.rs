@@ -0,0 +1,164 @@
+//! IR types are deliberately close to the machine: integers of fixed width, a double precision
+//! float, an untyped pointer and named aggregates. Every Alef type is mapped onto one of these
+//! during lowering: channels, poly values and ADT instances are all pointers, tuples become
+//! anonymous aggregates.
+
+use std::fmt::{Display, Formatter, Result};
+
+/// The size of a pointer on the targets we support, in bytes.
+pub const PTR_SIZE: u64 = 8;
+
+/// The size of a `va_list` on the targets we support, in bytes: the System V x86-64 one is a
+/// structure of two offsets and two pointers.
+pub const VA_LIST_SIZE: u64 = 24;
+
+/// A type in the IR.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Type {
+    /// No value, only valid as the return type of a function.
+    Void,
+
+    /// An 8 bit integer (`byte`).
+    I8,
+
+    /// A 16 bit integer (`sint`, `usint`).
+    I16,
+
+    /// A 32 bit integer (`int`, `uint`, runes and booleans).
+    I32,
+
+    /// A 64 bit integer (`lint`, `ulint`).
+    I64,
+
+    /// A double precision float (`float`).
+    F64,
+
+    /// An untyped pointer.
+    Ptr,
+
+    /// A reference to an aggregate defined in the module with a `type` definition.
+    Named(String),
+
+    /// A fixed-size array of elements.
+    Array(Box<Type>, u64),
+}
+
+impl Type {
+    /// Return true if the type is an integer type.
+    pub fn is_int(&self) -> bool {
+        matches!(self, Type::I8 | Type::I16 | Type::I32 | Type::I64)
+    }
+
+    /// Return true if the type is the float type.
+    pub fn is_float(&self) -> bool {
+        matches!(self, Type::F64)
+    }
+
+    /// Return true if values of this type fit in a register, i.e. they are not aggregates.
+    pub fn is_scalar(&self) -> bool {
+        self.is_int() || self.is_float() || *self == Type::Ptr
+    }
+
+    /// Return true if values of this type live in memory and are referred to by address.
+    pub fn is_aggregate(&self) -> bool {
+        matches!(self, Type::Named(_) | Type::Array(..))
+    }
+
+    /// The width in bits of an integer type, or None.
+    pub fn int_bits(&self) -> Option<u32> {
+        match self {
+            Type::I8 => Some(8),
+            Type::I16 => Some(16),
+            Type::I32 => Some(32),
+            Type::I64 => Some(64),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Type::Void => write!(f, "void"),
+            Type::I8 => write!(f, "i8"),
+            Type::I16 => write!(f, "i16"),
+            Type::I32 => write!(f, "i32"),
+            Type::I64 => write!(f, "i64"),
+            Type::F64 => write!(f, "f64"),
+            Type::Ptr => write!(f, "ptr"),
+            Type::Named(name) => write!(f, "%{}", name),
+            Type::Array(of, n) => write!(f, "[{} x {}]", n, of),
+        }
+    }
+}
+
+/// How the members of an aggregate share memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AggrKind {
+    /// Members are laid out one after the other (`aggr`, `adt`, tuples).
+    Struct,
+
+    /// Members overlap (`union`).
+    Union,
+}
+
+/// The definition of a named aggregate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeDef {
+    /// The name of the aggregate, without the leading `%`.
+    pub name: String,
+
+    /// Whether the fields overlap or not.
+    pub kind: AggrKind,
+
+    /// The types of the fields, in declaration order.
+    pub fields: Vec<Type>,
+}
+
+/// Size and alignment of a type, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    /// The size of the type, a multiple of the alignment.
+    pub size: u64,
+
+    /// The alignment of the type.
+    pub align: u64,
+}
+
+impl Layout {
+    /// Create the layout of a scalar of the given size, aligned to its size.
+    pub fn scalar(size: u64) -> Layout {
+        Layout { size, align: size }
+    }
+}
+
+/// Round `n` up to the next multiple of `align`.
+pub fn align_to(n: u64, align: u64) -> u64 {
+    match n.checked_rem(align) {
+        None | Some(0) => n,
+        Some(r) => n + (align - r),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        assert_eq!(Type::I32.to_string(), "i32");
+        assert_eq!(Type::Named("Pair".into()).to_string(), "%Pair");
+        assert_eq!(
+            Type::Array(Box::new(Type::Ptr), 4).to_string(),
+            "[4 x ptr]"
+        );
+    }
+
+    #[test]
+    fn align() {
+        assert_eq!(align_to(0, 8), 0);
+        assert_eq!(align_to(1, 8), 8);
+        assert_eq!(align_to(12, 4), 12);
+        assert_eq!(align_to(13, 4), 16);
+    }
+}
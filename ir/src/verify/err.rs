@@ -0,0 +1,12 @@
+use thiserror::Error;
+
+/// A well-formedness violation found by the verifier.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("{}{msg}", .func.as_ref().map(|f| format!("in ${}: ", f)).unwrap_or_default())]
+pub struct VerifyError {
+    /// The function where the violation was found, None for module-level definitions.
+    pub func: Option<String>,
+
+    /// A message describing the violation.
+    pub msg: String,
+}
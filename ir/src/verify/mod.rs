@@ -0,0 +1,711 @@
+//! Check that a module is well formed before handing it to a backend: names resolve, every
+//! temporary is defined exactly once, blocks are referenced by existing labels and operands have
+//! the types their instructions expect.
+
+pub mod err;
+
+use crate::func::{CallConv, Function, Signature};
+use crate::inst::{AltCase, BinOp, Inst, Terminator, Value};
+use crate::module::{DataItem, Module};
+use crate::ty::Type;
+use err::VerifyError;
+use std::collections::{HashMap, HashSet};
+
+struct FuncVerifier<'a> {
+    module: &'a Module,
+    func: &'a Function,
+    temps: HashMap<&'a str, Type>,
+    labels: HashSet<&'a str>,
+    errors: Vec<VerifyError>,
+}
+
+impl<'a> FuncVerifier<'a> {
+    fn error(&mut self, msg: String) {
+        self.errors.push(VerifyError {
+            func: Some(self.func.name.clone()),
+            msg,
+        });
+    }
+
+    fn check_type(&mut self, ty: &Type) {
+        if let Some(msg) = type_error(self.module, ty) {
+            self.error(msg);
+        }
+    }
+
+    /// Check the type of values that need a size, such as allocated or sent ones.
+    fn check_sized(&mut self, ty: &Type, what: &str) {
+        self.check_type(ty);
+        if *ty == Type::Void {
+            self.error(format!("{} of type void", what));
+        }
+    }
+
+    fn check_label(&mut self, label: &str) {
+        if !self.labels.contains(label) {
+            self.error(format!("unknown label @{}", label));
+        }
+    }
+
+    /// The type of a value, if it can be determined without context.
+    fn value_type(&self, v: &Value) -> Option<Type> {
+        match v {
+            Value::Temp(t) => self.temps.get(t.as_str()).cloned(),
+            Value::Global(_) => Some(Type::Ptr),
+            Value::Int(_) | Value::Float(_) => None,
+        }
+    }
+
+    /// Check that `v` can be used where a value of type `want` is expected.
+    fn expect(&mut self, v: &Value, want: &Type, what: &str) {
+        let want = if want.is_aggregate() { &Type::Ptr } else { want };
+        match v {
+            Value::Int(_) if want.is_int() || *want == Type::Ptr => {}
+            Value::Float(_) if want.is_float() => {}
+            Value::Int(_) | Value::Float(_) => {
+                self.error(format!("constant {} used as {} of type {}", v, what, want))
+            }
+            _ => {
+                if let Some(got) = self.value_type(v) {
+                    if got != *want {
+                        self.error(format!(
+                            "{} {} has type {}, expected {}",
+                            what, v, got, want
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_callee(&mut self, callee: &Value, args: &[(Type, Value)], fixed: Option<usize>) {
+        for (ty, v) in args {
+            self.check_sized(ty, "argument");
+            self.expect(v, ty, "argument");
+        }
+
+        let name = match callee {
+            Value::Global(name) => name,
+            Value::Temp(_) => {
+                self.expect(callee, &Type::Ptr, "callee");
+                return;
+            }
+            _ => {
+                self.error(format!("invalid callee {}", callee));
+                return;
+            }
+        };
+
+        let sig = match self.module.signature(name) {
+            Some(sig) => sig,
+            None => {
+                self.error(format!("call to unknown function ${}", name));
+                return;
+            }
+        };
+
+        let nfixed = fixed.unwrap_or(args.len());
+        if fixed.is_some() && !sig.variadic {
+            self.error(format!("variadic call to non-variadic function ${}", name));
+        }
+        if nfixed != sig.params.len() && !(sig.variadic && fixed.is_none()) {
+            self.error(format!(
+                "${} expects {} arguments, {} given",
+                name,
+                sig.params.len(),
+                nfixed
+            ));
+            return;
+        }
+        for (i, (want, (got, _))) in sig.params.iter().zip(args).enumerate() {
+            if want != got {
+                self.error(format!(
+                    "argument {} of ${} has type {}, expected {}",
+                    i, name, got, want
+                ));
+            }
+        }
+
+        // C reads variadic arguments after the default argument promotions.
+        if sig.conv == CallConv::C {
+            for (i, (ty, _)) in args.iter().enumerate().skip(nfixed) {
+                if matches!(ty, Type::I8 | Type::I16) {
+                    self.error(format!(
+                        "variadic argument {} of ${} has type {}, C expects it promoted to i32",
+                        i, name, ty
+                    ));
+                }
+            }
+        }
+    }
+
+    fn check_global(&mut self, v: &Value) {
+        if let Value::Global(name) = v {
+            if self.module.func(name).is_none()
+                && self.module.datum(name).is_none()
+                && !self.module.externs.iter().any(|e| &e.name == name)
+            {
+                self.error(format!("unknown global ${}", name));
+            }
+        }
+    }
+
+    fn check_inst(&mut self, inst: &Inst) {
+        for v in inst.uses() {
+            self.check_global(v);
+            if let Value::Temp(t) = v {
+                if !self.temps.contains_key(t.as_str()) {
+                    self.error(format!("use of undefined temporary %{}", t));
+                }
+            }
+        }
+
+        match inst {
+            Inst::Bin {
+                op, ty, lhs, rhs, ..
+            } => {
+                let int_only = !matches!(op, BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div);
+                if !(ty.is_int() || (ty.is_float() && !int_only)) {
+                    self.error(format!("invalid type {} for {}", ty, op));
+                }
+                self.expect(lhs, ty, "operand");
+                self.expect(rhs, ty, "operand");
+            }
+            Inst::Cmp { ty, lhs, rhs, .. } => {
+                if !ty.is_scalar() {
+                    self.error(format!("cannot compare values of type {}", ty));
+                }
+                self.expect(lhs, ty, "operand");
+                self.expect(rhs, ty, "operand");
+            }
+            Inst::Un { op, ty, arg, .. } => {
+                if !(ty.is_int() || (ty.is_float() && *op == crate::inst::UnOp::Neg)) {
+                    self.error(format!("invalid type {} for {}", ty, op));
+                }
+                self.expect(arg, ty, "operand");
+            }
+            Inst::Conv { from, arg, to, .. } => {
+                if !from.is_scalar() || !to.is_scalar() {
+                    self.error(format!("cannot convert {} to {}", from, to));
+                }
+                self.expect(arg, from, "operand");
+            }
+            Inst::Copy { ty, arg, .. } => {
+                if !ty.is_scalar() {
+                    self.error(format!("cannot copy a value of type {}", ty));
+                }
+                self.expect(arg, ty, "operand");
+            }
+            Inst::Alloca { ty, .. } | Inst::Alloc { ty, .. } => {
+                self.check_sized(ty, "allocation");
+            }
+            Inst::Load { ty, addr, .. } => {
+                if !ty.is_scalar() {
+                    self.error(format!("cannot load a value of type {}, use blit", ty));
+                }
+                self.expect(addr, &Type::Ptr, "address");
+            }
+            Inst::Store { ty, value, addr } => {
+                if !ty.is_scalar() {
+                    self.error(format!("cannot store a value of type {}, use blit", ty));
+                }
+                self.expect(value, ty, "stored value");
+                self.expect(addr, &Type::Ptr, "address");
+            }
+            Inst::Field {
+                aggr, base, index, ..
+            } => {
+                match self.module.typedef(aggr) {
+                    None => self.error(format!("unknown type %{}", aggr)),
+                    Some(def) if *index as usize >= def.fields.len() => self.error(format!(
+                        "%{} has no field {}",
+                        aggr, index
+                    )),
+                    _ => {}
+                }
+                self.expect(base, &Type::Ptr, "base address");
+            }
+            Inst::Index {
+                elem, base, index, ..
+            } => {
+                self.check_sized(elem, "indexed element");
+                self.expect(base, &Type::Ptr, "base address");
+                if let Some(t) = self.value_type(index) {
+                    if !t.is_int() {
+                        self.error(format!("index {} is not an integer", index));
+                    }
+                }
+            }
+            Inst::Blit { ty, dst, src } => {
+                self.check_type(ty);
+                if !ty.is_aggregate() {
+                    self.error(format!("blit of non-aggregate type {}", ty));
+                }
+                self.expect(dst, &Type::Ptr, "address");
+                self.expect(src, &Type::Ptr, "address");
+            }
+            Inst::Call {
+                ret,
+                callee,
+                args,
+                fixed,
+                dst,
+            } => {
+                self.check_type(ret);
+                if dst.is_some() && *ret == Type::Void {
+                    self.error("void call cannot define a temporary".into());
+                }
+                self.check_callee(callee, args, *fixed);
+                if let Value::Global(name) = callee {
+                    if let Some(sig) = self.module.signature(name) {
+                        if sig.ret != *ret {
+                            self.error(format!(
+                                "${} returns {}, called as returning {}",
+                                name, sig.ret, ret
+                            ));
+                        }
+                    }
+                }
+            }
+            Inst::Proc { callee, args } | Inst::Task { callee, args } => {
+                self.check_callee(callee, args, None)
+            }
+            Inst::ParSpawn {
+                group,
+                callee,
+                args,
+            } => {
+                self.expect(group, &Type::Ptr, "par group");
+                self.check_callee(callee, args, None);
+            }
+            Inst::ParJoin { group } => self.expect(group, &Type::Ptr, "par group"),
+            Inst::Unalloc { ptr } => self.expect(ptr, &Type::Ptr, "pointer"),
+            Inst::ChanNew { elem, cap, .. } => {
+                self.check_sized(elem, "channel element");
+                self.expect(cap, &Type::I64, "channel capacity");
+            }
+            Inst::Send { elem, chan, value } => {
+                self.check_sized(elem, "channel element");
+                self.expect(chan, &Type::Ptr, "channel");
+                self.expect(value, elem, "sent value");
+            }
+            Inst::Recv { elem, chan, .. } => {
+                self.check_sized(elem, "channel element");
+                self.expect(chan, &Type::Ptr, "channel");
+            }
+            Inst::CanSend { chan, .. } | Inst::CanRecv { chan, .. } => {
+                self.expect(chan, &Type::Ptr, "channel")
+            }
+            Inst::Rescue { label } => self.check_label(label),
+            Inst::Box { ty, value, .. } => {
+                self.check_sized(ty, "boxed value");
+                self.expect(value, ty, "boxed value");
+            }
+            Inst::Unbox { ty, poly, .. } => {
+                self.check_sized(ty, "unboxed value");
+                self.expect(poly, &Type::Ptr, "poly value");
+            }
+            Inst::VaStart { ap } => {
+                if !self.func.variadic {
+                    self.error("vastart in a function that is not variadic".into());
+                }
+                self.expect(ap, &Type::Ptr, "va_list");
+            }
+            Inst::VaArg { ty, ap, .. } => {
+                if !matches!(ty, Type::I32 | Type::I64 | Type::F64 | Type::Ptr) {
+                    self.error(format!(
+                        "vaarg of type {}, variadic arguments are i32, i64, f64 or ptr",
+                        ty
+                    ));
+                }
+                self.expect(ap, &Type::Ptr, "va_list");
+            }
+            Inst::ParBegin { .. } | Inst::Unrescue => {}
+        }
+    }
+
+    fn check_term(&mut self, term: &Terminator) {
+        for v in term.uses() {
+            self.check_global(v);
+            if let Value::Temp(t) = v {
+                if !self.temps.contains_key(t.as_str()) {
+                    self.error(format!("use of undefined temporary %{}", t));
+                }
+            }
+        }
+        for l in term.successors() {
+            self.check_label(l);
+        }
+
+        match term {
+            Terminator::Br { cond, .. } => self.expect(cond, &Type::I32, "condition"),
+            Terminator::Switch { ty, value, .. } => {
+                if !ty.is_int() {
+                    self.error(format!("cannot switch on type {}", ty));
+                }
+                self.expect(value, ty, "switch value");
+            }
+            Terminator::Ret(None) if self.func.ret != Type::Void => {
+                self.error(format!("missing return value of type {}", self.func.ret))
+            }
+            Terminator::Ret(Some((ty, v))) => {
+                if *ty != self.func.ret {
+                    self.error(format!(
+                        "returning {} from a function returning {}",
+                        ty, self.func.ret
+                    ));
+                }
+                self.expect(v, ty, "returned value");
+            }
+            Terminator::Alt(cases) => {
+                if cases.is_empty() {
+                    self.error("alt without cases".into());
+                }
+                for c in cases {
+                    self.check_sized(c.elem(), "channel element");
+                    self.expect(c.chan(), &Type::Ptr, "channel");
+                    match c {
+                        AltCase::Recv {
+                            slot: Some(slot), ..
+                        } => self.expect(slot, &Type::Ptr, "receive slot"),
+                        AltCase::Send { elem, value, .. } => self.expect(value, elem, "sent value"),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn run(mut self) -> Vec<VerifyError> {
+        let func = self.func;
+
+        for b in &func.blocks {
+            if !self.labels.insert(&b.label) {
+                self.error(format!("duplicate label @{}", b.label));
+            }
+        }
+
+        // Collect the definitions first: temporaries are not required to be defined before
+        // their uses in block order, only to be defined exactly once.
+        for p in &func.params {
+            let ty = if p.ty.is_aggregate() {
+                Type::Ptr
+            } else {
+                p.ty.clone()
+            };
+            if self.temps.insert(&p.name, ty).is_some() {
+                self.error(format!("duplicate parameter %{}", p.name));
+            }
+        }
+        for b in &func.blocks {
+            for inst in &b.insts {
+                if let (Some(dst), Some(ty)) = (inst.dst(), inst.dst_type()) {
+                    if self.temps.insert(dst, ty).is_some() {
+                        self.error(format!("temporary %{} defined twice", dst));
+                    }
+                }
+            }
+        }
+
+        for b in &func.blocks {
+            for inst in &b.insts {
+                self.check_inst(inst);
+            }
+            self.check_term(&b.term);
+        }
+
+        self.errors
+    }
+}
+
+/// Check that every type named in `ty` is defined and that its arrays have elements with a size.
+fn type_error(module: &Module, ty: &Type) -> Option<String> {
+    match ty {
+        Type::Named(name) if module.typedef(name).is_none() => {
+            Some(format!("unknown type %{}", name))
+        }
+        Type::Array(of, _) if **of == Type::Void => Some(format!("array of void {}", ty)),
+        Type::Array(of, _) => type_error(module, of),
+        _ => None,
+    }
+}
+
+/// Check the parameter and return types of a signature.
+fn signature_errors(module: &Module, sig: &Signature) -> Vec<String> {
+    let mut errors: Vec<String> = sig
+        .params
+        .iter()
+        .chain([&sig.ret])
+        .filter_map(|t| type_error(module, t))
+        .collect();
+    if sig.params.contains(&Type::Void) {
+        errors.push("parameter of type void".into());
+    }
+    errors.extend(check_c_signature(sig));
+    errors
+}
+
+/// Check that a `c` signature can be expressed in C.
+fn check_c_signature(sig: &Signature) -> Option<String> {
+    if sig.conv != CallConv::C {
+        return None;
+    }
+    let array = sig
+        .params
+        .iter()
+        .chain([&sig.ret])
+        .find(|t| matches!(t, Type::Array(..)));
+    array.map(|t| {
+        format!(
+            "C functions cannot pass or return the array type {} by value",
+            t
+        )
+    })
+}
+
+/// Verify a module, returning every violation found.
+pub fn verify(module: &Module) -> Result<(), Vec<VerifyError>> {
+    let mut errors = vec![];
+    let mut names = HashSet::new();
+    let module_error = |msg: String| VerifyError { func: None, msg };
+
+    for t in &module.types {
+        if module.types.iter().filter(|u| u.name == t.name).count() > 1 {
+            errors.push(module_error(format!("type %{} defined twice", t.name)));
+        }
+        if t.fields.is_empty() {
+            errors.push(module_error(format!("type %{} has no fields", t.name)));
+        }
+        if module.layout(&Type::Named(t.name.clone())).is_none() {
+            errors.push(module_error(format!(
+                "type %{} has no layout: unknown or recursive member type",
+                t.name
+            )));
+        }
+    }
+
+    let globals = module
+        .data
+        .iter()
+        .map(|d| &d.name)
+        .chain(module.externs.iter().map(|e| &e.name))
+        .chain(module.funcs.iter().map(|f| &f.name));
+    for name in globals {
+        if !names.insert(name) {
+            errors.push(module_error(format!("global ${} defined twice", name)));
+        }
+    }
+
+    for d in &module.data {
+        for item in &d.items {
+            match item {
+                DataItem::Addr(name) if !names.contains(name) => {
+                    errors.push(module_error(format!(
+                        "data ${} refers to unknown global ${}",
+                        d.name, name
+                    )))
+                }
+                DataItem::Int(ty, _) if !(ty.is_int() || *ty == Type::Ptr) => {
+                    errors.push(module_error(format!(
+                        "data ${} has an integer of type {}",
+                        d.name, ty
+                    )))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let sigs = module
+        .funcs
+        .iter()
+        .map(|f| (&f.name, f.signature()))
+        .chain(module.externs.iter().map(|e| (&e.name, e.sig.clone())));
+    for (name, sig) in sigs {
+        for msg in signature_errors(module, &sig) {
+            errors.push(VerifyError {
+                func: Some(name.clone()),
+                msg,
+            });
+        }
+    }
+
+    for func in &module.funcs {
+        let v = FuncVerifier {
+            module,
+            func,
+            temps: HashMap::new(),
+            labels: HashSet::new(),
+            errors: vec![],
+        };
+        errors.extend(v.run());
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::read::read;
+
+    fn errors(src: &str) -> Vec<String> {
+        let m = read(src).unwrap();
+        match verify(&m) {
+            Ok(()) => vec![],
+            Err(e) => e.into_iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn well_formed() {
+        let src = "
+            type %Pair = { i32, ptr }
+            extern fn $printf(ptr, ...) -> i32
+            data $fmt = str \"%d\\n\"
+            fn $main() -> i32 {
+            @start:
+                %p = alloca %Pair
+                %f = field %Pair %p, 0
+                store i32 7, %f
+                %v = load i32 %f
+                %r = call i32 $printf(ptr $fmt, ..., i32 %v)
+                ret i32 0
+            }";
+        assert_eq!(errors(src), Vec::<String>::new());
+    }
+
+    #[test]
+    fn undefined_and_duplicates() {
+        let src = "
+            extern fn $g(ptr) -> %Nope
+            extern fn $h([2 x %Gone], void) -> void
+            fn $f(i32 %a) -> i32 {
+            @start:
+                %a = add i32 %b, 1
+                jmp @nowhere
+            }";
+        let errs = errors(src);
+        assert!(errs.iter().any(|e| e.contains("defined twice")));
+        assert!(errs.iter().any(|e| e.contains("undefined temporary %b")));
+        assert!(errs.iter().any(|e| e.contains("unknown label @nowhere")));
+        assert!(errs.contains(&"in $g: unknown type %Nope".to_string()));
+        assert!(errs.contains(&"in $h: unknown type %Gone".to_string()));
+        assert!(errs.contains(&"in $h: parameter of type void".to_string()));
+
+        // Data built without the reader can hold integers of any type.
+        let mut m = read("data $d = { i32 1 }").unwrap();
+        m.data[0].items[0] = DataItem::Int(Type::F64, 1);
+        assert_eq!(
+            verify(&m).unwrap_err()[0].to_string(),
+            "data $d has an integer of type f64"
+        );
+    }
+
+    #[test]
+    fn void_types() {
+        // Backends need the size of these types: verified modules must have one.
+        let src = "
+            extern fn $e([2 x [3 x void]]) -> [1 x void]
+            fn $f() -> void {
+            @start:
+                %a = alloca [2 x void]
+                %b = alloc [3 x void]
+                %c = alloca void
+                %i = index void %a, 1
+                %ch = chan i32, 1
+                send void %ch, 0
+                ret
+            }";
+        assert_eq!(
+            errors(src),
+            [
+                "in $e: array of void [3 x void]",
+                "in $e: array of void [1 x void]",
+                "in $f: array of void [2 x void]",
+                "in $f: array of void [3 x void]",
+                "in $f: allocation of type void",
+                "in $f: indexed element of type void",
+                "in $f: channel element of type void",
+                "in $f: constant 0 used as sent value of type void",
+            ]
+        );
+    }
+
+    #[test]
+    fn type_mismatches() {
+        let src = "
+            fn $f(f64 %x) -> i32 {
+            @start:
+                %y = shl f64 %x, 1.0
+                %z = add i32 %x, 1
+                ret f64 %x
+            }";
+        let errs = errors(src);
+        assert!(errs.iter().any(|e| e.contains("invalid type f64 for shl")));
+        assert!(errs.iter().any(|e| e.contains("has type f64, expected i32")));
+        assert!(errs.iter().any(|e| e.contains("returning f64")));
+    }
+
+    #[test]
+    fn calls() {
+        let src = "
+            fn $g(i32 %a) -> void {
+            @start:
+                ret
+            }
+            fn $f() -> void {
+            @start:
+                call void $g()
+                call void $g(i64 1)
+                proc $h()
+                ret
+            }";
+        let errs = errors(src);
+        assert!(errs.iter().any(|e| e.contains("expects 1 arguments, 0 given")));
+        assert!(errs.iter().any(|e| e.contains("argument 0 of $g has type i64")));
+        assert!(errs.iter().any(|e| e.contains("unknown function $h")));
+    }
+
+    #[test]
+    fn c_functions() {
+        let src = "
+            extern c fn $printf(ptr, ...) -> i32
+            extern c fn $bad([4 x i32]) -> void
+            fn $notva(i32 %n) -> i32 {
+            @start:
+                %ap = alloca [3 x i64]
+                vastart %ap
+                ret i32 %n
+            }
+            c fn $sum(i32 %n, ...) -> i64 {
+            @start:
+                %ap = alloca [3 x i64]
+                vastart %ap
+                %a = vaarg i64 %ap
+                %b = vaarg i8 %ap
+                %r = call i32 $printf(ptr $sum, ..., i64 %a, i8 1, i32 2)
+                ret i64 %a
+            }";
+        assert_eq!(
+            errors(src),
+            [
+                "in $bad: C functions cannot pass or return the array type [4 x i32] by value",
+                "in $notva: vastart in a function that is not variadic",
+                "in $sum: vaarg of type i8, variadic arguments are i32, i64, f64 or ptr",
+                "in $sum: variadic argument 2 of $printf has type i8, C expects it promoted to i32",
+            ]
+        );
+    }
+
+    #[test]
+    fn recursive_type() {
+        let errs = errors("type %L = { i32, %L }");
+        assert!(errs.iter().any(|e| e.contains("recursive")));
+    }
+}
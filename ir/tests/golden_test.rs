@@ -0,0 +1,15 @@
+use alef_test_support::{module, tests_dir};
+use std::fs;
+
+/// Every file in `tests/golden` is in canonical form: reading it, verifying it and dumping it
+/// again must give back exactly the same text.
+#[test]
+fn test_golden() {
+    for path in fs::read_dir(tests_dir!("golden")).unwrap() {
+        let path = path.unwrap().path();
+        let text = fs::read_to_string(&path).unwrap();
+
+        let module = module(&text, &path.display().to_string());
+        assert_eq!(module.to_string(), text, "{} is not canonical", path.display());
+    }
+}
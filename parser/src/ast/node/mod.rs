@@ -2,18 +2,42 @@ pub mod dec;
 pub mod expr;
 pub mod stmt;
 
+use crate::source::loc::Range;
 use std::fmt::{self, Display};
 use std::vec::Vec;
 
 /// An AST for an Alef source.
 #[derive(Debug, Clone)]
 pub struct Program {
+    /// The name given by the `module` declaration, if any.
+    pub module: Option<String>,
+
+    /// The modules imported by the source, in order.
+    pub imports: Vec<Import>,
+
     /// The list of declarations.
     pub decs: Vec<Node>,
 }
 
+/// An `import` of another module, named by its dotted path (`import util.str;`).
+#[derive(Debug, Clone)]
+pub struct Import {
+    /// The dotted name of the module.
+    pub name: String,
+
+    /// The position of the name in the source.
+    pub range: Range,
+}
+
 impl Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ref m) = self.module {
+            writeln!(f, "module {};", m)?;
+        }
+        for i in &self.imports {
+            writeln!(f, "import {};", i.name)?;
+        }
+
         for dec in &self.decs {
             writeln!(f, "{}", dec)?;
         }
@@ -0,0 +1,62 @@
+use crate::diagnostic::category::Category;
+use crate::diagnostic::codes;
+use crate::diagnostic::err::{Diagnostic, Severity};
+use crate::source::loc::{Location, Range};
+use crate::source::FileId;
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Error thrown when a C declaration cannot be read or has no Alef equivalent.
+#[derive(Error, Debug)]
+#[error("cannot import C declaration")]
+pub struct CImportError {
+    /// The source generating this error.
+    pub file: FileId,
+
+    /// The position where this fault generated.
+    pub range: Range,
+
+    /// A message regarding the error.
+    pub msg: String,
+
+    /// Whether the declaration was only skipped, because it has no Alef equivalent.
+    pub warning: bool,
+}
+
+impl Diagnostic for CImportError {
+    fn severity(&self) -> Option<Severity> {
+        if self.warning {
+            Some(Severity::Warning)
+        } else {
+            None
+        }
+    }
+
+    fn category(&self) -> Option<Category> {
+        Some(Category::ImportC)
+    }
+
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0401))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+
+    fn help<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        if self.warning {
+            Some(Box::new("the declaration is left out of the Alef output"))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,1025 @@
+//! Importing C headers: the declarations of a header are translated to the Alef declarations
+//! that let Alef programs call into C libraries, libc first of all.
+//!
+//! The header is run through the preprocessor, so that its includes and conditional sections
+//! are handled like those of Alef sources, and the result is read as a subset of C: function
+//! prototypes, `struct`, `union` and `enum` definitions, typedefs and `extern` variables. Object
+//! macros expanding to integer constants become enumeration constants. C types are mapped to
+//! the Alef basic types of the same width on LP64 targets; declarations using a type with no Alef
+//! equivalent, such as bit-fields or 32 bit floats, are left out and reported as warnings.
+
+pub mod err;
+
+use crate::diagnostic::diag;
+use crate::lex::pp::{self, Kind, Lexer, Preprocessor, Tok};
+use crate::lex::token::KEYS_MAP;
+use crate::source::loc::{DefaultLocation, Range};
+use crate::source::sman::sman;
+use crate::source::FileId;
+use crate::types::BasicType;
+use err::CImportError;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+
+/// Qualifiers and extensions that do not change the meaning of a declaration for Alef.
+const IGNORED: &[&str] = &[
+    "const",
+    "volatile",
+    "restrict",
+    "register",
+    "auto",
+    "_Noreturn",
+    "__const",
+    "__volatile__",
+    "__restrict",
+    "__restrict__",
+    "__extension__",
+    "__signed__",
+];
+
+/// Extensions followed by a parenthesized argument, ignored with it.
+const IGNORED_CALLS: &[&str] = &[
+    "__attribute__",
+    "__attribute",
+    "__asm__",
+    "__asm",
+    "asm",
+    "__declspec",
+];
+
+/// The words of C basic types.
+const BASIC_WORDS: &[&str] = &[
+    "void", "char", "short", "int", "long", "float", "double", "signed", "unsigned", "_Bool",
+    "__int128", "_Complex",
+];
+
+/// The names of the Alef basic types, which C declarations cannot redefine.
+const ALEF_TYPES: &[&str] = &[
+    "byte", "sint", "usint", "int", "uint", "float", "lint", "ulint", "chan", "poly", "void",
+];
+
+/// The prefix of the names given to anonymous aggregates.
+const ANON: &str = "_anon";
+
+/// A C type, in terms of Alef types.
+#[derive(Debug, Clone)]
+pub enum CType {
+    Basic(BasicType),
+
+    /// An aggregate, union or typedef name.
+    Named(String),
+    Pointer(Box<CType>),
+
+    /// An array, of unknown size if None.
+    Array(Box<CType>, Option<i64>),
+    Func {
+        ret: Box<CType>,
+        params: Vec<Member>,
+        variadic: bool,
+    },
+}
+
+/// A member of an aggregate or a parameter of a function.
+#[derive(Debug, Clone)]
+pub struct Member {
+    /// The name, None for unnamed parameters and for unnamed aggregates included in another.
+    pub name: Option<String>,
+    pub ty: CType,
+}
+
+/// A declaration translated from C.
+#[derive(Debug, Clone)]
+pub enum Decl {
+    /// A `struct` (an Alef `aggr`) or a `union`.
+    Aggr {
+        union: bool,
+        name: String,
+        members: Vec<Member>,
+    },
+
+    /// An `enum` and the values of its constants.
+    Enum {
+        name: Option<String>,
+        members: Vec<(String, i64)>,
+    },
+    Typedef {
+        name: String,
+        ty: CType,
+    },
+
+    /// A function prototype.
+    Func {
+        name: String,
+        ty: CType,
+    },
+
+    /// An `extern` variable.
+    Var {
+        name: String,
+        ty: CType,
+    },
+
+    /// A `#define` of an integer constant.
+    Const {
+        name: String,
+        value: i64,
+    },
+}
+
+/// The Alef declarations equivalent to a C header.
+#[derive(Debug, Clone, Default)]
+pub struct Header {
+    pub decls: Vec<Decl>,
+}
+
+/// Write the declaration of `name`, or of an abstract declarator if empty, with type `ty`.
+fn declare(ty: &CType, name: &str) -> String {
+    match ty {
+        CType::Basic(b) if name.is_empty() => b.to_string(),
+        CType::Basic(b) => format!("{} {}", b, name),
+        CType::Named(n) if name.is_empty() => n.clone(),
+        CType::Named(n) => format!("{} {}", n, name),
+        CType::Pointer(to) => match **to {
+            CType::Array(..) | CType::Func { .. } => declare(to, &format!("(*{})", name)),
+            _ => declare(to, &format!("*{}", name)),
+        },
+        CType::Array(of, n) => {
+            let n = n.map(|n| n.to_string()).unwrap_or_default();
+            declare(of, &format!("{}[{}]", name, n))
+        }
+        CType::Func {
+            ret,
+            params,
+            variadic,
+        } => {
+            let mut ps: Vec<String> = params
+                .iter()
+                .map(|p| declare(&p.ty, p.name.as_deref().unwrap_or("")))
+                .collect();
+            if *variadic {
+                ps.push("...".into());
+            }
+            declare(ret, &format!("{}({})", name, ps.join(", ")))
+        }
+    }
+}
+
+impl Display for Header {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut consts = vec![];
+        for d in &self.decls {
+            match d {
+                Decl::Aggr {
+                    union,
+                    name,
+                    members,
+                } => {
+                    writeln!(f, "{} {}\n{{", if *union { "union" } else { "aggr" }, name)?;
+                    for m in members {
+                        let decl = match m.name {
+                            Some(ref n) => declare(&m.ty, n),
+                            None => declare(&m.ty, ""),
+                        };
+                        writeln!(f, "\t{};", decl)?;
+                    }
+                    writeln!(f, "}};\n")?;
+                }
+                Decl::Enum { name, members } => {
+                    match name {
+                        Some(n) => writeln!(f, "enum {}\n{{", n)?,
+                        None => writeln!(f, "enum\n{{")?,
+                    }
+                    for (n, v) in members {
+                        writeln!(f, "\t{} = {},", n, v)?;
+                    }
+                    writeln!(f, "}};\n")?;
+                }
+                Decl::Typedef { name, ty } => writeln!(f, "typedef {};", declare(ty, name))?,
+                Decl::Func { name, ty } | Decl::Var { name, ty } => {
+                    writeln!(f, "extern {};", declare(ty, name))?
+                }
+                Decl::Const { name, value } => consts.push((name, value)),
+            }
+        }
+
+        if !consts.is_empty() {
+            writeln!(f, "\nenum\n{{")?;
+            for (n, v) in consts {
+                writeln!(f, "\t{} = {},", n, v)?;
+            }
+            writeln!(f, "}};")?;
+        }
+        Ok(())
+    }
+}
+
+/// Why a declaration is not imported.
+struct Skip {
+    /// The index of the offending token.
+    at: usize,
+    msg: String,
+
+    /// Whether the declaration is valid C with no Alef equivalent, rather than unreadable.
+    warning: bool,
+
+    /// Whether the tokens of the declaration were already skipped.
+    skipped: bool,
+}
+
+type Res<T> = Result<T, Skip>;
+
+/// The parts of a declarator, applied in order to the type of the specifiers.
+enum Op {
+    Pointer,
+    Array(Option<i64>),
+    Func(Vec<Member>, bool),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Storage {
+    None,
+    Typedef,
+    Extern,
+    Static,
+}
+
+/// The reader of the preprocessed header.
+struct Importer {
+    file: FileId,
+    toks: Vec<Tok>,
+    pos: usize,
+    typedefs: HashSet<String>,
+
+    /// The values of the enumeration constants, for constant expressions.
+    consts: HashMap<String, i64>,
+    decls: Vec<Decl>,
+    anon: usize,
+    errors: usize,
+}
+
+impl Importer {
+    fn new(file: FileId, text: &str) -> Importer {
+        let mut lx = Lexer::new(text);
+        let mut toks = Vec::new();
+        loop {
+            let t = lx.next();
+            match t.kind {
+                Kind::Eof => break,
+                Kind::Newline => {}
+                _ if IGNORED.contains(&&*t.text) => {}
+                _ if IGNORED_CALLS.contains(&&*t.text) => {
+                    // The argument is skipped with the tokens following the extension.
+                    toks.push(t);
+                }
+                _ if &*t.text == "__inline" || &*t.text == "__inline__" => {
+                    let mut t = t;
+                    t.text = "inline".into();
+                    toks.push(t);
+                }
+                _ => toks.push(t),
+            }
+        }
+
+        // Remove the extensions with their arguments.
+        let mut out = Vec::with_capacity(toks.len());
+        let mut i = 0;
+        while i < toks.len() {
+            if IGNORED_CALLS.contains(&&*toks[i].text) {
+                i += 1;
+                let mut depth = 0;
+                while i < toks.len() {
+                    if toks[i].is("(") {
+                        depth += 1;
+                    } else if toks[i].is(")") {
+                        depth -= 1;
+                    } else if depth == 0 {
+                        break;
+                    }
+                    i += 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                continue;
+            }
+            out.push(toks[i].clone());
+            i += 1;
+        }
+
+        Importer {
+            file,
+            toks: out,
+            pos: 0,
+            typedefs: HashSet::new(),
+            consts: HashMap::new(),
+            decls: vec![],
+            anon: 0,
+            errors: 0,
+        }
+    }
+
+    fn peek_at(&self, n: usize) -> Option<&Tok> {
+        self.toks.get(self.pos + n)
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.peek_at(0).is_some_and(|t| t.is(text))
+    }
+
+    fn eat(&mut self, text: &str) -> bool {
+        let is = self.peek_is(text);
+        if is {
+            self.pos += 1;
+        }
+        is
+    }
+
+    fn fail<T>(&self, msg: String) -> Res<T> {
+        Err(Skip {
+            at: self.pos,
+            msg,
+            warning: false,
+            skipped: false,
+        })
+    }
+
+    fn unsupported<T>(&self, msg: String) -> Res<T> {
+        Err(Skip {
+            at: self.pos,
+            msg,
+            warning: true,
+            skipped: false,
+        })
+    }
+
+    fn expect(&mut self, text: &str) -> Res<()> {
+        if self.eat(text) {
+            return Ok(());
+        }
+        match self.peek_at(0) {
+            Some(t) => self.fail(format!("expected \"{}\", found \"{}\"", text, t.text)),
+            None => self.fail(format!("expected \"{}\" at the end of the header", text)),
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        match self.peek_at(0) {
+            Some(t) if t.kind == Kind::Ident && !BASIC_WORDS.contains(&&*t.text) => {
+                let name = t.text.to_string();
+                self.pos += 1;
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    fn report(&mut self, skip: &Skip) {
+        let (start, end) = match self.toks.get(skip.at).or_else(|| self.toks.last()) {
+            Some(t) => (t.start, t.end),
+            None => (0, 0),
+        };
+        let content = {
+            let sm = sman();
+            let (line, _) = sm.line_col(self.file, start);
+            sm.line(self.file, line).unwrap_or("").to_string()
+        };
+        if !skip.warning {
+            self.errors += 1;
+        }
+        let file = self.file;
+        diag(Box::new(CImportError {
+            file,
+            range: Range {
+                start: Box::new(DefaultLocation { file, index: start }),
+                end: Some(Box::new(DefaultLocation { file, index: end })),
+                content,
+            },
+            msg: skip.msg.clone(),
+            warning: skip.warning,
+        }));
+    }
+
+    /// Skip the declaration starting at `start`: up to a `;` outside of braces, or to the end
+    /// of a function body.
+    fn recover(&mut self, start: usize) {
+        self.pos = start;
+        let mut depth = 0usize;
+        while let Some(t) = self.peek_at(0).cloned() {
+            let body = t.is("{") && depth == 0 && self.pos > 0 && self.toks[self.pos - 1].is(")");
+            self.pos += 1;
+            if t.is("{") {
+                depth += 1;
+            } else if t.is("}") {
+                depth = depth.saturating_sub(1);
+            } else if t.is(";") && depth == 0 {
+                return;
+            }
+            if body {
+                self.skip_body();
+                return;
+            }
+        }
+    }
+
+    /// Skip a block whose `{` was just consumed.
+    fn skip_body(&mut self) {
+        let mut depth = 1;
+        while let Some(t) = self.peek_at(0).cloned() {
+            self.pos += 1;
+            if t.is("{") {
+                depth += 1;
+            } else if t.is("}") {
+                depth -= 1;
+                if depth == 0 {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// `TranslationUnit = { ExternalDecl } . `
+    fn unit(&mut self) {
+        while self.pos < self.toks.len() {
+            if self.eat(";") {
+                continue;
+            }
+            let start = self.pos;
+            if let Err(skip) = self.external() {
+                self.report(&skip);
+                if !skip.skipped {
+                    self.recover(start);
+                }
+            }
+        }
+    }
+
+    /// `ExternalDecl = Specifiers [ Declarator [ "=" Init ] { "," Declarator [ "=" Init ] } ] ";"
+    /// | Specifiers Declarator Body . `
+    fn external(&mut self) -> Res<()> {
+        let (storage, inline, base) = self.specifiers()?;
+        if self.eat(";") {
+            return Ok(());
+        }
+
+        loop {
+            let at = self.pos;
+            let (name, ty) = self.declarator(base.clone())?;
+            let name = match name {
+                Some(n) => n,
+                None => return self.fail("expected a name in the declaration".into()),
+            };
+            let func = matches!(ty, CType::Func { .. });
+
+            if func && self.eat("{") {
+                self.skip_body();
+                return Err(Skip {
+                    at,
+                    msg: format!(
+                        "the definition of {} is C code, only prototypes are imported",
+                        name
+                    ),
+                    warning: true,
+                    skipped: true,
+                });
+            }
+            if self.eat("=") {
+                self.initializer();
+            }
+            if func && (inline || storage == Storage::Static) {
+                return Err(Skip {
+                    at,
+                    msg: format!("{} is not an external function", name),
+                    warning: true,
+                    skipped: false,
+                });
+            }
+            self.declare(storage, name, ty, at)?;
+
+            if !self.eat(",") {
+                return self.expect(";");
+            }
+        }
+    }
+
+    /// Skip the initializer of a variable.
+    fn initializer(&mut self) {
+        let mut depth = 0usize;
+        while let Some(t) = self.peek_at(0).cloned() {
+            if depth == 0 && (t.is(",") || t.is(";")) {
+                return;
+            }
+            if t.is("(") || t.is("{") || t.is("[") {
+                depth += 1;
+            } else if t.is(")") || t.is("}") || t.is("]") {
+                depth = depth.saturating_sub(1);
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Add the declaration of `name` with type `ty`, declared at the token `at`.
+    fn declare(&mut self, storage: Storage, name: String, ty: CType, at: usize) -> Res<()> {
+        let skip = |msg: String| Skip {
+            at,
+            msg,
+            warning: true,
+            skipped: false,
+        };
+
+        if storage == Storage::Typedef {
+            self.typedefs.insert(name.clone());
+            if ALEF_TYPES.contains(&name.as_str()) {
+                // `typedef unsigned int uint;` is already built in.
+                return match ty {
+                    CType::Basic(ref b) if b.to_string() == name => Ok(()),
+                    _ => Err(skip(format!("{} is an Alef basic type", name))),
+                };
+            }
+            if KEYS_MAP.contains_key(name.as_str()) {
+                return Err(skip(format!("{} is an Alef keyword", name)));
+            }
+            if let CType::Named(ref n) = ty {
+                // `typedef struct S S;` and `typedef struct { ... } T;` define a single name.
+                if *n == name {
+                    return Ok(());
+                }
+                if n.starts_with(ANON) {
+                    if let Some(Decl::Aggr { name: aggr, .. }) = self.decls.last_mut() {
+                        if aggr == n {
+                            *aggr = name;
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            self.decls.push(Decl::Typedef { name, ty });
+            return Ok(());
+        }
+
+        if KEYS_MAP.contains_key(name.as_str()) {
+            return Err(skip(format!("{} is an Alef keyword", name)));
+        }
+        match ty {
+            CType::Func { .. } => self.decls.push(Decl::Func { name, ty }),
+            _ if storage == Storage::Static => {
+                return Err(skip(format!("{} is not an external variable", name)))
+            }
+            _ => self.decls.push(Decl::Var { name, ty }),
+        }
+        Ok(())
+    }
+
+    /// `Specifiers = { Storage | "inline" | TypeSpecifier } . `
+    fn specifiers(&mut self) -> Res<(Storage, bool, CType)> {
+        let start = self.pos;
+        let mut storage = Storage::None;
+        let mut inline = false;
+        let mut words: Vec<String> = vec![];
+        let mut ty = None;
+
+        while let Some(t) = self.peek_at(0).cloned() {
+            let text = t.text.to_string();
+            match text.as_str() {
+                "typedef" => storage = Storage::Typedef,
+                "extern" => storage = Storage::Extern,
+                "static" => storage = Storage::Static,
+                "inline" => inline = true,
+                w if BASIC_WORDS.contains(&w) => words.push(text),
+                "struct" | "union" => {
+                    self.pos += 1;
+                    ty = Some(self.aggr(text == "union")?);
+                    continue;
+                }
+                "enum" => {
+                    self.pos += 1;
+                    ty = Some(self.enumeration()?);
+                    continue;
+                }
+                "__builtin_va_list" if ty.is_none() && words.is_empty() => {
+                    return self.unsupported("va_list has no Alef equivalent".into());
+                }
+                _ if t.kind == Kind::Ident && ty.is_none() && words.is_empty() => {
+                    // A typedef name, or one from a header that could not be read.
+                    let next = self.peek_at(1);
+                    let unknown = next.is_some_and(|n| n.kind == Kind::Ident || n.is("*"));
+                    if !self.typedefs.contains(&text) && !unknown {
+                        break;
+                    }
+                    ty = Some(CType::Named(text));
+                }
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        let ty = match ty {
+            Some(ty) if words.is_empty() => ty,
+            Some(_) => return self.fail("conflicting type specifiers".into()),
+            None if words.is_empty() => return self.fail("expected a type".into()),
+            None => self.basic(&words).map_err(|s| Skip { at: start, ..s })?,
+        };
+        Ok((storage, inline, ty))
+    }
+
+    /// Map the words of a C basic type to an Alef type.
+    fn basic(&self, words: &[String]) -> Res<CType> {
+        let has = |w: &str| words.iter().any(|x| x == w);
+        let longs = words.iter().filter(|w| *w == "long").count();
+        let unsigned = has("unsigned");
+
+        let b = if has("_Complex") {
+            return self.unsupported("complex types have no Alef equivalent".into());
+        } else if has("__int128") {
+            return self.unsupported("128 bit integers have no Alef equivalent".into());
+        } else if has("void") {
+            BasicType::Void
+        } else if has("_Bool") || has("char") {
+            BasicType::Byte
+        } else if has("short") {
+            if unsigned {
+                BasicType::Usint
+            } else {
+                BasicType::Sint
+            }
+        } else if has("double") {
+            if longs > 0 {
+                return self.unsupported("long double has no Alef equivalent".into());
+            }
+            BasicType::Float
+        } else if has("float") {
+            return self.unsupported("float is 32 bits wide, Alef only has 64 bit floats".into());
+        } else if longs > 0 {
+            if unsigned {
+                BasicType::Ulint
+            } else {
+                BasicType::Lint
+            }
+        } else if unsigned {
+            BasicType::Uint
+        } else {
+            BasicType::Int
+        };
+        Ok(CType::Basic(b))
+    }
+
+    /// `AggrSpecifier = ( "struct" | "union" ) [ identifier ] [ "{" { Member } "}" ] . `
+    fn aggr(&mut self, union: bool) -> Res<CType> {
+        let tag = self.ident();
+        if !self.eat("{") {
+            return match tag {
+                Some(t) => Ok(CType::Named(t)),
+                None => self.fail("expected a name or a definition".into()),
+            };
+        }
+        let name = tag.unwrap_or_else(|| {
+            self.anon += 1;
+            format!("{}{}", ANON, self.anon)
+        });
+
+        let mut members = vec![];
+        while !self.eat("}") {
+            if self.pos >= self.toks.len() {
+                return self.fail(format!("unterminated definition of {}", name));
+            }
+            let (_, _, base) = self.specifiers()?;
+            if self.eat(";") {
+                members.push(Member {
+                    name: None,
+                    ty: base,
+                });
+                continue;
+            }
+            loop {
+                let (n, ty) = self.declarator(base.clone())?;
+                if self.peek_is(":") {
+                    return self.unsupported(format!(
+                        "bit-field {} has no Alef equivalent",
+                        n.unwrap_or_default()
+                    ));
+                }
+                if n.is_none() {
+                    return self.fail("expected a member name".into());
+                }
+                members.push(Member { name: n, ty });
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            self.expect(";")?;
+        }
+
+        self.decls.push(Decl::Aggr {
+            union,
+            name: name.clone(),
+            members,
+        });
+        Ok(CType::Named(name))
+    }
+
+    /// `EnumSpecifier = "enum" [ identifier ] [ "{" identifier [ "=" Const ] { "," ... } "}" ] . `
+    ///
+    /// Enumerations are integers in Alef.
+    fn enumeration(&mut self) -> Res<CType> {
+        let name = self.ident();
+        if !self.eat("{") {
+            return Ok(CType::Basic(BasicType::Int));
+        }
+
+        let mut members = vec![];
+        let mut value = 0;
+        while !self.eat("}") {
+            let member = match self.ident() {
+                Some(m) => m,
+                None => return self.fail("expected an enumeration constant".into()),
+            };
+            if self.eat("=") {
+                value = self.constant()?;
+            }
+            self.consts.insert(member.clone(), value);
+            members.push((member, value));
+            value += 1;
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+
+        self.decls.push(Decl::Enum { name, members });
+        Ok(CType::Basic(BasicType::Int))
+    }
+
+    /// Evaluate a constant expression, ending at a `,`, `}`, `]` or `;` outside of parentheses.
+    fn constant(&mut self) -> Res<i64> {
+        let start = self.pos;
+        let mut toks = vec![];
+        let mut depth = 0usize;
+        while let Some(t) = self.peek_at(0).cloned() {
+            if depth == 0 && (t.is(",") || t.is("}") || t.is("]") || t.is(";")) {
+                break;
+            }
+            if t.is("(") {
+                depth += 1;
+            } else if t.is(")") {
+                depth = depth.saturating_sub(1);
+            }
+            if t.is("sizeof") {
+                return self.unsupported("sizeof is not evaluated in constant expressions".into());
+            }
+            match self.consts.get(&*t.text) {
+                Some(v) if t.kind == Kind::Ident => {
+                    toks.push(Tok::new(Kind::Number, &v.to_string()))
+                }
+                _ => toks.push(t.clone()),
+            }
+            self.pos += 1;
+        }
+        match pp::eval(&toks) {
+            Some(v) => Ok(v),
+            None => Err(Skip {
+                at: start,
+                msg: "cannot evaluate the constant expression".into(),
+                warning: true,
+                skipped: false,
+            }),
+        }
+    }
+
+    /// Read a declarator and apply it to `base`.
+    fn declarator(&mut self, base: CType) -> Res<(Option<String>, CType)> {
+        let (name, ops) = self.declarator_ops()?;
+        let mut ty = base;
+        for op in ops {
+            ty = match op {
+                Op::Pointer => CType::Pointer(Box::new(ty)),
+                Op::Array(_) | Op::Func(..) if matches!(ty, CType::Func { .. }) => {
+                    return self
+                        .fail("functions cannot return functions or be array elements".into())
+                }
+                Op::Func(..) if matches!(ty, CType::Array(..)) => {
+                    return self.fail("functions cannot return arrays".into())
+                }
+                Op::Array(n) => CType::Array(Box::new(ty), n),
+                Op::Func(params, variadic) => CType::Func {
+                    ret: Box::new(ty),
+                    params,
+                    variadic,
+                },
+            };
+        }
+        Ok((name, ty))
+    }
+
+    /// `Declarator = { "*" } ( identifier | "(" Declarator ")" | ) { "[" [ Const ] "]" | Params } . `
+    fn declarator_ops(&mut self) -> Res<(Option<String>, Vec<Op>)> {
+        let mut ops = vec![];
+        while self.eat("*") {
+            ops.push(Op::Pointer);
+        }
+
+        let nested = self.peek_is("(")
+            && self.peek_at(1).is_some_and(|t| {
+                t.is("*")
+                    || t.is("(")
+                    || (t.kind == Kind::Ident
+                        && !self.typedefs.contains(&*t.text)
+                        && !BASIC_WORDS.contains(&&*t.text)
+                        && !["struct", "union", "enum"].contains(&&*t.text))
+            });
+        let (name, inner) = if nested {
+            self.pos += 1;
+            let inner = self.declarator_ops()?;
+            self.expect(")")?;
+            inner
+        } else {
+            (self.ident(), vec![])
+        };
+
+        let mut suffixes = vec![];
+        loop {
+            if self.eat("[") {
+                if self.eat("]") {
+                    suffixes.push(Op::Array(None));
+                } else {
+                    let n = self.constant()?;
+                    self.expect("]")?;
+                    suffixes.push(Op::Array(Some(n)));
+                }
+            } else if self.eat("(") {
+                let (params, variadic) = self.params()?;
+                suffixes.push(Op::Func(params, variadic));
+            } else {
+                break;
+            }
+        }
+
+        ops.extend(suffixes.into_iter().rev());
+        ops.extend(inner);
+        Ok((name, ops))
+    }
+
+    /// `Params = ")" | "void" ")" | Param { "," Param } [ "," "..." ] ")" . `
+    fn params(&mut self) -> Res<(Vec<Member>, bool)> {
+        let mut params = vec![];
+        if self.eat(")") {
+            return Ok((params, false));
+        }
+        if self.peek_is("void") && self.peek_at(1).is_some_and(|t| t.is(")")) {
+            self.pos += 2;
+            return Ok((params, false));
+        }
+
+        loop {
+            if self.eat("...") {
+                self.expect(")")?;
+                return Ok((params, true));
+            }
+            let (_, _, base) = self.specifiers()?;
+            let (mut name, ty) = self.declarator(base)?;
+            // Arrays and functions are passed by address.
+            let ty = match ty {
+                CType::Array(of, _) => CType::Pointer(of),
+                f @ CType::Func { .. } => CType::Pointer(Box::new(f)),
+                ty => ty,
+            };
+            if name.as_deref().is_some_and(|n| KEYS_MAP.contains_key(n)) {
+                name = None;
+            }
+            params.push(Member { name, ty });
+            if !self.eat(",") {
+                self.expect(")")?;
+                return Ok((params, false));
+            }
+        }
+    }
+}
+
+/// Translate the C header `filename`, preprocessed by `pp`, to Alef declarations. Declarations
+/// that cannot be translated are published as diagnostics and left out; the number of errors
+/// is returned with the header.
+pub fn import(pp: &mut Preprocessor, filename: String) -> anyhow::Result<(Header, usize)> {
+    let mut mbuf = pp.preprocess_file(filename)?;
+    let file = mbuf.get_file();
+    let text = mbuf.get_content();
+
+    let mut im = Importer::new(file, &text);
+    im.unit();
+
+    for (name, value) in pp.constants() {
+        if im.consts.contains_key(&name) || KEYS_MAP.contains_key(name.as_str()) {
+            continue;
+        }
+        if i32::try_from(value).is_err() {
+            log::debug!("{} = {} does not fit in an Alef constant", name, value);
+            continue;
+        }
+        im.decls.push(Decl::Const { name, value });
+    }
+    Ok((Header { decls: im.decls }, im.errors + pp.errors()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::sman::sman_mut;
+
+    fn import_str(src: &str) -> (String, usize) {
+        let mut pp = Preprocessor::new();
+        let file = sman_mut().add_str(src, "test.h".into());
+        let mut mbuf = pp.preprocess(file);
+        let text = mbuf.get_content();
+        let mut im = Importer::new(mbuf.get_file(), &text);
+        im.unit();
+        (Header { decls: im.decls }.to_string(), im.errors)
+    }
+
+    #[test]
+    fn prototypes() {
+        let (out, errors) = import_str(
+            "extern int printf(const char *restrict fmt, ...);\n\
+             void exit(int) __attribute__((__noreturn__));\n\
+             unsigned long strlen(const char *);\n\
+             void qsort(void *base, unsigned long n, unsigned long size,\n\
+                        int (*cmp)(const void *, const void *));\n\
+             char *(*handler)(int sig);\n\
+             extern char **environ;\n\
+             int main(int argc, char *argv[]);\n\
+             void abort(void);\n",
+        );
+        assert_eq!(errors, 0);
+        assert_eq!(
+            out,
+            "extern int printf(byte *fmt, ...);\n\
+             extern void exit(int);\n\
+             extern ulint strlen(byte *);\n\
+             extern void qsort(void *base, ulint n, ulint size, int (*cmp)(void *, void *));\n\
+             extern byte *(*handler)(int sig);\n\
+             extern byte **environ;\n\
+             extern int main(int argc, byte **argv);\n\
+             extern void abort();\n"
+        );
+    }
+
+    #[test]
+    fn types() {
+        let (out, errors) = import_str(
+            "typedef unsigned long size_t;\n\
+             typedef unsigned int uint;\n\
+             typedef struct { int quot, rem; } div_t;\n\
+             struct tm { int tm_sec; long tm_gmtoff; const char *tm_zone; };\n\
+             typedef struct _IO_FILE FILE;\n\
+             union u { short s[4]; double d; };\n\
+             enum color { RED, GREEN = 4, BLUE };\n\
+             int hue[BLUE + 1];\n\
+             size_t fread(void *, size_t, size_t, FILE *);\n",
+        );
+        assert_eq!(errors, 0);
+        assert_eq!(
+            out,
+            "typedef ulint size_t;\n\
+             aggr div_t\n{\n\tint quot;\n\tint rem;\n};\n\n\
+             aggr tm\n{\n\tint tm_sec;\n\tlint tm_gmtoff;\n\tbyte *tm_zone;\n};\n\n\
+             typedef _IO_FILE FILE;\n\
+             union u\n{\n\tsint s[4];\n\tfloat d;\n};\n\n\
+             enum color\n{\n\tRED = 0,\n\tGREEN = 4,\n\tBLUE = 5,\n};\n\n\
+             extern int hue[6];\n\
+             extern size_t fread(void *, size_t, size_t, FILE *);\n"
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        let (out, errors) = import_str(
+            "float sinf(float);\n\
+             struct flags { unsigned a : 1; int b; };\n\
+             static inline int twice(int x) { return x * 2; }\n\
+             long double fabsl(long double);\n\
+             int alloc(int);\n\
+             double sin(double);\n\
+             int broken(;\n\
+             int ok(void);\n",
+        );
+        // Only the syntax error counts, the rest are warnings.
+        assert_eq!(errors, 1);
+        assert_eq!(out, "extern float sin(float);\nextern int ok();\n");
+    }
+
+    #[test]
+    fn constants() {
+        let mut pp = Preprocessor::new();
+        let file = sman_mut().add_str(
+            "#define BUFSIZ 8192\n#define EOF (-1)\n#define MASK (1 << 4 | 1)\n\
+             #define NULL ((void *)0)\n#define stdin stdin\n#define F(x) x\n",
+            "consts.h".into(),
+        );
+        pp.preprocess(file);
+        assert_eq!(
+            pp.constants(),
+            [
+                ("BUFSIZ".to_string(), 8192),
+                ("EOF".to_string(), -1),
+                ("MASK".to_string(), 17)
+            ]
+        );
+    }
+}
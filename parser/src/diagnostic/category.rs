@@ -0,0 +1,95 @@
+//! The categories warnings are enabled and disabled by, on the command line with `-W<name>` and
+//! `-Wno-<name>` and in sources with
+//!
+//! ```text
+//! #pragma alef diagnostic ignored "<name>"
+//! #pragma alef diagnostic warning "<name>"
+//! ```
+//!
+//! which hold from the line of the pragma to the end of the file, or to the next pragma naming
+//! the same category. Categories that are off by default are turned on by raising the warning
+//! level, as `-Wall` does.
+
+use std::fmt::{Display, Formatter, Result};
+
+/// A category of warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// `#warning`, redefined macros and other dubious preprocessor input.
+    Preprocessor,
+
+    /// C declarations left out by `alef-check import-c`.
+    ImportC,
+
+    /// Literals and constructs spelled in a deprecated form.
+    Deprecated,
+
+    /// Variables, parameters and functions that are never used.
+    Unused,
+
+    /// Declarations hiding another one of an enclosing scope.
+    Shadowing,
+
+    /// Conversions that may lose precision or change the sign of a value.
+    ImplicitConversion,
+
+    /// Receives on channels nothing sends to.
+    RecvNeverSent,
+
+    /// Sends on unbuffered channels in programs with a single task.
+    SendSingleTask,
+
+    /// `alt`s none of whose cases can become ready.
+    AltNeverReady,
+}
+
+impl Category {
+    /// Every category.
+    pub const ALL: &'static [Category] = &[
+        Category::Preprocessor,
+        Category::ImportC,
+        Category::Deprecated,
+        Category::Unused,
+        Category::Shadowing,
+        Category::ImplicitConversion,
+        Category::RecvNeverSent,
+        Category::SendSingleTask,
+        Category::AltNeverReady,
+    ];
+
+    /// The name of the category, as used on the command line and in pragmas. The names of the
+    /// channel lints are those of `alef_ir::lint::Lint`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Category::Preprocessor => "preprocessor",
+            Category::ImportC => "import-c",
+            Category::Deprecated => "deprecated",
+            Category::Unused => "unused",
+            Category::Shadowing => "shadowing",
+            Category::ImplicitConversion => "implicit-conversion",
+            Category::RecvNeverSent => "recv-never-sent",
+            Category::SendSingleTask => "send-single-task",
+            Category::AltNeverReady => "alt-never-ready",
+        }
+    }
+
+    /// The warning level from which the category is on: 0 for the categories that are on by
+    /// default, 1 for those `-Wall` adds.
+    pub fn level(&self) -> u8 {
+        match self {
+            Category::Unused | Category::Shadowing | Category::ImplicitConversion => 1,
+            _ => 0,
+        }
+    }
+
+    /// Find a category by name.
+    pub fn from_name(name: &str) -> Option<Category> {
+        Category::ALL.iter().copied().find(|c| c.name() == name)
+    }
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.name())
+    }
+}
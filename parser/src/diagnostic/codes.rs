@@ -0,0 +1,96 @@
+//! The registry of diagnostic codes. Every diagnostic reports one of these codes, which stay the
+//! same across releases, and each code comes with a long-form explanation in `codes/<code>.md`
+//! that `alef-check explain` prints.
+//!
+//! Codes are grouped by the phase reporting them: E00xx for reading sources, E01xx for the
+//! scanner, E02xx for the preprocessor, E03xx for the parser, E04xx for the C importer, E05xx
+//! for the analyses of checked programs and E06xx for IR modules and the programs built from
+//! them. A code is never reused once it is retired.
+
+use std::fmt::{Display, Formatter, Result};
+
+/// A diagnostic code and its documentation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Code {
+    /// The code itself, e.g. `E0102`.
+    pub id: &'static str,
+
+    /// A one-line description of the diagnostics reported with the code.
+    pub summary: &'static str,
+
+    /// The explanation of the code, in Markdown, with examples.
+    pub explanation: &'static str,
+}
+
+impl Display for Code {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.id)
+    }
+}
+
+macro_rules! codes {
+    ($($id:ident: $summary:literal,)*) => {
+        $(
+            #[doc = $summary]
+            pub const $id: Code = Code {
+                id: stringify!($id),
+                summary: $summary,
+                explanation: include_str!(concat!("codes/", stringify!($id), ".md")),
+            };
+        )*
+
+        /// Every code, in order.
+        pub static CODES: &[Code] = &[$($id),*];
+    };
+}
+
+codes! {
+    E0001: "The next character of a source could not be read.",
+    E0101: "A symbol that cannot start any token was found in the source.",
+    E0102: "A numeric, character or string literal is malformed.",
+    E0103: "A preprocessor directive reached the scanner.",
+    E0104: "A comment is not terminated.",
+    E0105: "A literal is spelled in a deprecated form.",
+    E0201: "The preprocessor could not handle a directive or a macro.",
+    E0202: "A `#error` or `#warning` directive was reached.",
+    E0203: "A `#pragma alef diagnostic` is malformed and was ignored.",
+    E0301: "A declaration was expected.",
+    E0302: "The `module` or `import` declarations of a source are malformed.",
+    E0303: "A token the grammar requires is missing or mismatched.",
+    E0401: "A C declaration cannot be imported by `alef-check import-c`.",
+    E0501: "A channel is received from, but nothing ever sends to it.",
+    E0502: "A value is sent on an unbuffered channel in a program with a single task.",
+    E0503: "None of the cases of an `alt` can ever become ready.",
+    E0601: "An IR module cannot be read.",
+    E0602: "An IR module is not well formed.",
+    E0603: "The modules of a program cannot be linked together.",
+    E0604: "The interface of an imported module cannot be used.",
+    E0605: "A backend cannot translate an IR module.",
+}
+
+/// Find a code, ignoring the case of its letter.
+pub fn lookup(id: &str) -> Option<&'static Code> {
+    CODES.iter().find(|c| c.id.eq_ignore_ascii_case(id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry() {
+        // Sorted, hence unique.
+        assert!(CODES.windows(2).all(|w| w[0].id < w[1].id));
+        for c in CODES {
+            assert!(
+                c.id.len() == 5 && c.id.starts_with('E'),
+                "bad code {}",
+                c.id
+            );
+            // The explanation starts with the summary.
+            assert!(c.explanation.starts_with(c.summary), "{}", c.id);
+        }
+        assert_eq!(lookup("e0102"), Some(&E0102));
+        assert_eq!(lookup("E9999"), None);
+    }
+}
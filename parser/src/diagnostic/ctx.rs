@@ -0,0 +1,458 @@
+//! The diagnostic context of a compilation. Everything published with `diagnostic::diag` goes
+//! through it: the context drops the warnings of disabled categories, promotes warnings to
+//! errors when asked to, drops duplicates and the errors past the limit, counts what is left by
+//! severity and hands it to its DiagnosticsManager, so that the driver can tell at the end
+//! whether the compilation failed. It also keeps the edits of the machine-applicable fixes of
+//! what it published, for `alef-check fix`.
+
+use crate::diagnostic::category::Category;
+use crate::diagnostic::dman::{DefaultDiagnosticsManager, DiagnosticsManager, WARNING_LEVEL};
+use crate::diagnostic::err::{Diagnostic, LabeledSpan, Severity};
+use crate::diagnostic::fix::{Applicability, Edit, Suggestion};
+use crate::source::loc::Location;
+use crate::source::sman::sman;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::sync::{LazyLock, Mutex};
+use thiserror::Error;
+
+/// The context of the current compilation.
+pub static DIAGCTX: LazyLock<Mutex<DiagnosticContext>> = LazyLock::new(|| {
+    Mutex::new(DiagnosticContext::new(Box::new(
+        DefaultDiagnosticsManager::default(),
+    )))
+});
+
+/// Collects, filters and counts the diagnostics of a compilation.
+pub struct DiagnosticContext {
+    dman: Box<dyn DiagnosticsManager + Sync + Send>,
+
+    /// Whether warnings are published as errors.
+    pub werror: bool,
+
+    /// The number of errors after which further errors are counted but not published.
+    pub max_errors: Option<usize>,
+
+    /// The number of diagnostics published, by severity.
+    counts: [usize; 4],
+
+    /// The number of errors that went over `max_errors`.
+    dropped: usize,
+
+    /// The keys of the diagnostics published, to recognize duplicates.
+    seen: HashSet<String>,
+
+    /// The categories enabled or disabled on the command line.
+    categories: HashMap<Category, bool>,
+
+    /// The categories enabled or disabled by pragmas.
+    pragmas: Vec<Pragma>,
+
+    /// The edits of the machine-applicable fixes of the diagnostics published.
+    fixes: Vec<Edit>,
+}
+
+/// A category enabled or disabled by a pragma, from a line of a file on.
+struct Pragma {
+    file: String,
+    line: usize,
+    category: Category,
+    enabled: bool,
+}
+
+impl DiagnosticContext {
+    /// Create a context publishing diagnostics to `dman`.
+    pub fn new(dman: Box<dyn DiagnosticsManager + Sync + Send>) -> DiagnosticContext {
+        DiagnosticContext {
+            dman,
+            werror: false,
+            max_errors: None,
+            counts: [0; 4],
+            dropped: 0,
+            seen: HashSet::new(),
+            categories: HashMap::new(),
+            pragmas: vec![],
+            fixes: vec![],
+        }
+    }
+
+    /// Enable or disable a category of warnings, overriding the warning level.
+    pub fn enable(&mut self, category: Category, enabled: bool) {
+        self.categories.insert(category, enabled);
+    }
+
+    /// Enable or disable a category of warnings from `line` of the file `file` on, as a pragma
+    /// does.
+    pub fn pragma(&mut self, file: &str, line: usize, category: Category, enabled: bool) {
+        self.pragmas.push(Pragma {
+            file: file.to_string(),
+            line,
+            category,
+            enabled,
+        });
+    }
+
+    /// Return true if warnings of the category are published at `line` of the file `file`, or
+    /// outside of any source if `at` is None.
+    pub fn is_enabled(&self, category: Category, at: Option<(&str, usize)>) -> bool {
+        if let Some((file, line)) = at {
+            let pragma = self
+                .pragmas
+                .iter()
+                .filter(|p| p.category == category && p.file == file && p.line <= line)
+                .max_by_key(|p| p.line);
+            if let Some(p) = pragma {
+                return p.enabled;
+            }
+        }
+        match self.categories.get(&category) {
+            Some(enabled) => *enabled,
+            None => category.level() <= *WARNING_LEVEL.lock().unwrap(),
+        }
+    }
+
+    /// Replace the manager diagnostics are published to.
+    pub fn set_manager(&mut self, dman: Box<dyn DiagnosticsManager + Sync + Send>) {
+        self.dman = dman;
+    }
+
+    /// Publish a diagnostic, unless it was already published or there are too many errors.
+    pub fn publish(&mut self, diag: Box<dyn Diagnostic>) {
+        let mut diag = diag;
+        let mut severity = diag.severity().unwrap_or(Severity::Error);
+        if let (Severity::Warning, Some(category)) = (severity, diag.category()) {
+            let at = diag.loc().and_then(|l| {
+                let file = l.get_file()?;
+                let sm = sman();
+                let p = sm.presumed(file, l.get_mbuf_index());
+                Some((p.name.to_string(), p.line))
+            });
+            if !self.is_enabled(category, at.as_ref().map(|(f, l)| (f.as_str(), *l))) {
+                return;
+            }
+        }
+        if severity == Severity::Warning && self.werror {
+            severity = Severity::Error;
+            diag = Box::new(Promoted(diag));
+        }
+
+        if !self.seen.insert(key(severity, diag.as_ref())) {
+            return;
+        }
+
+        let error = matches!(severity, Severity::Fatal | Severity::Error);
+        if error && self.should_stop() {
+            self.dropped += 1;
+            return;
+        }
+        self.counts[severity as usize] += 1;
+        for s in diag.suggestions().unwrap_or_default() {
+            if s.applicability == Applicability::MachineApplicable {
+                self.fixes.extend(s.edits);
+            }
+        }
+        self.dman.publish(diag);
+    }
+
+    /// Take the edits of the machine-applicable fixes published so far.
+    pub fn take_fixes(&mut self) -> Vec<Edit> {
+        std::mem::take(&mut self.fixes)
+    }
+
+    /// The number of diagnostics of the given severity published so far.
+    pub fn count(&self, severity: Severity) -> usize {
+        self.counts[severity as usize]
+    }
+
+    /// The number of errors, fatal or not, including those over the limit.
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Fatal) + self.count(Severity::Error) + self.dropped
+    }
+
+    /// Return true if the compilation had errors.
+    pub fn has_errors(&self) -> bool {
+        self.errors() > 0
+    }
+
+    /// Return true once the error limit is reached, when compiling further is pointless.
+    pub fn should_stop(&self) -> bool {
+        self.max_errors.is_some_and(|max| self.errors() >= max)
+    }
+
+    /// Note the errors that were not published and let the manager flush its output.
+    pub fn finish(&mut self) {
+        if self.dropped > 0 {
+            self.counts[Severity::Info as usize] += 1;
+            self.dman.publish(Box::new(TooManyErrors {
+                dropped: self.dropped,
+                max: self.max_errors.unwrap_or(0),
+            }));
+        }
+        self.dman.finish();
+    }
+}
+
+/// What makes two diagnostics duplicates of each other: the same severity, code, message,
+/// reason and location.
+fn key(severity: Severity, diag: &dyn Diagnostic) -> String {
+    format!(
+        "{}\0{}\0{}\0{}\0{}",
+        severity,
+        diag.code().map(|c| c.to_string()).unwrap_or_default(),
+        diag,
+        diag.reason().map(|r| r.to_string()).unwrap_or_default(),
+        diag.loc().map(|l| l.to_string()).unwrap_or_default()
+    )
+}
+
+/// A warning published as an error because of `-Werror`.
+#[derive(Error, Debug)]
+#[error("{}", .0)]
+struct Promoted(Box<dyn Diagnostic>);
+
+impl Diagnostic for Promoted {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Error)
+    }
+
+    fn category(&self) -> Option<Category> {
+        self.0.category()
+    }
+
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        self.0.code()
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        self.0.loc()
+    }
+
+    fn context(&self) -> Option<String> {
+        self.0.context()
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+        self.0.labels()
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        self.0.reason()
+    }
+
+    fn help<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        self.0.help()
+    }
+
+    fn relatives(&self) -> Option<Vec<Box<dyn Diagnostic>>> {
+        self.0.relatives()
+    }
+
+    fn suggestions(&self) -> Option<Vec<Suggestion>> {
+        self.0.suggestions()
+    }
+}
+
+/// The note closing a compilation that went over the error limit.
+#[derive(Error, Debug)]
+#[error("{} more errors not shown", .dropped)]
+struct TooManyErrors {
+    dropped: usize,
+    max: usize,
+}
+
+impl Diagnostic for TooManyErrors {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Info)
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(format!(
+            "only the first {} errors are reported",
+            self.max
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::{FileId, SRCMAN};
+    use std::sync::Arc;
+
+    /// A manager remembering the severity and message of what it is given.
+    struct Collect(Arc<Mutex<Vec<String>>>);
+
+    impl DiagnosticsManager for Collect {
+        fn publish(&mut self, diag: Box<dyn Diagnostic>) {
+            let severity = diag.severity().unwrap_or(Severity::Error);
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{}: {}", severity, diag));
+        }
+    }
+
+    #[derive(Error, Debug)]
+    #[error("{}", .msg)]
+    struct Fake {
+        severity: Severity,
+        msg: &'static str,
+    }
+
+    impl Diagnostic for Fake {
+        fn severity(&self) -> Option<Severity> {
+            Some(self.severity)
+        }
+    }
+
+    fn context() -> (DiagnosticContext, Arc<Mutex<Vec<String>>>) {
+        let out = Arc::new(Mutex::new(vec![]));
+        (DiagnosticContext::new(Box::new(Collect(out.clone()))), out)
+    }
+
+    fn publish(ctx: &mut DiagnosticContext, severity: Severity, msg: &'static str) {
+        ctx.publish(Box::new(Fake { severity, msg }));
+    }
+
+    #[test]
+    fn counts_and_duplicates() {
+        let (mut ctx, out) = context();
+        publish(&mut ctx, Severity::Warning, "unused");
+        publish(&mut ctx, Severity::Warning, "unused");
+        publish(&mut ctx, Severity::Info, "unused");
+        assert!(!ctx.has_errors());
+        publish(&mut ctx, Severity::Error, "bad");
+        assert_eq!(ctx.count(Severity::Warning), 1);
+        assert_eq!(ctx.count(Severity::Info), 1);
+        assert_eq!(ctx.errors(), 1);
+        assert_eq!(
+            *out.lock().unwrap(),
+            ["warning: unused", "info: unused", "error: bad"]
+        );
+    }
+
+    #[test]
+    fn werror() {
+        let (mut ctx, out) = context();
+        ctx.werror = true;
+        publish(&mut ctx, Severity::Warning, "unused");
+        publish(&mut ctx, Severity::Info, "note");
+        assert_eq!(ctx.count(Severity::Warning), 0);
+        assert!(ctx.has_errors());
+        assert_eq!(*out.lock().unwrap(), ["error: unused", "info: note"]);
+    }
+
+    #[test]
+    fn max_errors() {
+        let (mut ctx, out) = context();
+        ctx.max_errors = Some(2);
+        publish(&mut ctx, Severity::Error, "a");
+        assert!(!ctx.should_stop());
+        publish(&mut ctx, Severity::Error, "b");
+        publish(&mut ctx, Severity::Error, "c");
+        publish(&mut ctx, Severity::Warning, "d");
+        assert!(ctx.should_stop());
+        assert_eq!(ctx.errors(), 3);
+        ctx.finish();
+        assert_eq!(
+            *out.lock().unwrap(),
+            [
+                "error: a",
+                "error: b",
+                "warning: d",
+                "info: 1 more errors not shown"
+            ]
+        );
+    }
+
+    #[derive(Error, Debug)]
+    #[error("channel never sent to")]
+    struct Lint {
+        category: Category,
+    }
+
+    impl Diagnostic for Lint {
+        fn severity(&self) -> Option<Severity> {
+            Some(Severity::Warning)
+        }
+
+        fn category(&self) -> Option<Category> {
+            Some(self.category)
+        }
+    }
+
+    #[test]
+    fn categories() {
+        let (mut ctx, out) = context();
+        assert!(ctx.is_enabled(Category::RecvNeverSent, None));
+        ctx.enable(Category::RecvNeverSent, false);
+        ctx.publish(Box::new(Lint {
+            category: Category::RecvNeverSent,
+        }));
+        ctx.publish(Box::new(Lint {
+            category: Category::AltNeverReady,
+        }));
+        assert_eq!(ctx.count(Severity::Warning), 1);
+        assert_eq!(*out.lock().unwrap(), ["warning: channel never sent to"]);
+
+        ctx.enable(Category::Unused, true);
+        assert!(ctx.is_enabled(Category::Unused, None));
+    }
+
+    #[test]
+    fn pragmas() {
+        let (mut ctx, _) = context();
+        let category = Category::SendSingleTask;
+        ctx.pragma("a.l", 3, category, false);
+        ctx.pragma("a.l", 8, category, true);
+        ctx.pragma("a.l", 12, category, false);
+        assert!(ctx.is_enabled(category, Some(("a.l", 2))));
+        assert!(!ctx.is_enabled(category, Some(("a.l", 3))));
+        assert!(ctx.is_enabled(category, Some(("a.l", 10))));
+        assert!(!ctx.is_enabled(category, Some(("a.l", 20))));
+        // Pragmas hold in their own file only, and override the command line.
+        assert!(ctx.is_enabled(category, Some(("b.l", 20))));
+        ctx.enable(category, false);
+        assert!(ctx.is_enabled(category, Some(("a.l", 10))));
+        assert!(!ctx.is_enabled(category, Some(("b.l", 20))));
+    }
+
+    #[derive(Error, Debug)]
+    #[error("missing semicolon ({})", .applicability)]
+    struct Missing {
+        file: FileId,
+        applicability: Applicability,
+    }
+
+    impl Diagnostic for Missing {
+        fn suggestions(&self) -> Option<Vec<Suggestion>> {
+            Some(vec![Suggestion {
+                msg: "insert `;`".into(),
+                edits: vec![Edit {
+                    file: self.file,
+                    start: 3,
+                    end: 3,
+                    text: ";".into(),
+                }],
+                applicability: self.applicability,
+            }])
+        }
+    }
+
+    #[test]
+    fn fixes() {
+        let (mut ctx, _) = context();
+        let file = SRCMAN.write().unwrap().add_str("x=1\n", "fixes.l".into());
+        ctx.publish(Box::new(Missing {
+            file,
+            applicability: Applicability::MaybeIncorrect,
+        }));
+        assert!(ctx.take_fixes().is_empty());
+        ctx.publish(Box::new(Missing {
+            file,
+            applicability: Applicability::MachineApplicable,
+        }));
+        assert_eq!(ctx.take_fixes().len(), 1);
+        assert!(ctx.take_fixes().is_empty());
+    }
+}
@@ -1,20 +1,169 @@
 use crate::diagnostic::err::{Diagnostic, LabeledSpan, Severity};
-use owo_colors::OwoColorize;
+use crate::diagnostic::fix::{Edit, Suggestion};
+use crate::source::loc::DefaultLocation;
+use crate::source::sman::{sman, FileId};
+use owo_colors::{OwoColorize, Style};
+use std::collections::BTreeSet;
+use std::env;
+use std::io::IsTerminal;
 use std::{fmt, sync::Mutex, sync::LazyLock};
 
-// @XXX This may be changed in favour of a better alternative?
-/// The warning level used to configure the global state of the singleton DiagnosticsManager.
+/// The warning level: the warning categories whose `Category::level` is at most this level are
+/// published unless they are disabled explicitly. `-Wall` raises it to 1.
 pub static WARNING_LEVEL: LazyLock<Mutex<u8>> = LazyLock::new(|| Mutex::new(0));
 
-pub static DIAGMAN: LazyLock<Mutex<Box<dyn DiagnosticsManager + Sync + Send>>> =
-    LazyLock::new(|| Mutex::new(Box::new(DefaultDiagnosticsManager {})));
-
 pub trait DiagnosticsManager: Sync {
     /// Publish a new diagnostic message.
     fn publish(&mut self, diag: Box<dyn Diagnostic>);
+
+    /// Flush whatever the manager holds back, once no more diagnostics will be published.
+    fn finish(&mut self) {}
+}
+
+/// Prints diagnostics as text on stderr, drawn with a theme.
+pub struct DefaultDiagnosticsManager {
+    theme: Theme,
+}
+
+impl DefaultDiagnosticsManager {
+    pub fn new(theme: Theme) -> Self {
+        Self { theme }
+    }
+}
+
+impl Default for DefaultDiagnosticsManager {
+    /// A manager drawing with the theme suiting stderr.
+    fn default() -> Self {
+        Self::new(Theme::detect())
+    }
+}
+
+/// How diagnostics are drawn: the characters of their snippets and the styles of their text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub characters: ThemeCharacters,
+    pub styles: ThemeStyles,
 }
 
-pub struct DefaultDiagnosticsManager {}
+impl Theme {
+    /// Unicode graphics with colors.
+    pub fn unicode() -> Self {
+        Self {
+            characters: ThemeCharacters::unicode(),
+            styles: ThemeStyles::ansi(),
+        }
+    }
+
+    /// Unicode graphics without colors.
+    pub fn unicode_nocolor() -> Self {
+        Self {
+            characters: ThemeCharacters::unicode(),
+            styles: ThemeStyles::none(),
+        }
+    }
+
+    /// ASCII graphics with colors.
+    pub fn ascii() -> Self {
+        Self {
+            characters: ThemeCharacters::ascii(),
+            styles: ThemeStyles::ansi(),
+        }
+    }
+
+    /// ASCII graphics without colors, for output that is not read on a terminal.
+    pub fn none() -> Self {
+        Self {
+            characters: ThemeCharacters::ascii(),
+            styles: ThemeStyles::none(),
+        }
+    }
+
+    /// The theme suiting stderr, with colors or without: ASCII graphics on dumb terminals and
+    /// unicode ones elsewhere.
+    pub fn terminal(colors: bool) -> Self {
+        let characters = if dumb_terminal() {
+            ThemeCharacters::ascii()
+        } else {
+            ThemeCharacters::unicode()
+        };
+        let styles = if colors {
+            ThemeStyles::ansi()
+        } else {
+            ThemeStyles::none()
+        };
+        Self { characters, styles }
+    }
+
+    /// The theme suiting stderr, with colors if `Theme::colors` says so.
+    pub fn detect() -> Self {
+        Self::terminal(Self::colors())
+    }
+
+    /// Whether stderr takes colors: it must be a terminal which is not dumb, and `NO_COLOR`
+    /// must not be set (see <https://no-color.org>).
+    pub fn colors() -> bool {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        std::io::stderr().is_terminal() && !dumb_terminal() && !no_color
+    }
+}
+
+fn dumb_terminal() -> bool {
+    env::var_os("TERM").is_some_and(|t| t == "dumb")
+}
+
+/// The styles of the parts of diagnostics. `Style::new()` leaves a part as it is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThemeStyles {
+    /// The severity of fatal errors and errors, and its symbol.
+    pub error: Style,
+    pub warning: Style,
+    pub info: Style,
+
+    pub code: Style,
+    pub message: Style,
+    pub linum: Style,
+
+    /// The underlines of labels and their messages.
+    pub highlight: Style,
+
+    /// The marks under the text suggestions add.
+    pub addition: Style,
+
+    /// The words introducing helps, suggestions and notes.
+    pub keyword: Style,
+}
+
+impl ThemeStyles {
+    /// ANSI colors and effects.
+    pub fn ansi() -> Self {
+        Self {
+            error: Style::new().red().bold(),
+            warning: Style::new().yellow().bold(),
+            info: Style::new().bold(),
+            code: Style::new().bold(),
+            message: Style::new().underline(),
+            linum: Style::new().bold(),
+            highlight: Style::new().red().bold(),
+            addition: Style::new().green().bold(),
+            keyword: Style::new().bold(),
+        }
+    }
+
+    /// No styles at all.
+    pub fn none() -> Self {
+        Self {
+            error: Style::new(),
+            warning: Style::new(),
+            info: Style::new(),
+            code: Style::new(),
+            message: Style::new(),
+            linum: Style::new(),
+            highlight: Style::new(),
+            addition: Style::new(),
+            keyword: Style::new(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct ThemeCharacters {
@@ -76,6 +225,34 @@ impl ThemeCharacters {
             info: "⚐".into(),
         }
     }
+
+    /// Graphical elements made of ASCII characters only.
+    pub fn ascii() -> Self {
+        Self {
+            hbar: '-',
+            vbar: '|',
+            xbar: '+',
+            vbar_break: ':',
+            uarrow: '^',
+            rarrow: '>',
+            ltop: ',',
+            mtop: 'v',
+            rtop: '.',
+            lbot: '`',
+            mbot: '^',
+            rbot: '\'',
+            lbox: '[',
+            rbox: ']',
+            lcross: '|',
+            rcross: '|',
+            underbar: '|',
+            underline: '^',
+            fatal: "X".into(),
+            error: "x".into(),
+            warning: "!".into(),
+            info: "i".into(),
+        }
+    }
 }
 
 //
@@ -92,6 +269,8 @@ impl ThemeCharacters {
 //
 //      |- CONTEXT
 //      v
+//   in file included from main.l:3  <- One line per including file, if any.
+//   in expansion of macro X defined at foo.h:2:9  <- If the culprit comes from a macro.
 //      ╭─ bad_file.l:10:7  <- Position in the file.
 //   10 │ x = 4 + "this_is_a_str"; <- The culprit line in the source.
 //      ·     ┬ ┬ ──────┬───────                               |
@@ -107,6 +286,17 @@ impl ThemeCharacters {
 //
 //      Help: Using '+' operator on a string and a non-string does not append the integer
 //            value to the string.  <- Optional help message.
+//
+//      suggestion: insert `;`          <- Optional fixes, with the line they change
+//    3 │ import io;                       as it reads once fixed and the text they
+//      ·          +                       insert marked.
+//
+//      note: x was declared before      <- Optional relatives, as notes with their own
+//      ╭─ util.h:4:5                       snippets. Labels pointing at spans of sources
+//    4 │ int x;                            get snippets of their own as well, one per
+//      ·     ┬                             source, and spans of several lines are
+//      ·     ╰─ previous declaration       underlined from their first to their last line.
+//      ╰────
 
 impl DiagnosticsManager for DefaultDiagnosticsManager {
     fn publish<'a>(&mut self, diag: Box<dyn Diagnostic + 'a>) {
@@ -127,11 +317,12 @@ impl DefaultDiagnosticsManager {
     }
 
     fn header(&self, f: &mut dyn fmt::Write, diag: &(dyn Diagnostic)) -> fmt::Result {
-        let severity = match diag.severity() {
-            Some(Severity::Fatal) => "fatal".red().bold().to_string(),
-            Some(Severity::Error) | None => "error".red().bold().to_string(),
-            Some(Severity::Warning) => "info".yellow().bold().to_string(),
-            Some(Severity::Info) => "info".bold().to_string(),
+        let styles = &self.theme.styles;
+        let (severity, style) = match diag.severity() {
+            Some(Severity::Fatal) => ("fatal", styles.error),
+            Some(Severity::Error) | None => ("error", styles.error),
+            Some(Severity::Warning) => ("warning", styles.warning),
+            Some(Severity::Info) => ("info", styles.info),
         };
 
         let code = match diag.code() {
@@ -142,18 +333,18 @@ impl DefaultDiagnosticsManager {
         writeln!(
             f,
             "{} {}: {}",
-            severity,
-            code.bold(),
-            diag.to_string().underline()
+            severity.style(style),
+            code.style(styles.code),
+            diag.to_string().style(styles.message)
         )?;
 
-        let charset = ThemeCharacters::unicode();
+        let charset = &self.theme.characters;
 
         let cause_symbol = match diag.severity() {
-            Some(Severity::Fatal) => charset.fatal.red().bold().to_string(),
-            Some(Severity::Error) | None => charset.error.red().bold().to_string(),
-            Some(Severity::Warning) => charset.warning.yellow().bold().to_string(),
-            Some(Severity::Info) => charset.info.bold().to_string(),
+            Some(Severity::Fatal) => &charset.fatal,
+            Some(Severity::Error) | None => &charset.error,
+            Some(Severity::Warning) => &charset.warning,
+            Some(Severity::Info) => &charset.info,
         };
 
         let cause = match diag.reason() {
@@ -165,7 +356,7 @@ impl DefaultDiagnosticsManager {
             .initial_indent("")
             .subsequent_indent("  ");
 
-        let cause = format!("{} {}", cause_symbol, cause);
+        let cause = format!("{} {}", cause_symbol.style(style), cause);
         writeln!(f)?;
         writeln!(f, "{}", textwrap::fill(&cause, opts))?;
         writeln!(f)?;
@@ -191,7 +382,7 @@ impl DefaultDiagnosticsManager {
         write!(
             f,
             " {:width$} {} ",
-            linum.to_string().bold(),
+            linum.to_string().style(self.theme.styles.linum),
             theme.vbar,
             width = width
         )?;
@@ -199,8 +390,33 @@ impl DefaultDiagnosticsManager {
     }
 
     fn context(&self, f: &mut dyn fmt::Write, diag: &(dyn Diagnostic)) -> fmt::Result {
+        // The chain of files including the one of the diagnostic, and the macro it comes from.
+        if let Some(loc) = diag.loc() {
+            for inc in loc.get_includes() {
+                writeln!(f, "in file included from {}:{}", inc.name, inc.line)?;
+            }
+            if let Some(e) = loc.get_expansion() {
+                match e.def {
+                    Some((file, index)) => writeln!(
+                        f,
+                        "in expansion of macro {} defined at {}",
+                        e.name,
+                        DefaultLocation { file, index }
+                    )?,
+                    None => writeln!(f, "in expansion of predefined macro {}", e.name)?,
+                }
+            }
+        }
+
+        // Labels with a span get snippets of their own, the others are on the context line.
+        let (spanned, mut labels): (Vec<LabeledSpan>, Vec<LabeledSpan>) = diag
+            .labels()
+            .into_iter()
+            .flatten()
+            .partition(|l| l.span.is_some());
+
         if let Some(src) = diag.context() {
-            let charset = ThemeCharacters::unicode();
+            let charset = &self.theme.characters;
 
             let lineno = if let Some(loc) = diag.loc() {
                 loc.get_line()
@@ -220,12 +436,6 @@ impl DefaultDiagnosticsManager {
             // Get the lines that we'll need to print.
             let mut lines = src.split('\n').collect::<Vec<&str>>();
             lines.retain(|x| !x.is_empty());
-            let mut labels = if let Some(labels) = diag.labels() {
-                labels.collect::<Vec<_>>()
-            } else {
-                std::vec!()
-            };
-
             labels.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap());
 
             let mut lines_labels = Vec::new();
@@ -247,9 +457,9 @@ impl DefaultDiagnosticsManager {
 
             let mut lineno = lineno;
             for (line, mut labels) in lines_labels {
-                self.write_linum(f, ln_indent, lineno, &charset)?;
+                self.write_linum(f, ln_indent, lineno, charset)?;
                 writeln!(f, "{}", line)?;
-                self.render_labels(f, ln_indent, &mut labels, &charset, line.to_string())?;
+                self.render_labels(f, ln_indent, &mut labels, charset, line.to_string())?;
                 lineno += 1;
             }
             writeln!(
@@ -260,7 +470,7 @@ impl DefaultDiagnosticsManager {
                 charset.hbar.to_string().repeat(3),
                 width = ln_indent
             )?;
-        } else {
+        } else if spanned.is_empty() {
             let context = if let Some(loc) = diag.loc() {
                 format!("{}", loc)
             } else {
@@ -269,6 +479,124 @@ impl DefaultDiagnosticsManager {
 
             writeln!(f, "{}", context)?;
         }
+        self.snippets(f, &spanned)
+    }
+
+    /// Print the labels pointing at spans: a snippet for each source, in the order the labels
+    /// come in, with the lines they cover. The lines inside spans of more than four lines are
+    /// left out.
+    fn snippets(&self, f: &mut dyn fmt::Write, labels: &[LabeledSpan]) -> fmt::Result {
+        let charset = &self.theme.characters;
+        let sm = sman();
+
+        let mut files: Vec<FileId> = vec![];
+        for l in labels {
+            if let Some(span) = l.span {
+                if !files.contains(&span.file) {
+                    files.push(span.file);
+                }
+            }
+        }
+
+        for file in files {
+            // The labels of the source, with the physical line and column they start and end
+            // at; the end is inclusive.
+            let mut marks = vec![];
+            for l in labels {
+                let Some(span) = l.span.filter(|s| s.file == file) else {
+                    continue;
+                };
+                let first = sm.line_col(file, span.start);
+                let last = if span.end > span.start {
+                    sm.line_col(file, span.end - 1)
+                } else {
+                    first
+                };
+                marks.push((span, first, last, &l.msg));
+            }
+            marks.sort_by_key(|m| m.0.start);
+
+            let mut lines = BTreeSet::new();
+            for (_, first, last, _) in &marks {
+                lines.insert(first.0);
+                lines.insert(last.0);
+                if last.0 - first.0 < 4 {
+                    lines.extend(first.0..last.0);
+                }
+            }
+            let presumed = |line| {
+                let offset = sm.line_start(file, line).unwrap_or(0);
+                sm.presumed(file, offset).line
+            };
+            let width = lines
+                .iter()
+                .map(|&l| presumed(l).to_string().len())
+                .max()
+                .unwrap_or(1);
+
+            let p = sm.presumed(file, marks[0].0.start);
+            writeln!(
+                f,
+                " {:width$} {}{} {}:{}:{}",
+                " ",
+                charset.ltop,
+                charset.hbar,
+                p.name,
+                p.line,
+                p.col,
+                width = width
+            )?;
+
+            let mut prev: Option<usize> = None;
+            for &line in &lines {
+                if prev.is_some_and(|p| line > p + 1) {
+                    self.write_no_linum(f, width, charset)?;
+                    writeln!(f)?;
+                }
+                prev = Some(line);
+
+                let text = sm.line(file, line).unwrap_or("");
+                self.write_linum(f, width, presumed(line), charset)?;
+                writeln!(f, "{}", text)?;
+
+                // Spans of several lines are underlined to the end of their first line, and
+                // from the start of their last line with their message.
+                let mut row = vec![];
+                for (_, first, last, msg) in &marks {
+                    if first.0 == line && last.0 > line {
+                        let mut under = String::new();
+                        for (i, c) in text.chars().enumerate() {
+                            if i + 1 >= first.1 {
+                                under.push(charset.underline);
+                            } else {
+                                under.push(if c.is_whitespace() { c } else { ' ' });
+                            }
+                        }
+                        self.write_no_linum(f, width, charset)?;
+                        writeln!(f, "{}", under.style(self.theme.styles.highlight))?;
+                    } else if last.0 == line {
+                        row.push(LabeledSpan {
+                            msg: (*msg).clone(),
+                            start: if first.0 == line { first.1 } else { 1 },
+                            end: last.1,
+                            span: None,
+                        });
+                    }
+                }
+                let mut row: Vec<&LabeledSpan> = row.iter().filter(|l| l.msg.is_some()).collect();
+                if !row.is_empty() {
+                    self.render_labels(f, width, &mut row, charset, text.to_string())?;
+                }
+            }
+            writeln!(
+                f,
+                " {:width$} {}{}",
+                " ",
+                charset.lbot,
+                charset.hbar.to_string().repeat(3),
+                width = width
+            )?;
+        }
         Ok(())
     }
 
@@ -280,10 +608,91 @@ impl DefaultDiagnosticsManager {
                 .initial_indent("  ")
                 .subsequent_indent("  ");
 
-            let footer = format!("{}: {}", "help".bold(), help);
+            let footer = format!("{}: {}", "help".style(self.theme.styles.keyword), help);
             writeln!(f, "{}", textwrap::fill(&footer, opts))?;
         }
 
+        for s in diag.suggestions().unwrap_or_default() {
+            self.suggestion(f, &s)?;
+        }
+
+        for rel in diag.relatives().unwrap_or_default() {
+            self.note(f, rel.as_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Show a relative of a diagnostic as a note attached to it.
+    fn note(&self, f: &mut dyn fmt::Write, rel: &dyn Diagnostic) -> fmt::Result {
+        writeln!(f)?;
+        writeln!(f, "  {}: {}", "note".style(self.theme.styles.keyword), rel)?;
+        if let Some(reason) = rel.reason() {
+            let opts = textwrap::Options::new(80)
+                .initial_indent("  ")
+                .subsequent_indent("  ");
+            writeln!(f, "{}", textwrap::fill(&reason.to_string(), opts))?;
+        }
+        if rel.loc().is_some() || rel.labels().is_some() {
+            self.context(f, rel)?;
+        }
+        self.footer(f, rel)
+    }
+
+    /// Show a fix: its message and, if it changes a single line, the line once fixed.
+    fn suggestion(&self, f: &mut dyn fmt::Write, s: &Suggestion) -> fmt::Result {
+        writeln!(
+            f,
+            "  {}: {}",
+            "suggestion".style(self.theme.styles.keyword),
+            s.msg
+        )?;
+
+        let Some(first) = s.edits.first() else {
+            return Ok(());
+        };
+        let sm = sman();
+        let content = sm.content(first.file);
+        let (Some(before), Some(after)) = (content.get(..first.start), content.get(first.end..))
+        else {
+            return Ok(());
+        };
+        let start = before.rfind('\n').map_or(0, |i| i + 1);
+        let end = after.find('\n').map_or(content.len(), |i| first.end + i);
+        if s.edits
+            .iter()
+            .any(|e| e.file != first.file || e.start < start || e.end > end)
+        {
+            return Ok(());
+        }
+        let mut edits: Vec<&Edit> = s.edits.iter().collect();
+        edits.sort_by_key(|e| e.start);
+
+        // The fixed line, and under it a '+' for every character the fix adds.
+        let mut line = String::new();
+        let mut marks = String::new();
+        let mut at = start;
+        for e in edits {
+            if e.start < at {
+                continue;
+            }
+            for c in content[at..e.start].chars() {
+                line.push(c);
+                marks.push(if c.is_whitespace() { c } else { ' ' });
+            }
+            line.push_str(&e.text);
+            marks.extend(e.text.chars().map(|_| '+'));
+            at = e.end;
+        }
+        line.push_str(&content[at..end]);
+
+        let charset = &self.theme.characters;
+        let lineno = sm.presumed(first.file, first.start).line;
+        let width = lineno.to_string().len();
+        self.write_linum(f, width, lineno, charset)?;
+        writeln!(f, "{}", line)?;
+        self.write_no_linum(f, width, charset)?;
+        writeln!(f, "{}", marks.trim_end().style(self.theme.styles.addition))?;
         Ok(())
     }
 
@@ -324,7 +733,7 @@ impl DefaultDiagnosticsManager {
             }
         }
         self.write_no_linum(f, ln_indent, charset)?;
-        writeln!(f, "{}", fline.red().bold())?;
+        writeln!(f, "{}", fline.style(self.theme.styles.highlight))?;
         for line in 0..n {
             let mut label_line = String::new();
             let mut col = 1;
@@ -380,7 +789,7 @@ impl DefaultDiagnosticsManager {
                 }
             }
             self.write_no_linum(f, ln_indent, charset)?;
-            writeln!(f, "{}", label_line.red().bold())?;
+            writeln!(f, "{}", label_line.style(self.theme.styles.highlight))?;
         }
 
         Ok(())
@@ -390,16 +799,19 @@ impl DefaultDiagnosticsManager {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::diagnostic::err::LabeledSpan;
+    use crate::diagnostic::err::{LabeledSpan, Note};
 
-    use crate::source::loc::{DefaultLocation, Location};
+    use crate::source::loc::{DefaultLocation, Location, Span};
+    use crate::source::{FileId, SRCMAN};
     use std::fmt::Display;
 
     use thiserror::Error;
 
     #[derive(Error, Debug)]
     #[error("incompatible types")]
-    struct FakeTypeError {}
+    struct FakeTypeError {
+        file: FileId,
+    }
 
     impl Diagnostic for FakeTypeError {
         fn severity(&self) -> Option<Severity> {
@@ -411,11 +823,10 @@ mod test {
         }
 
         fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+            // Line 10, column 7.
             Some(Box::new(DefaultLocation {
-                line: 10,
-                col: 7,
-                index: 0,
-                source_name: "bad_file.l".into(),
+                file: self.file,
+                index: 15,
             }))
         }
 
@@ -429,22 +840,26 @@ mod test {
                 msg: Some("int".into()),
                 start: 5,
                 end: 5,
+                span: None,
             });
             v.push(LabeledSpan {
                 msg: Some("cannot use operator '+' on these operands".into()),
                 start: 7,
                 end: 7,
+                span: None,
             });
             v.push(LabeledSpan {
                 msg: Some("string".into()),
                 start: 9,
                 end: 23,
+                span: None,
             });
 
             v.push(LabeledSpan {
                 msg: Some("string".into()),
                 start: 24,
                 end: 43,
+                span: None,
             });
 
             Some(Box::new(v.into_iter()))
@@ -457,12 +872,247 @@ mod test {
         fn help<'a>(&self) -> Option<Box<dyn Display + 'a>> {
             Some(Box::new("Using '+' operator on a string and a non-string does not append the integer value to the string."))
         }
+
+        fn suggestions(&self) -> Option<Vec<Suggestion>> {
+            Some(vec![Suggestion {
+                msg: "make the integer a string".into(),
+                edits: vec![Edit {
+                    file: self.file,
+                    start: 13,
+                    end: 14,
+                    text: "\"4\"".into(),
+                }],
+                applicability: crate::diagnostic::fix::Applicability::MaybeIncorrect,
+            }])
+        }
     }
 
     #[test]
     fn def_dman() {
-        let mut dman = DefaultDiagnosticsManager {};
-        let f = FakeTypeError {};
+        let mut dman = DefaultDiagnosticsManager::default();
+        let src = "\n".repeat(9) + "x = 4 + \"this_is_a_str\";\n";
+        let file = SRCMAN.write().unwrap().add_str(&src, "bad_file.l".into());
+        let f = FakeTypeError { file };
         dman.publish(Box::new(f));
     }
+
+    #[derive(Error, Debug)]
+    #[error("conflicting types for f")]
+    struct Conflict {
+        def: Span,
+        decl: Span,
+    }
+
+    impl Diagnostic for Conflict {
+        fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+            Some(Box::new(self.def.loc()))
+        }
+
+        fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+            Some(Box::new(
+                vec![LabeledSpan::new(self.def, Some("defined here".into()))].into_iter(),
+            ))
+        }
+
+        fn relatives(&self) -> Option<Vec<Box<dyn Diagnostic>>> {
+            Some(vec![Box::new(Note {
+                msg: "f is declared in f.h".into(),
+                span: Some(self.decl),
+                label: Some("declared here".into()),
+            })])
+        }
+    }
+
+    /// The text of rendered diagnostics, without colors and trailing blanks.
+    fn plain(s: &str) -> String {
+        let mut out = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                out.push(c);
+            }
+        }
+        out.lines().map(|l| format!("{}\n", l.trim_end())).collect()
+    }
+
+    #[test]
+    fn snippets() {
+        let mut sm = SRCMAN.write().unwrap();
+        let def = sm.add_str("int\nf(int a)\n{\n\treturn a;\n}\n", "f.l".into());
+        let decl = sm.add_str("int f(void);\n", "f.h".into());
+        drop(sm);
+        let diag = Conflict {
+            def: Span {
+                file: def,
+                start: 4,
+                end: 27,
+            },
+            decl: Span {
+                file: decl,
+                start: 4,
+                end: 11,
+            },
+        };
+
+        let dman = DefaultDiagnosticsManager::new(Theme::unicode());
+        let mut out = String::new();
+        dman.context(&mut out, &diag).unwrap();
+        dman.footer(&mut out, &diag).unwrap();
+        let want = [
+            "   ╭─ f.l:2:1",
+            " 2 │ f(int a)",
+            "   · ────────",
+            " 3 │ {",
+            " 4 │ \treturn a;",
+            " 5 │ }",
+            "   · ┬",
+            "   · ╰─ defined here",
+            "   ╰───",
+            "",
+            "  note: f is declared in f.h",
+            "   ╭─ f.h:1:5",
+            " 1 │ int f(void);",
+            "   ·     ───┬───",
+            "   ·        ╰─ declared here",
+            "   ╰───",
+        ];
+        assert_eq!(plain(&out), want.join("\n") + "\n");
+    }
+
+    #[derive(Error, Debug)]
+    #[error("unused value")]
+    struct UnusedValue {
+        file: FileId,
+    }
+
+    impl Diagnostic for UnusedValue {
+        fn severity(&self) -> Option<Severity> {
+            Some(Severity::Warning)
+        }
+
+        fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+            Some(Box::new("test::UnusedValue"))
+        }
+
+        fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+            Some(Box::new(DefaultLocation {
+                file: self.file,
+                index: 0,
+            }))
+        }
+
+        fn context(&self) -> Option<String> {
+            Some("x;".into())
+        }
+
+        fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+            Some(Box::new(
+                vec![LabeledSpan {
+                    msg: Some("this value is discarded".into()),
+                    start: 1,
+                    end: 1,
+                    span: None,
+                }]
+                .into_iter(),
+            ))
+        }
+
+        fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+            Some(Box::new("A value is computed but never used."))
+        }
+
+        fn suggestions(&self) -> Option<Vec<Suggestion>> {
+            let at = DefaultLocation {
+                file: self.file,
+                index: 0,
+            };
+            Some(vec![Suggestion {
+                msg: "assign it to `_`".into(),
+                edits: vec![Edit::insert(&at, "_ = ").unwrap()],
+                applicability: crate::diagnostic::fix::Applicability::MaybeIncorrect,
+            }])
+        }
+    }
+
+    #[test]
+    fn themes() {
+        let file = SRCMAN.write().unwrap().add_str("x;\n", "themes.l".into());
+        let render = |theme| {
+            let mut out = String::new();
+            DefaultDiagnosticsManager::new(theme)
+                .render(&mut out, &UnusedValue { file })
+                .unwrap();
+            out
+        };
+
+        let want = [
+            "",
+            "\x1b[33;1mwarning\x1b[0m \x1b[1mtest::UnusedValue\x1b[0m: \x1b[4munused value\x1b[0m",
+            "",
+            "\x1b[33;1m⚠\x1b[0m A value is computed but never used.",
+            "",
+            "   ╭─ themes.l:1:1",
+            " \x1b[1m1\x1b[0m │ x;",
+            "   · \x1b[31;1m┬\x1b[0m",
+            "   · \x1b[31;1m╰─ this value is discarded\x1b[0m",
+            "   ╰───",
+            "  \x1b[1msuggestion\x1b[0m: assign it to `_`",
+            " \x1b[1m1\x1b[0m │ _ = x;",
+            "   · \x1b[32;1m++++\x1b[0m",
+        ];
+        assert_eq!(render(Theme::unicode()), want.join("\n") + "\n");
+
+        let want = [
+            "",
+            "warning test::UnusedValue: unused value",
+            "",
+            "⚠ A value is computed but never used.",
+            "",
+            "   ╭─ themes.l:1:1",
+            " 1 │ x;",
+            "   · ┬",
+            "   · ╰─ this value is discarded",
+            "   ╰───",
+            "  suggestion: assign it to `_`",
+            " 1 │ _ = x;",
+            "   · ++++",
+        ];
+        assert_eq!(render(Theme::unicode_nocolor()), want.join("\n") + "\n");
+
+        let want = [
+            "",
+            "\x1b[33;1mwarning\x1b[0m \x1b[1mtest::UnusedValue\x1b[0m: \x1b[4munused value\x1b[0m",
+            "",
+            "\x1b[33;1m!\x1b[0m A value is computed but never used.",
+            "",
+            "   ,- themes.l:1:1",
+            " \x1b[1m1\x1b[0m | x;",
+            "   : \x1b[31;1m|\x1b[0m",
+            "   : \x1b[31;1m`- this value is discarded\x1b[0m",
+            "   `---",
+            "  \x1b[1msuggestion\x1b[0m: assign it to `_`",
+            " \x1b[1m1\x1b[0m | _ = x;",
+            "   : \x1b[32;1m++++\x1b[0m",
+        ];
+        assert_eq!(render(Theme::ascii()), want.join("\n") + "\n");
+
+        let want = [
+            "",
+            "warning test::UnusedValue: unused value",
+            "",
+            "! A value is computed but never used.",
+            "",
+            "   ,- themes.l:1:1",
+            " 1 | x;",
+            "   : |",
+            "   : `- this value is discarded",
+            "   `---",
+            "  suggestion: assign it to `_`",
+            " 1 | _ = x;",
+            "   : ++++",
+        ];
+        assert_eq!(render(Theme::none()), want.join("\n") + "\n");
+    }
 }
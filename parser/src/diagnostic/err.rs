@@ -1,6 +1,10 @@
-use crate::source::loc::Location;
+use crate::diagnostic::category::Category;
+use crate::diagnostic::fix::Suggestion;
+use crate::source::loc::{Location, Span};
 use std::fmt::Display;
+use thiserror::Error;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Severity {
     Fatal,
     Error,
@@ -8,11 +12,43 @@ pub enum Severity {
     Info,
 }
 
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Severity::Fatal => "fatal",
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A label of a diagnostic: a message attached to a part of the source.
 #[derive(Debug)]
 pub struct LabeledSpan {
     pub msg: Option<String>,
+
+    /// The first and the last column of the label on the context line of the diagnostic, for
+    /// labels without a span.
     pub start: usize,
     pub end: usize,
+
+    /// The part of a source the label points at, which may be in another file than the
+    /// diagnostic and span several lines.
+    pub span: Option<Span>,
+}
+
+impl LabeledSpan {
+    /// A label pointing at `span`.
+    pub fn new(span: Span, msg: Option<String>) -> LabeledSpan {
+        LabeledSpan {
+            msg,
+            start: 0,
+            end: 0,
+            span: Some(span),
+        }
+    }
 }
 
 // The handling of diagnostic messages in AF takes inspiration from zkat's miette library and
@@ -30,7 +66,13 @@ pub trait Diagnostic: std::error::Error {
         None
     }
 
-    /// A unique diagnostic code such as `foo::bar::baz` or `EO123`.
+    /// The category of a warning, which decides whether it is published. Warnings without one
+    /// are always published.
+    fn category(&self) -> Option<Category> {
+        None
+    }
+
+    /// A unique diagnostic code such as `E0102`, one of those of the `codes` registry.
     fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
         None
     }
@@ -45,6 +87,7 @@ pub trait Diagnostic: std::error::Error {
         None
     }
 
+    /// Messages attached to parts of the context line, or to spans of any source.
     fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
         None
     }
@@ -59,8 +102,45 @@ pub trait Diagnostic: std::error::Error {
         None
     }
 
-    /// Relative diagnostic messages.
+    /// Relative diagnostic messages, shown as notes attached to this one.
     fn relatives(&self) -> Option<Vec<Box<dyn Diagnostic>>> {
         None
     }
+
+    /// Fixes of the fault, which `alef-check fix` applies if they are machine-applicable.
+    fn suggestions(&self) -> Option<Vec<Suggestion>> {
+        None
+    }
+}
+
+/// A note attached to a diagnostic as one of its relatives, e.g. pointing at an earlier
+/// declaration of what the diagnostic is about.
+#[derive(Error, Debug, Clone)]
+#[error("{msg}")]
+pub struct Note {
+    pub msg: String,
+
+    /// What the note points at.
+    pub span: Option<Span>,
+
+    /// The label of the span.
+    pub label: Option<String>,
+}
+
+impl Diagnostic for Note {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Info)
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        self.span
+            .map(|s| Box::new(s.loc()) as Box<dyn Location + 'a>)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+        let span = self.span?;
+        Some(Box::new(
+            vec![LabeledSpan::new(span, self.label.clone())].into_iter(),
+        ))
+    }
 }
@@ -0,0 +1,151 @@
+//! Fix-its: the edits of a source a diagnostic suggests to fix the fault it reports. The text
+//! renderer shows them under the diagnostic, and `alef-check fix` applies the machine-applicable
+//! ones to the files in place.
+//!
+//! Edits are byte ranges of the source the diagnostic was reported in, which is usually the
+//! output of the preprocessor; `by_file` follows them back to the files they were read from.
+//! Edits falling in a macro expansion cannot be applied and are dropped.
+
+use crate::source::loc::Location;
+use crate::source::sman::{sman, FileId};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result};
+
+/// How sure a suggestion is to fix the fault without changing what the program means.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Applicability {
+    /// The suggestion is certainly right and can be applied without review.
+    MachineApplicable,
+
+    /// The suggestion is likely what was meant, but it must be reviewed.
+    MaybeIncorrect,
+}
+
+impl Display for Applicability {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let s = match self {
+            Applicability::MachineApplicable => "machine-applicable",
+            Applicability::MaybeIncorrect => "maybe-incorrect",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// The replacement of the bytes `start..end` of a source with `text`: insertions have an empty
+/// range and deletions an empty text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+impl Edit {
+    /// Replace the `len` bytes from `loc` with `text`, if `loc` is in a source.
+    pub fn replace(loc: &dyn Location, len: usize, text: &str) -> Option<Edit> {
+        let start = loc.get_mbuf_index();
+        Some(Edit {
+            file: loc.get_file()?,
+            start,
+            end: start + len,
+            text: text.to_string(),
+        })
+    }
+
+    /// Insert `text` at `loc`, if `loc` is in a source.
+    pub fn insert(loc: &dyn Location, text: &str) -> Option<Edit> {
+        Edit::replace(loc, 0, text)
+    }
+
+    /// Delete the `len` bytes from `loc`, if `loc` is in a source.
+    pub fn delete(loc: &dyn Location, len: usize) -> Option<Edit> {
+        Edit::replace(loc, len, "")
+    }
+}
+
+/// A fix suggested by a diagnostic.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// What the fix does, e.g. "insert `;`".
+    pub msg: String,
+
+    /// The edits making the fix, which must not overlap.
+    pub edits: Vec<Edit>,
+
+    pub applicability: Applicability,
+}
+
+/// Apply `edits`, which all refer to `content`, and return the new content and the number of
+/// edits applied. Edits overlapping an earlier one, and repeated ones, are skipped.
+pub fn apply(content: &str, edits: &[Edit]) -> (String, usize) {
+    let mut edits: Vec<&Edit> = edits.iter().collect();
+    edits.sort_by_key(|e| (e.start, e.end));
+    edits.dedup();
+
+    let mut out = String::with_capacity(content.len());
+    let mut at = 0;
+    let mut applied = 0;
+    for e in edits {
+        if e.start < at || e.end > content.len() {
+            continue;
+        }
+        out.push_str(&content[at..e.start]);
+        out.push_str(&e.text);
+        at = e.end;
+        applied += 1;
+    }
+    out.push_str(&content[at..]);
+    (out, applied)
+}
+
+/// Move `edits` to the files they were read from, keyed by file name. Edits that do not map
+/// back to a file read from disk, or whose bytes differ there, are dropped.
+pub fn by_file(edits: &[Edit]) -> BTreeMap<String, Vec<Edit>> {
+    let sm = sman();
+    let mut files: BTreeMap<String, Vec<Edit>> = BTreeMap::new();
+    for e in edits {
+        let Some((file, start)) = sm.spelling(e.file, e.start) else {
+            continue;
+        };
+        let end = start + (e.end - e.start);
+        let name = sm.name(file);
+        if sm.lookup(name) != Some(file)
+            || sm.content(file).get(start..end) != sm.content(e.file).get(e.start..e.end)
+        {
+            continue;
+        }
+        files.entry(name.to_string()).or_default().push(Edit {
+            file,
+            start,
+            end,
+            text: e.text.clone(),
+        });
+    }
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::loc::DefaultLocation;
+    use crate::source::SRCMAN;
+
+    #[test]
+    fn edits() {
+        let src = "import io\nx = (1];\nL\"a\";\n";
+        let file = SRCMAN.write().unwrap().add_str(src, "edits.l".into());
+        let at = |index| DefaultLocation { file, index };
+        let edits = vec![
+            Edit::replace(&at(16), 1, ")").unwrap(),
+            Edit::insert(&at(9), ";").unwrap(),
+            Edit::insert(&at(9), ";").unwrap(),
+            Edit::replace(&at(19), 1, "$").unwrap(),
+            // Overlaps the previous one.
+            Edit::delete(&at(19), 2).unwrap(),
+        ];
+        let (out, applied) = apply(src, &edits);
+        assert_eq!(out, "import io;\nx = (1);\n$\"a\";\n");
+        assert_eq!(applied, 3);
+    }
+}
@@ -0,0 +1,225 @@
+//! Diagnostics as JSON, for editors and CI systems. Every diagnostic is printed to stderr as a
+//! single line holding an object of the form
+//!
+//! ```text
+//! {"severity":"error","code":"E0102","message":"incompatible types",
+//!  "location":{"file":"bad_file.l","line":10,"column":7},
+//!  "labels":[{"message":"int","start":5,"end":5,"span":null}],
+//!  "reason":"operands must coerce to the same type.","help":null,"relatives":[],
+//!  "suggestions":[{"message":"insert `;`","applicability":"machine-applicable",
+//!   "edits":[{"start":{"file":"bad_file.l","line":9,"column":10},
+//!             "end":{"file":"bad_file.l","line":9,"column":10},"text":";"}]}]}
+//! ```
+//!
+//! where the parts a diagnostic does not have are `null`, or empty arrays for labels,
+//! relatives and suggestions. Relatives are objects of the same form. The `start` and `end` of a
+//! label are the columns of the source context of the diagnostic, as the text output underlines
+//! them, unless the label points at a span of a source: then `span` holds the `start` and `end`
+//! locations of the span, which may be in another file.
+
+use crate::diagnostic::dman::DiagnosticsManager;
+use crate::diagnostic::err::{Diagnostic, Severity};
+use crate::diagnostic::fix::Suggestion;
+use crate::source::loc::{DefaultLocation, Location};
+use crate::source::sman::sman;
+use serde_json::{json, Value};
+
+/// A manager printing every diagnostic as a line of JSON.
+pub struct JsonDiagnosticsManager {}
+
+impl DiagnosticsManager for JsonDiagnosticsManager {
+    fn publish(&mut self, diag: Box<dyn Diagnostic>) {
+        eprintln!("{}", to_json(diag.as_ref()));
+    }
+}
+
+/// The file, line and column of a location, taking line markers into account, or null if the
+/// location is not in a source.
+pub fn location(loc: &dyn Location) -> Value {
+    match loc.get_file() {
+        Some(file) => {
+            let sm = sman();
+            let p = sm.presumed(file, loc.get_mbuf_index());
+            json!({ "file": p.name, "line": p.line, "column": p.col })
+        }
+        None => Value::Null,
+    }
+}
+
+/// Serialize a suggestion, with the locations its edits start and end at.
+pub fn suggestion(s: &Suggestion) -> Value {
+    let edits: Vec<Value> = s
+        .edits
+        .iter()
+        .map(|e| {
+            let at = |index| {
+                location(&DefaultLocation {
+                    file: e.file,
+                    index,
+                })
+            };
+            json!({ "start": at(e.start), "end": at(e.end), "text": e.text })
+        })
+        .collect();
+    json!({
+        "message": s.msg,
+        "applicability": s.applicability.to_string(),
+        "edits": edits,
+    })
+}
+
+/// Serialize a diagnostic and its relatives.
+pub fn to_json(diag: &dyn Diagnostic) -> Value {
+    let labels: Vec<Value> = diag
+        .labels()
+        .map(|labels| {
+            labels
+                .map(|l| {
+                    let span = l.span.map(|s| {
+                        let at = |index| {
+                            location(&DefaultLocation {
+                                file: s.file,
+                                index,
+                            })
+                        };
+                        json!({ "start": at(s.start), "end": at(s.end) })
+                    });
+                    json!({ "message": l.msg, "start": l.start, "end": l.end, "span": span })
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let relatives: Vec<Value> = diag
+        .relatives()
+        .unwrap_or_default()
+        .iter()
+        .map(|r| to_json(r.as_ref()))
+        .collect();
+    let suggestions: Vec<Value> = diag
+        .suggestions()
+        .unwrap_or_default()
+        .iter()
+        .map(suggestion)
+        .collect();
+
+    json!({
+        "severity": diag.severity().unwrap_or(Severity::Error).to_string(),
+        "code": diag.code().map(|c| c.to_string()),
+        "message": diag.to_string(),
+        "location": diag.loc().map(|l| location(l.as_ref())).unwrap_or(Value::Null),
+        "labels": labels,
+        "reason": diag.reason().map(|r| r.to_string()),
+        "help": diag.help().map(|h| h.to_string()),
+        "relatives": relatives,
+        "suggestions": suggestions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::err::LabeledSpan;
+    use crate::diagnostic::fix::{Applicability, Edit};
+    use crate::source::{FileId, SRCMAN};
+    use std::fmt::Display;
+    use thiserror::Error;
+
+    #[derive(Error, Debug)]
+    #[error("unused variable")]
+    struct Unused {
+        file: FileId,
+    }
+
+    impl Diagnostic for Unused {
+        fn severity(&self) -> Option<Severity> {
+            Some(Severity::Warning)
+        }
+
+        fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+            Some(Box::new(DefaultLocation {
+                file: self.file,
+                index: 6,
+            }))
+        }
+
+        fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+            Some(Box::new(
+                vec![LabeledSpan {
+                    msg: Some("never read".into()),
+                    start: 5,
+                    end: 5,
+                    span: None,
+                }]
+                .into_iter(),
+            ))
+        }
+
+        fn help<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+            Some(Box::new("remove it"))
+        }
+
+        fn relatives(&self) -> Option<Vec<Box<dyn Diagnostic>>> {
+            Some(vec![Box::new(Declared {})])
+        }
+
+        fn suggestions(&self) -> Option<Vec<Suggestion>> {
+            Some(vec![Suggestion {
+                msg: "remove it".into(),
+                edits: vec![Edit {
+                    file: self.file,
+                    start: 5,
+                    end: 11,
+                    text: "".into(),
+                }],
+                applicability: Applicability::MaybeIncorrect,
+            }])
+        }
+    }
+
+    #[derive(Error, Debug)]
+    #[error("declared here")]
+    struct Declared {}
+
+    impl Diagnostic for Declared {}
+
+    #[test]
+    fn serialize() {
+        let file = SRCMAN
+            .write()
+            .unwrap()
+            .add_str("void\nint x;\n", "unused.l".into());
+        let v = to_json(&Unused { file });
+        assert_eq!(
+            v,
+            json!({
+                "severity": "warning",
+                "code": null,
+                "message": "unused variable",
+                "location": { "file": "unused.l", "line": 2, "column": 2 },
+                "labels": [{ "message": "never read", "start": 5, "end": 5, "span": null }],
+                "reason": null,
+                "help": "remove it",
+                "relatives": [{
+                    "severity": "error",
+                    "code": null,
+                    "message": "declared here",
+                    "location": null,
+                    "labels": [],
+                    "reason": null,
+                    "help": null,
+                    "relatives": [],
+                    "suggestions": [],
+                }],
+                "suggestions": [{
+                    "message": "remove it",
+                    "applicability": "maybe-incorrect",
+                    "edits": [{
+                        "start": { "file": "unused.l", "line": 2, "column": 1 },
+                        "end": { "file": "unused.l", "line": 2, "column": 7 },
+                        "text": "",
+                    }],
+                }],
+            })
+        );
+    }
+}
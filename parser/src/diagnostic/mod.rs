@@ -1,9 +1,32 @@
+pub mod category;
+pub mod codes;
+pub mod ctx;
 pub mod dman;
 pub mod err;
+pub mod fix;
+pub mod json;
+pub mod sarif;
 
-use dman::DIAGMAN;
+use ctx::{DiagnosticContext, DIAGCTX};
+use dman::DiagnosticsManager;
 use err::Diagnostic;
+use std::sync::MutexGuard;
 
 pub fn diag(diag: Box<dyn Diagnostic>) {
-    DIAGMAN.lock().as_mut().unwrap().publish(diag);
+    context().publish(diag);
+}
+
+/// The diagnostic context of the current compilation.
+pub fn context() -> MutexGuard<'static, DiagnosticContext> {
+    DIAGCTX.lock().unwrap()
+}
+
+/// Replace the manager diagnostics are published to.
+pub fn set_manager(dman: Box<dyn DiagnosticsManager + Sync + Send>) {
+    context().set_manager(dman);
+}
+
+/// Tell the manager that no more diagnostics will be published.
+pub fn finish() {
+    context().finish();
 }
@@ -0,0 +1,279 @@
+//! Diagnostics as a SARIF 2.1.0 log, the format code scanning services read. Unlike the other
+//! managers, this one holds every diagnostic back and prints a single log to stderr when it is
+//! finished.
+//!
+//! Each diagnostic is a result: its code is the rule, its severity the level (fatal and error
+//! are `error`, info is `note`), and its message is followed by its reason and help. Labels and
+//! relatives become related locations carrying their messages, and suggestions become fixes.
+
+use crate::diagnostic::dman::DiagnosticsManager;
+use crate::diagnostic::err::{Diagnostic, Severity};
+use crate::diagnostic::json;
+use crate::source::loc::DefaultLocation;
+use serde_json::{json, Map, Value};
+
+/// The schema of the logs.
+pub const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// A manager collecting diagnostics into a SARIF log.
+pub struct SarifDiagnosticsManager {
+    tool: String,
+    version: String,
+    results: Vec<Value>,
+}
+
+impl SarifDiagnosticsManager {
+    /// Create a manager for the tool `tool` at version `version`.
+    pub fn new(tool: &str, version: &str) -> SarifDiagnosticsManager {
+        SarifDiagnosticsManager {
+            tool: tool.to_string(),
+            version: version.to_string(),
+            results: vec![],
+        }
+    }
+
+    /// The log of the diagnostics published so far.
+    pub fn log(&self) -> Value {
+        json!({
+            "$schema": SCHEMA,
+            "version": "2.1.0",
+            "runs": [{
+                "tool": { "driver": { "name": self.tool, "version": self.version } },
+                "results": self.results,
+            }],
+        })
+    }
+}
+
+impl DiagnosticsManager for SarifDiagnosticsManager {
+    fn publish(&mut self, diag: Box<dyn Diagnostic>) {
+        self.results.push(result(diag.as_ref()));
+    }
+
+    fn finish(&mut self) {
+        eprintln!("{:#}", self.log());
+    }
+}
+
+/// A SARIF physical location from a location of `json::location`, with `region` added to it.
+fn physical(loc: &Value, region: Map<String, Value>) -> Option<Value> {
+    let mut r = Map::new();
+    r.insert("startLine".into(), loc.get("line")?.clone());
+    r.insert("startColumn".into(), loc.get("column")?.clone());
+    r.extend(region);
+    Some(json!({
+        "artifactLocation": { "uri": loc.get("file")? },
+        "region": r,
+    }))
+}
+
+/// A SARIF fix from a suggestion of `json::suggestion`, with a change per edit.
+fn fix(s: &Value) -> Value {
+    let mut changes = vec![];
+    for e in s["edits"].as_array().into_iter().flatten() {
+        let (start, end) = (&e["start"], &e["end"]);
+        changes.push(json!({
+            "artifactLocation": { "uri": start["file"] },
+            "replacements": [{
+                "deletedRegion": {
+                    "startLine": start["line"],
+                    "startColumn": start["column"],
+                    "endLine": end["line"],
+                    "endColumn": end["column"],
+                },
+                "insertedContent": { "text": e["text"] },
+            }],
+        }));
+    }
+    json!({ "description": { "text": s["message"] }, "artifactChanges": changes })
+}
+
+fn result(diag: &dyn Diagnostic) -> Value {
+    let level = match diag.severity() {
+        Some(Severity::Fatal) | Some(Severity::Error) | None => "error",
+        Some(Severity::Warning) => "warning",
+        Some(Severity::Info) => "note",
+    };
+
+    let mut text = diag.to_string();
+    if let Some(reason) = diag.reason() {
+        text.push_str(&format!("\n{}", reason));
+    }
+    if let Some(help) = diag.help() {
+        text.push_str(&format!("\nhelp: {}", help));
+    }
+
+    let loc = diag
+        .loc()
+        .map(|l| json::location(l.as_ref()))
+        .unwrap_or(Value::Null);
+
+    let mut related = vec![];
+    for l in diag.labels().into_iter().flatten() {
+        let Some(msg) = l.msg else { continue };
+        let mut r = json!({ "id": related.len(), "message": { "text": msg } });
+        let p = match l.span {
+            // Spans end after their last byte, as SARIF regions do.
+            Some(s) => {
+                let start = json::location(&s.loc());
+                let end = json::location(&DefaultLocation {
+                    file: s.file,
+                    index: s.end,
+                });
+                let mut region = Map::new();
+                region.insert("endLine".into(), end["line"].clone());
+                region.insert("endColumn".into(), end["column"].clone());
+                physical(&start, region)
+            }
+            None => {
+                let mut region = Map::new();
+                region.insert("startColumn".into(), l.start.into());
+                region.insert("endColumn".into(), (l.end + 1).into());
+                physical(&loc, region)
+            }
+        };
+        if let Some(p) = p {
+            r["physicalLocation"] = p;
+        }
+        related.push(r);
+    }
+    for rel in diag.relatives().unwrap_or_default() {
+        let mut r = json!({ "id": related.len(), "message": { "text": rel.to_string() } });
+        let rloc = rel
+            .loc()
+            .map(|l| json::location(l.as_ref()))
+            .unwrap_or(Value::Null);
+        if let Some(p) = physical(&rloc, Map::new()) {
+            r["physicalLocation"] = p;
+        }
+        related.push(r);
+    }
+
+    let mut r = json!({ "level": level, "message": { "text": text } });
+    if let Some(code) = diag.code() {
+        r["ruleId"] = code.to_string().into();
+    }
+    if let Some(p) = physical(&loc, Map::new()) {
+        r["locations"] = json!([{ "physicalLocation": p }]);
+    }
+    if !related.is_empty() {
+        r["relatedLocations"] = related.into();
+    }
+    let fixes: Vec<Value> = diag
+        .suggestions()
+        .unwrap_or_default()
+        .iter()
+        .map(|s| fix(&json::suggestion(s)))
+        .collect();
+    if !fixes.is_empty() {
+        r["fixes"] = fixes.into();
+    }
+    r
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::err::LabeledSpan;
+    use crate::diagnostic::fix::{Applicability, Edit, Suggestion};
+    use crate::source::loc::Location;
+    use crate::source::{FileId, SRCMAN};
+    use std::fmt::Display;
+    use thiserror::Error;
+
+    #[derive(Error, Debug)]
+    #[error("incompatible types")]
+    struct Mismatch {
+        file: FileId,
+    }
+
+    impl Diagnostic for Mismatch {
+        fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+            Some(Box::new("mismatch"))
+        }
+
+        fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+            Some(Box::new(DefaultLocation {
+                file: self.file,
+                index: 0,
+            }))
+        }
+
+        fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+            Some(Box::new(
+                vec![LabeledSpan {
+                    msg: Some("string".into()),
+                    start: 5,
+                    end: 7,
+                    span: None,
+                }]
+                .into_iter(),
+            ))
+        }
+
+        fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+            Some(Box::new("operands must coerce to the same type."))
+        }
+
+        fn suggestions(&self) -> Option<Vec<Suggestion>> {
+            Some(vec![Suggestion {
+                msg: "make the integer a string".into(),
+                edits: vec![Edit {
+                    file: self.file,
+                    start: 4,
+                    end: 5,
+                    text: "\"4\"".into(),
+                }],
+                applicability: Applicability::MaybeIncorrect,
+            }])
+        }
+    }
+
+    #[test]
+    fn log() {
+        let file = SRCMAN
+            .write()
+            .unwrap()
+            .add_str("x = 4 + \"s\";\n", "mismatch.l".into());
+        let mut dman = SarifDiagnosticsManager::new("alef-check", "0.1.0");
+        dman.publish(Box::new(Mismatch { file }));
+        let log = dman.log();
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(log["runs"][0]["tool"]["driver"]["name"], "alef-check");
+        assert_eq!(
+            log["runs"][0]["results"],
+            json!([{
+                "ruleId": "mismatch",
+                "level": "error",
+                "message": { "text": "incompatible types\noperands must coerce to the same type." },
+                "locations": [{ "physicalLocation": {
+                    "artifactLocation": { "uri": "mismatch.l" },
+                    "region": { "startLine": 1, "startColumn": 1 },
+                }}],
+                "relatedLocations": [{
+                    "id": 0,
+                    "message": { "text": "string" },
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": "mismatch.l" },
+                        "region": { "startLine": 1, "startColumn": 5, "endColumn": 8 },
+                    },
+                }],
+                "fixes": [{
+                    "description": { "text": "make the integer a string" },
+                    "artifactChanges": [{
+                        "artifactLocation": { "uri": "mismatch.l" },
+                        "replacements": [{
+                            "deletedRegion": {
+                                "startLine": 1,
+                                "startColumn": 5,
+                                "endLine": 1,
+                                "endColumn": 6,
+                            },
+                            "insertedContent": { "text": "\"4\"" },
+                        }],
+                    }],
+                }],
+            }])
+        );
+    }
+}
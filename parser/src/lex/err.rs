@@ -1,5 +1,9 @@
-use crate::diagnostic::err::{Diagnostic, LabeledSpan};
+use crate::diagnostic::category::Category;
+use crate::diagnostic::codes;
+use crate::diagnostic::err::{Diagnostic, LabeledSpan, Note, Severity};
+use crate::diagnostic::fix::Suggestion;
 use crate::source::loc::{Location, Range};
+use crate::source::FileId;
 use std::fmt::Display;
 use thiserror::Error;
 
@@ -7,8 +11,8 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 #[error("stray symbol {sym:?} in source")]
 pub struct StrayCharError {
-    /// The name of the source generating this read error.
-    pub source_name: String,
+    /// The source generating this error.
+    pub file: FileId,
 
     /// The position where this fault generated.
     pub range: Range,
@@ -22,7 +26,7 @@ pub struct StrayCharError {
 
 impl Diagnostic for StrayCharError {
     fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
-        Some(Box::new(std::any::type_name::<Self>()))
+        Some(Box::new(codes::E0101))
     }
 
     fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
@@ -54,6 +58,7 @@ impl Diagnostic for StrayCharError {
             msg: Some(self.msg.clone()),
             start,
             end,
+            span: None,
         });
 
         Some(Box::new(v.into_iter()))
@@ -64,8 +69,8 @@ impl Diagnostic for StrayCharError {
 #[derive(Error, Debug)]
 #[error("malformed literal")]
 pub struct LiteralError {
-    /// The name of the source generating this read error.
-    pub source_name: String,
+    /// The source generating this error.
+    pub file: FileId,
 
     /// The position where this fault generated.
     pub range: Range,
@@ -78,7 +83,7 @@ pub struct LiteralError {
 
 impl Diagnostic for LiteralError {
     fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
-        Some(Box::new(std::any::type_name::<LiteralError>()))
+        Some(Box::new(codes::E0102))
     }
 
     fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
@@ -107,6 +112,7 @@ impl Diagnostic for LiteralError {
                 msg: Some(format!("symbol '{}'", sym)),
                 start,
                 end,
+                span: None,
             });
         }
 
@@ -118,12 +124,76 @@ impl Diagnostic for LiteralError {
     }
 }
 
+/// Warning about a literal spelled in a deprecated form, such as a runestring with the `L`
+/// prefix of C.
+#[derive(Error, Debug)]
+#[error("deprecated {kind} literal")]
+pub struct DeprecatedLiteralWarning {
+    /// The source generating this warning.
+    pub file: FileId,
+
+    /// The deprecated part of the literal.
+    pub range: Range,
+
+    /// The kind of literal.
+    pub kind: String,
+
+    /// A message regarding the warning.
+    pub msg: String,
+
+    /// The rewrite of the literal in its current form.
+    pub fix: Option<Suggestion>,
+}
+
+impl Diagnostic for DeprecatedLiteralWarning {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+
+    fn category(&self) -> Option<Category> {
+        Some(Category::Deprecated)
+    }
+
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0105))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+
+    fn labels<'a>(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+        let start = self.range.start.get_col();
+        Some(Box::new(
+            vec![LabeledSpan {
+                msg: Some("deprecated prefix".into()),
+                start,
+                end: start,
+                span: None,
+            }]
+            .into_iter(),
+        ))
+    }
+
+    fn suggestions(&self) -> Option<Vec<Suggestion>> {
+        self.fix.clone().map(|s| vec![s])
+    }
+}
+
 /// Error thrown by the source when failing to read from a MemoryBuffer.
 #[derive(Error, Debug)]
 #[error("malformed or unremoved preprocessor directive")]
 pub struct PreprocessorDirectiveError {
-    /// The name of the source generating this read error.
-    pub source_name: String,
+    /// The source generating this error.
+    pub file: FileId,
 
     /// The position where this fault generated.
     pub range: Range,
@@ -134,7 +204,7 @@ pub struct PreprocessorDirectiveError {
 
 impl Diagnostic for PreprocessorDirectiveError {
     fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
-        Some(Box::new(std::any::type_name::<PreprocessorDirectiveError>()))
+        Some(Box::new(codes::E0103))
     }
 
     fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
@@ -154,12 +224,171 @@ impl Diagnostic for PreprocessorDirectiveError {
     }
 }
 
+/// Error or warning of the preprocessor.
+#[derive(Error, Debug)]
+#[error("cannot preprocess source")]
+pub struct PreprocessorError {
+    /// The source generating this error.
+    pub file: FileId,
+
+    /// The position where this fault generated.
+    pub range: Range,
+
+    /// A message regarding the error.
+    pub msg: String,
+
+    /// Whether this is only a warning.
+    pub warning: bool,
+
+    /// Notes pointing at what else is involved, such as an earlier definition.
+    pub notes: Vec<Note>,
+}
+
+impl Diagnostic for PreprocessorError {
+    fn severity(&self) -> Option<Severity> {
+        if self.warning {
+            Some(Severity::Warning)
+        } else {
+            None
+        }
+    }
+
+    fn category(&self) -> Option<Category> {
+        Some(Category::Preprocessor)
+    }
+
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0201))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+
+    fn relatives(&self) -> Option<Vec<Box<dyn Diagnostic>>> {
+        if self.notes.is_empty() {
+            return None;
+        }
+        Some(
+            self.notes
+                .iter()
+                .map(|n| Box::new(n.clone()) as Box<dyn Diagnostic>)
+                .collect(),
+        )
+    }
+}
+
+/// The message of a `#error` or `#warning` directive.
+#[derive(Error, Debug)]
+#[error("{msg}")]
+pub struct DirectiveMessage {
+    /// The source generating this error.
+    pub file: FileId,
+
+    /// The position of the directive.
+    pub range: Range,
+
+    /// The message the directive gives.
+    pub msg: String,
+
+    /// Whether the directive is `#warning`.
+    pub warning: bool,
+}
+
+impl Diagnostic for DirectiveMessage {
+    fn severity(&self) -> Option<Severity> {
+        if self.warning {
+            Some(Severity::Warning)
+        } else {
+            None
+        }
+    }
+
+    fn category(&self) -> Option<Category> {
+        Some(Category::Preprocessor)
+    }
+
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0202))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        let directive = if self.warning { "#warning" } else { "#error" };
+        Some(Box::new(format!("reported by a {} directive", directive)))
+    }
+}
+
+/// A `#pragma alef diagnostic` that is ignored.
+#[derive(Error, Debug)]
+#[error("ignoring #pragma alef diagnostic")]
+pub struct PragmaWarning {
+    /// The source generating this warning.
+    pub file: FileId,
+
+    /// The position of the pragma.
+    pub range: Range,
+
+    /// A message regarding the warning.
+    pub msg: String,
+}
+
+impl Diagnostic for PragmaWarning {
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+
+    fn category(&self) -> Option<Category> {
+        Some(Category::Preprocessor)
+    }
+
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0203))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+
+    fn help<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        let names: Vec<&str> = Category::ALL.iter().map(|c| c.name()).collect();
+        Some(Box::new(format!(
+            "the pragma is `#pragma alef diagnostic ignored|warning \"<category>\"`, with one of \
+             the categories {}",
+            names.join(", ")
+        )))
+    }
+}
+
 /// Error thrown by the source when failing to read from a MemoryBuffer.
 #[derive(Error, Debug)]
 #[error("malformed comment")]
 pub struct CommentError {
-    /// The name of the source generating this read error.
-    pub source_name: String,
+    /// The source generating this error.
+    pub file: FileId,
 
     /// The position where this fault generated.
     pub range: Range,
@@ -168,7 +397,23 @@ pub struct CommentError {
     pub msg: String,
 }
 
-impl Diagnostic for CommentError {}
+impl Diagnostic for CommentError {
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0104))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+}
 
 #[cfg(test)]
 mod tests {}
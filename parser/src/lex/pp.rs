@@ -0,0 +1,1721 @@
+//! The preprocessor reads a source and produces a new one, added to the SourceManager, where
+//! files are included, macros expanded and conditional sections removed, as the C preprocessor
+//! would. The scanner then reads the produced source.
+//!
+//! Text outside of directives and macro invocations is copied verbatim, so that comments and
+//! columns are kept; a macro invocation is replaced by its expansion. Every part of the produced
+//! source records its origin in the SourceManager, so that locations point back to the file the
+//! text came from and, inside a macro expansion, to the invocation of the macro.
+//!
+//! Supported directives are `#include`, `#define`, `#undef`, `#if`, `#ifdef`, `#ifndef`,
+//! `#elif`, `#else`, `#endif`, `#error`, `#warning`, `#line` and `#pragma`; line markers left by
+//! an external preprocessor are recorded as well. The only pragma understood is
+//! `#pragma alef diagnostic ignored|warning "<category>"` (see `diagnostic::category`), the
+//! others are ignored.
+
+use crate::{
+    diagnostic::{self, category::Category, diag, err::Note},
+    lex::err::{DirectiveMessage, PragmaWarning, PreprocessorError},
+    source::{
+        loc::{DefaultLocation, Range, Span},
+        sman::{sman, sman_mut, Expansion, Include, LineMarker, Origin},
+        FileId, MemoryBuffer,
+    },
+};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// The maximum depth of nested includes.
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// Punctuators of more than one character, longest first.
+const PUNCTUATORS: &[&str] = &[
+    "<<=", ">>=", "<-=", "...", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "<-", "->", "++",
+    "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "::", ":=", "##",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    Ident,
+    Number,
+
+    /// A string or character literal.
+    Literal,
+    Punct,
+    Newline,
+    Eof,
+
+    /// The result of an empty argument next to `##`, removed after substitution.
+    Placemarker,
+}
+
+/// A preprocessing token.
+#[derive(Debug, Clone)]
+pub(crate) struct Tok {
+    pub(crate) kind: Kind,
+    pub(crate) text: Rc<str>,
+
+    /// Whether the token is preceded by blanks or comments.
+    space: bool,
+
+    /// The offsets of the token in the source it was read from.
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+
+    /// The macros that must not be expanded again in this token.
+    hide: Rc<Vec<Rc<str>>>,
+}
+
+impl Tok {
+    pub(crate) fn new(kind: Kind, text: &str) -> Tok {
+        Tok {
+            kind,
+            text: text.into(),
+            space: false,
+            start: 0,
+            end: 0,
+            hide: Rc::default(),
+        }
+    }
+
+    pub(crate) fn is(&self, text: &str) -> bool {
+        matches!(self.kind, Kind::Punct | Kind::Ident) && &*self.text == text
+    }
+}
+
+/// Splits a source into preprocessing tokens.
+#[derive(Debug, Clone)]
+pub(crate) struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub(crate) fn new(src: &'a str) -> Lexer<'a> {
+        Lexer { src, pos: 0 }
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.src[self.pos..].chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    /// Skip blanks, comments and escaped newlines and return true if there were any.
+    fn skip_space(&mut self) -> bool {
+        let start = self.pos;
+        loop {
+            match (self.peek(0), self.peek(1)) {
+                (Some(' ' | '\t' | '\r' | '\x0b' | '\x0c'), _) => {
+                    self.bump();
+                }
+                (Some('\\'), Some('\n')) => {
+                    self.pos += 2;
+                }
+                (Some('\\'), Some('\r')) if self.peek(2) == Some('\n') => {
+                    self.pos += 3;
+                }
+                (Some('/'), Some('*')) => {
+                    self.pos = match self.src[self.pos + 2..].find("*/") {
+                        Some(i) => self.pos + 2 + i + 2,
+                        None => self.src.len(),
+                    };
+                }
+                (Some('/'), Some('/')) => {
+                    self.pos = match self.src[self.pos..].find('\n') {
+                        Some(i) => self.pos + i,
+                        None => self.src.len(),
+                    };
+                }
+                _ => return self.pos != start,
+            }
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> Tok {
+        let space = self.skip_space();
+        let start = self.pos;
+        let kind = match self.bump() {
+            None => Kind::Eof,
+            Some('\n') => Kind::Newline,
+            Some(c) if c.is_alphabetic() || c == '_' || !c.is_ascii() => {
+                while let Some(c) = self.peek(0) {
+                    if !(c.is_alphanumeric() || c == '_' || !c.is_ascii()) {
+                        break;
+                    }
+                    self.bump();
+                }
+                Kind::Ident
+            }
+            Some(c)
+                if c.is_ascii_digit()
+                    || (c == '.' && self.peek(0).is_some_and(|c| c.is_ascii_digit())) =>
+            {
+                let mut prev = c;
+                while let Some(c) = self.peek(0) {
+                    let exp = matches!(prev, 'e' | 'E' | 'p' | 'P') && matches!(c, '+' | '-');
+                    if !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || exp) {
+                        break;
+                    }
+                    prev = c;
+                    self.bump();
+                }
+                Kind::Number
+            }
+            Some(q @ ('"' | '\'')) => {
+                while let Some(c) = self.peek(0) {
+                    if c == '\n' {
+                        break;
+                    }
+                    self.bump();
+                    if c == q {
+                        break;
+                    }
+                    if c == '\\' && self.peek(0).is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                Kind::Literal
+            }
+            Some(_) => {
+                let rest = &self.src[start..];
+                if let Some(p) = PUNCTUATORS.iter().find(|p| rest.starts_with(**p)) {
+                    self.pos = start + p.len();
+                }
+                Kind::Punct
+            }
+        };
+        Tok {
+            kind,
+            text: self.src[start..self.pos].into(),
+            space,
+            start,
+            end: self.pos,
+            hide: Rc::default(),
+        }
+    }
+
+    /// The tokens up to the end of the line, which is not consumed.
+    fn line(&mut self) -> Vec<Tok> {
+        let mut toks = Vec::new();
+        loop {
+            let save = self.pos;
+            let t = self.next();
+            if matches!(t.kind, Kind::Newline | Kind::Eof) {
+                self.pos = save;
+                return toks;
+            }
+            toks.push(t);
+        }
+    }
+}
+
+/// Tokens to expand: those pending, then those of the source being preprocessed, if any.
+struct Stream<'l, 's> {
+    pending: VecDeque<Tok>,
+    lexer: Option<&'l mut Lexer<'s>>,
+
+    /// The end of the last token read from the source.
+    end: usize,
+}
+
+impl<'l, 's> Stream<'l, 's> {
+    fn next(&mut self) -> Tok {
+        if let Some(t) = self.pending.pop_front() {
+            return t;
+        }
+        match self.lexer {
+            Some(ref mut lx) => {
+                let t = lx.next();
+                if t.kind != Kind::Eof {
+                    self.end = t.end;
+                }
+                t
+            }
+            None => Tok::new(Kind::Eof, ""),
+        }
+    }
+
+    /// Return true if the next token, after newlines, is a left parenthesis.
+    fn at_paren(&self) -> bool {
+        if let Some(t) = self.pending.front() {
+            return t.is("(");
+        }
+        if let Some(ref lx) = self.lexer {
+            let mut lx = (**lx).clone();
+            let mut t = lx.next();
+            while t.kind == Kind::Newline {
+                t = lx.next();
+                // A directive ends the invocation.
+                if t.is("#") {
+                    return false;
+                }
+            }
+            return t.is("(");
+        }
+        false
+    }
+}
+
+#[derive(Debug)]
+struct Macro {
+    name: Rc<str>,
+
+    /// The parameters, none for an object-like macro.
+    params: Option<Vec<Rc<str>>>,
+
+    /// Whether the last parameter is `...`, bound to `__VA_ARGS__`.
+    variadic: bool,
+    body: Vec<Tok>,
+
+    /// Where the macro was defined.
+    def: Option<(FileId, usize)>,
+}
+
+impl Macro {
+    /// Return true if the two definitions are the same, so that redefining is not a mistake.
+    fn same(&self, other: &Macro) -> bool {
+        self.params == other.params
+            && self.variadic == other.variadic
+            && self.body.len() == other.body.len()
+            && self
+                .body
+                .iter()
+                .zip(other.body.iter())
+                .enumerate()
+                .all(|(i, (a, b))| a.text == b.text && (i == 0 || a.space == b.space))
+    }
+
+    fn param(&self, t: &Tok) -> Option<usize> {
+        if t.kind != Kind::Ident {
+            return None;
+        }
+        self.params.as_ref()?.iter().position(|p| *p == t.text)
+    }
+}
+
+/// A section of `#if` and the like.
+#[derive(Debug)]
+struct Cond {
+    /// The source and offset of the directive opening the section.
+    at: (FileId, usize),
+
+    /// Whether the enclosing section is included.
+    parent: bool,
+
+    /// Whether the current branch is included.
+    active: bool,
+
+    /// Whether one of the branches was included.
+    taken: bool,
+
+    /// Whether `#else` was seen.
+    done: bool,
+}
+
+/// The preprocessor.
+#[derive(Debug, Default)]
+pub struct Preprocessor {
+    /// The directories where included files are searched.
+    include_dirs: Vec<PathBuf>,
+
+    macros: HashMap<Rc<str>, Rc<Macro>>,
+    conds: Vec<Cond>,
+
+    /// The files including the current one.
+    includes: Arc<[Include]>,
+
+    /// The source being produced and the origins of its parts.
+    out: String,
+    origins: Vec<Origin>,
+
+    /// The source and the range reported by errors.
+    site: Option<(FileId, usize, usize)>,
+
+    errors: usize,
+}
+
+impl Preprocessor {
+    /// Create a new preprocessor, with no include directories nor macros.
+    pub fn new() -> Preprocessor {
+        Preprocessor::default()
+    }
+
+    /// Search included files in `dir` too, after the directories already added. Files included
+    /// with `#include "file"` are first searched in the directory of the including file.
+    pub fn include_dir(&mut self, dir: PathBuf) {
+        self.include_dirs.push(dir);
+    }
+
+    /// Define a macro from a `name` or `name=value` definition, as given on the command line; a
+    /// `name` alone is defined as 1.
+    pub fn define(&mut self, def: &str) {
+        let (name, value) = def.split_once('=').unwrap_or((def, "1"));
+        let mut lx = Lexer::new(value);
+        let mut body = lx.line();
+        if let Some(t) = body.first_mut() {
+            t.space = false;
+        }
+        let name: Rc<str> = name.into();
+        self.macros.insert(
+            name.clone(),
+            Rc::new(Macro {
+                name,
+                params: None,
+                variadic: false,
+                body,
+                def: None,
+            }),
+        );
+    }
+
+    /// Remove the definition of the macro `name`.
+    pub fn undef(&mut self, name: &str) {
+        self.macros.remove(name);
+    }
+
+    /// Return true if the macro `name` is defined.
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+    }
+
+    /// The number of errors reported so far.
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// The object-like macros defined by the sources that expand to an integer constant
+    /// expression, with their values, ordered by source and position of definition.
+    pub fn constants(&mut self) -> Vec<(String, i64)> {
+        let mut macros: Vec<Rc<Macro>> = self
+            .macros
+            .values()
+            .filter(|m| m.params.is_none() && m.def.is_some())
+            .cloned()
+            .collect();
+        macros.sort_by_key(|m| m.def);
+
+        let mut constants = Vec::new();
+        for m in macros {
+            let name = Tok::new(Kind::Ident, &m.name);
+            if let Some(v) = eval(&self.expand_all(&[name])) {
+                constants.push((m.name.to_string(), v));
+            }
+        }
+        constants
+    }
+
+    /// Read the file `filename` into the SourceManager and preprocess it.
+    pub fn preprocess_file(&mut self, filename: String) -> anyhow::Result<MemoryBuffer> {
+        let file = sman_mut().add_file(filename)?;
+        Ok(self.preprocess(file))
+    }
+
+    /// Preprocess the source `file` and return a MemoryBuffer reading the result, which is a new
+    /// source with the same name. Errors are published as diagnostics.
+    pub fn preprocess(&mut self, file: FileId) -> MemoryBuffer {
+        log::trace!("Preprocessor::preprocess({})", file);
+        self.includes = Arc::from([]);
+        self.source(file);
+
+        let out = std::mem::take(&mut self.out);
+        let origins = std::mem::take(&mut self.origins);
+        let mut sm = sman_mut();
+        let name = sm.name(file).to_string();
+        let id = sm.add_str(&out, name);
+        sm.set_origins(id, origins);
+        sm.buffer(id)
+    }
+
+    fn skipping(&self) -> bool {
+        self.conds.last().is_some_and(|c| !c.active)
+    }
+
+    fn report(
+        &mut self,
+        file: FileId,
+        start: usize,
+        end: usize,
+        msg: String,
+        warning: bool,
+        notes: Vec<Note>,
+    ) {
+        if !warning {
+            self.errors += 1;
+        }
+        diag(Box::new(PreprocessorError {
+            file,
+            range: range(file, start, end),
+            msg,
+            warning,
+            notes,
+        }));
+    }
+
+    /// Report an error at the current directive or macro invocation.
+    fn error(&mut self, msg: String) {
+        if let Some((file, start, end)) = self.site {
+            self.report(file, start, end, msg, false, vec![]);
+        }
+    }
+
+    fn warn(&mut self, msg: String) {
+        if let Some((file, start, end)) = self.site {
+            self.report(file, start, end, msg, true, vec![]);
+        }
+    }
+
+    /// Report the message of the current `#error` or `#warning` directive.
+    fn message(&mut self, msg: String, warning: bool) {
+        if let Some((file, start, end)) = self.site {
+            if !warning {
+                self.errors += 1;
+            }
+            diag(Box::new(DirectiveMessage {
+                file,
+                range: range(file, start, end),
+                msg,
+                warning,
+            }));
+        }
+    }
+
+    /// Report why the current `#pragma alef diagnostic` is ignored.
+    fn ignore_pragma(&mut self, msg: String) {
+        if let Some((file, start, end)) = self.site {
+            diag(Box::new(PragmaWarning {
+                file,
+                range: range(file, start, end),
+                msg,
+            }));
+        }
+    }
+
+    /// Copy the text between `from` and `to` of the source `file`, whose content is `content`.
+    fn emit(&mut self, file: FileId, content: &str, from: usize, to: usize) {
+        if from >= to {
+            return;
+        }
+        let offset = self.out.len();
+        let contiguous = self.origins.last().is_some_and(|o| {
+            o.expansion.is_none()
+                && o.file == file
+                && Arc::ptr_eq(&o.includes, &self.includes)
+                && o.src + (offset - o.offset) == from
+        });
+        if !contiguous {
+            self.origins.push(Origin {
+                offset,
+                file,
+                src: from,
+                expansion: None,
+                includes: self.includes.clone(),
+            });
+        }
+        self.out.push_str(&content[from..to]);
+    }
+
+    /// Preprocess the source `file` into the output.
+    fn source(&mut self, file: FileId) {
+        let content = sman().content(file);
+        let mut lx = Lexer::new(&content);
+        let depth = self.conds.len();
+        let mut copied = 0;
+        let mut bol = true;
+
+        loop {
+            let tok = lx.next();
+            match tok.kind {
+                Kind::Eof => break,
+                Kind::Newline => {
+                    bol = true;
+                    if self.skipping() {
+                        // Keep the lines of skipped sections.
+                        self.emit(file, &content, tok.start, tok.end);
+                        copied = tok.end;
+                    }
+                }
+                _ if bol && tok.is("#") => {
+                    if !self.skipping() {
+                        self.emit(file, &content, copied, tok.start);
+                    }
+                    let line = lx.line();
+                    copied = lx.pos;
+                    self.directive(file, &content, tok.start, lx.pos, line);
+                    bol = false;
+                }
+                _ if self.skipping() => {
+                    copied = tok.end;
+                    bol = false;
+                }
+                _ => {
+                    bol = false;
+                    let m = match self.macros.get(&tok.text) {
+                        Some(m) if tok.kind == Kind::Ident => m.clone(),
+                        _ => continue,
+                    };
+                    let mut st = Stream {
+                        pending: VecDeque::new(),
+                        lexer: Some(&mut lx),
+                        end: tok.end,
+                    };
+                    if m.params.is_some() && !st.at_paren() {
+                        continue;
+                    }
+                    st.pending.push_back(tok.clone());
+                    self.emit(file, &content, copied, tok.start);
+                    self.site = Some((file, tok.start, tok.end));
+                    let toks = self.expand(&mut st);
+                    copied = st.end;
+                    let text = join(&toks);
+                    self.origins.push(Origin {
+                        offset: self.out.len(),
+                        file,
+                        src: tok.start,
+                        expansion: Some(Arc::new(Expansion {
+                            name: (*m.name).into(),
+                            def: m.def,
+                        })),
+                        includes: self.includes.clone(),
+                    });
+                    // Keep the expansion from running into the text around it.
+                    if let (Some(c), Some(d)) = (self.out.chars().next_back(), text.chars().next())
+                    {
+                        if glues(c, d) {
+                            self.out.push(' ');
+                        }
+                    }
+                    self.out.push_str(&text);
+                    if let (Some(c), Some(d)) =
+                        (text.chars().next_back(), content[copied..].chars().next())
+                    {
+                        if glues(c, d) {
+                            self.out.push(' ');
+                        }
+                    }
+                }
+            }
+        }
+        if !self.skipping() {
+            self.emit(file, &content, copied, content.len());
+        }
+
+        for c in self.conds.split_off(depth) {
+            let (file, at) = c.at;
+            self.report(
+                file,
+                at,
+                at + 1,
+                "unterminated conditional directive".into(),
+                false,
+                vec![],
+            );
+        }
+    }
+
+    /// Handle the directive at offset `start` of the source `file`, whose tokens after the `#`
+    /// are `line`, up to offset `end`.
+    fn directive(&mut self, file: FileId, content: &str, start: usize, end: usize, line: Vec<Tok>) {
+        self.site = Some((file, start, end));
+        let name = match line.first() {
+            Some(t) => t.clone(),
+            // The null directive.
+            None => return,
+        };
+        let args = &line[1..];
+        let skipping = self.skipping();
+
+        match &*name.text {
+            "if" | "ifdef" | "ifndef" => {
+                let active = !skipping
+                    && match &*name.text {
+                        "if" => self.condition(args),
+                        "ifdef" => self.defined_arg(&name, args),
+                        _ => !self.defined_arg(&name, args),
+                    };
+                self.conds.push(Cond {
+                    at: (file, start),
+                    parent: !skipping,
+                    active,
+                    taken: active,
+                    done: false,
+                });
+            }
+            "elif" | "else" => {
+                let (parent, taken, done) = match self.conds.last() {
+                    Some(c) => (c.parent, c.taken, c.done),
+                    None => {
+                        self.error(format!("#{} without #if", name.text));
+                        return;
+                    }
+                };
+                if done {
+                    self.error(format!("#{} after #else", name.text));
+                }
+                let active = parent && !taken && (&*name.text == "else" || self.condition(args));
+                let c = self.conds.last_mut().unwrap();
+                c.active = active;
+                c.taken |= active;
+                c.done = &*name.text == "else";
+            }
+            "endif" => {
+                if self.conds.pop().is_none() {
+                    self.error("#endif without #if".into());
+                }
+            }
+            _ if skipping => {}
+            "include" => self.include(file, content, start, args),
+            "define" => self.define_directive(file, args),
+            "undef" => {
+                if let Some(t) = self.macro_name(&name, args) {
+                    self.macros.remove(&t.text);
+                }
+            }
+            "line" => self.line_marker(file, content, start, end, args),
+            _ if name.kind == Kind::Number => self.line_marker(file, content, start, end, &line),
+            "error" | "warning" => {
+                let msg = match (args.first(), args.last()) {
+                    (Some(f), Some(l)) => content[f.start..l.end].to_string(),
+                    _ => String::new(),
+                };
+                self.message(msg, &*name.text == "warning");
+            }
+            "pragma" => self.pragma(file, start, args),
+            _ => self.error(format!("unknown preprocessor directive #{}", name.text)),
+        }
+    }
+
+    /// The name of the macro the directive `dir` refers to.
+    fn macro_name<'t>(&mut self, dir: &Tok, args: &'t [Tok]) -> Option<&'t Tok> {
+        match args.first() {
+            Some(t) if t.kind == Kind::Ident => {
+                if args.len() > 1 {
+                    self.warn(format!("extra tokens at the end of #{}", dir.text));
+                }
+                Some(t)
+            }
+            _ => {
+                self.error(format!("#{} expects a macro name", dir.text));
+                None
+            }
+        }
+    }
+
+    fn defined_arg(&mut self, dir: &Tok, args: &[Tok]) -> bool {
+        match self.macro_name(dir, args) {
+            Some(t) => self.macros.contains_key(&t.text),
+            None => false,
+        }
+    }
+
+    /// Handle `#include "file"` or `#include <file>`.
+    fn include(&mut self, file: FileId, content: &str, start: usize, args: &[Tok]) {
+        let (name, quoted) = match args {
+            [t, ..] if t.kind == Kind::Literal && t.text.starts_with('"') && t.text.len() > 1 => {
+                (t.text[1..t.text.len() - 1].to_string(), true)
+            }
+            [lt, rest @ ..] if lt.is("<") => match rest.iter().find(|t| t.is(">")) {
+                Some(gt) => (content[lt.end..gt.start].to_string(), false),
+                None => {
+                    self.error("missing '>' in #include".into());
+                    return;
+                }
+            },
+            _ => {
+                self.error("#include expects \"FILENAME\" or <FILENAME>".into());
+                return;
+            }
+        };
+        if self.includes.len() >= MAX_INCLUDE_DEPTH {
+            self.error("#include nested too deeply".into());
+            return;
+        }
+
+        let mut dirs = Vec::new();
+        if quoted {
+            let sm = sman();
+            let parent = Path::new(sm.name(file)).parent();
+            dirs.push(parent.map_or_else(PathBuf::new, Path::to_path_buf));
+        }
+        dirs.extend(self.include_dirs.iter().cloned());
+        let path = match dirs.iter().map(|d| d.join(&name)).find(|p| p.is_file()) {
+            Some(path) => path,
+            None => {
+                self.error(format!("cannot find include file {:?}", name));
+                return;
+            }
+        };
+        let id = match sman_mut().add_file(path.to_string_lossy().into()) {
+            Ok(id) => id,
+            Err(e) => {
+                self.error(format!(
+                    "cannot read include file {}: {}",
+                    path.display(),
+                    e
+                ));
+                return;
+            }
+        };
+
+        let include = {
+            let sm = sman();
+            let p = sm.presumed(file, start);
+            Include {
+                name: p.name.into(),
+                line: p.line,
+            }
+        };
+        let includes: Arc<[Include]> = std::iter::once(include)
+            .chain(self.includes.iter().cloned())
+            .collect();
+        let outer = std::mem::replace(&mut self.includes, includes);
+        self.source(id);
+        self.includes = outer;
+    }
+
+    /// Handle `#define`.
+    fn define_directive(&mut self, file: FileId, args: &[Tok]) {
+        let name = match args.first() {
+            Some(t) if t.kind == Kind::Ident => t.clone(),
+            _ => {
+                self.error("#define expects a macro name".into());
+                return;
+            }
+        };
+        if &*name.text == "defined" {
+            self.error("\"defined\" cannot be used as a macro name".into());
+            return;
+        }
+
+        let mut rest = &args[1..];
+        let mut params = None;
+        let mut variadic = false;
+        // A function-like macro has a parenthesis right after its name.
+        if rest.first().is_some_and(|t| t.is("(") && !t.space) {
+            let mut ps = Vec::new();
+            let mut i = 1;
+            loop {
+                match rest.get(i) {
+                    Some(t) if t.is(")") && ps.is_empty() => break,
+                    Some(t) if t.kind == Kind::Ident && !variadic => ps.push(t.text.clone()),
+                    Some(t) if t.is("...") && !variadic => {
+                        ps.push("__VA_ARGS__".into());
+                        variadic = true;
+                    }
+                    _ => {
+                        self.error("malformed macro parameter list".into());
+                        return;
+                    }
+                }
+                match rest.get(i + 1) {
+                    Some(t) if t.is(",") => i += 2,
+                    Some(t) if t.is(")") => {
+                        i += 1;
+                        break;
+                    }
+                    _ => {
+                        self.error("missing ')' in macro parameter list".into());
+                        return;
+                    }
+                }
+            }
+            rest = &rest[i + 1..];
+            params = Some(ps);
+        }
+
+        let mut body = rest.to_vec();
+        if let Some(t) = body.first_mut() {
+            t.space = false;
+        }
+        if body.first().is_some_and(|t| t.is("##")) || body.last().is_some_and(|t| t.is("##")) {
+            self.error("'##' cannot appear at either end of a macro expansion".into());
+            return;
+        }
+
+        let m = Macro {
+            name: name.text.clone(),
+            params,
+            variadic,
+            body,
+            def: Some((file, name.start)),
+        };
+        if let Some(params) = &m.params {
+            for (i, t) in m.body.iter().enumerate() {
+                if t.is("#") && m.body.get(i + 1).and_then(|t| m.param(t)).is_none() {
+                    self.error("'#' is not followed by a macro parameter".into());
+                    return;
+                }
+                if t.is("__VA_ARGS__") && !params.iter().any(|p| &**p == "__VA_ARGS__") {
+                    self.warn("__VA_ARGS__ can only appear in a variadic macro".into());
+                }
+            }
+        }
+        if let Some(old) = self.macros.get(&m.name) {
+            if !old.same(&m) {
+                let notes = old
+                    .def
+                    .map(|(file, start)| Note {
+                        msg: format!("{} was defined before", m.name),
+                        span: Some(Span {
+                            file,
+                            start,
+                            end: start + m.name.len(),
+                        }),
+                        label: Some("previous definition".into()),
+                    })
+                    .into_iter()
+                    .collect();
+                if let Some((file, start, end)) = self.site {
+                    let msg = format!("macro {} redefined", m.name);
+                    self.report(file, start, end, msg, true, notes);
+                }
+            }
+        }
+        self.macros.insert(m.name.clone(), Rc::new(m));
+    }
+
+    /// Handle `# linenum "filename" flags` and `#line linenum "filename"`.
+    /// Handle `#pragma alef diagnostic ignored|warning "<category>"`.
+    fn pragma(&mut self, file: FileId, start: usize, args: &[Tok]) {
+        let words: Vec<&str> = args.iter().map(|t| &*t.text).collect();
+        let (action, name) = match words[..] {
+            ["alef", "diagnostic", action, name] => (action, name),
+            ["alef", "diagnostic", ..] => {
+                self.ignore_pragma("expected an action and a category".into());
+                return;
+            }
+            _ => return,
+        };
+        let enabled = match action {
+            "ignored" => false,
+            "warning" => true,
+            _ => {
+                self.ignore_pragma(format!("unknown action {}", action));
+                return;
+            }
+        };
+        let category = name
+            .strip_prefix('"')
+            .and_then(|n| n.strip_suffix('"'))
+            .and_then(Category::from_name);
+        match category {
+            Some(category) => {
+                let (name, line) = {
+                    let sm = sman();
+                    let p = sm.presumed(file, start);
+                    (p.name.to_string(), p.line)
+                };
+                diagnostic::context().pragma(&name, line, category, enabled);
+            }
+            None => self.ignore_pragma(format!("unknown warning category {}", name)),
+        }
+    }
+
+    fn line_marker(&mut self, file: FileId, content: &str, start: usize, end: usize, args: &[Tok]) {
+        let line = match args.first().map(|t| t.text.parse::<usize>()) {
+            Some(Ok(line)) => line,
+            _ => {
+                self.error("malformed line marker, expected a line number".into());
+                return;
+            }
+        };
+        let name = match args.get(1) {
+            Some(t) if t.kind == Kind::Literal && t.text.starts_with('"') && t.text.len() > 1 => {
+                Some(unescape(&t.text[1..t.text.len() - 1]))
+            }
+            Some(_) => {
+                self.error("malformed line marker, expected a file name".into());
+                return;
+            }
+            None => None,
+        };
+        let mut flags = Vec::new();
+        for t in args.iter().skip(2) {
+            match t.text.parse::<u8>() {
+                Ok(f @ 1..=4) if t.kind == Kind::Number => flags.push(f),
+                _ => {
+                    self.error("malformed line marker, expected a flag between 1 and 4".into());
+                    return;
+                }
+            }
+        }
+        let next = if content[end..].starts_with('\n') {
+            end + 1
+        } else {
+            end
+        };
+        sman_mut().add_line_marker(file, start, next, LineMarker { line, name, flags });
+    }
+
+    /// Expand the pending tokens of `st`, and any token of the source they need, until there
+    /// are no pending tokens left.
+    fn expand(&mut self, st: &mut Stream) -> Vec<Tok> {
+        let mut out = Vec::new();
+        while let Some(t) = st.pending.pop_front() {
+            match self.expand_macro(&t, st) {
+                Some(toks) => {
+                    for t in toks.into_iter().rev() {
+                        st.pending.push_front(t);
+                    }
+                }
+                None => out.push(t),
+            }
+        }
+        out
+    }
+
+    /// Fully expand `toks`.
+    fn expand_all(&mut self, toks: &[Tok]) -> Vec<Tok> {
+        let mut st = Stream {
+            pending: toks.iter().cloned().collect(),
+            lexer: None,
+            end: 0,
+        };
+        self.expand(&mut st)
+    }
+
+    /// If `t` is the name of a macro to expand, read its arguments from `st` and return the
+    /// expansion, to be rescanned.
+    fn expand_macro(&mut self, t: &Tok, st: &mut Stream) -> Option<Vec<Tok>> {
+        if t.kind != Kind::Ident || t.hide.contains(&t.text) {
+            return None;
+        }
+        let m = self.macros.get(&t.text)?.clone();
+
+        let (args, hide) = match m.params {
+            None => (Vec::new(), t.hide.to_vec()),
+            Some(ref params) => {
+                if !st.at_paren() {
+                    return None;
+                }
+                let (args, rparen) = self.arguments(&m, st)?;
+                let args = match self.check_arguments(&m, params, args) {
+                    Some(args) => args,
+                    None => return Some(Vec::new()),
+                };
+                let hide = t
+                    .hide
+                    .iter()
+                    .filter(|h| rparen.hide.contains(h))
+                    .cloned()
+                    .collect();
+                (args, hide)
+            }
+        };
+
+        let mut hide = hide;
+        hide.push(m.name.clone());
+        let mut toks = self.substitute(&m, &args);
+        for (i, r) in toks.iter_mut().enumerate() {
+            let mut h = r.hide.to_vec();
+            h.extend(hide.iter().filter(|n| !r.hide.contains(n)).cloned());
+            r.hide = Rc::new(h);
+            if i == 0 {
+                r.space = t.space;
+            }
+        }
+        Some(toks)
+    }
+
+    /// Read the arguments of an invocation of `m`, starting from the left parenthesis, and
+    /// return them with the right parenthesis.
+    fn arguments(&mut self, m: &Macro, st: &mut Stream) -> Option<(Vec<Vec<Tok>>, Tok)> {
+        let mut t = st.next();
+        while t.kind == Kind::Newline {
+            t = st.next();
+        }
+        debug_assert!(t.is("("));
+
+        let mut args = vec![Vec::new()];
+        let mut depth = 0;
+        let mut space = false;
+        loop {
+            let mut t = st.next();
+            match t.kind {
+                Kind::Eof => {
+                    self.error(format!(
+                        "unterminated argument list invoking macro {}",
+                        m.name
+                    ));
+                    return None;
+                }
+                Kind::Newline => {
+                    space = true;
+                    continue;
+                }
+                _ => {}
+            }
+            t.space |= space;
+            space = false;
+            if t.is(")") && depth == 0 {
+                return Some((args, t));
+            } else if t.is(",") && depth == 0 {
+                args.push(Vec::new());
+                continue;
+            } else if t.is("(") {
+                depth += 1;
+            } else if t.is(")") {
+                depth -= 1;
+            }
+            args.last_mut().unwrap().push(t);
+        }
+    }
+
+    /// Match the arguments of an invocation of `m` with its parameters, gathering the variable
+    /// ones in `__VA_ARGS__`.
+    fn check_arguments(
+        &mut self,
+        m: &Macro,
+        params: &[Rc<str>],
+        mut args: Vec<Vec<Tok>>,
+    ) -> Option<Vec<Vec<Tok>>> {
+        // An empty argument list has no arguments, rather than an empty one.
+        if params.is_empty() && args.len() == 1 && args[0].is_empty() {
+            return Some(Vec::new());
+        }
+        if m.variadic && args.len() >= params.len() {
+            let rest = args.split_off(params.len() - 1);
+            let mut va = Vec::new();
+            for (i, a) in rest.into_iter().enumerate() {
+                if i > 0 {
+                    va.push(Tok::new(Kind::Punct, ","));
+                }
+                va.extend(a);
+            }
+            args.push(va);
+        } else if m.variadic && args.len() + 1 == params.len() {
+            args.push(Vec::new());
+        }
+        if args.len() != params.len() {
+            self.error(format!(
+                "macro {} takes {} arguments, but {} were given",
+                m.name,
+                params.len(),
+                args.len()
+            ));
+            return None;
+        }
+        Some(args)
+    }
+
+    /// Replace the parameters in the body of `m` with `args`, stringifying and pasting tokens.
+    fn substitute(&mut self, m: &Macro, args: &[Vec<Tok>]) -> Vec<Tok> {
+        let body = &m.body;
+        let mut out: Vec<Tok> = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let t = &body[i];
+            let pasted = body.get(i + 1).is_some_and(|t| t.is("##"));
+
+            if t.is("#") && m.params.is_some() {
+                if let Some(p) = body.get(i + 1).and_then(|t| m.param(t)) {
+                    let mut s = Tok::new(Kind::Literal, &stringify(&args[p]));
+                    s.space = t.space;
+                    out.push(s);
+                    i += 2;
+                    continue;
+                }
+            }
+
+            if t.is("##") {
+                let rhs = match body.get(i + 1) {
+                    Some(r) => match m.param(r) {
+                        Some(p) if args[p].is_empty() => vec![Tok::new(Kind::Placemarker, "")],
+                        Some(p) => args[p].clone(),
+                        None => vec![r.clone()],
+                    },
+                    None => Vec::new(),
+                };
+                let mut rhs = rhs.into_iter();
+                match (out.pop(), rhs.next()) {
+                    (Some(l), Some(r)) => {
+                        for t in self.paste(l, r) {
+                            out.push(t);
+                        }
+                    }
+                    (l, r) => out.extend(l.into_iter().chain(r)),
+                }
+                out.extend(rhs);
+                i += 2;
+                continue;
+            }
+
+            match m.param(t) {
+                Some(p) => {
+                    let arg = if pasted {
+                        if args[p].is_empty() {
+                            vec![Tok::new(Kind::Placemarker, "")]
+                        } else {
+                            args[p].clone()
+                        }
+                    } else {
+                        self.expand_all(&args[p])
+                    };
+                    let first = out.len();
+                    out.extend(arg);
+                    if let Some(a) = out.get_mut(first) {
+                        a.space = t.space;
+                    }
+                }
+                None => out.push(t.clone()),
+            }
+            i += 1;
+        }
+        out.retain(|t| t.kind != Kind::Placemarker);
+        out
+    }
+
+    /// Paste `l` and `r` into a single token.
+    fn paste(&mut self, l: Tok, r: Tok) -> Vec<Tok> {
+        if l.kind == Kind::Placemarker {
+            return vec![r];
+        }
+        if r.kind == Kind::Placemarker {
+            return vec![l];
+        }
+        let text = format!("{}{}", l.text, r.text);
+        let mut lx = Lexer::new(&text);
+        let t = lx.next();
+        if t.space || t.end != text.len() {
+            self.error(format!(
+                "pasting {} and {} does not give a valid token",
+                l.text, r.text
+            ));
+            return vec![l, r];
+        }
+        vec![Tok {
+            space: l.space,
+            ..Tok::new(t.kind, &text)
+        }]
+    }
+
+    /// Evaluate the expression of `#if` or `#elif`.
+    fn condition(&mut self, args: &[Tok]) -> bool {
+        let mut toks = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            if !args[i].is("defined") {
+                toks.push(args[i].clone());
+                i += 1;
+                continue;
+            }
+            let name = match (args.get(i + 1), args.get(i + 2), args.get(i + 3)) {
+                (Some(l), Some(n), Some(r)) if l.is("(") && n.kind == Kind::Ident && r.is(")") => {
+                    i += 4;
+                    n
+                }
+                (Some(n), ..) if n.kind == Kind::Ident => {
+                    i += 2;
+                    n
+                }
+                _ => {
+                    self.error("operator \"defined\" requires an identifier".into());
+                    return false;
+                }
+            };
+            let v = if self.macros.contains_key(&name.text) {
+                "1"
+            } else {
+                "0"
+            };
+            toks.push(Tok::new(Kind::Number, v));
+        }
+        if toks.is_empty() {
+            self.error("#if with no expression".into());
+            return false;
+        }
+
+        let toks = self.expand_all(&toks);
+        let mut e = Expr {
+            toks: &toks,
+            pos: 0,
+            dead: 0,
+        };
+        let v = e.ternary().and_then(|v| match toks.get(e.pos) {
+            Some(t) => Err(format!("missing binary operator before {}", t.text)),
+            None => Ok(v),
+        });
+        match v {
+            Ok(v) => v != 0,
+            Err(msg) => {
+                self.error(msg);
+                false
+            }
+        }
+    }
+}
+
+/// The range of `file` from `start` to `end`, whose content is the line holding `start`.
+fn range(file: FileId, start: usize, end: usize) -> Range {
+    let content = {
+        let sm = sman();
+        let (line, _) = sm.line_col(file, start);
+        sm.line(file, line).unwrap_or("").to_string()
+    };
+    Range {
+        start: Box::new(DefaultLocation { file, index: start }),
+        end: Some(Box::new(DefaultLocation { file, index: end })),
+        content,
+    }
+}
+
+/// The text of the tokens of an expansion.
+fn join(toks: &[Tok]) -> String {
+    let mut s = String::new();
+    let mut prev: Option<&Tok> = None;
+    for t in toks {
+        if let Some(p) = prev {
+            if t.space || pastes(p, t) {
+                s.push(' ');
+            }
+        }
+        s.push_str(&t.text);
+        prev = Some(t);
+    }
+    s
+}
+
+/// Return true if `a` and `b` would be read as different tokens if written next to each other.
+fn pastes(a: &Tok, b: &Tok) -> bool {
+    let text = format!("{}{}", a.text, b.text);
+    let t = Lexer::new(&text).next();
+    t.space || *t.text != *a.text
+}
+
+/// Return true if the characters `c` and `d` could be part of a single token when written next
+/// to each other.
+fn glues(c: char, d: char) -> bool {
+    let word = |c: char| c.is_alphanumeric() || c == '_' || c == '.' || !c.is_ascii();
+    if word(c) && word(d) {
+        return true;
+    }
+    let s = format!("{}{}", c, d);
+    s == "/*" || s == "//" || PUNCTUATORS.iter().any(|p| p.starts_with(&s))
+}
+
+/// A string literal with the text of `toks`.
+fn stringify(toks: &[Tok]) -> String {
+    let mut s = String::from("\"");
+    for (i, t) in toks.iter().enumerate() {
+        if i > 0 && t.space {
+            s.push(' ');
+        }
+        if t.kind == Kind::Literal {
+            for c in t.text.chars() {
+                if c == '"' || c == '\\' {
+                    s.push('\\');
+                }
+                s.push(c);
+            }
+        } else {
+            s.push_str(&t.text);
+        }
+    }
+    s.push('"');
+    s
+}
+
+/// Remove the escapes of a file name in a line marker.
+fn unescape(s: &str) -> String {
+    let mut out = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The value of a constant expression with no identifiers, None if it is not one.
+pub(crate) fn eval(toks: &[Tok]) -> Option<i64> {
+    if toks.is_empty() || toks.iter().any(|t| t.kind == Kind::Ident) {
+        return None;
+    }
+    let mut e = Expr {
+        toks,
+        pos: 0,
+        dead: 0,
+    };
+    let v = e.ternary().ok()?;
+    (e.pos == toks.len()).then_some(v)
+}
+
+/// The value of the integer constant `s`.
+fn integer(s: &str) -> Option<i64> {
+    let s = s.trim_end_matches(['u', 'U', 'l', 'L']);
+    let v = if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16)
+    } else if s.len() > 1 && s.starts_with('0') {
+        u64::from_str_radix(&s[1..], 8)
+    } else {
+        s.parse::<u64>()
+    };
+    v.ok().map(|v| v as i64)
+}
+
+/// The value of the character constant `s`, quotes included.
+fn character(s: &str) -> Option<i64> {
+    let inner = s.strip_prefix('\'')?.strip_suffix('\'')?;
+    let mut chars = inner.chars();
+    let v = match chars.next()? {
+        '\\' => match chars.next()? {
+            'n' => '\n' as i64,
+            't' => '\t' as i64,
+            'r' => '\r' as i64,
+            'b' => 8,
+            'f' => 12,
+            'v' => 11,
+            'a' => 7,
+            'x' => return i64::from_str_radix(chars.as_str(), 16).ok(),
+            c @ '0'..='7' => {
+                let digits = format!("{}{}", c, chars.as_str());
+                return i64::from_str_radix(&digits, 8).ok();
+            }
+            c => c as i64,
+        },
+        c => c as i64,
+    };
+    chars.next().is_none().then_some(v)
+}
+
+/// The evaluation of a `#if` expression, where every macro was expanded.
+struct Expr<'a> {
+    toks: &'a [Tok],
+    pos: usize,
+
+    /// Greater than zero while evaluating an operand that does not count, such as the right
+    /// one of `0 && x`, where dividing by zero is not an error.
+    dead: usize,
+}
+
+impl<'a> Expr<'a> {
+    fn peek(&self) -> Option<&'a Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), String> {
+        match self.peek() {
+            Some(t) if t.is(text) => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(format!("missing '{}' in #if", text)),
+        }
+    }
+
+    /// `c ? a : b`, the loosest binding operator.
+    fn ternary(&mut self) -> Result<i64, String> {
+        let c = self.binary(1)?;
+        if !self.peek().is_some_and(|t| t.is("?")) {
+            return Ok(c);
+        }
+        self.pos += 1;
+        self.dead += (c == 0) as usize;
+        let a = self.ternary()?;
+        self.dead -= (c == 0) as usize;
+        self.expect(":")?;
+        self.dead += (c != 0) as usize;
+        let b = self.ternary()?;
+        self.dead -= (c != 0) as usize;
+        Ok(if c != 0 { a } else { b })
+    }
+
+    fn precedence(t: &Tok) -> Option<u8> {
+        if t.kind != Kind::Punct {
+            return None;
+        }
+        Some(match &*t.text {
+            "||" => 1,
+            "&&" => 2,
+            "|" => 3,
+            "^" => 4,
+            "&" => 5,
+            "==" | "!=" => 6,
+            "<" | ">" | "<=" | ">=" => 7,
+            "<<" | ">>" => 8,
+            "+" | "-" => 9,
+            "*" | "/" | "%" => 10,
+            _ => return None,
+        })
+    }
+
+    /// Binary operators binding at least as `min`.
+    fn binary(&mut self, min: u8) -> Result<i64, String> {
+        let mut l = self.unary()?;
+        while let Some(op) = self.peek() {
+            let prec = match Expr::precedence(op) {
+                Some(p) if p >= min => p,
+                _ => break,
+            };
+            self.pos += 1;
+            let short = match &*op.text {
+                "&&" => l == 0,
+                "||" => l != 0,
+                _ => false,
+            };
+            self.dead += short as usize;
+            let r = self.binary(prec + 1)?;
+            self.dead -= short as usize;
+            l = match &*op.text {
+                "||" => (l != 0 || r != 0) as i64,
+                "&&" => (l != 0 && r != 0) as i64,
+                "|" => l | r,
+                "^" => l ^ r,
+                "&" => l & r,
+                "==" => (l == r) as i64,
+                "!=" => (l != r) as i64,
+                "<" => (l < r) as i64,
+                ">" => (l > r) as i64,
+                "<=" => (l <= r) as i64,
+                ">=" => (l >= r) as i64,
+                "<<" => l.wrapping_shl(r as u32),
+                ">>" => l.wrapping_shr(r as u32),
+                "+" => l.wrapping_add(r),
+                "-" => l.wrapping_sub(r),
+                "*" => l.wrapping_mul(r),
+                _ if r == 0 && self.dead > 0 => 0,
+                _ if r == 0 => return Err("division by zero in #if".into()),
+                "/" => l.wrapping_div(r),
+                _ => l.wrapping_rem(r),
+            };
+        }
+        Ok(l)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        let t = match self.peek() {
+            Some(t) => t,
+            None => return Err("unexpected end of #if expression".into()),
+        };
+        self.pos += 1;
+        match t.kind {
+            Kind::Punct => match &*t.text {
+                "!" => Ok((self.unary()? == 0) as i64),
+                "~" => Ok(!self.unary()?),
+                "-" => Ok(self.unary()?.wrapping_neg()),
+                "+" => self.unary(),
+                "(" => {
+                    let v = self.ternary()?;
+                    self.expect(")")?;
+                    Ok(v)
+                }
+                _ => Err(format!("unexpected {} in #if", t.text)),
+            },
+            Kind::Number => integer(&t.text)
+                .ok_or_else(|| format!("invalid integer constant {} in #if", t.text)),
+            Kind::Literal => {
+                character(&t.text).ok_or_else(|| format!("invalid constant {} in #if", t.text))
+            }
+            // Identifiers that are not macros are zero.
+            Kind::Ident => Ok(0),
+            _ => Err("unexpected end of #if expression".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::scan::Scanner;
+    use crate::source::sman::sman_mut;
+
+    /// Preprocess `src` and return the result with the number of errors.
+    fn pp(src: &str) -> (String, usize) {
+        let mut p = Preprocessor::new();
+        let file = sman_mut().add_str(src, "pp.l".into());
+        let mut mb = p.preprocess(file);
+        (mb.get_content(), p.errors())
+    }
+
+    fn ok(src: &str) -> String {
+        let (out, errors) = pp(src);
+        assert_eq!(errors, 0, "errors preprocessing {:?}", src);
+        out
+    }
+
+    #[test]
+    fn objects() {
+        assert_eq!(
+            ok("#define N 10\nint a[N]; /* N */ // N\nbyte *s = \"N\";\n"),
+            "\nint a[10]; /* N */ // N\nbyte *s = \"N\";\n"
+        );
+        assert_eq!(ok("#define A B\n#define B A\nA B\n"), "\n\nA B\n");
+        assert_eq!(ok("#define E\n[E]\n#undef E\nE\n"), "\n[]\n\nE\n");
+        assert_eq!(
+            ok("#define LONG 1 + \\\n 2\nLONG\n"),
+            "\n1 + 2\n",
+            "continued lines"
+        );
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            ok("#define sq(x) ((x)*(x))\nsq(a+1)\n"),
+            "\n((a+1)*(a+1))\n"
+        );
+        assert_eq!(ok("#define f(x) x\nf (1) f\n"), "\n1 f\n");
+        assert_eq!(
+            ok("#define max(a, b) ((a) > (b) ? (a) : (b))\nmax(f(1, 2),\n  3);\n"),
+            "\n((f(1, 2)) > (3) ? (f(1, 2)) : (3));\n"
+        );
+        assert_eq!(ok("#define g(x) x\n#define f g\nf(f)(1)\n"), "\n\ng(1)\n");
+        assert_eq!(ok("#define neg(x) -x\n-neg(1)\n"), "\n- -1\n");
+        assert_eq!(ok("#define v() 1\nv()\n"), "\n1\n");
+    }
+
+    #[test]
+    fn operators() {
+        assert_eq!(
+            ok("#define str(x) #x\nstr(a  \"b\\n\" 'c')\n"),
+            "\n\"a \\\"b\\\\n\\\" 'c'\"\n"
+        );
+        assert_eq!(
+            ok("#define cat(a, b) a ## b\ncat(x, 1) cat(, y) cat(z,)\n"),
+            "\nx1 y z\n"
+        );
+        assert_eq!(
+            ok("#define p(fmt, ...) print(fmt, __VA_ARGS__)\np(\"%d %d\", 1, 2)\n"),
+            "\nprint(\"%d %d\", 1, 2)\n"
+        );
+        assert_eq!(pp("#define cat(a, b) a ## b\ncat(+, /)\n").1, 1);
+        assert_eq!(pp("#define f(x) #y\n").1, 1);
+    }
+
+    #[test]
+    fn conditionals() {
+        let src =
+            "#define A 2\n#if A > 1 && defined(A) && !defined B\na\n#elif 1\nb\n#else\nc\n#endif\n";
+        assert_eq!(ok(src), "\n\na\n\n\n\n\n\n");
+        let src = "#ifdef X\nx\n#ifndef Y\ny\n#endif\n#else\nz\n#endif\n";
+        assert_eq!(ok(src), "\n\n\n\n\n\nz\n\n");
+        assert_eq!(
+            ok("#if 0 && 1 / 0\na\n#elif (0 ? 1 / 0 : 0x10) == 16\nb\n#endif\n"),
+            "\n\n\nb\n\n"
+        );
+        assert_eq!(
+            ok("#if '\\n' == 10 && 010 == 8 && -1 < 0 && U == 0\na\n#endif\n"),
+            "\na\n\n"
+        );
+        // Directives in skipped sections are not checked.
+        assert_eq!(
+            ok("#if 0\n#bogus\n#include <nothing>\n#endif\n"),
+            "\n\n\n\n"
+        );
+
+        assert_eq!(pp("#if 1\n").1, 1);
+        assert_eq!(pp("#endif\n").1, 1);
+        assert_eq!(pp("#if 1 / 0\n#endif\n").1, 1);
+        assert_eq!(pp("#if 1 2\n#endif\n").1, 1);
+        assert_eq!(pp("#if 1\n#else\n#else\n#endif\n").1, 1);
+        assert_eq!(pp("#bogus\n").1, 1);
+    }
+
+    #[test]
+    fn messages() {
+        // Warnings and ignored pragmas are not errors.
+        assert_eq!(
+            ok("#warning hello\n#pragma alef diagnostic ignored \"nothing\"\n"),
+            "\n\n"
+        );
+        assert_eq!(pp("#error stop here\n").1, 1);
+    }
+
+    #[test]
+    fn includes() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("alef-pp-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sys"))?;
+        std::fs::write(dir.join("sys/lib.h"), "#define LIB 1\nint lib;\n")?;
+        std::fs::write(dir.join("local.h"), "#include <lib.h>\nint local = LIB;\n")?;
+        let main = dir.join("main.l");
+        std::fs::write(
+            &main,
+            "#include \"local.h\"\nint\nmain(void)\n{\n\treturn local;\n}\n",
+        )?;
+
+        let mut p = Preprocessor::new();
+        p.include_dir(dir.join("sys"));
+        let mb = p.preprocess_file(main.to_string_lossy().into())?;
+        assert_eq!(p.errors(), 0);
+        assert!(p.is_defined("LIB"));
+
+        let mut scanner = Scanner::new(Box::new(mb), None);
+        let mut toks = Vec::new();
+        loop {
+            let t = scanner.tok();
+            if t.is_end() {
+                break;
+            }
+            toks.push(t);
+        }
+        let loc = |i: usize| toks[i].get_range().start;
+
+        // `int lib;` comes from lib.h, included from local.h, included from main.l.
+        let lib = loc(0);
+        assert_eq!(
+            lib.to_string(),
+            format!("{}:2:1", dir.join("sys/lib.h").display())
+        );
+        let includes: Vec<_> = lib
+            .get_includes()
+            .iter()
+            .map(|i| (i.name.to_string(), i.line))
+            .collect();
+        assert_eq!(
+            includes,
+            [
+                (dir.join("local.h").to_string_lossy().into(), 1),
+                (main.to_string_lossy().into(), 1)
+            ]
+        );
+
+        // `LIB` in `int local = LIB;` is an expansion.
+        let one = loc(6);
+        assert_eq!(
+            one.to_string(),
+            format!("{}:2:13", dir.join("local.h").display())
+        );
+        let e = one.get_expansion().unwrap();
+        assert_eq!(&*e.name, "LIB");
+        let (file, offset) = e.def.unwrap();
+        let def = DefaultLocation {
+            file,
+            index: offset,
+        };
+        assert_eq!(
+            def.to_string(),
+            format!("{}:1:9", dir.join("sys/lib.h").display())
+        );
+        assert!(loc(7).get_expansion().is_none());
+
+        // `return` in main.l.
+        let ret = loc(14);
+        assert_eq!(ret.to_string(), format!("{}:5:2", main.display()));
+        assert!(ret.get_includes().is_empty());
+
+        assert_eq!(pp("#include \"nothing.h\"\n").1, 1);
+        assert_eq!(pp("#include nothing\n").1, 1);
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn line_markers() {
+        let mut p = Preprocessor::new();
+        let src = "# 7 \"orig.l\"\nint a;\n";
+        let file = sman_mut().add_str(src, "pp.i".into());
+        let mb = p.preprocess(file);
+        assert_eq!(p.errors(), 0);
+        let mut scanner = Scanner::new(Box::new(mb), None);
+        assert_eq!(scanner.tok().get_range().start.to_string(), "orig.l:7:1");
+    }
+}
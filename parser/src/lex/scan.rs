@@ -1,8 +1,15 @@
 use crate::{
-    diagnostic::diag,
+    diagnostic::{
+        diag,
+        fix::{Applicability, Edit, Suggestion},
+    },
     lex::cman::CommentManager,
     lex::{comment::Comment, err::*, token::*},
-    source::{loc::*, MemoryBuffer, EOF_CHAR},
+    source::{
+        loc::*,
+        sman::{sman_mut, LineMarker},
+        MemoryBuffer, EOF_CHAR,
+    },
 };
 use std::i64;
 
@@ -498,6 +505,7 @@ impl Scanner {
             '\'' => self.character(),
             '\"' => self.string(),
             '$' => self.runestring(),
+            'L' if self.pch(1) == '"' => self.runestring(),
             _ => {
                 let mut name: String = "".to_string();
 
@@ -823,9 +831,18 @@ impl Scanner {
     fn runestring(&mut self) -> Token {
         log::trace!("Scanner::runestring");
 
-        // Consume the starting '$"'.
+        // Consume the starting '$"', or the 'L"' of C wide strings, which is deprecated.
+        let prefix = self.get_loc();
         let mut p = self.ch();
-        assert!(p == '$');
+        assert!(p == '$' || p == 'L');
+        if p == 'L' {
+            self.make_deprecated_warn(
+                prefix,
+                "runestring",
+                "the `L` prefix of C wide strings is deprecated, runestrings start with `$`.",
+                "$",
+            );
+        }
 
         p = self.ch();
         assert!(p == '"');
@@ -885,77 +902,108 @@ impl Scanner {
     fn preproc(&mut self) {
         log::trace!("Scanner::preproc");
         // Source file name and line number information is conveyed by lines of the
-        // form # linenum filename flags - Directly consume '# '.
+        // form # linenum "filename" flags, which are recorded in the SourceManager so that
+        // locations refer to the original files; anything else was left by the preprocessor.
         let lstart = self.get_loc();
 
-        let mut p = self.ch();
+        let p = self.ch();
         assert!(p == '#');
 
-        let mut lineno = "".to_owned();
-        let mut filename = "".to_owned();
-        p = self.pch(0);
-        if p.is_ascii_digit() {
-            // Read the line number.
-            while p.is_ascii_digit() {
-                lineno.push(p);
-                self.ch();
-                p = self.pch(0);
-            }
+        self.skip_blanks();
+        if !self.pch(0).is_ascii_digit() {
+            self.skip_line();
+            self.make_preproc_warn(lstart, "unremoved preprocessor directive");
+            return;
+        }
 
-            if p != ' ' {
-                //emit::error!(&range, "wrong preprocessor directive, expected a digit");
-            } else {
-                self.ch();
+        match self.line_marker() {
+            Ok(marker) => {
+                if self.pch(0) == '\n' {
+                    self.ch();
+                }
+                let at = lstart.get_mbuf_index();
+                let next = self.get_loc().get_mbuf_index();
+                sman_mut().add_line_marker(self.src.get_file(), at, next, marker);
+            }
+            Err(msg) => {
+                self.skip_line();
+                self.make_preproc_warn(lstart, msg);
             }
+        }
+    }
 
-            p = self.pch(0);
+    /// Read the line number, the file name and the flags of a line marker, up to the end of the
+    /// line.
+    fn line_marker(&mut self) -> Result<LineMarker, &'static str> {
+        log::trace!("Scanner::line_marker");
+        let mut lineno = String::new();
+        while self.pch(0).is_ascii_digit() {
+            lineno.push(self.ch());
+        }
+        let line = lineno
+            .parse::<usize>()
+            .map_err(|_| "malformed line marker, the line number is out of range")?;
 
-            // Read the filename.
-            while p != ' ' {
-                filename.push(p);
-                self.ch();
+        self.skip_blanks();
+        let mut p = self.pch(0);
+        let name = if p == '"' {
+            self.ch();
+            let mut filename = String::new();
+            loop {
                 p = self.pch(0);
-
-                // The filename must be on a single line.
-                if p == '\n' {
-                    //                    let range = self.src.get_range(lstart.as_ref(), None);
-                    //emit::error!(&range, "wrong preprocessor directive, expected a filename");
-                    //@TODO: preprocessor errors and warnings??
-                    break;
+                if p == '\n' || p == EOF_CHAR {
+                    return Err("malformed line marker, unterminated file name");
                 }
-            }
-
-            self.ch();
-            p = self.pch(0);
-
-            if p == '1' {
-                // Start a new file.
-                while p != '\n' && p != EOF_CHAR {
-                    self.ch();
-                    p = self.pch(0);
+                self.ch();
+                match p {
+                    '"' => break,
+                    '\\' => {
+                        p = self.pch(0);
+                        if p == '\n' || p == EOF_CHAR {
+                            return Err("malformed line marker, unterminated file name");
+                        }
+                        filename.push(self.ch());
+                    }
+                    _ => filename.push(p),
                 }
-
-                // Should the lman be a _source manager_?
-                // So, instead of passing MemoryBuffers to the lman we should
-                // just pass file names or strings and let the lman keep the
-                // map of filenames and actual memory buffers..?
-                // Otherwise, who owns this MemBuffer?
-
-                // @TODO: manage inclusion of files...
-                // let mbuf = MemoryBuffer::from_file(filename);
-                // if let Ok(mbuf) = mbuf {
-                //     self.lman.new_file(&mbuf, lineno.parse::<u32>().unwrap());
-                // }
             }
+            Some(filename)
+        } else if p == '\n' || p == EOF_CHAR {
+            None
         } else {
-            p = self.pch(0);
+            return Err("malformed line marker, expected a file name");
+        };
 
-            while p != '\n' && p != EOF_CHAR {
-                self.ch();
-                p = self.pch(0);
+        let mut flags = Vec::new();
+        loop {
+            self.skip_blanks();
+            p = self.pch(0);
+            if p == '\n' || p == EOF_CHAR {
+                break;
+            }
+            if !('1'..='4').contains(&p) || self.pch(1).is_ascii_digit() {
+                return Err("malformed line marker, expected a flag between 1 and 4");
             }
+            self.ch();
+            flags.push(p as u8 - b'0');
+        }
 
-            self.make_preproc_warn(lstart, "unremoved preprocessor directive");
+        Ok(LineMarker { line, name, flags })
+    }
+
+    /// Consume spaces and tabs.
+    fn skip_blanks(&mut self) {
+        while self.pch(0) == ' ' || self.pch(0) == '\t' {
+            self.ch();
+        }
+    }
+
+    /// Consume the rest of the line, but not the newline.
+    fn skip_line(&mut self) {
+        let mut p = self.pch(0);
+        while p != '\n' && p != EOF_CHAR {
+            self.ch();
+            p = self.pch(0);
         }
     }
 
@@ -1022,7 +1070,7 @@ impl Scanner {
     fn make_stray_err(&self, lstart: Box<dyn Location>, msg: &str, sym: &str) {
         let range = self.src.get_range(lstart.as_ref(), None);
         let err = Box::new(StrayCharError {
-            source_name: self.src.get_name(),
+            file: self.src.get_file(),
             range: Range {
                 start: range.start,
                 end: range.end,
@@ -1038,7 +1086,7 @@ impl Scanner {
     fn make_literal_err(&self, lstart: Box<dyn Location>, msg: &str, sym: Option<char>) {
         let range = self.src.get_range(lstart.as_ref(), None);
         let err = Box::new(LiteralError {
-            source_name: self.src.get_name(),
+            file: self.src.get_file(),
             range: Range {
                 start: range.start,
                 end: range.end,
@@ -1050,10 +1098,39 @@ impl Scanner {
         diag(err);
     }
 
+    /// Warn about the deprecated spelling of a literal starting at `lstart`, up to the current
+    /// location, and suggest replacing it with `current`.
+    fn make_deprecated_warn(
+        &self,
+        lstart: Box<dyn Location>,
+        kind: &str,
+        msg: &str,
+        current: &str,
+    ) {
+        let range = self.src.get_range(lstart.as_ref(), None);
+        let len = range.end.as_ref().map_or(0, |e| e.get_mbuf_index()) - lstart.get_mbuf_index();
+        let fix = Edit::replace(lstart.as_ref(), len, current).map(|e| Suggestion {
+            msg: format!("write `{}` instead", current),
+            edits: vec![e],
+            applicability: Applicability::MachineApplicable,
+        });
+        diag(Box::new(DeprecatedLiteralWarning {
+            file: self.src.get_file(),
+            range: Range {
+                start: range.start,
+                end: range.end,
+                content: self.src.get_current_line(),
+            },
+            kind: kind.to_string(),
+            msg: msg.to_string(),
+            fix,
+        }));
+    }
+
     fn make_preproc_warn(&self, lstart: Box<dyn Location>, msg: &str) {
         let range = self.src.get_range(lstart.as_ref(), None);
         let err = Box::new(PreprocessorDirectiveError {
-            source_name: self.src.get_name(),
+            file: self.src.get_file(),
             range: Range {
                 start: range.start,
                 end: range.end,
@@ -1067,7 +1144,7 @@ impl Scanner {
     fn make_comment_err(&self, lstart: Box<dyn Location>, msg: &str) {
         let range = self.src.get_range(lstart.as_ref(), None);
         let err = Box::new(CommentError {
-            source_name: self.src.get_name(),
+            file: self.src.get_file(),
             range: Range {
                 start: range.start,
                 end: range.end,
@@ -1334,11 +1411,46 @@ mod test {
     }
 
     #[test]
-    fn scan_runestrings() {}
+    fn scan_runestrings() {
+        // `L"..."` is the deprecated spelling of `$"..."`, but `L` alone is an identifier.
+        let mb = MemoryBuffer::from_str("$\"aé\" L\"b\" L \"c\"", "Runestrings".to_string());
+        let mut scanner = Scanner::new(Box::new(mb), None);
+
+        assert!(matches!(scanner.tok(), Token::Runestring(_, true, s) if s == "aé"));
+        assert!(matches!(scanner.tok(), Token::Runestring(_, true, s) if s == "b"));
+        assert!(matches!(scanner.tok(), Token::Identifier(_, s) if s == "L"));
+        assert!(matches!(scanner.tok(), Token::String(_, true, s) if s == "c"));
+        assert!(scanner.tok().is_end());
+    }
 
     #[test]
     fn scan_locs() {}
 
+    #[test]
+    fn scan_line_markers() {
+        let src = "# 1 \"main.l\"\nint a;\n#  1 \"inc/\\\"q\\\".h\" 1 3\nb\n# 3 \"main.l\" 2\n  c\n# 7\nd";
+        let mb = MemoryBuffer::from_str(src, "main.i".to_string());
+        let mut scanner = Scanner::new(Box::new(mb), None);
+
+        let want = |scanner: &mut Scanner, name: &str, loc: &str, includes: &[(&str, usize)]| {
+            let tok = scanner.tok();
+            assert_eq!(tok.is_identifier(), true);
+            let range = tok.get_range();
+            assert_eq!(range.start.to_string(), loc, "location of {}", name);
+            let got = range.start.get_includes();
+            let got: Vec<_> = got.iter().map(|i| (&*i.name, i.line)).collect();
+            assert_eq!(got, includes, "includes of {}", name);
+        };
+
+        want(&mut scanner, "int", "main.l:1:1", &[]);
+        want(&mut scanner, "a", "main.l:1:5", &[]);
+        scanner.tok();
+        want(&mut scanner, "b", "inc/\"q\".h:1:1", &[("main.l", 2)]);
+        want(&mut scanner, "c", "main.l:3:3", &[]);
+        want(&mut scanner, "d", "main.l:7:1", &[]);
+        assert_eq!(scanner.tok().is_end(), true);
+    }
+
     #[test]
     fn scan_entire_source() {}
 }
@@ -21,7 +21,9 @@ pub static KEYS_MAP: phf::Map<&'static str, Keyword> = phf_map! {
     "for" => Keyword::For,
     "goto" => Keyword::Goto,
     "if" => Keyword::If,
+    "import" => Keyword::Import,
     "intern" => Keyword::Intern,
+    "module" => Keyword::Module,
     "nil" => Keyword::Nil,
     "par" => Keyword::Par,
     "proc" => Keyword::Proc,
@@ -330,8 +332,12 @@ pub enum Keyword {
     Goto,
     /// If keyword: "if".
     If,
+    /// Import keyword: "import".
+    Import,
     /// Intern keyword: "intern".
     Intern,
+    /// Module keyword: "module".
+    Module,
     /// Nil keyword: "nil".
     Nil,
     /// Par keyword: "par".
@@ -390,7 +396,9 @@ impl fmt::Display for Keyword {
             Keyword::For => s = String::from("for"),
             Keyword::Goto => s = String::from("goto"),
             Keyword::If => s = String::from("if"),
+            Keyword::Import => s = String::from("import"),
             Keyword::Intern => s = String::from("intern"),
+            Keyword::Module => s = String::from("module"),
             Keyword::Nil => s = String::from("nil"),
             Keyword::Par => s = String::from("par"),
             Keyword::Proc => s = String::from("proc"),
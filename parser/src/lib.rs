@@ -11,6 +11,9 @@ pub mod ast {
     /// Visitor trait.
     pub mod visit;
 }
+/// Importing C headers as Alef declarations.
+pub mod cimport;
+
 /// Diagnostic tooling.
 pub mod diagnostic;
 
@@ -26,6 +29,10 @@ pub mod lex {
     /// ignored and discarded.
     pub mod comment;
 
+    /// The preprocessor: includes files, expands macros and removes conditional sections before
+    /// the scanner reads the source.
+    pub mod pp;
+
     /// The scanner: fetches chars from the MemoryBuffer and creates tokens.
     pub mod scan;
 
@@ -36,6 +43,9 @@ pub mod lex {
     pub mod err;
 }
 
+/// The module system: resolving imports to files and ordering the modules of a program.
+pub mod module;
+
 /// Operations and representation of Alef source files.
 pub mod source;
 
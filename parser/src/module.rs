@@ -0,0 +1,287 @@
+//! Modules are the unit of compilation of multi-file programs: every source is a module, and
+//! names the modules it uses with `import`. An import `util.str` refers to the file `util/str.l`,
+//! searched first in the directory of the importing module and then in every directory of the
+//! search path, in order.
+//!
+//! `load` walks the imports from the root module and returns the whole program in dependency
+//! order, so that every module comes after the modules it imports; cyclic imports are errors.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The extension of Alef sources.
+pub const SOURCE_EXT: &str = "l";
+
+/// Errors found while loading the modules of a program.
+#[derive(Error, Debug)]
+pub enum ModuleError {
+    /// An imported module matches no file on the search path.
+    #[error("{from}: cannot find module {name}")]
+    NotFound { name: String, from: String },
+
+    /// A module imports itself, directly or through other modules; the path starts and ends
+    /// with the same module.
+    #[error("import cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+
+    /// A module cannot be read.
+    #[error("{0}: {1}")]
+    Load(String, anyhow::Error),
+}
+
+/// Maps module names to files.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+    /// The directories searched after the one of the importing module.
+    paths: Vec<PathBuf>,
+
+    /// The extension of module files.
+    ext: String,
+}
+
+impl Resolver {
+    /// Create a resolver for files with the extension `ext` and an empty search path.
+    pub fn new(ext: &str) -> Resolver {
+        Resolver {
+            paths: vec![],
+            ext: ext.to_string(),
+        }
+    }
+
+    /// Append a directory to the search path.
+    pub fn path(&mut self, dir: PathBuf) {
+        self.paths.push(dir);
+    }
+
+    /// The relative path of the file of a module: `util.str` is `util/str.<ext>`.
+    pub fn file_name(&self, name: &str) -> PathBuf {
+        let mut p: PathBuf = name.split('.').collect();
+        p.set_extension(&self.ext);
+        p
+    }
+
+    /// Find the file of the module `name`, imported by the module in `from` if any.
+    pub fn resolve(&self, name: &str, from: Option<&Path>) -> Option<PathBuf> {
+        let rel = self.file_name(name);
+        let local = from.map(|f| f.parent().unwrap_or_else(|| Path::new("")));
+        local
+            .into_iter()
+            .chain(self.paths.iter().map(|p| p.as_path()))
+            .map(|dir| dir.join(&rel))
+            .find(|p| p.is_file())
+    }
+}
+
+/// A module of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unit {
+    /// The name of the module, as imported; the root module is named after its file.
+    pub name: String,
+
+    /// The file of the module.
+    pub path: PathBuf,
+
+    /// The names of the imported modules.
+    pub imports: Vec<String>,
+
+    /// The files of the imported modules, in the order of `imports`. A file imported under
+    /// several names is loaded once, and every import refers to the `path` of its unit.
+    pub files: Vec<PathBuf>,
+}
+
+/// Load the program whose main module is in `root`, reading the imports of every module with
+/// `imports`. The modules are returned in dependency order, the root last.
+pub fn load<F>(root: &Path, resolver: &Resolver, mut imports: F) -> Result<Vec<Unit>, ModuleError>
+where
+    F: FnMut(&Path) -> anyhow::Result<Vec<String>>,
+{
+    let name = root
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut loader = Loader {
+        resolver,
+        imports: &mut imports,
+        done: HashMap::new(),
+        stack: vec![],
+        units: vec![],
+    };
+    loader.visit(name, root.to_path_buf())?;
+    Ok(loader.units)
+}
+
+/// The depth-first walk of the imports.
+struct Loader<'a, F> {
+    resolver: &'a Resolver,
+    imports: &'a mut F,
+
+    /// The files of the modules already loaded, by canonical file.
+    done: HashMap<PathBuf, PathBuf>,
+
+    /// The modules being loaded, from the root to the current one.
+    stack: Vec<(String, PathBuf)>,
+
+    /// The loaded modules, in dependency order.
+    units: Vec<Unit>,
+}
+
+impl<'a, F> Loader<'a, F>
+where
+    F: FnMut(&Path) -> anyhow::Result<Vec<String>>,
+{
+    /// Load the module `name` in `path` and its imports, and return the file of its unit.
+    fn visit(&mut self, name: String, path: PathBuf) -> Result<PathBuf, ModuleError> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if let Some(file) = self.done.get(&key) {
+            return Ok(file.clone());
+        }
+        if let Some(i) = self.stack.iter().position(|(_, p)| *p == key) {
+            let mut cycle: Vec<String> = self.stack[i..].iter().map(|(n, _)| n.clone()).collect();
+            cycle.push(name);
+            return Err(ModuleError::Cycle(cycle));
+        }
+
+        let imports =
+            (self.imports)(&path).map_err(|e| ModuleError::Load(path.display().to_string(), e))?;
+        self.stack.push((name.clone(), key.clone()));
+        let mut files = vec![];
+        for import in &imports {
+            let file = self.resolver.resolve(import, Some(&path)).ok_or_else(|| {
+                ModuleError::NotFound {
+                    name: import.clone(),
+                    from: path.display().to_string(),
+                }
+            })?;
+            files.push(self.visit(import.clone(), file)?);
+        }
+        self.stack.pop();
+
+        self.done.insert(key, path.clone());
+        self.units.push(Unit {
+            name,
+            path: path.clone(),
+            imports,
+            files,
+        });
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Write `files`, each a name and its imports, in a fresh directory, with one module per file.
+    fn program(test: &str, files: &[(&str, &[&str])]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("alef-module-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        for (file, imports) in files {
+            let path = dir.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, imports.join("\n")).unwrap();
+        }
+        dir
+    }
+
+    fn read_imports(path: &Path) -> anyhow::Result<Vec<String>> {
+        let text = fs::read_to_string(path)?;
+        Ok(text.lines().map(|l| l.to_string()).collect())
+    }
+
+    #[test]
+    fn resolve() {
+        let dir = program("resolve", &[("main.l", &[]), ("lib/util/str.l", &[])]);
+        let mut r = Resolver::new(SOURCE_EXT);
+        assert_eq!(r.file_name("util.str"), Path::new("util/str.l"));
+        assert_eq!(r.resolve("util.str", Some(&dir.join("main.l"))), None);
+
+        r.path(dir.join("lib"));
+        assert_eq!(
+            r.resolve("util.str", Some(&dir.join("main.l"))),
+            Some(dir.join("lib/util/str.l"))
+        );
+        assert_eq!(
+            r.resolve("main", Some(&dir.join("x.l"))),
+            Some(dir.join("main.l"))
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn order() {
+        let dir = program(
+            "order",
+            &[
+                ("main.l", &["io", "util.str"]),
+                ("io.l", &["util.str"]),
+                ("util/str.l", &[]),
+            ],
+        );
+        let units = load(
+            &dir.join("main.l"),
+            &Resolver::new(SOURCE_EXT),
+            read_imports,
+        )
+        .unwrap();
+        let names: Vec<&str> = units.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["util.str", "io", "main"]);
+        assert_eq!(units[1].imports, ["util.str"]);
+        assert_eq!(units[1].files, [units[0].path.clone()]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn aliases() {
+        // `lib/x.l` reaches `lib/util.l` as `util`, the root as `lib.util`.
+        let dir = program(
+            "aliases",
+            &[
+                ("main.l", &["lib.util", "lib.x"]),
+                ("lib/x.l", &["util"]),
+                ("lib/util.l", &[]),
+            ],
+        );
+        let units = load(
+            &dir.join("main.l"),
+            &Resolver::new(SOURCE_EXT),
+            read_imports,
+        )
+        .unwrap();
+        let names: Vec<&str> = units.iter().map(|u| u.name.as_str()).collect();
+        assert_eq!(names, ["lib.util", "lib.x", "main"]);
+        assert_eq!(units[1].imports, ["util"]);
+        assert_eq!(units[1].files, [units[0].path.clone()]);
+        assert_eq!(
+            units[2].files,
+            [units[0].path.clone(), units[1].path.clone()]
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn errors() {
+        let dir = program(
+            "errors",
+            &[
+                ("main.l", &["a"]),
+                ("a.l", &["b"]),
+                ("b.l", &["a"]),
+                ("lost.l", &["nowhere"]),
+            ],
+        );
+        let r = Resolver::new(SOURCE_EXT);
+        match load(&dir.join("main.l"), &r, read_imports) {
+            Err(e @ ModuleError::Cycle(_)) => {
+                assert_eq!(e.to_string(), "import cycle: a -> b -> a")
+            }
+            r => panic!("expected a cycle, got {:?}", r),
+        }
+        match load(&dir.join("lost.l"), &r, read_imports) {
+            Err(ModuleError::NotFound { name, .. }) => assert_eq!(name, "nowhere"),
+            r => panic!("expected a missing module, got {:?}", r),
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,8 @@
+use crate::diagnostic::codes;
 use crate::diagnostic::err::{Diagnostic, LabeledSpan};
 use crate::lex::token::Token;
 use crate::source::loc::{Location, Range};
+use crate::source::FileId;
 use std::fmt::Display;
 use thiserror::Error;
 
@@ -8,8 +10,8 @@ use thiserror::Error;
 #[derive(Error, Debug)]
 #[error("cannot parse declaration")]
 pub struct ParseDeclError {
-    /// The name of the source generating this read error.
-    pub source_name: String,
+    /// The source generating this error.
+    pub file: FileId,
 
     /// The position where this fault generated.
     pub range: Range,
@@ -20,7 +22,7 @@ pub struct ParseDeclError {
 
 impl Diagnostic for ParseDeclError {
     fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
-        Some(Box::new(std::any::type_name::<Self>()))
+        Some(Box::new(codes::E0301))
     }
 
     fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
@@ -55,6 +57,7 @@ impl Diagnostic for ParseDeclError {
             msg: Some("expected complex type definition, identifier or typedef".into()),
             start,
             end,
+            span: None,
         });
 
         Some(Box::new(v.into_iter()))
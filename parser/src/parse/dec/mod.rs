@@ -47,7 +47,7 @@ impl<'a> DeclParser<'a> {
             let tdef = self.type_def(scope);
         }
         diag(Box::new(ParseDeclError {
-            source_name: self.scanner.src.get_name(),
+            file: self.scanner.src.get_file(),
             range: Range {
                 start: t.get_range().start,
                 end: t.get_range().end,
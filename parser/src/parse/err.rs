@@ -0,0 +1,59 @@
+use crate::diagnostic::codes;
+use crate::diagnostic::err::{Diagnostic, LabeledSpan};
+use crate::diagnostic::fix::Suggestion;
+use crate::lex::token::Token;
+use crate::source::loc::{Location, Range};
+use crate::source::FileId;
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Error thrown when a token the grammar requires is missing or mismatched.
+#[derive(Error, Debug)]
+#[error("expected \"{expected}\", found {found}")]
+pub struct UnexpectedTokenError {
+    /// The source generating this error.
+    pub file: FileId,
+
+    /// The position where this fault generated.
+    pub range: Range,
+
+    /// The token required.
+    pub expected: String,
+
+    /// The token found instead.
+    pub found: Token,
+
+    /// The fix, if one is known.
+    pub fix: Option<Suggestion>,
+}
+
+impl Diagnostic for UnexpectedTokenError {
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0303))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn labels<'a>(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+        let start = self.range.start.get_col();
+        Some(Box::new(
+            vec![LabeledSpan {
+                msg: Some(format!("expected \"{}\"", self.expected)),
+                start,
+                end: start,
+                span: None,
+            }]
+            .into_iter(),
+        ))
+    }
+
+    fn suggestions(&self) -> Option<Vec<Suggestion>> {
+        self.fix.clone().map(|s| vec![s])
+    }
+}
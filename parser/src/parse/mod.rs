@@ -1,22 +1,34 @@
 #![allow(unused_assignments, dead_code, unused_imports, unused_variables)]
 pub mod dec;
+mod err;
 pub mod expr;
+pub mod module;
 pub mod stmt;
 pub mod ty;
 
 use crate::{
-    ast::node::{Node, Program},
-    lex::{cman::CommentManager, scan::Scanner},
-    source::MemoryBuffer,
+    ast::node::{Import, Node, Program},
+    diagnostic::{
+        diag,
+        fix::{Applicability, Edit, Suggestion},
+    },
+    lex::{
+        cman::CommentManager,
+        scan::Scanner,
+        token::{Delimiter, Token},
+    },
+    source::{loc::Range, MemoryBuffer},
 };
 use dec::DeclParser;
+use err::UnexpectedTokenError;
+use module::ModuleParser;
 
 macro_rules! expect_tok {
     ($self:ident,$is:ident, $tok:expr) => {
         let t = $self.scanner.ptok(0);
         if !t.$is($tok) {
             // @TODO ERRSYNC
-            todo!()
+            $crate::parse::unexpected($self.scanner, t, &$tok.to_string());
         } else {
             $self.scanner.tok();
         }
@@ -24,6 +36,56 @@ macro_rules! expect_tok {
 }
 pub(crate) use expect_tok;
 
+/// Report `found` where the token `expected` is required, with its fix, and recover: a closing
+/// delimiter of the wrong kind is taken for the expected one and consumed, anything else is left
+/// to be read as if the expected token had been there.
+pub(crate) fn unexpected(scanner: &mut Scanner, found: Token, expected: &str) {
+    let range = found.get_range();
+    let (fix, mismatched) = fix(&found, expected);
+    diag(Box::new(UnexpectedTokenError {
+        file: scanner.src.get_file(),
+        range: Range {
+            start: range.start,
+            end: range.end,
+            content: scanner.get_line(),
+        },
+        expected: expected.to_string(),
+        found,
+        fix,
+    }));
+
+    if mismatched {
+        scanner.tok();
+    }
+}
+
+/// The fix for `found` where `expected` is required, and whether `found` is a mismatched closing
+/// delimiter. Only the replacement of such a delimiter is machine-applicable: an insertion goes
+/// before `found`, which may be on a later line than the one missing the token.
+fn fix(found: &Token, expected: &str) -> (Option<Suggestion>, bool) {
+    let range = found.get_range();
+    match found {
+        Token::Delimiter(_, d @ (Delimiter::Rparen | Delimiter::Rbrack | Delimiter::Rbrace))
+            if matches!(expected, ")" | "]" | "}") =>
+        {
+            let fix = Edit::replace(range.start.as_ref(), 1, expected).map(|e| Suggestion {
+                msg: format!("replace `{}` with `{}`", d, expected),
+                edits: vec![e],
+                applicability: Applicability::MachineApplicable,
+            });
+            (fix, true)
+        }
+        _ => {
+            let fix = Edit::insert(range.start.as_ref(), expected).map(|e| Suggestion {
+                msg: format!("insert `{}`", expected),
+                edits: vec![e],
+                applicability: Applicability::MaybeIncorrect,
+            });
+            (fix, false)
+        }
+    }
+}
+
 /// The parser creates a new AST representing a source file.
 pub struct Parser {
     scanner: Scanner,
@@ -37,8 +99,14 @@ impl Parser {
         }
     }
 
+    /// Parse only the module header of the source: the name of the module and its imports.
+    pub fn header(&mut self) -> (Option<String>, Vec<Import>) {
+        ModuleParser::new(&mut self.scanner).header()
+    }
+
     /// Parse an Alef program and return a node::Program.
     pub fn parse(&mut self) -> Program {
+        let (module, imports) = self.header();
         let mut dec_parser = DeclParser::new(&mut self.scanner);
         let mut decs = std::vec! {};
 
@@ -46,6 +114,51 @@ impl Parser {
             decs.push(d);
         }
 
-        Program { decs }
+        Program {
+            module,
+            imports,
+            decs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unexpected_tokens() {
+        // A mismatched closing delimiter is consumed, as if it were the expected one.
+        let mbuf = MemoryBuffer::from_str("x] y", "unexpected.l".to_string());
+        let mut scanner = Scanner::new(Box::new(mbuf), None);
+        scanner.tok();
+        let t = scanner.ptok(0);
+        unexpected(&mut scanner, t, ")");
+        assert!(scanner.ptok(0).is_identifier());
+
+        // Anything else is left to be read.
+        let t = scanner.ptok(0);
+        unexpected(&mut scanner, t, ";");
+        assert!(scanner.ptok(0).is_identifier());
+    }
+
+    #[test]
+    fn insertions_are_not_machine_applicable() {
+        let mbuf = MemoryBuffer::from_str("x\n]\ny", "insertions.l".to_string());
+        let mut scanner = Scanner::new(Box::new(mbuf), None);
+        for _ in 0..3 {
+            let t = scanner.tok();
+            for expected in [";", ",", "(", ")", "]", "}", "{", "=", "of"] {
+                let (fix, mismatched) = fix(&t, expected);
+                let fix = fix.unwrap();
+                if mismatched {
+                    assert!(t.is_delimiter(Delimiter::Rbrack));
+                    assert_eq!(fix.applicability, Applicability::MachineApplicable);
+                } else {
+                    assert_eq!(fix.msg, format!("insert `{}`", expected));
+                    assert_ne!(fix.applicability, Applicability::MachineApplicable);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,73 @@
+use crate::diagnostic::codes;
+use crate::diagnostic::err::{Diagnostic, LabeledSpan};
+use crate::diagnostic::fix::Suggestion;
+use crate::source::loc::{Location, Range};
+use crate::source::FileId;
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Error thrown when the `module` or `import` declarations of a source are malformed.
+#[derive(Error, Debug)]
+#[error("cannot parse module header")]
+pub struct ParseHeaderError {
+    /// The source generating this error.
+    pub file: FileId,
+
+    /// The position where this fault generated.
+    pub range: Range,
+
+    /// What was expected instead.
+    pub msg: String,
+
+    /// How the declaration should read, if it helps.
+    pub help: Option<String>,
+
+    /// The fix, if one is known.
+    pub fix: Option<Suggestion>,
+}
+
+impl Diagnostic for ParseHeaderError {
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0302))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn context(&self) -> Option<String> {
+        Some(self.range.content.clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+
+    fn help<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        self.help
+            .clone()
+            .map(|h| Box::new(h) as Box<dyn Display + 'a>)
+    }
+
+    fn labels<'a>(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan>>> {
+        let start = self.range.start.get_col();
+        let end = match self.range.end {
+            Some(ref e) => e.get_col(),
+            None => start,
+        };
+
+        Some(Box::new(
+            vec![LabeledSpan {
+                msg: Some(self.msg.clone()),
+                start,
+                end,
+                span: None,
+            }]
+            .into_iter(),
+        ))
+    }
+
+    fn suggestions(&self) -> Option<Vec<Suggestion>> {
+        self.fix.clone().map(|s| vec![s])
+    }
+}
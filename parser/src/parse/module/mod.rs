@@ -0,0 +1,219 @@
+mod err;
+use crate::{
+    ast::node::Import,
+    diagnostic::{
+        diag,
+        fix::{Applicability, Edit, Suggestion},
+    },
+    lex::{
+        scan::Scanner,
+        token::{Delimiter, Keyword, Operator, Token},
+    },
+    source::loc::Range,
+};
+use err::*;
+
+/// The parser of the module header: the `module` and `import` declarations preceding every
+/// other declaration of a source.
+pub struct ModuleParser<'a> {
+    scanner: &'a mut Scanner,
+}
+
+impl<'a> ModuleParser<'a> {
+    /// Create a new ModuleParser.
+    pub fn new(scanner: &'a mut Scanner) -> ModuleParser<'a> {
+        ModuleParser { scanner }
+    }
+
+    /// Parse the header, returning the name of the module and its imports.
+    ///
+    /// `Header = [ "module" Name ";" ] { Import } . `
+    pub fn header(&mut self) -> (Option<String>, Vec<Import>) {
+        let mut module = None;
+        if self.scanner.ptok(0).is_keyword(Keyword::Module) {
+            self.scanner.tok();
+            if let Some((name, range)) = self.name() {
+                if self.semi(&range) {
+                    module = Some(name);
+                }
+            }
+        }
+
+        let mut imports = vec![];
+        while self.scanner.ptok(0).is_keyword(Keyword::Import) {
+            self.scanner.tok();
+            self.import(&mut imports);
+        }
+
+        (module, imports)
+    }
+
+    /// `Import = "import" Name { "," Name } ";" . `
+    fn import(&mut self, imports: &mut Vec<Import>) {
+        let mut names: Vec<Import> = vec![];
+        loop {
+            match self.name() {
+                Some((name, range)) => names.push(Import { name, range }),
+                None => return,
+            }
+            if !self.scanner.ptok(0).is_delimiter(Delimiter::Comma) {
+                break;
+            }
+            self.scanner.tok();
+        }
+
+        let last = names.last().unwrap().range.clone();
+        if self.semi(&last) {
+            imports.append(&mut names);
+        }
+    }
+
+    /// `Name = identifier { "." identifier } . `
+    fn name(&mut self) -> Option<(String, Range)> {
+        let first = self.scanner.ptok(0);
+        let mut name = match first {
+            Token::Identifier(_, ref id) => id.clone(),
+            t => {
+                self.error(&t, format!("expected a module name, found {}", t));
+                return None;
+            }
+        };
+        let mut last = self.scanner.tok();
+
+        while self.scanner.ptok(0).is_operator(Operator::Dot) {
+            self.scanner.tok();
+            match self.scanner.ptok(0) {
+                Token::Identifier(_, ref id) => {
+                    name.push('.');
+                    name.push_str(id);
+                    last = self.scanner.tok();
+                }
+                t => {
+                    self.error(
+                        &t,
+                        format!("expected an identifier after \".\", found {}", t),
+                    );
+                    return None;
+                }
+            }
+        }
+
+        let range = Range {
+            start: first.get_range().start,
+            end: last.get_range().end,
+            content: self.scanner.get_line(),
+        };
+        Some((name, range))
+    }
+
+    /// Consume the `;` ending a declaration after `after`, or report its absence. A `;` missing
+    /// at the end of a line is certainly the one to insert, and the declaration is read as if it
+    /// were there; otherwise the rest of the declaration is skipped.
+    fn semi(&mut self, after: &Range) -> bool {
+        let t = self.scanner.ptok(0);
+        if t.is_delimiter(Delimiter::Semi) {
+            self.scanner.tok();
+            return true;
+        }
+
+        let end = after.end.as_ref().unwrap_or(&after.start);
+        let eol = t.is_end() || t.get_range().start.get_line() > end.get_line();
+        let fix = Edit::insert(end.as_ref(), ";").map(|e| Suggestion {
+            msg: "insert `;`".to_string(),
+            edits: vec![e],
+            applicability: if eol {
+                Applicability::MachineApplicable
+            } else {
+                Applicability::MaybeIncorrect
+            },
+        });
+        self.report(&t, format!("expected \";\", found {}", t), None, fix);
+        if !eol {
+            self.skip();
+        }
+        eol
+    }
+
+    /// Report a malformed module name and skip to the end of the declaration.
+    fn error(&mut self, t: &Token, msg: String) {
+        let help = "module names are identifiers separated by dots, as in `import util.str;`";
+        self.report(t, msg, Some(help.to_string()), None);
+        self.skip();
+    }
+
+    fn report(&mut self, t: &Token, msg: String, help: Option<String>, fix: Option<Suggestion>) {
+        diag(Box::new(ParseHeaderError {
+            file: self.scanner.src.get_file(),
+            range: Range {
+                start: t.get_range().start,
+                end: t.get_range().end,
+                content: self.scanner.get_line(),
+            },
+            msg,
+            help,
+            fix,
+        }));
+    }
+
+    /// Skip to the end of the declaration.
+    fn skip(&mut self) {
+        loop {
+            let t = self.scanner.ptok(0);
+            if t.is_end() {
+                break;
+            }
+            self.scanner.tok();
+            if t.is_delimiter(Delimiter::Semi) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::MemoryBuffer;
+
+    fn header(src: &str) -> (Option<String>, Vec<String>) {
+        let mbuf = MemoryBuffer::from_str(src, "header.l".to_string());
+        let mut scanner = Scanner::new(Box::new(mbuf), None);
+        let (module, imports) = ModuleParser::new(&mut scanner).header();
+        (module, imports.into_iter().map(|i| i.name).collect())
+    }
+
+    #[test]
+    fn headers() {
+        assert_eq!(header("int x;"), (None, vec![]));
+        assert_eq!(
+            header("module main;\nimport io;\nimport util.str, util.list;\nint x;"),
+            (
+                Some("main".to_string()),
+                vec![
+                    "io".to_string(),
+                    "util.str".to_string(),
+                    "util.list".to_string()
+                ]
+            )
+        );
+
+        // Malformed imports are dropped, the following ones are still read.
+        assert_eq!(
+            header("import util.;\nimport io;\n"),
+            (None, vec!["io".to_string()])
+        );
+
+        // A `;` missing at the end of a line is taken as inserted, one missing elsewhere is not.
+        assert_eq!(
+            header("module main\nimport io\nimport util.str;\n"),
+            (
+                Some("main".to_string()),
+                vec!["io".to_string(), "util.str".to_string()]
+            )
+        );
+        assert_eq!(
+            header("import io str;\nimport util;\n"),
+            (None, vec!["util".to_string()])
+        );
+    }
+}
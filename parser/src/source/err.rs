@@ -1,13 +1,16 @@
+use crate::diagnostic::codes;
 use crate::diagnostic::err::Diagnostic;
-use crate::source::loc::Range;
+use crate::source::loc::{Location, Range};
+use crate::source::FileId;
+use std::fmt::Display;
 use thiserror::Error;
 
 /// Error thrown by the source when failing to read from a MemoryBuffer.
 #[derive(Error, Debug)]
 #[error("can't read from source")]
 pub struct SourceReadError {
-    /// The name of the source generating this read error.
-    pub source_name: String,
+    /// The source generating this error.
+    pub file: FileId,
 
     /// The position where this fault generated.
     pub range: Range,
@@ -19,7 +22,19 @@ pub struct SourceReadError {
     pub msg: String,
 }
 
-impl Diagnostic for SourceReadError {}
+impl Diagnostic for SourceReadError {
+    fn code<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(codes::E0001))
+    }
+
+    fn loc<'a>(&self) -> Option<Box<dyn Location + 'a>> {
+        Some(self.range.start.box_clone())
+    }
+
+    fn reason<'a>(&self) -> Option<Box<dyn Display + 'a>> {
+        Some(Box::new(self.msg.clone()))
+    }
+}
 
 #[cfg(test)]
 mod tests {}
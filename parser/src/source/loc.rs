@@ -1,6 +1,7 @@
 //! Locations are simple elements that contain a reference to a precise column-line-index position
-//! in a determinate MemoryBuffer.
+//! in a determinate source of the SourceManager.
 
+use super::sman::{sman, Expansion, FileId, Include};
 use std::fmt::{Debug, Display, Formatter, Result};
 
 /// This trait is the abstract representation of a location in a source file.
@@ -20,6 +21,21 @@ pub trait Location: Display {
         0
     }
 
+    /// Get the source of the location in the SourceManager.
+    fn get_file(&self) -> Option<FileId> {
+        None
+    }
+
+    /// Get the files including the one of the location, the outermost last.
+    fn get_includes(&self) -> Vec<Include> {
+        Vec::new()
+    }
+
+    /// Get the macro expansion the location is part of.
+    fn get_expansion(&self) -> Option<Expansion> {
+        None
+    }
+
     /// Return true if the location is the special predeclared location.
     fn is_predeclared(&self) -> bool {
         false
@@ -104,27 +120,48 @@ pub fn generate_unknown_range() -> Range {
     }
 }
 
-/// The default implementation of locations.
-#[derive(Debug)]
+/// The default implementation of locations: a byte offset in a source of the SourceManager. The
+/// line and the column are computed when asked for, and they are the presumed ones: if the
+/// source carries line markers, they refer to the file the location originally came from.
+#[derive(Debug, Clone, Copy)]
 pub struct DefaultLocation {
-    pub line: usize,
-    pub col: usize,
+    pub file: FileId,
     pub index: usize,
-    pub source_name: String,
+}
+
+impl DefaultLocation {
+    fn line_col(&self) -> (usize, usize) {
+        let sm = sman();
+        let p = sm.presumed(self.file, self.index);
+        (p.line, p.col)
+    }
 }
 
 impl Location for DefaultLocation {
     fn get_line(&self) -> usize {
-        self.line
+        self.line_col().0
     }
 
     fn get_col(&self) -> usize {
-        self.col
+        self.line_col().1
     }
 
     fn get_mbuf_index(&self) -> usize {
         self.index
     }
+
+    fn get_file(&self) -> Option<FileId> {
+        Some(self.file)
+    }
+
+    fn get_includes(&self) -> Vec<Include> {
+        sman().presumed(self.file, self.index).includes.to_vec()
+    }
+
+    fn get_expansion(&self) -> Option<Expansion> {
+        sman().presumed(self.file, self.index).expansion.cloned()
+    }
+
     fn is_predeclared(&self) -> bool {
         false
     }
@@ -134,18 +171,45 @@ impl Location for DefaultLocation {
     }
 
     fn box_clone(&self) -> Box<dyn Location> {
-        Box::new(DefaultLocation {
-            line: self.line,
-            col: self.col,
-            source_name: self.source_name.clone(),
-            index: self.index,
-        })
+        Box::new(*self)
     }
 }
 
 impl Display for DefaultLocation {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "{}:{}:{}", self.source_name, self.line, self.col)
+        let sm = sman();
+        let p = sm.presumed(self.file, self.index);
+        write!(f, "{}:{}:{}", p.name, p.line, p.col)
+    }
+}
+
+/// The bytes `start..end` of a source, which may span several lines. Unlike ranges, spans are
+/// plain data and can point anywhere, e.g. at a declaration in another file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// The span of a range, if it is in a source.
+    pub fn of(range: &Range) -> Option<Span> {
+        let start = range.start.get_mbuf_index();
+        let end = range.end.as_ref().map_or(start, |e| e.get_mbuf_index());
+        Some(Span {
+            file: range.start.get_file()?,
+            start,
+            end: end.max(start),
+        })
+    }
+
+    /// The location the span starts at.
+    pub fn loc(&self) -> DefaultLocation {
+        DefaultLocation {
+            file: self.file,
+            index: self.start,
+        }
     }
 }
 
@@ -4,42 +4,36 @@
 //! because it prevents diagnostic and general faults when a user updates the source during the
 //! compilation and because it may be faster than other alternatives (e.g. reading every time from
 //! a file.)
+//!
+//! Every source is owned by the SourceManager, which identifies it with a FileId; a MemoryBuffer
+//! reads a source, and the locations it creates only refer to the source by its FileId.
 
 pub mod err;
 pub mod loc;
+pub mod sman;
 
 use self::loc::{DefaultLocation, Location, Range};
+pub use self::sman::{FileId, SourceManager, SRCMAN};
 use err::SourceReadError;
 use log::trace;
-use std::io;
+use sman::{sman, sman_mut};
+use std::sync::Arc;
 
-/// The in-memory representation of a source. The underlying content representation is a simple
-/// primitive `str`.
+/// The in-memory representation of a source. The content itself is owned by the SourceManager,
+/// a MemoryBuffer only keeps track of how much of it was read.
 #[derive(Debug)]
 pub struct MemoryBuffer {
-    /// The content of the MemoryBuffer.
-    content: String,
-
-    /// The length of the content.
-    len: usize,
+    /// The source in the SourceManager.
+    file: FileId,
 
-    /// The name of the source.
-    name: String,
+    /// The content of the MemoryBuffer.
+    content: Arc<str>,
 
     /// The index of the byte read up to.
     nindex: usize,
 
     /// The index of the last '\n' seen.
     nlindex: usize,
-
-    /// The current line.
-    line: usize,
-
-    /// The current column.
-    col: usize,
-
-    /// (line,col) -> index map.
-    map: std::collections::HashMap<(usize, usize), usize>,
 }
 
 /// The EOF char is returned when reading from a MemoryBuffer after all the chars in the source
@@ -48,44 +42,35 @@ pub const EOF_CHAR: char = '\u{0}';
 
 impl std::fmt::Display for MemoryBuffer {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}:[{}]", self.name.clone(), self.len)
+        write!(f, "{}:[{}]", self.get_name(), self.content.len())
     }
 }
 
 impl MemoryBuffer {
-    /// Create a new MemoryBuffer from a file.
-    pub fn from_file(filename: String) -> anyhow::Result<MemoryBuffer> {
-        trace!("create mbuf from file");
-        let mut file = std::fs::File::open(filename.clone())?;
-        let content = io::read_to_string(&mut file)?;
-        log::trace!("created mbuf with content {:?}", content);
-        let len = content.len();
-        Ok(MemoryBuffer {
+    /// Create a new MemoryBuffer reading `content`, the content of the source `file`.
+    pub(crate) fn new(file: FileId, content: Arc<str>) -> MemoryBuffer {
+        MemoryBuffer {
+            file,
             content,
-            len,
-            name: filename,
             nindex: 0,
             nlindex: 0,
-            line: 1,
-            col: 1,
-            map: std::collections::HashMap::new(),
-        })
+        }
     }
 
-    /// Create a new MemoryBuffer from a string.
+    /// Create a new MemoryBuffer from a file, adding it to the SourceManager.
+    pub fn from_file(filename: String) -> anyhow::Result<MemoryBuffer> {
+        trace!("create mbuf from file");
+        let mut sm = sman_mut();
+        let file = sm.add_file(filename)?;
+        Ok(sm.buffer(file))
+    }
+
+    /// Create a new MemoryBuffer from a string, adding it to the SourceManager.
     pub fn from_str(content: &str, name: String) -> MemoryBuffer {
         trace!("create mbuf from string");
-        let l = content.len();
-        MemoryBuffer {
-            len: l,
-            name,
-            content: content.to_string(),
-            nindex: 0,
-            nlindex: 0,
-            line: 1,
-            col: 1,
-            map: std::collections::HashMap::new(),
-        }
+        let mut sm = sman_mut();
+        let file = sm.add_str(content, name);
+        sm.buffer(file)
     }
 
     /// Fetch and consume the next unread char in the source or return EOF_CHAR if all are read.
@@ -101,13 +86,6 @@ impl MemoryBuffer {
             let c = s.chars().next();
             if let Some(c) = c {
                 self.nindex += c.len_utf8();
-                if c == '\n' {
-                    self.col = 1;
-                    self.line += 1;
-                } else {
-                    self.col += 1;
-                }
-                self.map.insert((self.line, self.col), self.nindex);
                 if c == '\n' {
                     self.nlindex = self.nindex;
                 }
@@ -118,7 +96,7 @@ impl MemoryBuffer {
         let lstart = self.get_location();
         let range = self.get_range(lstart.as_ref(), None);
         Err(SourceReadError {
-            source_name: self.name.clone(),
+            file: self.file,
             range,
             index: self.nindex,
             msg: "cannot read next character from source!".to_string(),
@@ -145,20 +123,18 @@ impl MemoryBuffer {
         let lstart = self.get_location();
         let range = self.get_range(lstart.as_ref(), None);
         Err(SourceReadError {
-            source_name: self.name.clone(),
+            file: self.file,
             range,
             index: self.nindex,
             msg: format!("cannot peek {}-th character from source!", l),
         })
     }
 
-    /// Create a location for the current column-line-index position in the source.
+    /// Create a location for the current position in the source.
     pub fn get_location(&mut self) -> Box<dyn Location> {
         trace!("MemoryBuffer::get_location");
         Box::new(DefaultLocation {
-            line: self.line,
-            col: self.col,
-            source_name: self.name.clone(),
+            file: self.file,
             index: self.nindex,
         })
     }
@@ -183,28 +159,18 @@ impl MemoryBuffer {
             start,
             end.is_none()
         );
-        let start_index = if let Some(index) = self.map.get(&(start.get_line(), start.get_col())) {
-            *index
-        } else {
-            0
-        };
+        let start_index = start.get_mbuf_index();
 
         let end: Box<dyn Location> = if let Some(end) = end {
             end.box_clone()
         } else {
             Box::new(DefaultLocation {
-                line: self.line,
-                col: self.col,
-                source_name: self.name.clone(),
+                file: self.file,
                 index: self.nindex,
             })
         };
 
-        let end_index = if let Some(index) = self.map.get(&(end.get_line(), end.get_col())) {
-            *index
-        } else {
-            0
-        };
+        let end_index = end.get_mbuf_index();
 
         let mut content = self.content[start_index..end_index].to_string();
 
@@ -231,7 +197,12 @@ impl MemoryBuffer {
 
     /// Get the name of the source.
     pub fn get_name(&self) -> String {
-        self.name.clone()
+        sman().name(self.file).to_string()
+    }
+
+    /// Get the source in the SourceManager.
+    pub fn get_file(&self) -> FileId {
+        self.file
     }
 }
 
@@ -0,0 +1,591 @@
+//! The source manager owns the content of every source read during a compilation and hands out
+//! compact `FileId`s referring to them. Locations only keep a `FileId` and a byte offset: the
+//! name of the source, the line and the column are looked up in the source manager when they
+//! are needed, that is mostly when a diagnostic is rendered.
+//!
+//! Sources are never released, so a `FileId` stays valid until the end of the compilation.
+//!
+//! The output of the C preprocessor carries lines of the form `# linenum "filename" flags`,
+//! stating that the line that follows is line `linenum` of `filename`. The scanner records them
+//! as `LineMarker`s, and the source manager uses them to translate an offset in the preprocessed
+//! source into its _presumed_ location: the file and the line it originally came from, along
+//! with the chain of files that included it.
+//!
+//! The built-in preprocessor instead records the _origin_ of every piece of its output: the
+//! offset in the source it was copied from, or the macro invocation it is the expansion of.
+
+use super::MemoryBuffer;
+use log::trace;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::sync::{Arc, LazyLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// The source manager shared by the scanner, the parser and the diagnostics.
+pub static SRCMAN: LazyLock<RwLock<SourceManager>> =
+    LazyLock::new(|| RwLock::new(SourceManager::new()));
+
+/// Lock the shared source manager for reading.
+pub fn sman() -> RwLockReadGuard<'static, SourceManager> {
+    SRCMAN.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Lock the shared source manager for writing.
+pub fn sman_mut() -> RwLockWriteGuard<'static, SourceManager> {
+    SRCMAN.write().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The identifier of a source in the source manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// The index of the source in the source manager.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl Display for FileId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// The flag of a line marker entering an included file.
+pub const FLAG_ENTER: u8 = 1;
+
+/// The flag of a line marker returning to the including file.
+pub const FLAG_RETURN: u8 = 2;
+
+/// The flag of a line marker for text coming from a system header.
+pub const FLAG_SYSTEM: u8 = 3;
+
+/// The flag of a line marker for text to be treated as wrapped in an `extern "C"` block, only
+/// meaningful to C++ and ignored.
+pub const FLAG_EXTERN_C: u8 = 4;
+
+/// A `# linenum "filename" flags` line of preprocessed source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMarker {
+    /// The line number of the line following the marker.
+    pub line: usize,
+
+    /// The file the line following the marker comes from, or none to keep the current one.
+    pub name: Option<String>,
+
+    /// The flags, some of `FLAG_ENTER`, `FLAG_RETURN`, `FLAG_SYSTEM` and `FLAG_EXTERN_C`.
+    pub flags: Vec<u8>,
+}
+
+/// A file including another one, at a given line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Include {
+    pub name: Arc<str>,
+    pub line: usize,
+}
+
+/// Where a location comes from, according to the line markers of its source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Presumed<'a> {
+    /// The file name.
+    pub name: &'a str,
+
+    /// The line, starting at 1.
+    pub line: usize,
+
+    /// The column, starting at 1.
+    pub col: usize,
+
+    /// The files including `name`, the outermost last.
+    pub includes: &'a [Include],
+
+    /// Whether the location is in a system header.
+    pub system: bool,
+
+    /// The macro expansion the location is part of.
+    pub expansion: Option<&'a Expansion>,
+}
+
+/// A macro expansion in the output of the preprocessor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    /// The name of the macro.
+    pub name: Arc<str>,
+
+    /// The source and the offset where the macro was defined, none if it was predefined.
+    pub def: Option<(FileId, usize)>,
+}
+
+/// Where a part of a source produced by the preprocessor comes from.
+#[derive(Debug, Clone)]
+pub struct Origin {
+    /// The offset in the produced source where the part begins.
+    pub offset: usize,
+
+    /// The source the part comes from.
+    pub file: FileId,
+
+    /// The offset in `file` the part was copied from, or of the name of the macro invocation
+    /// it is the expansion of.
+    pub src: usize,
+
+    /// The macro expansion, if the part is one.
+    pub expansion: Option<Arc<Expansion>>,
+
+    /// The files including `file`, the outermost last.
+    pub includes: Arc<[Include]>,
+}
+
+/// A line marker, once applied to the source.
+#[derive(Debug)]
+struct Directive {
+    /// The offset of the line the marker applies to.
+    offset: usize,
+
+    /// The physical line at `offset`.
+    phys: usize,
+
+    /// The presumed line at `offset`.
+    line: usize,
+
+    name: Arc<str>,
+    includes: Arc<[Include]>,
+    system: bool,
+}
+
+/// A source owned by the source manager.
+#[derive(Debug)]
+struct SourceFile {
+    /// The name of the source.
+    name: String,
+
+    /// The content of the source.
+    content: Arc<str>,
+
+    /// The byte offset where each line starts.
+    lines: Vec<usize>,
+
+    /// The line markers, by offset.
+    directives: Vec<Directive>,
+
+    /// The origins of the parts of the source, by offset, if the preprocessor produced it.
+    origins: Vec<Origin>,
+}
+
+impl SourceFile {
+    fn new(name: String, content: Arc<str>) -> SourceFile {
+        let mut lines = vec![0];
+        lines.extend(content.match_indices('\n').map(|(i, _)| i + 1));
+        SourceFile {
+            name,
+            content,
+            lines,
+            directives: Vec::new(),
+            origins: Vec::new(),
+        }
+    }
+
+    /// The physical line and column of `offset`.
+    fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.content.len());
+        let line = self.lines.partition_point(|&start| start <= offset);
+        let start = self.lines[line - 1];
+        let col = self
+            .content
+            .get(start..offset)
+            .map_or(offset - start, |s| s.chars().count());
+        (line, col + 1)
+    }
+
+    /// The directive in effect at `offset`.
+    fn directive(&self, offset: usize) -> Option<&Directive> {
+        let i = self.directives.partition_point(|d| d.offset <= offset);
+        i.checked_sub(1).map(|i| &self.directives[i])
+    }
+
+    fn presumed(&self, offset: usize) -> Presumed<'_> {
+        let (line, col) = self.line_col(offset);
+        match self.directive(offset) {
+            Some(d) => Presumed {
+                name: &d.name,
+                line: d.line + line - d.phys,
+                col,
+                includes: &d.includes,
+                system: d.system,
+                expansion: None,
+            },
+            None => Presumed {
+                name: &self.name,
+                line,
+                col,
+                includes: &[],
+                system: false,
+                expansion: None,
+            },
+        }
+    }
+
+    /// The origin of `offset`.
+    fn origin(&self, offset: usize) -> Option<&Origin> {
+        let i = self.origins.partition_point(|o| o.offset <= offset);
+        i.checked_sub(1).map(|i| &self.origins[i])
+    }
+}
+
+/// The collection of every source of the compilation.
+#[derive(Debug, Default)]
+pub struct SourceManager {
+    /// The sources, indexed by `FileId`.
+    files: Vec<SourceFile>,
+
+    /// Sources read from a file, by file name.
+    names: HashMap<String, FileId>,
+}
+
+impl SourceManager {
+    /// Create an empty source manager.
+    pub fn new() -> SourceManager {
+        SourceManager::default()
+    }
+
+    fn push(&mut self, name: String, content: Arc<str>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        trace!("SourceManager: {} is {}", name, id);
+        self.files.push(SourceFile::new(name, content));
+        id
+    }
+
+    /// Read the file `filename`, unless it was already read, and return its identifier.
+    pub fn add_file(&mut self, filename: String) -> io::Result<FileId> {
+        if let Some(id) = self.names.get(&filename) {
+            return Ok(*id);
+        }
+        let content = std::fs::read_to_string(&filename)?;
+        let id = self.push(filename.clone(), content.into());
+        self.names.insert(filename, id);
+        Ok(id)
+    }
+
+    /// Add a source with content `content` named `name`. Every call creates a new source, even
+    /// if one with the same name exists.
+    pub fn add_str(&mut self, content: &str, name: String) -> FileId {
+        self.push(name, content.into())
+    }
+
+    fn file(&self, id: FileId) -> Option<&SourceFile> {
+        self.files.get(id.index())
+    }
+
+    /// The number of sources.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Return true if there are no sources.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// The identifier of the file named `filename`, if it was read.
+    pub fn lookup(&self, filename: &str) -> Option<FileId> {
+        self.names.get(filename).copied()
+    }
+
+    /// The name of the source `id`.
+    pub fn name(&self, id: FileId) -> &str {
+        self.file(id).map_or("<unknown>", |f| f.name.as_str())
+    }
+
+    /// The content of the source `id`.
+    pub fn content(&self, id: FileId) -> Arc<str> {
+        self.file(id)
+            .map_or_else(|| "".into(), |f| f.content.clone())
+    }
+
+    /// The line and the column, both starting at 1, of the byte `offset` of the source `id`.
+    /// Columns count characters, not bytes.
+    pub fn line_col(&self, id: FileId, offset: usize) -> (usize, usize) {
+        self.file(id).map_or((0, 0), |f| f.line_col(offset))
+    }
+
+    /// The presumed location of the byte `offset` of the source `id`, taking its line markers
+    /// into account. If the preprocessor produced the source, this is the presumed location of
+    /// the origin of `offset`; for a macro expansion, that of the name of the macro invocation.
+    pub fn presumed(&self, id: FileId, offset: usize) -> Presumed<'_> {
+        let f = match self.file(id) {
+            Some(f) => f,
+            None => {
+                return Presumed {
+                    name: "<unknown>",
+                    line: 0,
+                    col: 0,
+                    includes: &[],
+                    system: false,
+                    expansion: None,
+                }
+            }
+        };
+        match f.origin(offset) {
+            // The origin is always an earlier source, so this terminates.
+            Some(o) if o.file != id => {
+                let src = match o.expansion {
+                    Some(_) => o.src,
+                    None => o.src + (offset - o.offset),
+                };
+                let mut p = self.presumed(o.file, src);
+                if p.includes.is_empty() {
+                    p.includes = &o.includes;
+                }
+                p.expansion = o.expansion.as_deref();
+                p
+            }
+            _ => f.presumed(offset),
+        }
+    }
+
+    /// The source and the offset the byte `offset` of the source `id` was copied from, following
+    /// the output of the preprocessor back to the sources it read. Bytes of macro expansions
+    /// were not copied from anywhere, and have none.
+    pub fn spelling(&self, id: FileId, offset: usize) -> Option<(FileId, usize)> {
+        let f = self.file(id)?;
+        match f.origin(offset) {
+            Some(o) if o.file != id => match o.expansion {
+                Some(_) => None,
+                None => self.spelling(o.file, o.src + (offset - o.offset)),
+            },
+            _ => Some((id, offset)),
+        }
+    }
+
+    /// Set the origins of the parts of the source `id`, produced by the preprocessor.
+    pub fn set_origins(&mut self, id: FileId, origins: Vec<Origin>) {
+        if let Some(f) = self.files.get_mut(id.index()) {
+            f.origins = origins;
+        }
+    }
+
+    /// Apply `marker`, found at offset `at` of the source `id`, to the line starting at offset
+    /// `next`. Markers must be added in the order they appear in the source; those already
+    /// applied, when a source is read again, are ignored.
+    pub fn add_line_marker(&mut self, id: FileId, at: usize, next: usize, marker: LineMarker) {
+        let f = match self.files.get_mut(id.index()) {
+            Some(f) => f,
+            None => return,
+        };
+        if f.directives.last().is_some_and(|d| d.offset >= next) {
+            return;
+        }
+        let here = f.presumed(at);
+        let mut includes = here.includes.to_vec();
+        if marker.flags.contains(&FLAG_ENTER) {
+            includes.insert(
+                0,
+                Include {
+                    name: here.name.into(),
+                    line: here.line,
+                },
+            );
+        } else if marker.flags.contains(&FLAG_RETURN) && !includes.is_empty() {
+            includes.remove(0);
+        }
+        let name = match marker.name {
+            Some(name) => name.into(),
+            None => here.name.into(),
+        };
+        trace!(
+            "SourceManager: line marker {} {:?} {:?} in {}",
+            marker.line,
+            name,
+            marker.flags,
+            id
+        );
+
+        let d = Directive {
+            offset: next,
+            phys: f.line_col(next).0,
+            line: marker.line,
+            name,
+            includes: includes.into(),
+            system: marker.flags.contains(&FLAG_SYSTEM),
+        };
+        f.directives.push(d);
+    }
+
+    /// The `line`-th line of the source `id`, starting at 1, without its newline.
+    pub fn line(&self, id: FileId, line: usize) -> Option<&str> {
+        let f = self.file(id)?;
+        let start = *f.lines.get(line.checked_sub(1)?)?;
+        let end = f.lines.get(line).map_or(f.content.len(), |&end| end - 1);
+        f.content.get(start..end)
+    }
+
+    /// The byte offset where the line `line`, starting at 1, of the source `id` starts.
+    pub fn line_start(&self, id: FileId, line: usize) -> Option<usize> {
+        let f = self.file(id)?;
+        f.lines.get(line.checked_sub(1)?).copied()
+    }
+
+    /// The byte offset of the `col`-th character of the line `line` of the source `id`, both
+    /// starting at 1: the inverse of `line_col`. A column past the end of the line is its end.
+    pub fn offset(&self, id: FileId, line: usize, col: usize) -> Option<usize> {
+        let start = self.line_start(id, line)?;
+        let text = self.line(id, line)?;
+        let i = text
+            .char_indices()
+            .nth(col.saturating_sub(1))
+            .map_or(text.len(), |(i, _)| i);
+        Some(start + i)
+    }
+
+    /// Create a MemoryBuffer reading the source `id` from the start.
+    pub fn buffer(&self, id: FileId) -> MemoryBuffer {
+        MemoryBuffer::new(id, self.content(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        let mut sm = SourceManager::new();
+        let a = sm.add_str("ab\ncàd\n\nx", "a".into());
+        let b = sm.add_str("", "b".into());
+        assert_ne!(a, b);
+        assert_eq!(sm.name(a), "a");
+        assert_eq!(sm.name(b), "b");
+
+        assert_eq!(sm.line_col(a, 0), (1, 1));
+        assert_eq!(sm.line_col(a, 2), (1, 3));
+        assert_eq!(sm.line_col(a, 3), (2, 1));
+        // 'à' is two bytes long.
+        assert_eq!(sm.line_col(a, 6), (2, 3));
+        assert_eq!(sm.line_col(a, 8), (3, 1));
+        assert_eq!(sm.line_col(a, 9), (4, 1));
+        assert_eq!(sm.line_col(a, 100), (4, 2));
+        assert_eq!(sm.line_col(b, 0), (1, 1));
+
+        assert_eq!(sm.line(a, 1), Some("ab"));
+        assert_eq!(sm.line(a, 2), Some("càd"));
+        assert_eq!(sm.line(a, 3), Some(""));
+        assert_eq!(sm.line(a, 4), Some("x"));
+        assert_eq!(sm.line(a, 5), None);
+        assert_eq!(sm.line(a, 0), None);
+
+        assert_eq!(sm.offset(a, 2, 3), Some(6));
+        assert_eq!(sm.line_col(a, 6), (2, 3));
+        assert_eq!(sm.offset(a, 2, 9), Some(7));
+        assert_eq!(sm.offset(a, 4, 1), Some(9));
+        assert_eq!(sm.offset(a, 5, 1), None);
+    }
+
+    #[test]
+    fn files() -> io::Result<()> {
+        let mut sm = SourceManager::new();
+        let path = format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR"));
+        let a = sm.add_file(path.clone())?;
+        let b = sm.add_file(path.clone())?;
+        assert_eq!(a, b);
+        assert_eq!(sm.lookup(&path), Some(a));
+        assert_eq!(sm.len(), 1);
+        assert!(sm.content(a).starts_with("[package]"));
+
+        let c = sm.add_str("", path);
+        assert_ne!(a, c);
+        assert!(sm.add_file("/nonexistent/file.l".into()).is_err());
+        Ok(())
+    }
+
+    fn marker(line: usize, name: &str, flags: &[u8]) -> LineMarker {
+        LineMarker {
+            line,
+            name: Some(name.into()),
+            flags: flags.into(),
+        }
+    }
+
+    #[test]
+    fn line_markers() {
+        let src = "# 1 \"main.l\"\na\nb\n# 1 \"foo.h\" 1 3\nfoo\n# 5 \"main.l\" 2\nc\n";
+        let mut sm = SourceManager::new();
+        let id = sm.add_str(src, "main.i".into());
+
+        let at = |s: &str| src.find(s).unwrap();
+        let next = |s: &str| at(s) + src[at(s)..].find('\n').unwrap() + 1;
+
+        // Before any marker, locations are physical.
+        let p = sm.presumed(id, 0);
+        assert_eq!((p.name, p.line, p.col), ("main.i", 1, 1));
+
+        sm.add_line_marker(id, 0, next("# 1 \"main"), marker(1, "main.l", &[]));
+        sm.add_line_marker(id, at("# 1 \"foo"), next("# 1 \"foo"), marker(1, "foo.h", &[1, 3]));
+        sm.add_line_marker(id, at("# 5"), next("# 5"), marker(5, "main.l", &[2]));
+
+        let p = sm.presumed(id, at("b"));
+        assert_eq!((p.name, p.line, p.col), ("main.l", 2, 1));
+        assert!(p.includes.is_empty());
+        assert!(!p.system);
+
+        let p = sm.presumed(id, at("\nfoo\n") + 3);
+        assert_eq!((p.name, p.line, p.col), ("foo.h", 1, 3));
+        assert_eq!(
+            p.includes,
+            &[Include {
+                name: "main.l".into(),
+                line: 3
+            }]
+        );
+        assert!(p.system);
+
+        let p = sm.presumed(id, at("c"));
+        assert_eq!((p.name, p.line), ("main.l", 5));
+        assert!(p.includes.is_empty());
+        assert!(!p.system);
+
+        // Physical locations are unaffected.
+        assert_eq!(sm.line_col(id, at("c")), (7, 1));
+    }
+
+    #[test]
+    fn origins() {
+        let mut sm = SourceManager::new();
+        let raw = sm.add_str("#define N 10\nint a[N];\n", "a.l".into());
+        let out = sm.add_str("\nint a[10];\n", "a.l".into());
+        let expansion = Arc::new(Expansion {
+            name: "N".into(),
+            def: Some((raw, 8)),
+        });
+        let origin = |offset, src, expansion| Origin {
+            offset,
+            file: raw,
+            src,
+            expansion,
+            includes: Arc::from([]),
+        };
+        sm.set_origins(
+            out,
+            vec![
+                origin(0, 12, None),
+                origin(7, 19, Some(expansion.clone())),
+                origin(9, 20, None),
+            ],
+        );
+
+        let p = sm.presumed(out, 1);
+        assert_eq!((p.name, p.line, p.col, p.expansion), ("a.l", 2, 1, None));
+        // Both characters of the expansion are at the invocation.
+        for offset in [7, 8] {
+            let p = sm.presumed(out, offset);
+            assert_eq!((p.line, p.col), (2, 7));
+            assert_eq!(p.expansion, Some(&*expansion));
+        }
+        let p = sm.presumed(out, 9);
+        assert_eq!((p.line, p.col, p.expansion), (2, 8, None));
+
+        assert_eq!(sm.spelling(out, 1), Some((raw, 13)));
+        assert_eq!(sm.spelling(out, 7), None);
+        assert_eq!(sm.spelling(out, 9), Some((raw, 20)));
+    }
+}
@@ -36,7 +36,11 @@ pub enum BasicType {
     Float,
     Lint,
     Ulint,
-    Chan { variants: VariantsList },
+    Chan {
+        variants: VariantsList,
+        /// The buffer size of the `ChanBufDim`, if any: `chan(int)[8]`.
+        buffer: Option<Box<expr::Expr>>,
+    },
     Poly { name: String },
 }
 
@@ -52,13 +56,49 @@ impl Hash for BasicType {
             BasicType::Float => "float".hash(state),
             BasicType::Lint => "lint".hash(state),
             BasicType::Ulint => "ulint".hash(state),
-            BasicType::Chan { variants } => variants.hash(state),
+            BasicType::Chan { variants, buffer } => {
+                variants.hash(state);
+                buffer.hash(state);
+            }
             BasicType::Poly { name } => name.hash(state),
         };
         state.finish();
     }
 }
 
+impl Display for BasicType {
+    /// Format a basic type as it is written in Alef sources.
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            BasicType::Void => write!(f, "void"),
+            BasicType::Byte => write!(f, "byte"),
+            BasicType::Sint => write!(f, "sint"),
+            BasicType::Usint => write!(f, "usint"),
+            BasicType::Int => write!(f, "int"),
+            BasicType::Uint => write!(f, "uint"),
+            BasicType::Float => write!(f, "float"),
+            BasicType::Lint => write!(f, "lint"),
+            BasicType::Ulint => write!(f, "ulint"),
+            BasicType::Chan { variants, buffer } => {
+                write!(f, "chan(")?;
+                for (i, v) in variants.get_variants().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, ")")?;
+                // Expressions have no text form yet.
+                if buffer.is_some() {
+                    write!(f, "[...]")?;
+                }
+                Ok(())
+            }
+            BasicType::Poly { name } => write!(f, "{}", name),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Hash)]
 pub struct PointerType {
     pub points_to: Box<Type>,
@@ -0,0 +1,354 @@
+//! Channels keep up to `cap` buffered elements and two queues of blocked threads, one for
+//! senders and one for receivers. A send hands the element directly to a blocked receiver if
+//! there is one, otherwise it is buffered if there is room, otherwise the sender blocks; a
+//! receive is symmetric, and refills the buffer from the first blocked sender.
+//!
+//! The state of every channel is protected by a single lock, so that `alt` can look at all its
+//! channels at once. A blocked thread is represented by a `Waiter`, queued on every channel it
+//! waits on; the first thread that completes one of its cases marks it done, which makes its
+//! entries in the other queues stale.
+
+use crate::fatal;
+use crate::mem::{self, BlockKind};
+use crate::sched::{block, blocking, lock, thread_id, unblock, Op};
+use std::cell::{Cell, UnsafeCell};
+use std::collections::hash_map::RandomState;
+use std::collections::VecDeque;
+use std::hash::{BuildHasher, Hasher};
+use std::ptr;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+
+/// The direction of an `alt` case that receives.
+pub const ALT_RECV: i64 = 0;
+
+/// The direction of an `alt` case that sends.
+pub const ALT_SEND: i64 = 1;
+
+/// The lock protecting the state of every channel.
+static LOCK: Mutex<()> = Mutex::new(());
+
+type Guard = MutexGuard<'static, ()>;
+
+/// Data only accessed while holding `LOCK`.
+struct Locked<T>(UnsafeCell<T>);
+
+// SAFETY: the content is only reachable through `get`, which requires the guard of `LOCK`.
+unsafe impl<T: Send> Sync for Locked<T> {}
+
+impl<T> Locked<T> {
+    fn get<'a>(&'a self, _guard: &'a mut Guard) -> &'a mut T {
+        // SAFETY: the guard proves that `LOCK` is held and borrowing it mutably prevents a second
+        // reference from being created through it.
+        unsafe { &mut *self.0.get() }
+    }
+}
+
+/// A blocked thread; `done` is the index of the case that completed.
+struct Waiter {
+    thread: u64,
+    done: Mutex<Option<usize>>,
+    cond: Condvar,
+}
+
+impl Waiter {
+    fn new(thread: u64) -> Arc<Waiter> {
+        Arc::new(Waiter {
+            thread,
+            done: Mutex::new(None),
+            cond: Condvar::new(),
+        })
+    }
+}
+
+/// A case a thread is blocked on.
+struct Entry {
+    waiter: Arc<Waiter>,
+    case: usize,
+
+    /// The element to send, or where to store the received one (null to drop it).
+    buf: *mut u8,
+}
+
+// SAFETY: the buffer belongs to the blocked thread, which does not touch it until it is woken.
+unsafe impl Send for Entry {}
+
+impl Entry {
+    fn stale(&self) -> bool {
+        lock(&self.waiter.done).is_some()
+    }
+
+    /// Complete the case and wake the thread up.
+    fn wake(self) {
+        unblock(self.waiter.thread);
+        *lock(&self.waiter.done) = Some(self.case);
+        self.waiter.cond.notify_one();
+    }
+}
+
+#[derive(Default)]
+struct State {
+    buf: VecDeque<Box<[u8]>>,
+    sendq: VecDeque<Entry>,
+    recvq: VecDeque<Entry>,
+}
+
+/// A channel.
+pub struct Chan {
+    elem: usize,
+    cap: usize,
+    state: Locked<State>,
+}
+
+/// Remove and return the first entry of `q` that is not stale.
+fn pop(q: &mut VecDeque<Entry>) -> Option<Entry> {
+    while let Some(e) = q.pop_front() {
+        if !e.stale() {
+            return Some(e);
+        }
+    }
+    None
+}
+
+impl Chan {
+    /// Copy `n` bytes unless `dst` is null.
+    ///
+    /// # Safety
+    /// Both pointers must be valid for `elem` bytes, or `dst` null.
+    unsafe fn copy(&self, dst: *mut u8, src: *const u8) {
+        if !dst.is_null() {
+            ptr::copy_nonoverlapping(src, dst, self.elem);
+        }
+    }
+
+    fn can_send(&self, g: &mut Guard) -> bool {
+        let s = self.state.get(g);
+        s.buf.len() < self.cap || s.recvq.iter().any(|e| !e.stale())
+    }
+
+    fn can_recv(&self, g: &mut Guard) -> bool {
+        let s = self.state.get(g);
+        !s.buf.is_empty() || s.sendq.iter().any(|e| !e.stale())
+    }
+
+    /// Send the element at `src` if it can be done without blocking.
+    unsafe fn try_send(&self, g: &mut Guard, src: *const u8) -> bool {
+        let s = self.state.get(g);
+        if let Some(r) = pop(&mut s.recvq) {
+            self.copy(r.buf, src);
+            r.wake();
+            true
+        } else if s.buf.len() < self.cap {
+            s.buf
+                .push_back(std::slice::from_raw_parts(src, self.elem).into());
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Receive an element into `dst` if it can be done without blocking.
+    unsafe fn try_recv(&self, g: &mut Guard, dst: *mut u8) -> bool {
+        let s = self.state.get(g);
+        if let Some(v) = s.buf.pop_front() {
+            self.copy(dst, v.as_ptr());
+            if let Some(w) = pop(&mut s.sendq) {
+                s.buf
+                    .push_back(std::slice::from_raw_parts(w.buf, self.elem).into());
+                w.wake();
+            }
+            true
+        } else if let Some(w) = pop(&mut s.sendq) {
+            self.copy(dst, w.buf);
+            w.wake();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Queue `waiter` for case `case`.
+    fn wait(&self, g: &mut Guard, send: bool, waiter: &Arc<Waiter>, case: usize, buf: *mut u8) {
+        let s = self.state.get(g);
+        let q = if send { &mut s.sendq } else { &mut s.recvq };
+        // Entries of alts completed on other channels would otherwise pile up.
+        q.retain(|e| !e.stale());
+        q.push_back(Entry {
+            waiter: waiter.clone(),
+            case,
+            buf,
+        });
+    }
+}
+
+/// Block in `op` on `chans` until one of the cases queued for `waiter` completes and return its
+/// index.
+fn sleep(g: Guard, waiter: &Waiter, op: Op, chans: Vec<usize>) -> usize {
+    block(op, chans);
+    drop(g);
+    blocking(|| {
+        let mut done = lock(&waiter.done);
+        loop {
+            if let Some(case) = *done {
+                return case;
+            }
+            done = waiter.cond.wait(done).unwrap_or_else(|e| e.into_inner());
+        }
+    })
+}
+
+fn global() -> Guard {
+    lock(&LOCK)
+}
+
+/// The channel `c` points to.
+///
+/// # Safety
+/// `c` must be null or come from `alef_chan_new` and not be released.
+unsafe fn chan<'a>(c: *mut Chan) -> &'a Chan {
+    match c.as_ref() {
+        Some(c) => c,
+        None => fatal(1, "operation on a nil channel"),
+    }
+}
+
+/// `ptr alef_chan_new(i64 elem_size, i64 cap)`: create a channel, synchronous if `cap` is 0.
+#[no_mangle]
+pub extern "C" fn alef_chan_new(elem: i64, cap: i64) -> *mut Chan {
+    let c = mem::block(std::mem::size_of::<Chan>(), BlockKind::Chan) as *mut Chan;
+    // SAFETY: the block is large enough and aligned to 16 bytes.
+    unsafe {
+        c.write(Chan {
+            elem: elem.max(0) as usize,
+            cap: cap.max(0) as usize,
+            state: Locked(UnsafeCell::new(State::default())),
+        });
+    }
+    c
+}
+
+/// `void alef_chan_send(ptr chan, ptr src)`: send the element stored at `src`.
+///
+/// # Safety
+/// `chan` must come from `alef_chan_new`; `src` must be valid for the size of an element.
+#[no_mangle]
+pub unsafe extern "C" fn alef_chan_send(c: *mut Chan, src: *mut u8) {
+    let thread = thread_id();
+    let c = chan(c);
+    let mut g = global();
+    if !c.try_send(&mut g, src) {
+        let waiter = Waiter::new(thread);
+        c.wait(&mut g, true, &waiter, 0, src);
+        sleep(g, &waiter, Op::Send, vec![c as *const Chan as usize]);
+    }
+}
+
+/// `void alef_chan_recv(ptr chan, ptr dst)`: receive an element and store it at `dst`.
+///
+/// # Safety
+/// `chan` must come from `alef_chan_new`; `dst` must be null or valid for the size of an
+/// element.
+#[no_mangle]
+pub unsafe extern "C" fn alef_chan_recv(c: *mut Chan, dst: *mut u8) {
+    let thread = thread_id();
+    let c = chan(c);
+    let mut g = global();
+    if !c.try_recv(&mut g, dst) {
+        let waiter = Waiter::new(thread);
+        c.wait(&mut g, false, &waiter, 0, dst);
+        sleep(g, &waiter, Op::Recv, vec![c as *const Chan as usize]);
+    }
+}
+
+/// `i32 alef_chan_cansend(ptr chan)`: return non-zero if a send would not block.
+///
+/// # Safety
+/// `chan` must come from `alef_chan_new`.
+#[no_mangle]
+pub unsafe extern "C" fn alef_chan_cansend(c: *mut Chan) -> i32 {
+    chan(c).can_send(&mut global()) as i32
+}
+
+/// `i32 alef_chan_canrecv(ptr chan)`: return non-zero if a receive would not block.
+///
+/// # Safety
+/// `chan` must come from `alef_chan_new`.
+#[no_mangle]
+pub unsafe extern "C" fn alef_chan_canrecv(c: *mut Chan) -> i32 {
+    chan(c).can_recv(&mut global()) as i32
+}
+
+/// A case of `alef_alt`, laid out like `alef.altcase`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct AltCase {
+    /// The channel.
+    pub chan: *mut Chan,
+
+    /// `ALT_RECV` or `ALT_SEND`.
+    pub dir: i64,
+
+    /// The element to send, or where to store the received one (null to drop it).
+    pub buf: *mut u8,
+}
+
+thread_local! {
+    static SEED: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish() | 1);
+}
+
+/// A pseudo-random number below `n`.
+fn random(n: usize) -> usize {
+    SEED.with(|s| {
+        let mut x = s.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        s.set(x);
+        (x % n as u64) as usize
+    })
+}
+
+/// `i32 alef_alt(ptr cases, i32 n)`: wait until one of the `n` cases can proceed, perform it
+/// and return its index. When several cases are ready one of them is chosen at random, so that
+/// no channel is starved.
+///
+/// # Safety
+/// `cases` must point to `n` cases whose channels come from `alef_chan_new` and whose buffers
+/// are valid for the size of an element.
+#[no_mangle]
+pub unsafe extern "C" fn alef_alt(cases: *const AltCase, n: i32) -> i32 {
+    let cases = std::slice::from_raw_parts(cases, n.max(0) as usize);
+    if cases.is_empty() {
+        fatal(1, "alt without cases");
+    }
+    let thread = thread_id();
+    let mut g = global();
+    let ready: Vec<usize> = (0..cases.len())
+        .filter(|&i| {
+            let c = chan(cases[i].chan);
+            if cases[i].dir == ALT_SEND {
+                c.can_send(&mut g)
+            } else {
+                c.can_recv(&mut g)
+            }
+        })
+        .collect();
+
+    if !ready.is_empty() {
+        let i = ready[random(ready.len())];
+        let c = chan(cases[i].chan);
+        let done = if cases[i].dir == ALT_SEND {
+            c.try_send(&mut g, cases[i].buf)
+        } else {
+            c.try_recv(&mut g, cases[i].buf)
+        };
+        debug_assert!(done);
+        return i as i32;
+    }
+
+    let waiter = Waiter::new(thread);
+    for (i, case) in cases.iter().enumerate() {
+        chan(case.chan).wait(&mut g, case.dir == ALT_SEND, &waiter, i, case.buf);
+    }
+    let chans = cases.iter().map(|c| c.chan as usize).collect();
+    sleep(g, &waiter, Op::Alt, chans) as i32
+}
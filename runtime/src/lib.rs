@@ -0,0 +1,48 @@
+//! The runtime library linked with compiled Alef programs.
+//!
+//! The crate exports the C functions listed in `alef_backend::runtime`, which is the only
+//! interface between generated code and the runtime: every backend lowers allocation, channels,
+//! `alt`, processes, tasks and `par` blocks to calls to them. It is built both as a Rust library,
+//! for the tests, and as a static library to link with the output of the backends.
+//!
+//! Processes are OS threads and are scheduled preemptively. Tasks are OS threads too, but every
+//! process owns a baton that only one of its tasks holds at a time: a task gives the baton up
+//! only when it blocks on a channel or a `par` join, when it yields or when it terminates, and
+//! the waiting tasks get it in the order they asked for it. Tasks of a process thus behave like
+//! the round-robin coroutines of the original implementation without any stack switching.
+//!
+//! When every thread is blocked the runtime reports what each one waits for and terminates the
+//! program with `EXIT_DEADLOCK`, instead of letting it hang.
+
+use std::io::Write;
+
+/// Heap allocation.
+pub mod mem;
+
+/// Processes, tasks and `par` groups.
+pub mod sched;
+
+/// Channels and `alt`.
+pub mod chan;
+
+/// Print a message and terminate the program with `status`.
+pub(crate) fn fatal(status: i32, msg: &str) -> ! {
+    let _ = writeln!(std::io::stderr(), "alef: {}", msg);
+    std::process::exit(status)
+}
+
+/// The exit status of a program that raised an exception outside of any `rescue` block.
+pub const EXIT_UNHANDLED: i32 = 2;
+
+/// The exit status of a program whose threads are all blocked.
+pub const EXIT_DEADLOCK: i32 = 3;
+
+/// The environment variable that enables the debug mode of the runtime, in which deadlock
+/// reports say where every thread blocked.
+pub const DEBUG_VAR: &str = "ALEF_DEBUG";
+
+/// `void alef_raise()`: report an exception raised outside of any `rescue` block.
+#[no_mangle]
+pub extern "C" fn alef_raise() {
+    fatal(EXIT_UNHANDLED, "unhandled exception")
+}
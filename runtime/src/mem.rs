@@ -0,0 +1,110 @@
+//! Every block handed out by the runtime starts with a header recording its size and what it
+//! holds, so that `alef_unalloc` can release plain storage and channels alike.
+
+use crate::fatal;
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::ffi::c_void;
+
+/// The size of the header, which keeps the blocks aligned to 16 bytes.
+const HEADER: usize = 16;
+
+/// What a block holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u64)]
+pub(crate) enum BlockKind {
+    /// Storage for the program.
+    Data = 0,
+
+    /// A `chan::Chan`.
+    Chan = 1,
+}
+
+fn layout(size: usize) -> Layout {
+    match size
+        .checked_add(HEADER)
+        .and_then(|n| Layout::from_size_align(n, HEADER).ok())
+    {
+        Some(l) => l,
+        None => fatal(1, "allocation too large"),
+    }
+}
+
+/// Allocate `size` zeroed bytes after a header of kind `kind`.
+pub(crate) fn block(size: usize, kind: BlockKind) -> *mut u8 {
+    let layout = layout(size);
+    // SAFETY: the layout is never empty because of the header.
+    let p = unsafe { alloc_zeroed(layout) };
+    if p.is_null() {
+        fatal(1, "out of memory");
+    }
+    // SAFETY: the header fits in the block and is aligned for u64.
+    unsafe {
+        (p as *mut u64).write(size as u64);
+        (p as *mut u64).add(1).write(kind as u64);
+        p.add(HEADER)
+    }
+}
+
+/// The kind of the block at `p`.
+///
+/// # Safety
+/// `p` must have been returned by `block` and not released.
+pub(crate) unsafe fn kind(p: *mut u8) -> BlockKind {
+    match (p.sub(HEADER) as *const u64).add(1).read() {
+        0 => BlockKind::Data,
+        _ => BlockKind::Chan,
+    }
+}
+
+/// Release the block at `p`.
+///
+/// # Safety
+/// `p` must have been returned by `block` and not released; what it holds must already be
+/// dropped.
+pub(crate) unsafe fn release(p: *mut u8) {
+    let start = p.sub(HEADER);
+    let size = (start as *const u64).read() as usize;
+    dealloc(start, layout(size));
+}
+
+/// `ptr alef_alloc(i64 size)`: allocate `size` zeroed bytes, terminating the program when out
+/// of memory.
+#[no_mangle]
+pub extern "C" fn alef_alloc(size: i64) -> *mut c_void {
+    let size = usize::try_from(size).unwrap_or_else(|_| fatal(1, "negative allocation size"));
+    block(size, BlockKind::Data) as *mut c_void
+}
+
+/// `void alef_unalloc(ptr p)`: free memory returned by `alef_alloc`, or a channel; null is
+/// ignored.
+///
+/// # Safety
+/// `p` must be null or a pointer returned by the runtime that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn alef_unalloc(p: *mut c_void) {
+    let p = p as *mut u8;
+    if p.is_null() {
+        return;
+    }
+    if kind(p) == BlockKind::Chan {
+        std::ptr::drop_in_place(p as *mut crate::chan::Chan);
+    }
+    release(p);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zeroed() {
+        let p = alef_alloc(64) as *mut u8;
+        assert_eq!(p as usize % HEADER, 0);
+        unsafe {
+            assert!(std::slice::from_raw_parts(p, 64).iter().all(|b| *b == 0));
+            assert_eq!(kind(p), BlockKind::Data);
+            alef_unalloc(p as *mut c_void);
+            alef_unalloc(std::ptr::null_mut());
+        }
+    }
+}
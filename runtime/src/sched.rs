@@ -0,0 +1,425 @@
+//! Processes are OS threads with a baton of their own; tasks are OS threads sharing the baton of
+//! the process that started them. A thread that is not running Alef code (because it is blocked
+//! or finished) never holds a baton.
+//!
+//! Every thread that calls into the runtime is registered, and marked as blocked while it waits
+//! on a channel, an `alt` or a `par` join. A blocked thread is only woken up by a thread that is
+//! not, so when all of them are blocked the program is deadlocked: the runtime then prints what
+//! every thread is waiting for and terminates it with `EXIT_DEADLOCK`. Threads are marked as
+//! running again by whoever wakes them up, before they are woken, so that the count is never
+//! behind.
+
+use crate::{fatal, DEBUG_VAR, EXIT_DEADLOCK};
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread;
+
+/// The entry point of a process or task, called with its environment.
+pub type Entry = extern "C" fn(*mut c_void);
+
+/// A pointer that is handed over to another thread.
+#[derive(Clone, Copy)]
+pub(crate) struct SendPtr(pub *mut c_void);
+
+// SAFETY: generated code hands the environment over to the new thread, which owns it from then
+// on.
+unsafe impl Send for SendPtr {}
+
+/// Lock `m`, ignoring poisoning: the runtime never panics while holding its locks.
+pub(crate) fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// The tickets of a process baton: `serving` is the ticket of the holder, or of the next
+/// holder if nobody holds it.
+#[derive(Default)]
+struct Tickets {
+    next: u64,
+    serving: u64,
+}
+
+static NEXT_PROC: AtomicU64 = AtomicU64::new(1);
+
+/// A process, i.e. the baton its tasks take turns holding.
+pub(crate) struct Proc {
+    id: u64,
+    tickets: Mutex<Tickets>,
+    cond: Condvar,
+}
+
+impl Proc {
+    fn new() -> Arc<Proc> {
+        Arc::new(Proc {
+            id: NEXT_PROC.fetch_add(1, Ordering::Relaxed),
+            tickets: Mutex::default(),
+            cond: Condvar::new(),
+        })
+    }
+
+    /// Take a ticket for the baton.
+    fn ticket(&self) -> u64 {
+        let mut t = lock(&self.tickets);
+        t.next += 1;
+        t.next - 1
+    }
+
+    /// Wait for the baton; tasks get it in the order they took their tickets.
+    fn wait(&self, ticket: u64) {
+        let mut t = lock(&self.tickets);
+        while t.serving != ticket {
+            t = self.cond.wait(t).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Wait for the baton.
+    fn acquire(&self) {
+        self.wait(self.ticket());
+    }
+
+    /// Hand the baton to the next task.
+    fn release(&self) {
+        lock(&self.tickets).serving += 1;
+        self.cond.notify_all();
+    }
+
+    /// Hand the baton to the next task and wait for it after the tasks already waiting. The
+    /// ticket is taken together with the release, or the next task could yield and take its
+    /// ticket first.
+    fn pass(&self) {
+        let ticket = {
+            let mut t = lock(&self.tickets);
+            t.serving += 1;
+            t.next += 1;
+            t.next - 1
+        };
+        self.cond.notify_all();
+        self.wait(ticket);
+    }
+}
+
+/// What a blocked thread waits for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Op {
+    Send,
+    Recv,
+    Alt,
+    Join,
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Op::Send => "send on channel",
+            Op::Recv => "recv on channel",
+            Op::Alt => "alt on channels",
+            Op::Join => "join of par group",
+        })
+    }
+}
+
+/// A blocked thread.
+struct Blocked {
+    proc: u64,
+    op: Op,
+    objects: Vec<usize>,
+
+    /// Where the thread blocked, in debug mode.
+    trace: Option<Backtrace>,
+}
+
+/// The registered threads.
+struct Threads {
+    next: u64,
+    live: usize,
+    blocked: BTreeMap<u64, Blocked>,
+}
+
+static THREADS: Mutex<Threads> = Mutex::new(Threads {
+    next: 1,
+    live: 0,
+    blocked: BTreeMap::new(),
+});
+
+/// Register a new thread and return its identifier.
+fn register() -> u64 {
+    let mut t = lock(&THREADS);
+    t.live += 1;
+    t.next += 1;
+    t.next - 1
+}
+
+/// Withdraw the registration of a thread that terminates.
+fn unregister() {
+    let mut t = lock(&THREADS);
+    t.live -= 1;
+    check(&t);
+}
+
+/// The registration of the calling thread.
+struct Current {
+    proc: Arc<Proc>,
+    id: u64,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Current>> = const { RefCell::new(None) };
+}
+
+/// The process and identifier of the calling thread. The first thread of the program is not
+/// started by the runtime, so it is registered the first time it calls into the runtime; it is
+/// never unregistered, since the program terminates with it.
+fn current() -> (Arc<Proc>, u64) {
+    CURRENT.with(|c| {
+        let mut c = c.borrow_mut();
+        let c = c.get_or_insert_with(|| {
+            let proc = Proc::new();
+            proc.acquire();
+            Current {
+                proc,
+                id: register(),
+            }
+        });
+        (c.proc.clone(), c.id)
+    })
+}
+
+/// The identifier of the calling thread, registering it if needed.
+pub(crate) fn thread_id() -> u64 {
+    current().1
+}
+
+/// Return true if the runtime runs in debug mode.
+fn debug() -> bool {
+    static DEBUG: OnceLock<bool> = OnceLock::new();
+    *DEBUG.get_or_init(|| std::env::var_os(DEBUG_VAR).is_some_and(|v| !v.is_empty() && v != "0"))
+}
+
+/// Mark the calling thread as blocked in `op` on `objects`. This must be done while holding the
+/// lock that whoever wakes the thread up takes, and the thread must then wait with `blocking`.
+pub(crate) fn block(op: Op, objects: Vec<usize>) {
+    let (proc, id) = current();
+    let trace = debug().then(Backtrace::force_capture);
+    let mut t = lock(&THREADS);
+    t.blocked.insert(
+        id,
+        Blocked {
+            proc: proc.id,
+            op,
+            objects,
+            trace,
+        },
+    );
+    check(&t);
+}
+
+/// Mark thread `id` as running again, before waking it up.
+pub(crate) fn unblock(id: u64) {
+    lock(&THREADS).blocked.remove(&id);
+}
+
+/// The runtime functions that block; the frame calling them is where a thread blocked.
+const BLOCKING: &[&str] = &[
+    "alef_chan_send",
+    "alef_chan_recv",
+    "alef_alt",
+    "alef_par_join",
+];
+
+/// The function that called into the runtime in the rendering of a backtrace, with its location
+/// if known.
+fn site(trace: &str) -> Option<String> {
+    let mut frames: Vec<(&str, Option<&str>)> = vec![];
+    for line in trace.lines() {
+        let line = line.trim();
+        if let Some(at) = line.strip_prefix("at ") {
+            if let Some(f) = frames.last_mut() {
+                f.1 = Some(at);
+            }
+        } else if let Some((n, sym)) = line.split_once(": ") {
+            if n.chars().all(|c| c.is_ascii_digit()) {
+                frames.push((sym, None));
+            }
+        }
+    }
+    let entry = frames.iter().rposition(|f| BLOCKING.contains(&f.0))?;
+    let (sym, at) = frames.get(entry + 1)?;
+    Some(match at {
+        Some(at) => format!("in {} at {}", sym, at),
+        None => format!("in {}", sym),
+    })
+}
+
+/// Report a deadlock and terminate the program if every registered thread is blocked.
+fn check(t: &Threads) {
+    if t.live == 0 || t.blocked.len() < t.live {
+        return;
+    }
+    let mut report = String::from("deadlock, every thread is blocked");
+    for (id, b) in &t.blocked {
+        let objects: Vec<_> = b.objects.iter().map(|o| format!("{:#x}", o)).collect();
+        report.push_str(&format!(
+            "\n  thread {} of process {}: {} {}",
+            id,
+            b.proc,
+            b.op,
+            objects.join(", ")
+        ));
+        if let Some(site) = b.trace.as_ref().and_then(|t| site(&t.to_string())) {
+            report.push(' ');
+            report.push_str(&site);
+        }
+    }
+    if !debug() {
+        report.push_str(&format!(
+            "\nalef: set {}=1 to see where the threads blocked",
+            DEBUG_VAR
+        ));
+    }
+    fatal(EXIT_DEADLOCK, &report)
+}
+
+/// Run `f`, which blocks, without holding the baton of the process.
+pub(crate) fn blocking<T>(f: impl FnOnce() -> T) -> T {
+    let (proc, _) = current();
+    proc.release();
+    let r = f();
+    proc.acquire();
+    r
+}
+
+/// Start a thread of process `proc` running `entry(env)`, then `exit`. The thread is registered
+/// and takes its ticket here, so that it counts as running from now on and tasks run in the
+/// order they were started.
+fn start(proc: Arc<Proc>, entry: Entry, env: *mut c_void, exit: impl FnOnce() + Send + 'static) {
+    let env = SendPtr(env);
+    let ticket = proc.ticket();
+    let id = register();
+    let spawned = thread::Builder::new().spawn(move || {
+        let env = env;
+        CURRENT.with(|c| {
+            *c.borrow_mut() = Some(Current {
+                proc: proc.clone(),
+                id,
+            })
+        });
+        proc.wait(ticket);
+        entry(env.0);
+        proc.release();
+        exit();
+        unregister();
+    });
+    if spawned.is_err() {
+        fatal(1, "cannot start a thread");
+    }
+}
+
+/// `void alef_proc(ptr fn, ptr env)`: run `fn(env)` in a new process.
+#[no_mangle]
+pub extern "C" fn alef_proc(entry: Entry, env: *mut c_void) {
+    current();
+    start(Proc::new(), entry, env, || {});
+}
+
+/// `void alef_task(ptr fn, ptr env)`: run `fn(env)` in a new task of the current process; it
+/// starts running when the tasks before it block, yield or terminate.
+#[no_mangle]
+pub extern "C" fn alef_task(entry: Entry, env: *mut c_void) {
+    start(current().0, entry, env, || {});
+}
+
+/// `void alef_yield()`: let the other tasks of the process run.
+#[no_mangle]
+pub extern "C" fn alef_yield() {
+    current().0.pass();
+}
+
+/// The members of a group still running, and the thread waiting for them.
+#[derive(Default)]
+struct Members {
+    live: usize,
+    joiner: Option<u64>,
+}
+
+/// A group of processes started by a `par` block.
+#[derive(Default)]
+pub struct Group {
+    members: Mutex<Members>,
+    cond: Condvar,
+}
+
+/// `ptr alef_par_begin()`: start a group of processes for a `par` block.
+#[no_mangle]
+pub extern "C" fn alef_par_begin() -> *const Group {
+    Arc::into_raw(Arc::default())
+}
+
+/// `void alef_par_spawn(ptr group, ptr fn, ptr env)`: run `fn(env)` as a member of the group.
+///
+/// # Safety
+/// `group` must come from `alef_par_begin` and not be joined yet.
+#[no_mangle]
+pub unsafe extern "C" fn alef_par_spawn(group: *const Group, entry: Entry, env: *mut c_void) {
+    current();
+    // Every member keeps the group alive until it is done with it.
+    Arc::increment_strong_count(group);
+    let group = Arc::from_raw(group);
+    lock(&group.members).live += 1;
+    start(Proc::new(), entry, env, move || {
+        let mut m = lock(&group.members);
+        m.live -= 1;
+        if m.live == 0 {
+            if let Some(joiner) = m.joiner.take() {
+                unblock(joiner);
+            }
+            group.cond.notify_all();
+        }
+    });
+}
+
+/// `void alef_par_join(ptr group)`: wait for every member of the group and release it.
+///
+/// # Safety
+/// `group` must come from `alef_par_begin` and not be joined yet.
+#[no_mangle]
+pub unsafe extern "C" fn alef_par_join(group: *const Group) {
+    let group = Arc::from_raw(group);
+    {
+        let mut m = lock(&group.members);
+        if m.live == 0 {
+            return;
+        }
+        m.joiner = Some(thread_id());
+        block(Op::Join, vec![Arc::as_ptr(&group) as usize]);
+    }
+    blocking(|| {
+        let mut m = lock(&group.members);
+        while m.live > 0 {
+            m = group.cond.wait(m).unwrap_or_else(|e| e.into_inner());
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sites() {
+        let trace = "   0: alef_runtime::sched::block
+             at ./src/sched.rs:210:49
+   1: alef_chan_recv
+             at ./src/chan.rs:253:9
+   2: wait
+             at ./prog.alef.c:21:2
+   3: alef__thunk__0
+";
+        assert_eq!(site(trace).unwrap(), "in wait at ./prog.alef.c:21:2");
+        assert_eq!(site("   0: alef_alt\n   1: main\n").unwrap(), "in main");
+        assert_eq!(site("   0: main\n"), None);
+    }
+}
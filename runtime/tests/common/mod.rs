@@ -0,0 +1,55 @@
+// Each test crate only uses some of the helpers.
+#![allow(dead_code)]
+
+use alef_test_support::tests_dir;
+use std::ffi::c_void;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How long a test may take before it is considered deadlocked.
+pub const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Run `f` in a thread of its own and fail if it does not finish within `TIMEOUT`.
+pub fn watchdog(name: &str, f: impl FnOnce() + Send + 'static) {
+    let (tx, rx) = mpsc::channel();
+    let worker = thread::spawn(move || {
+        f();
+        let _ = tx.send(());
+    });
+    match rx.recv_timeout(TIMEOUT) {
+        Ok(()) => worker.join().unwrap(),
+        Err(mpsc::RecvTimeoutError::Disconnected) => {
+            if let Err(e) = worker.join() {
+                std::panic::resume_unwind(e);
+            }
+        }
+        Err(mpsc::RecvTimeoutError::Timeout) => panic!("{} did not terminate", name),
+    }
+}
+
+/// Leak `v` as the environment of a process or task.
+pub fn env<T>(v: T) -> *mut c_void {
+    Box::into_raw(Box::new(v)) as *mut c_void
+}
+
+/// Take back an environment created by `env`.
+///
+/// # Safety
+/// `p` must come from `env::<T>` and not be taken back yet.
+pub unsafe fn take<T>(p: *mut c_void) -> T {
+    *Box::from_raw(p as *mut T)
+}
+
+/// The directory of the test programs of a sibling crate.
+pub fn programs(krate: &str) -> PathBuf {
+    tests_dir!(krate, "programs")
+}
+
+/// The static library built next to the test executable, if there is one.
+pub fn static_lib() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let lib = exe.parent()?.parent()?.join("libalef_runtime.a");
+    lib.exists().then_some(lib)
+}
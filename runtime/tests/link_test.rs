@@ -0,0 +1,173 @@
+mod common;
+
+use alef_backend::{c, runtime};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// The static library and a scratch directory, None if the test cannot link programs.
+fn setup(test: &str) -> Option<(PathBuf, PathBuf)> {
+    if !alef_test_support::have_cc() {
+        eprintln!("no C compiler, skipping");
+        return None;
+    }
+    let Some(lib) = common::static_lib() else {
+        eprintln!("no static library, skipping");
+        return None;
+    };
+    let dir = alef_test_support::scratch_dir(&format!("runtime-{}", test));
+    Some((lib, dir))
+}
+
+/// Compile `sources` with debugging information and link them with the runtime.
+fn link(lib: &Path, dir: &Path, name: &str, sources: &[PathBuf]) -> PathBuf {
+    let exe = dir.join(name);
+    let out = Command::new("cc")
+        .args(["-g", "-o"])
+        .arg(&exe)
+        .args(sources)
+        .arg(lib)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "cannot link {}:\n{}",
+        name,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    exe
+}
+
+/// Compile and link `sources` with the runtime, run the result and return its output.
+fn link_and_run(lib: &Path, dir: &Path, name: &str, sources: &[PathBuf]) -> Output {
+    Command::new(link(lib, dir, name, sources))
+        .output()
+        .unwrap()
+}
+
+/// Translate the IR module at `path` to C in `dir` and return the path of the C file.
+fn translate(dir: &Path, path: &Path) -> PathBuf {
+    let module = alef_test_support::module(
+        &fs::read_to_string(path).unwrap(),
+        &path.display().to_string(),
+    );
+    let name = path.file_stem().unwrap().to_string_lossy();
+    let src = dir.join(format!("{}.alef.c", name));
+    fs::write(&src, c::emit(&module).unwrap()).unwrap();
+    src
+}
+
+/// Every function of the ABI is exported by the static library.
+#[test]
+fn test_exports() {
+    let Some((lib, dir)) = setup("exports") else {
+        return;
+    };
+    let mut src = String::new();
+    for f in runtime::FUNCTIONS {
+        src.push_str(&format!("extern void {}(void);\n", f));
+    }
+    src.push_str("void (*const functions[])(void) = {\n");
+    for f in runtime::FUNCTIONS {
+        src.push_str(&format!("    {},\n", f));
+    }
+    src.push_str("};\nint main(void) { return functions[0] == 0; }\n");
+    let path = dir.join("exports.c");
+    fs::write(&path, src).unwrap();
+
+    let out = link_and_run(&lib, &dir, "exports", &[path]);
+    assert!(out.status.success());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// The lines of `s`, sorted.
+fn lines(s: &str) -> Vec<&str> {
+    let mut lines: Vec<_> = s.lines().collect();
+    lines.sort_unstable();
+    lines
+}
+
+/// The concurrent programs of the virtual machine behave the same when compiled to C. Unlike
+/// the machine, the runtime does not fix the order in which processes run, so only the lines
+/// printed are compared, not their order.
+#[test]
+fn test_programs() {
+    let Some((lib, dir)) = setup("programs") else {
+        return;
+    };
+    let programs = common::programs("vm");
+    let mut ran = 0;
+    for entry in fs::read_dir(&programs).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|e| e != "air") {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let Ok(expected) = fs::read_to_string(programs.join(format!("{}.out", name))) else {
+            continue;
+        };
+
+        let src = translate(&dir, &path);
+
+        common::watchdog(&name.clone(), {
+            let (lib, dir) = (lib.clone(), dir.clone());
+            move || {
+                let out = link_and_run(&lib, &dir, &name, &[src]);
+                assert!(out.status.success(), "{} exited with {}", name, out.status);
+                assert_eq!(
+                    lines(&String::from_utf8(out.stdout).unwrap()),
+                    lines(&expected),
+                    "output of {} differs",
+                    name
+                );
+            }
+        });
+        ran += 1;
+    }
+    assert!(ran > 0, "no programs in {}", programs.display());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// A program whose processes all wait on a channel is reported and terminated, and in debug
+/// mode the report says where they blocked.
+#[test]
+fn test_deadlock() {
+    let Some((lib, dir)) = setup("deadlock") else {
+        return;
+    };
+    let src = translate(&dir, &common::programs("vm").join("deadlock.air"));
+    let exe = link(&lib, &dir, "deadlock", &[src]);
+
+    for debug in [false, true] {
+        let mut cmd = Command::new(&exe);
+        if debug {
+            cmd.env(alef_runtime::DEBUG_VAR, "1");
+        } else {
+            cmd.env_remove(alef_runtime::DEBUG_VAR);
+        }
+        common::watchdog("deadlock", move || {
+            let out = cmd.output().unwrap();
+            assert_eq!(out.status.code(), Some(alef_runtime::EXIT_DEADLOCK));
+            let err = String::from_utf8(out.stderr).unwrap();
+            let lines: Vec<_> = err.lines().collect();
+            assert_eq!(
+                lines[0], "alef: deadlock, every thread is blocked",
+                "{}",
+                err
+            );
+            assert!(lines[1].contains("thread 1 of process 1: recv on channel 0x"));
+            assert!(lines[2].contains("thread 2 of process 2: recv on channel 0x"));
+            if debug {
+                assert_eq!(lines.len(), 3, "{}", err);
+                assert!(lines[1].contains(" in main at "), "{}", err);
+                assert!(lines[2].contains(" in wait at "), "{}", err);
+                assert!(lines[2].contains("deadlock.alef.c:"), "{}", err);
+            } else {
+                assert_eq!(lines.len(), 4, "{}", err);
+                assert!(lines[3].contains(alef_runtime::DEBUG_VAR));
+            }
+        });
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,384 @@
+mod common;
+
+use alef_runtime::chan::*;
+use alef_runtime::mem::alef_unalloc;
+use alef_runtime::sched::*;
+use std::ffi::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+type Chan = *mut alef_runtime::chan::Chan;
+
+fn send(c: Chan, mut v: i64) {
+    unsafe { alef_chan_send(c, &mut v as *mut i64 as *mut u8) }
+}
+
+fn recv(c: Chan) -> i64 {
+    let mut v = 0i64;
+    unsafe { alef_chan_recv(c, &mut v as *mut i64 as *mut u8) };
+    v
+}
+
+fn free(c: Chan) {
+    unsafe { alef_unalloc(c as *mut c_void) }
+}
+
+/// Start a `par` group running `entry` once for every environment.
+fn par<T>(entry: Entry, envs: impl IntoIterator<Item = T>) -> *const Group {
+    let g = alef_par_begin();
+    for e in envs {
+        unsafe { alef_par_spawn(g, entry, common::env(e)) };
+    }
+    g
+}
+
+struct Producer {
+    chan: Chan,
+    first: i64,
+    count: i64,
+}
+
+extern "C" fn produce(env: *mut c_void) {
+    let p: Producer = unsafe { common::take(env) };
+    for i in 0..p.count {
+        send(p.chan, p.first + i);
+    }
+}
+
+/// Many processes sending on one channel: nothing is lost or duplicated.
+fn many_senders(cap: i64) {
+    const PROCS: i64 = 16;
+    const COUNT: i64 = 2000;
+    let c = alef_chan_new(8, cap);
+    let g = par(
+        produce,
+        (0..PROCS).map(|p| Producer {
+            chan: c,
+            first: p * COUNT,
+            count: COUNT,
+        }),
+    );
+    let mut seen = vec![false; (PROCS * COUNT) as usize];
+    for _ in 0..PROCS * COUNT {
+        let v = recv(c) as usize;
+        assert!(!seen[v], "{} received twice", v);
+        seen[v] = true;
+    }
+    unsafe { alef_par_join(g) };
+    assert_eq!(unsafe { alef_chan_canrecv(c) }, 0);
+    free(c);
+}
+
+#[test]
+fn test_sync_many_senders() {
+    common::watchdog("sync_many_senders", || many_senders(0));
+}
+
+#[test]
+fn test_buffered_many_senders() {
+    common::watchdog("buffered_many_senders", || many_senders(8));
+}
+
+/// The elements of one sender are received in the order they were sent.
+#[test]
+fn test_fifo() {
+    common::watchdog("fifo", || {
+        for cap in [0, 1, 16] {
+            let c = alef_chan_new(8, cap);
+            let g = par(
+                produce,
+                [Producer {
+                    chan: c,
+                    first: 0,
+                    count: 10000,
+                }],
+            );
+            for i in 0..10000 {
+                assert_eq!(recv(c), i);
+            }
+            unsafe { alef_par_join(g) };
+            free(c);
+        }
+    });
+}
+
+/// Elements larger than a word are copied whole, and receivers may drop them.
+#[test]
+fn test_large_elements() {
+    common::watchdog("large_elements", || {
+        let c = alef_chan_new(64, 2);
+        let mut out = [0u8; 64];
+        for i in 0..4u8 {
+            let mut v = [i; 64];
+            if i < 2 {
+                unsafe { alef_chan_send(c, v.as_mut_ptr()) };
+            } else {
+                unsafe {
+                    alef_chan_recv(c, out.as_mut_ptr());
+                    alef_chan_send(c, v.as_mut_ptr());
+                }
+                assert_eq!(out, [i - 2; 64]);
+            }
+        }
+        unsafe {
+            alef_chan_recv(c, std::ptr::null_mut());
+            alef_chan_recv(c, out.as_mut_ptr());
+        }
+        assert_eq!(out, [3; 64]);
+        free(c);
+    });
+}
+
+/// When both cases of an `alt` are always ready, neither is starved.
+#[test]
+fn test_alt_fairness() {
+    common::watchdog("alt_fairness", || {
+        const COUNT: i64 = 10000;
+        let a = alef_chan_new(8, 64);
+        let b = alef_chan_new(8, 64);
+        let g = par(
+            produce,
+            [a, b].map(|chan| Producer {
+                chan,
+                first: 0,
+                count: COUNT,
+            }),
+        );
+
+        let mut va = 0i64;
+        let mut vb = 0i64;
+        let cases = [
+            AltCase {
+                chan: a,
+                dir: ALT_RECV,
+                buf: &mut va as *mut i64 as *mut u8,
+            },
+            AltCase {
+                chan: b,
+                dir: ALT_RECV,
+                buf: &mut vb as *mut i64 as *mut u8,
+            },
+        ];
+        let mut next = [0i64; 2];
+        for _ in 0..COUNT {
+            let i = unsafe { alef_alt(cases.as_ptr(), 2) } as usize;
+            let v = if i == 0 { va } else { vb };
+            assert_eq!(v, next[i], "case {} out of order", i);
+            next[i] += 1;
+        }
+        for (i, n) in next.iter().enumerate() {
+            assert!(*n > COUNT / 4, "case {} chosen {} times", i, n);
+        }
+
+        // Drain what is left so that the producers terminate.
+        for _ in next[0]..COUNT {
+            recv(a);
+        }
+        for _ in next[1]..COUNT {
+            recv(b);
+        }
+        unsafe { alef_par_join(g) };
+        free(a);
+        free(b);
+    });
+}
+
+struct Consumer {
+    chan: Chan,
+    count: i64,
+    total: Arc<AtomicUsize>,
+}
+
+extern "C" fn consume(env: *mut c_void) {
+    let c: Consumer = unsafe { common::take(env) };
+    for _ in 0..c.count {
+        c.total.fetch_add(recv(c.chan) as usize, Ordering::Relaxed);
+    }
+}
+
+/// An `alt` sending to whichever receiver is ready.
+#[test]
+fn test_alt_send() {
+    common::watchdog("alt_send", || {
+        const COUNT: i64 = 5000;
+        let total = Arc::new(AtomicUsize::new(0));
+        let a = alef_chan_new(8, 0);
+        let b = alef_chan_new(8, 0);
+        let g = par(
+            consume,
+            [a, b].map(|chan| Consumer {
+                chan,
+                count: COUNT,
+                total: total.clone(),
+            }),
+        );
+
+        let mut v = 1i64;
+        let mut sent = [0i64; 2];
+        while sent.iter().sum::<i64>() < 2 * COUNT {
+            let mut cases = vec![];
+            for (i, chan) in [a, b].into_iter().enumerate() {
+                if sent[i] < COUNT {
+                    cases.push((
+                        i,
+                        AltCase {
+                            chan,
+                            dir: ALT_SEND,
+                            buf: &mut v as *mut i64 as *mut u8,
+                        },
+                    ));
+                }
+            }
+            let raw: Vec<AltCase> = cases.iter().map(|c| c.1).collect();
+            let k = unsafe { alef_alt(raw.as_ptr(), raw.len() as i32) } as usize;
+            sent[cases[k].0] += 1;
+        }
+        unsafe { alef_par_join(g) };
+        assert_eq!(total.load(Ordering::Relaxed), 2 * COUNT as usize);
+        free(a);
+        free(b);
+    });
+}
+
+/// `alt`s competing for the same channels never complete two cases at once nor lose an element.
+#[test]
+fn test_alt_contention() {
+    common::watchdog("alt_contention", || {
+        const COUNT: i64 = 3000;
+        let chans = [alef_chan_new(8, 0), alef_chan_new(8, 1)];
+        let total = Arc::new(AtomicUsize::new(0));
+        let g = par(alt_consume, (0..4).map(|_| (chans, COUNT, total.clone())));
+        let p = par(
+            produce,
+            chans.into_iter().flat_map(|chan| {
+                [0, 1].map(|_| Producer {
+                    chan,
+                    first: 1,
+                    count: COUNT,
+                })
+            }),
+        );
+        unsafe {
+            alef_par_join(p);
+            alef_par_join(g);
+        }
+        let one = (COUNT * (COUNT + 1) / 2) as usize;
+        assert_eq!(total.load(Ordering::Relaxed), 4 * one);
+        chans.into_iter().for_each(free);
+    });
+}
+
+extern "C" fn alt_consume(env: *mut c_void) {
+    let (chans, count, total): ([Chan; 2], i64, Arc<AtomicUsize>) = unsafe { common::take(env) };
+    let mut v = 0i64;
+    let cases = chans.map(|chan| AltCase {
+        chan,
+        dir: ALT_RECV,
+        buf: &mut v as *mut i64 as *mut u8,
+    });
+    for _ in 0..count {
+        unsafe { alef_alt(cases.as_ptr(), 2) };
+        total.fetch_add(v as usize, Ordering::Relaxed);
+    }
+}
+
+struct Worker {
+    id: usize,
+    running: Arc<AtomicUsize>,
+    log: Arc<Mutex<Vec<usize>>>,
+    done: Chan,
+}
+
+extern "C" fn work(env: *mut c_void) {
+    let w: Worker = unsafe { common::take(env) };
+    for _ in 0..100 {
+        assert_eq!(w.running.fetch_add(1, Ordering::SeqCst), 0, "tasks overlap");
+        w.log.lock().unwrap().push(w.id);
+        w.running.fetch_sub(1, Ordering::SeqCst);
+        alef_yield();
+    }
+    send(w.done, w.id as i64);
+}
+
+/// Tasks of a process never run at the same time, and yielding lets the others run.
+#[test]
+fn test_tasks() {
+    common::watchdog("tasks", || {
+        const TASKS: usize = 4;
+        let running = Arc::new(AtomicUsize::new(0));
+        let log = Arc::new(Mutex::new(vec![]));
+        let done = alef_chan_new(8, 0);
+        for id in 0..TASKS {
+            let env = common::env(Worker {
+                id,
+                running: running.clone(),
+                log: log.clone(),
+                done,
+            });
+            alef_task(work, env);
+        }
+        // The tasks start running once this one blocks.
+        assert!(log.lock().unwrap().is_empty());
+        for _ in 0..TASKS {
+            recv(done);
+        }
+        let log = log.lock().unwrap();
+        assert_eq!(log.len(), TASKS * 100);
+        // Tasks take turns in the order they were started.
+        for (i, id) in log.iter().enumerate() {
+            assert_eq!(*id, i % TASKS, "task {} ran out of turn at {}", id, i);
+        }
+        free(done);
+    });
+}
+
+extern "C" fn ring(env: *mut c_void) {
+    let (input, output, rounds): (Chan, Chan, i64) = unsafe { common::take(env) };
+    for _ in 0..rounds {
+        let v = recv(input);
+        send(output, v + 1);
+    }
+}
+
+extern "C" fn nested(env: *mut c_void) {
+    let n: i64 = unsafe { common::take(env) };
+    if n > 0 {
+        let g = par(nested, [n - 1, n - 1]);
+        unsafe { alef_par_join(g) };
+    }
+}
+
+/// Groups whose members communicate in a ring, and nested groups, are joined over and over
+/// without leaving any thread behind.
+#[test]
+fn test_shutdown() {
+    common::watchdog("shutdown", || {
+        const PROCS: usize = 8;
+        const ROUNDS: i64 = 10;
+        for _ in 0..20 {
+            let chans: Vec<Chan> = (0..=PROCS).map(|_| alef_chan_new(8, 0)).collect();
+            let g = par(ring, (0..PROCS).map(|i| (chans[i], chans[i + 1], ROUNDS)));
+            for r in 0..ROUNDS {
+                send(chans[0], r);
+                assert_eq!(recv(chans[PROCS]), r + PROCS as i64);
+            }
+            unsafe { alef_par_join(g) };
+            chans.into_iter().for_each(free);
+
+            let g = par(nested, [4i64]);
+            unsafe { alef_par_join(g) };
+        }
+        // Processes outside of any group are not waited for, but run to completion.
+        let c = alef_chan_new(8, 0);
+        alef_proc(
+            produce,
+            common::env(Producer {
+                chan: c,
+                first: 7,
+                count: 1,
+            }),
+        );
+        assert_eq!(recv(c), 7);
+        free(c);
+    });
+}
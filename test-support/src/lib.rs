@@ -0,0 +1,82 @@
+//! Helpers shared by the tests of the Alef crates: reading the test programs, comparing outputs
+//! with golden files and building programs with the C compiler.
+
+use alef_ir::{module::Module, read::read, verify::verify};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The directory `tests/<dir>` of the crate whose tests use the macro, or of its sibling crate
+/// `krate` in the workspace.
+#[macro_export]
+macro_rules! tests_dir {
+    ($dir:expr) => {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join($dir)
+    };
+    ($krate:expr, $dir:expr) => {
+        std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join($krate)
+            .join("tests")
+            .join($dir)
+    };
+}
+
+/// Read and verify the module in `text`, read from `name`.
+pub fn module(text: &str, name: &str) -> Module {
+    let module = read(text).unwrap_or_else(|e| panic!("error reading {}: {}", name, e));
+    if let Err(errs) = verify(&module) {
+        let errs: Vec<_> = errs.iter().map(|e| e.to_string()).collect();
+        panic!("{} is not well formed:\n{}", name, errs.join("\n"));
+    }
+    module
+}
+
+/// Read and verify every `.air` file in `dir`, returning their names and modules sorted by name.
+pub fn load_modules(dir: &Path) -> Vec<(String, Module)> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|p| p.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "air"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let text = fs::read_to_string(&path).unwrap();
+            let module = module(&text, &path.display().to_string());
+            let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+            (name, module)
+        })
+        .collect()
+}
+
+/// Compare `actual` with the expected output stored in `<dir>/<name>.<ext>`; when the
+/// `ALEF_BLESS` environment variable is set, store `actual` as the expected output instead.
+pub fn check_golden(dir: &Path, name: &str, ext: &str, actual: &str) {
+    let path = dir.join(format!("{}.{}", name, ext));
+
+    if std::env::var_os("ALEF_BLESS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e));
+    assert_eq!(actual, expected, "output differs from {}", path.display());
+}
+
+/// Return true if a C compiler is available to link the test programs.
+pub fn have_cc() -> bool {
+    Command::new("cc").arg("--version").output().is_ok()
+}
+
+/// A directory for the files generated by a test, removed before it is returned.
+pub fn scratch_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("alef-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
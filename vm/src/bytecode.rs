@@ -0,0 +1,428 @@
+//! Functions are arrays of fixed-size instructions operating on a file of 64 bit registers.
+//!
+//! Every temporary of the IR gets a register; constants are kept in the last registers of the
+//! file, which are filled from the function's constant pool when a frame is created, so that
+//! instructions never carry operands of variable size. Operand lists (call arguments, `switch`
+//! cases, `alt` cases) live in tables of the function and are referred to by index.
+//!
+//! Integer registers are kept sign-extended from the width of their type, floats are stored as
+//! their bits and pointers as they are.
+
+use alef_ir::inst::{BinOp, CmpOp, ConvOp, UnOp};
+use alef_ir::ty::Type;
+
+/// A register number.
+pub type Reg = u16;
+
+/// The register number used when an instruction has no destination or no operand.
+pub const NO_REG: Reg = Reg::MAX;
+
+/// The segment number of function pointers; the offset is the index of the function.
+pub const FUNC_SEG: u64 = u32::MAX as u64;
+
+/// The type of a scalar operand; pointers are `I64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Ty {
+    I8,
+    I16,
+    I32,
+    I64,
+    F64,
+}
+
+impl Ty {
+    /// The bytecode type of a scalar IR type.
+    pub fn of(ty: &Type) -> Ty {
+        match ty {
+            Type::I8 => Ty::I8,
+            Type::I16 => Ty::I16,
+            Type::I32 => Ty::I32,
+            Type::F64 => Ty::F64,
+            _ => Ty::I64,
+        }
+    }
+
+    /// The size in bytes.
+    pub fn size(self) -> u32 {
+        match self {
+            Ty::I8 => 1,
+            Ty::I16 => 2,
+            Ty::I32 => 4,
+            Ty::I64 | Ty::F64 => 8,
+        }
+    }
+
+    /// Bring `v` to the canonical form of a register of this type.
+    pub fn norm(self, v: u64) -> u64 {
+        match self {
+            Ty::I8 => v as i8 as u64,
+            Ty::I16 => v as i16 as u64,
+            Ty::I32 => v as i32 as u64,
+            Ty::I64 | Ty::F64 => v,
+        }
+    }
+
+    /// The bits of `v` that belong to a value of this type, zero-extended.
+    pub fn mask(self, v: u64) -> u64 {
+        match self {
+            Ty::I8 => v & 0xff,
+            Ty::I16 => v & 0xffff,
+            Ty::I32 => v & 0xffff_ffff,
+            Ty::I64 | Ty::F64 => v,
+        }
+    }
+}
+
+/// How a value is passed around: in a register or by address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// No value.
+    Void,
+
+    /// A scalar held in a register.
+    Scalar(Ty),
+
+    /// An aggregate of the given size, the register holds its address.
+    Mem(u32),
+}
+
+impl Kind {
+    /// The kind of values of an IR type.
+    pub fn of(ty: &Type, size: impl FnOnce(&Type) -> u64) -> Kind {
+        match ty {
+            Type::Void => Kind::Void,
+            ty if ty.is_aggregate() => Kind::Mem(size(ty) as u32),
+            ty => Kind::Scalar(Ty::of(ty)),
+        }
+    }
+
+    /// The number of bytes a value occupies in memory.
+    pub fn size(self) -> u32 {
+        match self {
+            Kind::Void => 0,
+            Kind::Scalar(ty) => ty.size(),
+            Kind::Mem(size) => size,
+        }
+    }
+}
+
+/// How a `Spawn` starts the new thread of control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Spawn {
+    /// A new process (`proc`).
+    Proc,
+
+    /// A new task of the current process (`task`).
+    Task,
+
+    /// A process that is part of a `par` group (`spawn`).
+    Par,
+}
+
+/// A single instruction. Jump targets are positions in the code of the function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// `dst = src`
+    Mov { dst: Reg, src: Reg },
+
+    /// `dst = lhs <op> rhs`
+    Bin {
+        op: BinOp,
+        ty: Ty,
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+
+    /// `dst = lhs <op> rhs`, 0 or 1.
+    Cmp {
+        op: CmpOp,
+        ty: Ty,
+        dst: Reg,
+        lhs: Reg,
+        rhs: Reg,
+    },
+
+    /// `dst = <op> arg`
+    Un {
+        op: UnOp,
+        ty: Ty,
+        dst: Reg,
+        arg: Reg,
+    },
+
+    /// `dst = <op> arg`, converting from `from` to `to`.
+    Conv {
+        op: ConvOp,
+        from: Ty,
+        to: Ty,
+        dst: Reg,
+        arg: Reg,
+    },
+
+    /// `dst = the address of the byte at offset in the stack frame`
+    Frame { dst: Reg, offset: u32 },
+
+    /// `dst = *addr`
+    Load { ty: Ty, dst: Reg, addr: Reg },
+
+    /// `*addr = src`
+    Store { ty: Ty, src: Reg, addr: Reg },
+
+    /// `*ap = the address of the first variadic argument of the frame`
+    VaStart { ap: Reg },
+
+    /// `dst = the next variadic argument of the va_list at ap`
+    VaArg { ty: Ty, dst: Reg, ap: Reg },
+
+    /// `dst = base + offset`
+    Offset { dst: Reg, base: Reg, offset: u32 },
+
+    /// `dst = base + index * size`
+    Index {
+        dst: Reg,
+        base: Reg,
+        index: Reg,
+        size: u32,
+    },
+
+    /// Copy `size` bytes from the address in `src` to the address in `dst`.
+    Blit { dst: Reg, src: Reg, size: u32 },
+
+    /// Call the function `callee` points to with the arguments of call site `site`.
+    ///
+    /// If the function returns an aggregate, `dst` already holds the address it is copied to.
+    Call { dst: Reg, callee: Reg, site: u32 },
+
+    /// Return the value in `src`, or nothing if it is `NO_REG`.
+    Ret { src: Reg },
+
+    /// Jump to `target`.
+    Jmp { target: u32 },
+
+    /// Jump to `then` if `cond` is not 0, to `else_` otherwise.
+    Br { cond: Reg, then: u32, else_: u32 },
+
+    /// Jump to the target of `value` in switch table `table`.
+    Switch { ty: Ty, value: Reg, table: u32 },
+
+    /// Stop with an error.
+    Hlt,
+
+    /// `dst = ` the address of `size` zeroed bytes on the heap.
+    Alloc { dst: Reg, size: u32 },
+
+    /// Release heap storage.
+    Unalloc { ptr: Reg },
+
+    /// `dst = ` a new channel of elements of `size` bytes with `cap` buffered slots.
+    Chan { dst: Reg, size: u32, cap: Reg },
+
+    /// Send `src` on `chan`, blocking until it is delivered or buffered.
+    Send { elem: Kind, chan: Reg, src: Reg },
+
+    /// Receive from `chan`; aggregates are copied to the address already in `dst`.
+    Recv { elem: Kind, dst: Reg, chan: Reg },
+
+    /// `dst = ` 1 if a send on `chan` would not block.
+    CanSend { dst: Reg, chan: Reg },
+
+    /// `dst = ` 1 if a receive from `chan` would not block.
+    CanRecv { dst: Reg, chan: Reg },
+
+    /// Start a new thread of control running a call; `group` is only used by `Spawn::Par`.
+    Spawn {
+        how: Spawn,
+        group: Reg,
+        callee: Reg,
+        site: u32,
+    },
+
+    /// `dst = ` a new `par` group.
+    Par { dst: Reg },
+
+    /// Wait until every process of `group` has terminated.
+    Join { group: Reg },
+
+    /// Push `target` on the rescue stack of the frame.
+    Rescue { target: u32 },
+
+    /// Pop the rescue stack of the frame.
+    Unrescue,
+
+    /// Pop the rescue stack of the frame and jump to the target.
+    Raise,
+
+    /// Wait until one of the cases of alt table `table` can proceed and jump to its target.
+    Alt { table: u32 },
+}
+
+/// The operands of a call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Site {
+    /// The arguments; aggregates are copied into the frame of the callee.
+    pub args: Vec<(Kind, Reg)>,
+
+    /// What the call returns.
+    pub ret: Kind,
+}
+
+/// The cases of a `switch`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitchTable {
+    /// The values and their targets.
+    pub cases: Vec<(i64, u32)>,
+
+    /// The target when no case matches.
+    pub default: u32,
+}
+
+/// A case of an `alt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AltArm {
+    /// True for a send, false for a receive.
+    pub send: bool,
+
+    /// The channel.
+    pub chan: Reg,
+
+    /// The element type of the channel.
+    pub elem: Kind,
+
+    /// The value sent, or the address the received value is stored to (`NO_REG` to drop it).
+    pub reg: Reg,
+
+    /// The target when the case is selected.
+    pub target: u32,
+}
+
+/// A formal parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Param {
+    /// How the argument is passed.
+    pub kind: Kind,
+
+    /// For aggregates, the offset in the frame of the private copy.
+    pub slot: u32,
+}
+
+/// A function translated to bytecode.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The name of the function, without the leading `$`.
+    pub name: String,
+
+    /// The parameters, held in the first registers.
+    pub params: Vec<Param>,
+
+    /// What the function returns.
+    pub ret: Kind,
+
+    /// The number of registers holding temporaries; the constants follow them.
+    pub temps: u16,
+
+    /// The values of the constant registers.
+    pub consts: Vec<u64>,
+
+    /// The size of the stack frame in bytes.
+    pub frame: u32,
+
+    /// The instructions.
+    pub code: Vec<Op>,
+
+    /// The call sites.
+    pub sites: Vec<Site>,
+
+    /// The switch tables.
+    pub switches: Vec<SwitchTable>,
+
+    /// The alt tables.
+    pub alts: Vec<Vec<AltArm>>,
+
+    /// The position and label of every basic block, for the disassembler.
+    pub labels: Vec<(u32, String)>,
+}
+
+impl Function {
+    /// The total number of registers of a frame.
+    pub fn regs(&self) -> usize {
+        self.temps as usize + self.consts.len()
+    }
+}
+
+/// A data definition, loaded in its own segment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Data {
+    /// The name of the data, without the leading `$`.
+    pub name: String,
+
+    /// The initial content, with the addresses of globals already resolved.
+    pub bytes: Vec<u8>,
+}
+
+/// A whole program.
+///
+/// Data definition `i` is loaded in segment `i + 1`, so addresses of data are known in advance.
+/// Function pointers use segment `FUNC_SEG`: offsets below the number of functions refer to
+/// `funcs`, the following ones to `externs`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The data definitions.
+    pub data: Vec<Data>,
+
+    /// The names of the external functions, provided by the machine.
+    pub externs: Vec<String>,
+
+    /// The functions.
+    pub funcs: Vec<Function>,
+
+    /// The index of `$main`, if any.
+    pub main: Option<u32>,
+}
+
+impl Program {
+    /// The address of data definition `index`.
+    pub fn data_addr(index: usize) -> u64 {
+        (index as u64 + 1) << 32
+    }
+
+    /// The pointer to function `index`.
+    pub fn func_addr(index: usize) -> u64 {
+        FUNC_SEG << 32 | index as u64
+    }
+
+    /// The name of what `addr` points to, if it is the address of a function or of data.
+    pub fn symbol(&self, addr: u64) -> Option<&str> {
+        let (seg, off) = (addr >> 32, addr & 0xffff_ffff);
+        if seg == FUNC_SEG {
+            let off = off as usize;
+            return match off.checked_sub(self.funcs.len()) {
+                None => Some(&self.funcs[off].name),
+                Some(i) => self.externs.get(i).map(|s| s.as_str()),
+            };
+        }
+
+        match (seg as usize).checked_sub(1) {
+            Some(i) if off == 0 && i < self.data.len() => Some(&self.data[i].name),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compact() {
+        assert!(std::mem::size_of::<Op>() <= 16);
+    }
+
+    #[test]
+    fn norm() {
+        assert_eq!(Ty::I8.norm(200), -56i64 as u64);
+        assert_eq!(Ty::I8.mask(-56i64 as u64), 200);
+        assert_eq!(Ty::I32.norm(0xffff_ffff), u64::MAX);
+        assert_eq!(Ty::I16.mask(u64::MAX), 0xffff);
+    }
+}
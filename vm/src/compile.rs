@@ -0,0 +1,626 @@
+//! Translation of a verified module into bytecode.
+//!
+//! The translation is a single pass over the blocks of each function: temporaries are numbered
+//! first, so that uses before the definition (which the IR allows across blocks) need no special
+//! care, then every instruction is translated to one or two bytecode instructions. Jumps are
+//! emitted with block numbers and patched to code positions at the end.
+
+use crate::bytecode::{
+    AltArm, Data, Function, Kind, Op, Param, Program, Reg, Site, Spawn, SwitchTable, Ty, NO_REG,
+};
+use crate::err::CompileError;
+use alef_ir::func::{self, Block};
+use alef_ir::inst::{AltCase, Inst, Terminator, Value};
+use alef_ir::module::{DataItem, Module};
+use alef_ir::ty::{align_to, Type};
+use std::collections::HashMap;
+
+/// Translate `module` into a program.
+pub fn compile(module: &Module) -> Result<Program, CompileError> {
+    let mut globals = HashMap::new();
+    for (i, d) in module.data.iter().enumerate() {
+        globals.insert(d.name.as_str(), Program::data_addr(i));
+    }
+    for (i, f) in module.funcs.iter().enumerate() {
+        globals.insert(f.name.as_str(), Program::func_addr(i));
+    }
+    for (i, e) in module.externs.iter().enumerate() {
+        globals.insert(e.name.as_str(), Program::func_addr(module.funcs.len() + i));
+    }
+
+    let data = module
+        .data
+        .iter()
+        .map(|d| Data {
+            name: d.name.clone(),
+            bytes: data_bytes(&d.items, &globals),
+        })
+        .collect();
+
+    let funcs = module
+        .funcs
+        .iter()
+        .map(|f| FnCompiler::new(module, &globals, f).compile())
+        .collect::<Result<_, _>>()?;
+
+    Ok(Program {
+        data,
+        externs: module.externs.iter().map(|e| e.name.clone()).collect(),
+        funcs,
+        main: module
+            .funcs
+            .iter()
+            .position(|f| f.name == "main")
+            .map(|i| i as u32),
+    })
+}
+
+/// The initial content of a data definition.
+fn data_bytes(items: &[DataItem], globals: &HashMap<&str, u64>) -> Vec<u8> {
+    let mut bytes = vec![];
+    for item in items {
+        match item {
+            DataItem::Str(s) => {
+                bytes.extend_from_slice(s.as_bytes());
+                bytes.push(0);
+            }
+            DataItem::Runestr(s) => {
+                for c in s.chars().chain(Some('\0')) {
+                    bytes.extend_from_slice(&(c as u32).to_le_bytes());
+                }
+            }
+            DataItem::Int(ty, v) => {
+                let size = item.size() as usize;
+                bytes.extend_from_slice(&Ty::of(ty).mask(*v as u64).to_le_bytes()[..size]);
+            }
+            DataItem::Float(f) => bytes.extend_from_slice(&f.to_bits().to_le_bytes()),
+            DataItem::Addr(name) => bytes.extend_from_slice(&globals[name.as_str()].to_le_bytes()),
+            DataItem::Zero(n) => bytes.resize(bytes.len() + *n as usize, 0),
+        }
+    }
+    bytes
+}
+
+/// The translation state of a single function.
+struct FnCompiler<'a> {
+    module: &'a Module,
+    globals: &'a HashMap<&'a str, u64>,
+    func: &'a func::Function,
+
+    /// The register of every temporary.
+    regs: HashMap<&'a str, Reg>,
+
+    /// The constant pool and the index of every constant in it.
+    consts: Vec<u64>,
+    const_index: HashMap<u64, usize>,
+
+    /// The size of the stack frame so far.
+    frame: u64,
+
+    code: Vec<Op>,
+    sites: Vec<Site>,
+    switches: Vec<SwitchTable>,
+    alts: Vec<Vec<AltArm>>,
+}
+
+impl<'a> FnCompiler<'a> {
+    fn new(
+        module: &'a Module,
+        globals: &'a HashMap<&'a str, u64>,
+        func: &'a func::Function,
+    ) -> FnCompiler<'a> {
+        FnCompiler {
+            module,
+            globals,
+            func,
+            regs: HashMap::new(),
+            consts: vec![],
+            const_index: HashMap::new(),
+            frame: 0,
+            code: vec![],
+            sites: vec![],
+            switches: vec![],
+            alts: vec![],
+        }
+    }
+
+    fn compile(mut self) -> Result<Function, CompileError> {
+        let f = self.func;
+        for p in &f.params {
+            self.regs.insert(&p.name, self.regs.len() as Reg);
+        }
+        for b in &f.blocks {
+            for i in &b.insts {
+                if let Some(dst) = i.dst() {
+                    let n = self.regs.len() as Reg;
+                    self.regs.entry(dst).or_insert(n);
+                }
+            }
+        }
+
+        let params = f
+            .params
+            .iter()
+            .map(|p| {
+                let kind = self.kind(&p.ty);
+                let slot = match kind {
+                    Kind::Mem(_) => self.slot(&p.ty),
+                    _ => 0,
+                };
+                Param { kind, slot }
+            })
+            .collect();
+
+        let mut starts = vec![];
+        let mut labels = vec![];
+        for b in &f.blocks {
+            starts.push(self.code.len() as u32);
+            labels.push((self.code.len() as u32, b.label.clone()));
+            self.block(b);
+        }
+        self.patch(&starts);
+
+        let max = NO_REG as usize;
+        if self.regs.len() + self.consts.len() > max {
+            return Err(CompileError::TooManyRegisters {
+                func: f.name.clone(),
+                max,
+            });
+        }
+        let frame =
+            u32::try_from(self.frame).map_err(|_| CompileError::FrameTooLarge(f.name.clone()))?;
+
+        Ok(Function {
+            name: f.name.clone(),
+            params,
+            ret: self.kind(&f.ret),
+            temps: self.regs.len() as u16,
+            consts: self.consts,
+            frame,
+            code: self.code,
+            sites: self.sites,
+            switches: self.switches,
+            alts: self.alts,
+            labels,
+        })
+    }
+
+    fn size(&self, ty: &Type) -> u64 {
+        self.module.layout(ty).map_or(0, |l| l.size)
+    }
+
+    fn kind(&self, ty: &Type) -> Kind {
+        Kind::of(ty, |t| self.size(t))
+    }
+
+    /// Reserve room for a value of type `ty` in the frame and return its offset.
+    fn slot(&mut self, ty: &Type) -> u32 {
+        let l = self
+            .module
+            .layout(ty)
+            .unwrap_or(alef_ir::ty::Layout::scalar(1));
+        self.frame = align_to(self.frame, l.align);
+        let off = self.frame;
+        self.frame += l.size;
+        off as u32
+    }
+
+    fn temp(&self, name: &str) -> Reg {
+        self.regs[name]
+    }
+
+    /// The register holding `v`, used as a value of type `ty`.
+    fn val(&mut self, v: &Value, ty: Ty) -> Reg {
+        let bits = match v {
+            Value::Temp(name) => return self.temp(name),
+            Value::Global(name) => self.globals[name.as_str()],
+            Value::Int(n) => ty.norm(*n as u64),
+            Value::Float(f) => f.to_bits(),
+        };
+        let index = match self.const_index.get(&bits) {
+            Some(i) => *i,
+            None => {
+                self.consts.push(bits);
+                self.const_index.insert(bits, self.consts.len() - 1);
+                self.consts.len() - 1
+            }
+        };
+        // Constants follow the temporaries, whose number is known before translation starts.
+        (self.regs.len() + index) as Reg
+    }
+
+    fn ptr(&mut self, v: &Value) -> Reg {
+        self.val(v, Ty::I64)
+    }
+
+    fn site(&mut self, args: &[(Type, Value)], ret: &Type) -> u32 {
+        let args = args
+            .iter()
+            .map(|(ty, v)| {
+                let kind = self.kind(ty);
+                (kind, self.val(v, Ty::of(ty)))
+            })
+            .collect();
+        let ret = self.kind(ret);
+        self.sites.push(Site { args, ret });
+        (self.sites.len() - 1) as u32
+    }
+
+    fn label(&self, label: &str) -> u32 {
+        self.func.block_index(label).expect("unknown label") as u32
+    }
+
+    fn emit(&mut self, op: Op) {
+        self.code.push(op);
+    }
+
+    fn block(&mut self, b: &'a Block) {
+        for i in &b.insts {
+            self.inst(i);
+        }
+        self.term(&b.term);
+    }
+
+    fn inst(&mut self, i: &'a Inst) {
+        match i {
+            Inst::Bin {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let ty = Ty::of(ty);
+                let (lhs, rhs) = (self.val(lhs, ty), self.val(rhs, ty));
+                let dst = self.temp(dst);
+                self.emit(Op::Bin {
+                    op: *op,
+                    ty,
+                    dst,
+                    lhs,
+                    rhs,
+                });
+            }
+            Inst::Cmp {
+                dst,
+                op,
+                ty,
+                lhs,
+                rhs,
+            } => {
+                let ty = Ty::of(ty);
+                let (lhs, rhs) = (self.val(lhs, ty), self.val(rhs, ty));
+                let dst = self.temp(dst);
+                self.emit(Op::Cmp {
+                    op: *op,
+                    ty,
+                    dst,
+                    lhs,
+                    rhs,
+                });
+            }
+            Inst::Un { dst, op, ty, arg } => {
+                let ty = Ty::of(ty);
+                let arg = self.val(arg, ty);
+                let dst = self.temp(dst);
+                self.emit(Op::Un {
+                    op: *op,
+                    ty,
+                    dst,
+                    arg,
+                });
+            }
+            Inst::Conv {
+                dst,
+                op,
+                from,
+                arg,
+                to,
+            } => {
+                let (from, to) = (Ty::of(from), Ty::of(to));
+                let arg = self.val(arg, from);
+                let dst = self.temp(dst);
+                self.emit(Op::Conv {
+                    op: *op,
+                    from,
+                    to,
+                    dst,
+                    arg,
+                });
+            }
+            Inst::Copy { dst, ty, arg } => {
+                let src = self.val(arg, Ty::of(ty));
+                let dst = self.temp(dst);
+                self.emit(Op::Mov { dst, src });
+            }
+            Inst::Alloca { dst, ty } => {
+                let offset = self.slot(ty);
+                let dst = self.temp(dst);
+                self.emit(Op::Frame { dst, offset });
+            }
+            Inst::Load { dst, ty, addr } => {
+                let addr = self.ptr(addr);
+                let dst = self.temp(dst);
+                self.emit(Op::Load {
+                    ty: Ty::of(ty),
+                    dst,
+                    addr,
+                });
+            }
+            Inst::Store { ty, value, addr } => {
+                let ty = Ty::of(ty);
+                let src = self.val(value, ty);
+                let addr = self.ptr(addr);
+                self.emit(Op::Store { ty, src, addr });
+            }
+            Inst::VaStart { ap } => {
+                let ap = self.ptr(ap);
+                self.emit(Op::VaStart { ap });
+            }
+            Inst::VaArg { dst, ty, ap } => {
+                let ap = self.ptr(ap);
+                let dst = self.temp(dst);
+                self.emit(Op::VaArg {
+                    ty: Ty::of(ty),
+                    dst,
+                    ap,
+                });
+            }
+            Inst::Field {
+                dst,
+                aggr,
+                base,
+                index,
+            } => {
+                let offset = self.module.field_offset(aggr, *index as usize).unwrap_or(0) as u32;
+                let base = self.ptr(base);
+                let dst = self.temp(dst);
+                self.emit(Op::Offset { dst, base, offset });
+            }
+            Inst::Index {
+                dst,
+                elem,
+                base,
+                index,
+            } => {
+                let size = self.size(elem) as u32;
+                let base = self.ptr(base);
+                let index = self.val(index, Ty::I64);
+                let dst = self.temp(dst);
+                self.emit(Op::Index {
+                    dst,
+                    base,
+                    index,
+                    size,
+                });
+            }
+            Inst::Blit { ty, dst, src } => {
+                let size = self.size(ty) as u32;
+                let (dst, src) = (self.ptr(dst), self.ptr(src));
+                self.emit(Op::Blit { dst, src, size });
+            }
+            Inst::Call {
+                dst,
+                ret,
+                callee,
+                args,
+                ..
+            } => {
+                let callee = self.ptr(callee);
+                let site = self.site(args, ret);
+                let dst = match dst {
+                    Some(dst) => self.temp(dst),
+                    None => NO_REG,
+                };
+                if ret.is_aggregate() && dst != NO_REG {
+                    let offset = self.slot(ret);
+                    self.emit(Op::Frame { dst, offset });
+                }
+                self.emit(Op::Call { dst, callee, site });
+            }
+            Inst::Alloc { dst, ty } => {
+                let size = self.size(ty) as u32;
+                let dst = self.temp(dst);
+                self.emit(Op::Alloc { dst, size });
+            }
+            Inst::Unalloc { ptr } => {
+                let ptr = self.ptr(ptr);
+                self.emit(Op::Unalloc { ptr });
+            }
+            Inst::ChanNew { dst, elem, cap } => {
+                let size = self.size(elem) as u32;
+                let cap = self.val(cap, Ty::I64);
+                let dst = self.temp(dst);
+                self.emit(Op::Chan { dst, size, cap });
+            }
+            Inst::Send { elem, chan, value } => {
+                let kind = self.kind(elem);
+                let chan = self.ptr(chan);
+                let src = self.val(value, Ty::of(elem));
+                self.emit(Op::Send {
+                    elem: kind,
+                    chan,
+                    src,
+                });
+            }
+            Inst::Recv { dst, elem, chan } => {
+                let kind = self.kind(elem);
+                let chan = self.ptr(chan);
+                let dst = self.temp(dst);
+                if let Kind::Mem(_) = kind {
+                    let offset = self.slot(elem);
+                    self.emit(Op::Frame { dst, offset });
+                }
+                self.emit(Op::Recv {
+                    elem: kind,
+                    dst,
+                    chan,
+                });
+            }
+            Inst::CanSend { dst, chan } => {
+                let chan = self.ptr(chan);
+                let dst = self.temp(dst);
+                self.emit(Op::CanSend { dst, chan });
+            }
+            Inst::CanRecv { dst, chan } => {
+                let chan = self.ptr(chan);
+                let dst = self.temp(dst);
+                self.emit(Op::CanRecv { dst, chan });
+            }
+            Inst::Proc { callee, args } => self.spawn(Spawn::Proc, None, callee, args),
+            Inst::Task { callee, args } => self.spawn(Spawn::Task, None, callee, args),
+            Inst::ParSpawn {
+                group,
+                callee,
+                args,
+            } => self.spawn(Spawn::Par, Some(group), callee, args),
+            Inst::ParBegin { dst } => {
+                let dst = self.temp(dst);
+                self.emit(Op::Par { dst });
+            }
+            Inst::ParJoin { group } => {
+                let group = self.ptr(group);
+                self.emit(Op::Join { group });
+            }
+            Inst::Rescue { label } => {
+                let target = self.label(label);
+                self.emit(Op::Rescue { target });
+            }
+            Inst::Unrescue => self.emit(Op::Unrescue),
+            Inst::Box { dst, ty, value } => {
+                let size = self.size(ty) as u32;
+                let src = self.val(value, Ty::of(ty));
+                let dst = self.temp(dst);
+                self.emit(Op::Alloc { dst, size });
+                if ty.is_aggregate() {
+                    self.emit(Op::Blit { dst, src, size });
+                } else {
+                    self.emit(Op::Store {
+                        ty: Ty::of(ty),
+                        src,
+                        addr: dst,
+                    });
+                }
+            }
+            Inst::Unbox { dst, ty, poly } => {
+                let poly = self.ptr(poly);
+                let dst = self.temp(dst);
+                if ty.is_aggregate() {
+                    self.emit(Op::Mov { dst, src: poly });
+                } else {
+                    self.emit(Op::Load {
+                        ty: Ty::of(ty),
+                        dst,
+                        addr: poly,
+                    });
+                }
+            }
+        }
+    }
+
+    fn spawn(&mut self, how: Spawn, group: Option<&Value>, callee: &Value, args: &[(Type, Value)]) {
+        let group = group.map_or(NO_REG, |g| self.ptr(g));
+        let callee = self.ptr(callee);
+        let site = self.site(args, &Type::Void);
+        self.emit(Op::Spawn {
+            how,
+            group,
+            callee,
+            site,
+        });
+    }
+
+    fn term(&mut self, t: &Terminator) {
+        match t {
+            Terminator::Jmp(l) => {
+                let target = self.label(l);
+                self.emit(Op::Jmp { target });
+            }
+            Terminator::Br { cond, then, else_ } => {
+                let cond = self.val(cond, Ty::I32);
+                let (then, else_) = (self.label(then), self.label(else_));
+                self.emit(Op::Br { cond, then, else_ });
+            }
+            Terminator::Switch {
+                ty,
+                value,
+                default,
+                cases,
+            } => {
+                let ty = Ty::of(ty);
+                let value = self.val(value, ty);
+                let cases = cases
+                    .iter()
+                    .map(|(n, l)| (ty.norm(*n as u64) as i64, self.label(l)))
+                    .collect();
+                let default = self.label(default);
+                self.switches.push(SwitchTable { cases, default });
+                let table = (self.switches.len() - 1) as u32;
+                self.emit(Op::Switch { ty, value, table });
+            }
+            Terminator::Ret(v) => {
+                let src = match v {
+                    Some((ty, v)) => self.val(v, Ty::of(ty)),
+                    None => NO_REG,
+                };
+                self.emit(Op::Ret { src });
+            }
+            Terminator::Alt(cases) => {
+                let arms = cases
+                    .iter()
+                    .map(|c| {
+                        let elem = self.kind(c.elem());
+                        let chan = self.ptr(c.chan());
+                        let target = self.label(c.target());
+                        let (send, reg) = match c {
+                            AltCase::Recv { slot, .. } => {
+                                (false, slot.as_ref().map_or(NO_REG, |s| self.ptr(s)))
+                            }
+                            AltCase::Send { elem, value, .. } => {
+                                (true, self.val(value, Ty::of(elem)))
+                            }
+                        };
+                        AltArm {
+                            send,
+                            chan,
+                            elem,
+                            reg,
+                            target,
+                        }
+                    })
+                    .collect();
+                self.alts.push(arms);
+                let table = (self.alts.len() - 1) as u32;
+                self.emit(Op::Alt { table });
+            }
+            Terminator::Raise => self.emit(Op::Raise),
+            Terminator::Hlt => self.emit(Op::Hlt),
+        }
+    }
+
+    /// Replace block numbers with the position of the blocks.
+    fn patch(&mut self, starts: &[u32]) {
+        let at = |b: &mut u32| *b = starts[*b as usize];
+        for op in &mut self.code {
+            match op {
+                Op::Jmp { target } | Op::Rescue { target } => at(target),
+                Op::Br { then, else_, .. } => {
+                    at(then);
+                    at(else_);
+                }
+                _ => {}
+            }
+        }
+        for s in &mut self.switches {
+            at(&mut s.default);
+            for (_, t) in &mut s.cases {
+                at(t);
+            }
+        }
+        for arms in &mut self.alts {
+            for a in arms {
+                at(&mut a.target);
+            }
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! The textual form of the bytecode, printed by `alef-check disasm`.
+//!
+//! Each function starts with a header giving its registers and frame size, followed by the
+//! constant registers and the code; jump targets are code positions, annotated with the label
+//! of the block they start. Constants that are addresses of globals are shown by name.
+
+use crate::bytecode::{Function, Kind, Op, Program, Reg, Spawn, Ty, NO_REG};
+use alef_ir::inst::{BinOp, CmpOp, ConvOp, UnOp};
+use std::fmt::{Display, Formatter, Result};
+
+impl Display for Ty {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let s = match self {
+            Ty::I8 => "i8",
+            Ty::I16 => "i16",
+            Ty::I32 => "i32",
+            Ty::I64 => "i64",
+            Ty::F64 => "f64",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Kind::Void => write!(f, "void"),
+            Kind::Scalar(ty) => write!(f, "{}", ty),
+            Kind::Mem(size) => write!(f, "[{}]", size),
+        }
+    }
+}
+
+fn bin_name(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "add",
+        BinOp::Sub => "sub",
+        BinOp::Mul => "mul",
+        BinOp::Div => "div",
+        BinOp::UDiv => "udiv",
+        BinOp::Rem => "rem",
+        BinOp::URem => "urem",
+        BinOp::And => "and",
+        BinOp::Or => "or",
+        BinOp::Xor => "xor",
+        BinOp::Shl => "shl",
+        BinOp::Shr => "shr",
+        BinOp::UShr => "ushr",
+    }
+}
+
+fn cmp_name(op: CmpOp) -> &'static str {
+    match op {
+        CmpOp::Eq => "eq",
+        CmpOp::Ne => "ne",
+        CmpOp::Lt => "lt",
+        CmpOp::Le => "le",
+        CmpOp::Gt => "gt",
+        CmpOp::Ge => "ge",
+        CmpOp::ULt => "ult",
+        CmpOp::ULe => "ule",
+        CmpOp::UGt => "ugt",
+        CmpOp::UGe => "uge",
+    }
+}
+
+fn un_name(op: UnOp) -> &'static str {
+    match op {
+        UnOp::Neg => "neg",
+        UnOp::Not => "not",
+    }
+}
+
+fn conv_name(op: ConvOp) -> &'static str {
+    match op {
+        ConvOp::Sext => "sext",
+        ConvOp::Zext => "zext",
+        ConvOp::Trunc => "trunc",
+        ConvOp::SiToF => "sitof",
+        ConvOp::UiToF => "uitof",
+        ConvOp::FToSi => "ftosi",
+        ConvOp::FToUi => "ftoui",
+        ConvOp::Bitcast => "bitcast",
+    }
+}
+
+fn reg(r: Reg) -> String {
+    if r == NO_REG {
+        "_".to_string()
+    } else {
+        format!("r{}", r)
+    }
+}
+
+/// Print a function in the context of its program.
+struct Disasm<'a> {
+    prog: &'a Program,
+    func: &'a Function,
+}
+
+impl Disasm<'_> {
+    /// A jump target with the label of its block.
+    fn target(&self, pc: u32) -> String {
+        match self.func.labels.iter().find(|(at, _)| *at == pc) {
+            Some((_, l)) => format!("{:04} @{}", pc, l),
+            None => format!("{:04}", pc),
+        }
+    }
+
+    fn args(&self, site: u32) -> String {
+        let site = &self.func.sites[site as usize];
+        let args: Vec<_> = site
+            .args
+            .iter()
+            .map(|(k, r)| format!("{} {}", k, reg(*r)))
+            .collect();
+        format!("({})", args.join(", "))
+    }
+
+    fn op(&self, f: &mut Formatter<'_>, op: &Op) -> Result {
+        match *op {
+            Op::Mov { dst, src } => write!(f, "mov {}, {}", reg(dst), reg(src)),
+            Op::Bin {
+                op,
+                ty,
+                dst,
+                lhs,
+                rhs,
+            } => write!(
+                f,
+                "{}.{} {}, {}, {}",
+                bin_name(op),
+                ty,
+                reg(dst),
+                reg(lhs),
+                reg(rhs)
+            ),
+            Op::Cmp {
+                op,
+                ty,
+                dst,
+                lhs,
+                rhs,
+            } => write!(
+                f,
+                "{}.{} {}, {}, {}",
+                cmp_name(op),
+                ty,
+                reg(dst),
+                reg(lhs),
+                reg(rhs)
+            ),
+            Op::Un { op, ty, dst, arg } => {
+                write!(f, "{}.{} {}, {}", un_name(op), ty, reg(dst), reg(arg))
+            }
+            Op::Conv {
+                op,
+                from,
+                to,
+                dst,
+                arg,
+            } => write!(
+                f,
+                "{}.{}.{} {}, {}",
+                conv_name(op),
+                from,
+                to,
+                reg(dst),
+                reg(arg)
+            ),
+            Op::Frame { dst, offset } => write!(f, "frame {}, {}", reg(dst), offset),
+            Op::Load { ty, dst, addr } => write!(f, "load.{} {}, [{}]", ty, reg(dst), reg(addr)),
+            Op::Store { ty, src, addr } => write!(f, "store.{} {}, [{}]", ty, reg(src), reg(addr)),
+            Op::VaStart { ap } => write!(f, "vastart [{}]", reg(ap)),
+            Op::VaArg { ty, dst, ap } => write!(f, "vaarg.{} {}, [{}]", ty, reg(dst), reg(ap)),
+            Op::Offset { dst, base, offset } => {
+                write!(f, "offset {}, {}, {}", reg(dst), reg(base), offset)
+            }
+            Op::Index {
+                dst,
+                base,
+                index,
+                size,
+            } => write!(
+                f,
+                "index {}, {}, {} * {}",
+                reg(dst),
+                reg(base),
+                reg(index),
+                size
+            ),
+            Op::Blit { dst, src, size } => {
+                write!(f, "blit [{}], [{}], {}", reg(dst), reg(src), size)
+            }
+            Op::Call { dst, callee, site } => {
+                write!(f, "call {}, {}{}", reg(dst), reg(callee), self.args(site))
+            }
+            Op::Ret { src } => write!(f, "ret {}", reg(src)),
+            Op::Jmp { target } => write!(f, "jmp {}", self.target(target)),
+            Op::Br { cond, then, else_ } => write!(
+                f,
+                "br {}, {}, {}",
+                reg(cond),
+                self.target(then),
+                self.target(else_)
+            ),
+            Op::Switch { ty, value, table } => {
+                write!(f, "switch.{} {}", ty, reg(value))?;
+                let table = &self.func.switches[table as usize];
+                for (n, t) in &table.cases {
+                    write!(f, "\n              {} -> {}", n, self.target(*t))?;
+                }
+                write!(f, "\n              _ -> {}", self.target(table.default))
+            }
+            Op::Hlt => write!(f, "hlt"),
+            Op::Alloc { dst, size } => write!(f, "alloc {}, {}", reg(dst), size),
+            Op::Unalloc { ptr } => write!(f, "unalloc {}", reg(ptr)),
+            Op::Chan { dst, size, cap } => {
+                write!(f, "chan {}, {}, {}", reg(dst), size, reg(cap))
+            }
+            Op::Send { elem, chan, src } => {
+                write!(f, "send.{} {}, {}", elem, reg(chan), reg(src))
+            }
+            Op::Recv { elem, dst, chan } => {
+                write!(f, "recv.{} {}, {}", elem, reg(dst), reg(chan))
+            }
+            Op::CanSend { dst, chan } => write!(f, "cansend {}, {}", reg(dst), reg(chan)),
+            Op::CanRecv { dst, chan } => write!(f, "canrecv {}, {}", reg(dst), reg(chan)),
+            Op::Spawn {
+                how,
+                group,
+                callee,
+                site,
+            } => match how {
+                Spawn::Proc => write!(f, "proc {}{}", reg(callee), self.args(site)),
+                Spawn::Task => write!(f, "task {}{}", reg(callee), self.args(site)),
+                Spawn::Par => write!(
+                    f,
+                    "spawn {}, {}{}",
+                    reg(group),
+                    reg(callee),
+                    self.args(site)
+                ),
+            },
+            Op::Par { dst } => write!(f, "par {}", reg(dst)),
+            Op::Join { group } => write!(f, "join {}", reg(group)),
+            Op::Rescue { target } => write!(f, "rescue {}", self.target(target)),
+            Op::Unrescue => write!(f, "unrescue"),
+            Op::Raise => write!(f, "raise"),
+            Op::Alt { table } => {
+                write!(f, "alt")?;
+                for a in &self.func.alts[table as usize] {
+                    let (what, operand) = if a.send {
+                        ("send", reg(a.reg))
+                    } else {
+                        ("recv", format!("[{}]", reg(a.reg)))
+                    };
+                    write!(
+                        f,
+                        "\n              {}.{} {}, {} -> {}",
+                        what,
+                        a.elem,
+                        reg(a.chan),
+                        operand,
+                        self.target(a.target)
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Display for Disasm<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let func = self.func;
+        let params: Vec<_> = func.params.iter().map(|p| p.kind.to_string()).collect();
+        writeln!(
+            f,
+            "fn ${}({}) -> {}: {} registers, frame {}",
+            func.name,
+            params.join(", "),
+            func.ret,
+            func.regs(),
+            func.frame
+        )?;
+        for (i, c) in func.consts.iter().enumerate() {
+            let r = func.temps as usize + i;
+            match self.prog.symbol(*c) {
+                Some(name) => writeln!(f, "    r{} = ${}", r, name)?,
+                None => writeln!(f, "    r{} = {}", r, *c as i64)?,
+            }
+        }
+        for (pc, op) in func.code.iter().enumerate() {
+            for (_, l) in func.labels.iter().filter(|(at, _)| *at == pc as u32) {
+                writeln!(f, "@{}:", l)?;
+            }
+            write!(f, "    {:04}  ", pc)?;
+            self.op(f, op)?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for (i, d) in self.data.iter().enumerate() {
+            writeln!(
+                f,
+                "data ${} at {:#x}, {} bytes",
+                d.name,
+                Program::data_addr(i),
+                d.bytes.len()
+            )?;
+            for chunk in d.bytes.chunks(16) {
+                let hex: Vec<_> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                let text: String = chunk
+                    .iter()
+                    .map(|b| {
+                        if b.is_ascii_graphic() || *b == b' ' {
+                            *b as char
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect();
+                writeln!(f, "    {:<47}  |{}|", hex.join(" "), text)?;
+            }
+        }
+        for e in &self.externs {
+            writeln!(f, "extern ${}", e)?;
+        }
+        for func in &self.funcs {
+            writeln!(f)?;
+            write!(f, "{}", Disasm { prog: self, func })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,104 @@
+use thiserror::Error;
+
+/// Error returned when a module cannot be translated to bytecode.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CompileError {
+    /// The function needs more registers than an instruction can address.
+    #[error("in ${func}: more than {max} registers needed")]
+    TooManyRegisters {
+        /// The function being translated.
+        func: String,
+
+        /// The number of registers available.
+        max: usize,
+    },
+
+    /// The stack frame of the function does not fit in a segment.
+    #[error("in ${0}: stack frame too large")]
+    FrameTooLarge(String),
+}
+
+/// Error that stops the machine.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VmError {
+    /// The program has no `$main` function.
+    #[error("the program has no $main function")]
+    NoMain,
+
+    /// A load, store or copy touched memory outside of a live segment.
+    #[error("invalid access of {len} bytes at {addr:#x}")]
+    Fault {
+        /// The first address accessed.
+        addr: u64,
+
+        /// The number of bytes accessed.
+        len: u64,
+    },
+
+    /// `unalloc` or `free` of something that was not allocated on the heap.
+    #[error("invalid release of {0:#x}")]
+    BadFree(u64),
+
+    /// A call through a pointer that does not point to a function.
+    #[error("{0:#x} is not a function")]
+    NotAFunction(u64),
+
+    /// A channel operation on a pointer that does not point to a channel.
+    #[error("{0:#x} is not a channel")]
+    NotAChannel(u64),
+
+    /// A `spawn` or `join` on a pointer that does not point to a `par` group.
+    #[error("{0:#x} is not a par group")]
+    NotAGroup(u64),
+
+    /// A call to an external function the machine does not provide.
+    #[error("call to unknown external function ${0}")]
+    UnknownExtern(String),
+
+    /// An integer division or remainder by zero.
+    #[error("division by zero")]
+    DivisionByZero,
+
+    /// Too many nested calls.
+    #[error("stack overflow")]
+    StackOverflow,
+
+    /// `raise` without an active rescue block.
+    #[error("exception raised outside of any rescue block")]
+    Unhandled,
+
+    /// A `hlt` was reached.
+    #[error("reached hlt")]
+    Halted,
+
+    /// The program called `abort`.
+    #[error("abort called")]
+    Abort,
+
+    /// A host function was called with arguments it cannot handle.
+    #[error("{func}: {msg}")]
+    Host {
+        /// The external function.
+        func: String,
+
+        /// A message describing the problem.
+        msg: String,
+    },
+
+    /// Every process and task is blocked.
+    #[error("deadlock: {}", .0.join("; "))]
+    Deadlock(Vec<String>),
+
+    /// An error raised while executing an instruction.
+    #[error("in ${func} at {pc:04}: {err}")]
+    At {
+        /// The function being executed.
+        func: String,
+
+        /// The position of the instruction.
+        pc: u32,
+
+        /// The error.
+        err: Box<VmError>,
+    },
+}
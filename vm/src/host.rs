@@ -0,0 +1,414 @@
+//! The external functions a program can call: the part of the C library the test programs and
+//! the lowering of Alef's built-ins need.
+
+use crate::bytecode::Kind;
+use crate::err::VmError;
+use crate::mem::{Memory, SegKind};
+use std::io::Write;
+
+/// What a host function did.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// It returned a value (0 for `void` functions).
+    Value(u64),
+
+    /// It terminated the program with an exit status.
+    Exit(i32),
+}
+
+/// Call the external function `name`.
+pub fn call(
+    name: &str,
+    args: &[(Kind, u64)],
+    mem: &mut Memory,
+    out: &mut dyn Write,
+) -> Result<Outcome, VmError> {
+    let fail = |msg: String| VmError::Host {
+        func: name.to_string(),
+        msg,
+    };
+    let arg = |i: usize| {
+        args.get(i)
+            .map(|a| a.1)
+            .ok_or_else(|| fail(format!("missing argument {}", i + 1)))
+    };
+
+    let ret = match name {
+        "printf" => {
+            let text = format(mem.cstr(arg(0)?)?, &args[1..], mem).map_err(fail)?;
+            out.write_all(&text).map_err(|e| fail(e.to_string()))?;
+            text.len() as u64
+        }
+        "puts" => {
+            let mut text = mem.cstr(arg(0)?)?.to_vec();
+            text.push(b'\n');
+            out.write_all(&text).map_err(|e| fail(e.to_string()))?;
+            1
+        }
+        "putchar" => {
+            let c = arg(0)?;
+            out.write_all(&[c as u8]).map_err(|e| fail(e.to_string()))?;
+            c & 0xff
+        }
+        "exit" => return Ok(Outcome::Exit(arg(0)? as i32)),
+        "abort" => return Err(VmError::Abort),
+        "malloc" => mem.alloc(arg(0)? as u32, SegKind::Heap),
+        "free" => {
+            let p = arg(0)?;
+            if p != 0 {
+                mem.free(p, SegKind::Heap)?;
+            }
+            0
+        }
+        _ => return Err(VmError::UnknownExtern(name.to_string())),
+    };
+    Ok(Outcome::Value(ret))
+}
+
+/// A conversion specification of `printf`.
+#[derive(Debug, Default)]
+struct Spec {
+    left: bool,
+    plus: bool,
+    space: bool,
+    alt: bool,
+    zero: bool,
+    width: usize,
+    prec: Option<usize>,
+    /// The size in bytes of integer arguments, from the length modifier.
+    size: u32,
+}
+
+/// Format `fmt` like C's `printf` does; the width of integer arguments comes from the length
+/// modifiers, as in C, and not from the type of the argument.
+pub fn format(fmt: &[u8], args: &[(Kind, u64)], mem: &Memory) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    let mut args = args.iter().map(|a| a.1);
+    let mut i = 0;
+    while i < fmt.len() {
+        if fmt[i] != b'%' {
+            out.push(fmt[i]);
+            i += 1;
+            continue;
+        }
+        i += 1;
+
+        let mut spec = Spec {
+            size: 4,
+            ..Spec::default()
+        };
+        while let Some(c) = fmt.get(i) {
+            match c {
+                b'-' => spec.left = true,
+                b'+' => spec.plus = true,
+                b' ' => spec.space = true,
+                b'#' => spec.alt = true,
+                b'0' => spec.zero = true,
+                _ => break,
+            }
+            i += 1;
+        }
+
+        let mut next = || args.next().ok_or("too few arguments");
+        if fmt.get(i) == Some(&b'*') {
+            let w = next()? as i32;
+            spec.left |= w < 0;
+            spec.width = w.unsigned_abs() as usize;
+            i += 1;
+        } else {
+            while let Some(d @ b'0'..=b'9') = fmt.get(i) {
+                spec.width = spec.width * 10 + (d - b'0') as usize;
+                i += 1;
+            }
+        }
+        if fmt.get(i) == Some(&b'.') {
+            i += 1;
+            let mut p = 0;
+            if fmt.get(i) == Some(&b'*') {
+                p = (next()? as i32).max(0) as usize;
+                i += 1;
+            } else {
+                while let Some(d @ b'0'..=b'9') = fmt.get(i) {
+                    p = p * 10 + (d - b'0') as usize;
+                    i += 1;
+                }
+            }
+            spec.prec = Some(p);
+        }
+        while let Some(c) = fmt.get(i) {
+            match c {
+                b'h' => spec.size /= 2,
+                b'l' | b'j' | b'z' | b't' => spec.size = 8,
+                _ => break,
+            }
+            spec.size = spec.size.max(1);
+            i += 1;
+        }
+
+        let conv = *fmt.get(i).ok_or("incomplete conversion")?;
+        i += 1;
+        let body = match conv {
+            b'%' => {
+                out.push(b'%');
+                continue;
+            }
+            b'd' | b'i' => {
+                let v = sext(next()?, spec.size);
+                let sign = sign(&spec, v < 0);
+                pad(
+                    &spec,
+                    sign,
+                    &digits(&spec, v.unsigned_abs(), 10, false),
+                    true,
+                )
+            }
+            b'u' | b'x' | b'X' | b'o' => {
+                let v = zext(next()?, spec.size);
+                let (radix, upper) = match conv {
+                    b'u' => (10, false),
+                    b'o' => (8, false),
+                    b'x' => (16, false),
+                    _ => (16, true),
+                };
+                let mut d = digits(&spec, v, radix, upper);
+                let mut prefix = "";
+                if spec.alt && radix == 16 && v != 0 {
+                    prefix = if upper { "0X" } else { "0x" };
+                } else if spec.alt && radix == 8 && !d.starts_with('0') {
+                    d.insert(0, '0');
+                }
+                pad(&spec, prefix, &d, true)
+            }
+            b'c' => {
+                // Bytes are not characters: pad a placeholder and put the raw byte in its place.
+                let c = next()? as u8;
+                let mut s = pad(&spec, "", "c", false).into_bytes();
+                let at = s.iter().position(|b| *b == b'c').unwrap();
+                s[at] = c;
+                out.extend_from_slice(&s);
+                continue;
+            }
+            b's' => {
+                let p = next()?;
+                let s = if p == 0 {
+                    b"(null)".to_vec()
+                } else {
+                    mem.cstr(p).map_err(|e| e.to_string())?.to_vec()
+                };
+                let s = match spec.prec {
+                    Some(n) if n < s.len() => &s[..n],
+                    _ => &s[..],
+                };
+                let s = String::from_utf8_lossy(s);
+                pad(&spec, "", &s, false)
+            }
+            b'p' => {
+                let p = next()?;
+                if p == 0 {
+                    pad(&spec, "", "(nil)", false)
+                } else {
+                    pad(&spec, "0x", &format!("{:x}", p), false)
+                }
+            }
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' => {
+                let f = f64::from_bits(next()?);
+                let sign = sign(&spec, f.is_sign_negative() && !f.is_nan());
+                let upper = conv.is_ascii_uppercase();
+                if !f.is_finite() {
+                    let s = match (f.is_nan(), upper) {
+                        (true, false) => "nan",
+                        (true, true) => "NAN",
+                        (false, false) => "inf",
+                        (false, true) => "INF",
+                    };
+                    pad(&spec, sign, s, false)
+                } else {
+                    let s = float(&spec, f.abs(), conv.to_ascii_lowercase(), upper);
+                    pad(&spec, sign, &s, true)
+                }
+            }
+            c => return Err(format!("unsupported conversion %{}", c as char)),
+        };
+        out.extend_from_slice(body.as_bytes());
+    }
+    Ok(out)
+}
+
+/// Sign-extend an integer argument of `size` bytes.
+fn sext(v: u64, size: u32) -> i64 {
+    let shift = 64 - size * 8;
+    ((v << shift) as i64) >> shift
+}
+
+/// Zero-extend an integer argument of `size` bytes.
+fn zext(v: u64, size: u32) -> u64 {
+    let shift = 64 - size * 8;
+    (v << shift) >> shift
+}
+
+fn sign(spec: &Spec, negative: bool) -> &'static str {
+    if negative {
+        "-"
+    } else if spec.plus {
+        "+"
+    } else if spec.space {
+        " "
+    } else {
+        ""
+    }
+}
+
+/// The digits of an integer, with at least as many digits as the precision asks.
+fn digits(spec: &Spec, v: u64, radix: u32, upper: bool) -> String {
+    if spec.prec == Some(0) && v == 0 {
+        return String::new();
+    }
+    let mut s = match radix {
+        8 => format!("{:o}", v),
+        16 if upper => format!("{:X}", v),
+        16 => format!("{:x}", v),
+        _ => v.to_string(),
+    };
+    if let Some(p) = spec.prec {
+        while s.len() < p {
+            s.insert(0, '0');
+        }
+    }
+    s
+}
+
+/// Pad `prefix` followed by `body` to the width; zero padding goes between them and only applies
+/// to numbers, and to integers only without a precision.
+fn pad(spec: &Spec, prefix: &str, body: &str, numeric: bool) -> String {
+    let len = prefix.len() + body.chars().count();
+    let fill = spec.width.saturating_sub(len);
+    if spec.left {
+        format!("{}{}{}", prefix, body, " ".repeat(fill))
+    } else if spec.zero && numeric && (spec.prec.is_none() || body.contains(['.', 'e', 'E'])) {
+        format!("{}{}{}", prefix, "0".repeat(fill), body)
+    } else {
+        format!("{}{}{}", " ".repeat(fill), prefix, body)
+    }
+}
+
+/// Format a finite, non-negative float for conversion `conv` (`f`, `e` or `g`).
+fn float(spec: &Spec, f: f64, conv: u8, upper: bool) -> String {
+    let prec = spec.prec.unwrap_or(6);
+    let mut s = match conv {
+        b'f' => fixed(f, prec, spec.alt),
+        b'e' => exp(f, prec, spec.alt),
+        _ => {
+            let p = prec.max(1);
+            let x = exp_of(f, p - 1);
+            let s = if x < -4 || x >= p as i32 {
+                exp(f, p - 1, spec.alt)
+            } else {
+                fixed(f, (p as i32 - 1 - x) as usize, spec.alt)
+            };
+            if spec.alt {
+                s
+            } else {
+                trim_zeros(&s)
+            }
+        }
+    };
+    if upper {
+        s.make_ascii_uppercase();
+    }
+    s
+}
+
+fn fixed(f: f64, prec: usize, alt: bool) -> String {
+    let mut s = format!("{:.*}", prec, f);
+    if alt && prec == 0 {
+        s.push('.');
+    }
+    s
+}
+
+/// The decimal exponent of `f` once rounded to `prec` digits after the point.
+fn exp_of(f: f64, prec: usize) -> i32 {
+    let s = format!("{:.*e}", prec, f);
+    s[s.find('e').unwrap() + 1..].parse().unwrap()
+}
+
+fn exp(f: f64, prec: usize, alt: bool) -> String {
+    let s = format!("{:.*e}", prec, f);
+    let (mantissa, e) = s.split_at(s.find('e').unwrap());
+    let e: i32 = e[1..].parse().unwrap();
+    let dot = if alt && prec == 0 { "." } else { "" };
+    let sign = if e < 0 { '-' } else { '+' };
+    format!("{}{}e{}{:02}", mantissa, dot, sign, e.unsigned_abs())
+}
+
+/// Remove the trailing zeros of the fraction, and the point if nothing is left after it.
+fn trim_zeros(s: &str) -> String {
+    let (num, e) = s.split_at(s.find('e').unwrap_or(s.len()));
+    let num = if num.contains('.') {
+        num.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        num
+    };
+    format!("{}{}", num, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::Ty;
+
+    fn printf(fmt: &str, args: &[u64]) -> String {
+        let args: Vec<_> = args.iter().map(|a| (Kind::Scalar(Ty::I64), *a)).collect();
+        String::from_utf8(format(fmt.as_bytes(), &args, &Memory::new()).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(
+            printf("%d|%5d|%-5d|%05d", &[-1i64 as u64, 42, 42, 42]),
+            "-1|   42|42   |00042"
+        );
+        assert_eq!(
+            printf("%u %ld %lu", &[u64::MAX, u64::MAX, u64::MAX]),
+            "4294967295 -1 18446744073709551615"
+        );
+        assert_eq!(
+            printf("%x %#X %#o %hhd", &[255, 255, 8, 200]),
+            "ff 0XFF 010 -56"
+        );
+        assert_eq!(printf("%+.3d|% d|%.0d", &[7, 7, 0]), "+007| 7|");
+        assert_eq!(printf("%*d|%c%%", &[4, 1, 'z' as u64]), "   1|z%");
+    }
+
+    #[test]
+    fn floats() {
+        let f = |x: f64| x.to_bits();
+        assert_eq!(
+            printf("%f %.1f %.0f", &[f(1.5), f(2.25), f(2.5)]),
+            "1.500000 2.2 2"
+        );
+        assert_eq!(
+            printf("%e %.2E", &[f(1234.5), f(-0.000123)]),
+            "1.234500e+03 -1.23E-04"
+        );
+        assert_eq!(
+            printf("%g %g %g %g", &[f(100000.0), f(1e6), f(0.0001), f(1.5e-5)]),
+            "100000 1e+06 0.0001 1.5e-05"
+        );
+        assert_eq!(
+            printf("%08.2f|%-7.1f|%+g", &[f(-3.25), f(2.0), f(0.5)]),
+            "-0003.25|2.0    |+0.5"
+        );
+        assert_eq!(printf("%f %G", &[f(f64::INFINITY), f(f64::NAN)]), "inf NAN");
+    }
+
+    #[test]
+    fn strings() {
+        let mut mem = Memory::new();
+        let s = mem.alloc_bytes(b"alef\0".to_vec(), SegKind::Data);
+        let args = [(Kind::Scalar(Ty::I64), s), (Kind::Scalar(Ty::I64), s)];
+        let out = format(b"[%6s|%.2s]", &args, &mem).unwrap();
+        assert_eq!(out, b"[  alef|al]");
+        assert!(format(b"%n", &args, &mem).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+//! A portable execution target for the Alef IR.
+//!
+//! `compile::compile` translates a verified `alef_ir::module::Module` into a compact,
+//! register-based bytecode and `machine::run` executes it. Unlike the native backends, the
+//! Alef-specific instructions are not expanded into runtime calls: channels, `alt`, processes,
+//! tasks, `par` groups and `raise`/`rescue` are implemented by the machine itself, which schedules
+//! every process and task cooperatively on a single thread. Runs are therefore deterministic,
+//! which makes the machine suitable for tests and for debugging concurrent programs.
+//!
+//! Memory is made of segments, one for each data definition, stack frame and heap allocation; a
+//! pointer is a segment number in the upper 32 bits and an offset in the lower ones, so accesses
+//! out of bounds or to released storage are reported instead of corrupting the program.
+
+/// The bytecode and the programs made of it.
+pub mod bytecode;
+
+/// Translation of IR modules into bytecode.
+pub mod compile;
+
+/// The textual form of the bytecode.
+pub mod disasm;
+
+/// Errors reported by the compiler and the machine.
+pub mod err;
+
+/// Segmented memory.
+pub mod mem;
+
+/// Functions of the C library the machine provides.
+pub mod host;
+
+/// The machine and its scheduler.
+pub mod machine;
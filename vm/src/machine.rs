@@ -0,0 +1,870 @@
+//! The machine executing a program.
+//!
+//! Processes and tasks are threads of control with their own stack of frames, all scheduled
+//! cooperatively on the host thread: the scheduler runs the first ready thread until it blocks,
+//! terminates or has executed `QUANTUM` instructions, then moves to the next one. The order is
+//! fully determined by the program, so every run of a program behaves the same way.
+//!
+//! Channels keep a buffer of `cap` elements and two queues of blocked threads, one for senders
+//! and one for receivers. A send hands the value directly to a blocked receiver if there is one,
+//! otherwise it goes to the buffer if there is room, otherwise the sender blocks; receives are
+//! symmetric. A thread blocked in an `alt` waits in the queues of every channel of the alt; the
+//! first communication that completes one of its cases wakes it up and makes its entries in the
+//! other queues stale, which is detected with the number of the wait they belong to.
+
+use crate::bytecode::{Function, Kind, Op, Program, Reg, Site, Spawn, Ty, FUNC_SEG, NO_REG};
+use crate::err::VmError;
+use crate::host::{self, Outcome};
+use crate::mem::{decode, Memory, SegKind};
+use alef_ir::inst::{BinOp, CmpOp, ConvOp, UnOp};
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// The number of instructions a thread runs before the scheduler switches to the next one.
+pub const QUANTUM: u32 = 1000;
+
+/// The maximum number of frames of a thread.
+pub const MAX_FRAMES: usize = 100_000;
+
+/// The exit status of a program whose threads are all blocked, as with the native runtime.
+pub const EXIT_DEADLOCK: i32 = 3;
+
+/// Run `program` from `$main`, writing its output to `out`, and return its exit status: the value
+/// returned by `$main`, or the argument of `exit`.
+pub fn run(program: &Program, out: &mut dyn Write) -> Result<i32, VmError> {
+    let main = program.main.ok_or(VmError::NoMain)?;
+    let mut m = Machine::new(program, out);
+    let frame = m.frame(main, &[], NO_REG)?;
+    m.start(frame, None);
+    m.schedule()
+}
+
+/// A thread number.
+type Tid = usize;
+
+/// An activation of a function.
+#[derive(Debug)]
+struct Frame {
+    func: u32,
+    pc: u32,
+    regs: Vec<u64>,
+
+    /// The address of the stack frame, 0 if the function needs none.
+    stack: u64,
+
+    /// The address of the arguments passed after the fixed parameters, one 8 byte slot each,
+    /// 0 if there are none.
+    varargs: u64,
+
+    /// The targets of the active rescue blocks, innermost last.
+    handlers: Vec<u32>,
+
+    /// The register of the caller receiving the result.
+    dst: Reg,
+}
+
+/// Where a received value goes.
+#[derive(Debug, Clone, Copy)]
+enum Dest {
+    Drop,
+    Reg(Reg, Ty),
+    Mem(u64),
+}
+
+/// A communication a blocked thread is waiting for.
+#[derive(Debug)]
+struct Arm {
+    chan: u32,
+
+    /// The value sent, None for receives.
+    value: Option<Vec<u8>>,
+    dest: Dest,
+
+    /// Where the thread continues when this arm completes, None for the next instruction.
+    target: Option<u32>,
+}
+
+#[derive(Debug)]
+enum Wait {
+    /// Waiting for one of the arms to complete.
+    Comm(Vec<Arm>),
+
+    /// Waiting for a `par` group to terminate.
+    Join,
+}
+
+#[derive(Debug)]
+struct Thread {
+    frames: Vec<Frame>,
+
+    /// The `par` group the thread belongs to.
+    group: Option<u32>,
+
+    /// What the thread is blocked on, None if it is ready.
+    wait: Option<Wait>,
+
+    /// The number of the current wait, to recognize stale queue entries.
+    serial: u64,
+}
+
+/// A waiting thread, the serial number of its wait and the index of the arm.
+type Waiter = (Tid, u64, usize);
+
+#[derive(Debug)]
+struct Chan {
+    cap: usize,
+    buf: VecDeque<Vec<u8>>,
+    sendq: VecDeque<Waiter>,
+    recvq: VecDeque<Waiter>,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    live: usize,
+    joiners: Vec<Tid>,
+}
+
+/// What happened after an instruction.
+enum Flow {
+    /// Go on with the next instruction.
+    Next,
+
+    /// The thread is blocked.
+    Block,
+
+    /// The thread terminated, returning a value from its first frame.
+    Done(u64),
+
+    /// The program terminated.
+    Exit(i32),
+}
+
+struct Machine<'a> {
+    prog: &'a Program,
+    out: &'a mut dyn Write,
+    mem: Memory,
+    threads: Vec<Option<Thread>>,
+    ready: VecDeque<Tid>,
+    chans: Vec<Chan>,
+    groups: Vec<Group>,
+}
+
+impl<'a> Machine<'a> {
+    fn new(prog: &'a Program, out: &'a mut dyn Write) -> Machine<'a> {
+        let mut mem = Memory::new();
+        for d in &prog.data {
+            mem.alloc_bytes(d.bytes.clone(), SegKind::Data);
+        }
+        Machine {
+            prog,
+            out,
+            mem,
+            threads: vec![],
+            ready: VecDeque::new(),
+            chans: vec![],
+            groups: vec![],
+        }
+    }
+
+    /// Create a frame for a call of function `func`.
+    fn frame(&mut self, func: u32, args: &[(Kind, u64)], dst: Reg) -> Result<Frame, VmError> {
+        let f = &self.prog.funcs[func as usize];
+        let mut regs = vec![0; f.regs()];
+        regs[f.temps as usize..].copy_from_slice(&f.consts);
+        let stack = if f.frame > 0 {
+            self.mem.alloc(f.frame, SegKind::Stack)
+        } else {
+            0
+        };
+        let extra = &args[f.params.len().min(args.len())..];
+        let varargs = if extra.is_empty() {
+            0
+        } else {
+            self.mem.alloc(extra.len() as u32 * 8, SegKind::Stack)
+        };
+        for (i, (_, v)) in extra.iter().enumerate() {
+            self.mem.store(Ty::I64, varargs + i as u64 * 8, *v)?;
+        }
+        for (i, (p, (_, v))) in f.params.iter().zip(args).enumerate() {
+            regs[i] = match p.kind {
+                Kind::Mem(size) => {
+                    let copy = stack + p.slot as u64;
+                    self.mem.copy(copy, *v, size as u64)?;
+                    copy
+                }
+                _ => *v,
+            };
+        }
+        Ok(Frame {
+            func,
+            pc: 0,
+            regs,
+            stack,
+            varargs,
+            handlers: vec![],
+            dst,
+        })
+    }
+
+    /// Start a thread with `frame` as its first frame.
+    fn start(&mut self, frame: Frame, group: Option<u32>) {
+        self.threads.push(Some(Thread {
+            frames: vec![frame],
+            group,
+            wait: None,
+            serial: 0,
+        }));
+        self.ready.push_back(self.threads.len() - 1);
+    }
+
+    fn schedule(&mut self) -> Result<i32, VmError> {
+        loop {
+            let Some(tid) = self.ready.pop_front() else {
+                return Err(VmError::Deadlock(self.blocked()));
+            };
+            let mut t = self.threads[tid].take().expect("ready thread is gone");
+            let flow = self.slice(tid, &mut t);
+            let flow = match flow {
+                Ok(flow) => flow,
+                Err(err) => {
+                    let Some(frame) = t.frames.last() else {
+                        return Err(err);
+                    };
+                    return Err(VmError::At {
+                        func: self.prog.funcs[frame.func as usize].name.clone(),
+                        pc: frame.pc.saturating_sub(1),
+                        err: Box::new(err),
+                    });
+                }
+            };
+            match flow {
+                Flow::Next => {
+                    self.threads[tid] = Some(t);
+                    self.ready.push_back(tid);
+                }
+                Flow::Block => self.threads[tid] = Some(t),
+                Flow::Done(v) if tid == 0 => {
+                    let ret = self.prog.funcs[self.prog.main.unwrap() as usize].ret;
+                    return Ok(match ret {
+                        Kind::Scalar(_) => v as i32,
+                        _ => 0,
+                    });
+                }
+                Flow::Done(_) => {
+                    if let Some(g) = t.group {
+                        let group = &mut self.groups[g as usize];
+                        group.live -= 1;
+                        if group.live == 0 {
+                            for j in std::mem::take(&mut group.joiners) {
+                                self.wake(j);
+                            }
+                        }
+                    }
+                }
+                Flow::Exit(status) => return Ok(status),
+            }
+        }
+    }
+
+    /// Describe what every thread is blocked on.
+    fn blocked(&self) -> Vec<String> {
+        self.threads
+            .iter()
+            .enumerate()
+            .filter_map(|(tid, t)| {
+                let t = t.as_ref()?;
+                let frame = t.frames.last()?;
+                let what = match t.wait.as_ref()? {
+                    Wait::Join => "join",
+                    Wait::Comm(arms) if arms.len() > 1 => "alt",
+                    Wait::Comm(arms) if arms[0].value.is_some() => "send",
+                    Wait::Comm(_) => "recv",
+                };
+                let name = &self.prog.funcs[frame.func as usize].name;
+                Some(format!(
+                    "thread {} blocked in {} in ${} at {:04}",
+                    tid,
+                    what,
+                    name,
+                    frame.pc.saturating_sub(1)
+                ))
+            })
+            .collect()
+    }
+
+    /// Make a blocked thread ready again.
+    fn wake(&mut self, tid: Tid) {
+        if let Some(t) = self.threads[tid].as_mut() {
+            t.wait = None;
+            self.ready.push_back(tid);
+        }
+    }
+
+    /// Run thread `tid` for at most `QUANTUM` instructions.
+    fn slice(&mut self, tid: Tid, t: &mut Thread) -> Result<Flow, VmError> {
+        for _ in 0..QUANTUM {
+            match self.step(tid, t)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn chan(&self, addr: u64) -> Result<u32, VmError> {
+        match self.mem.kind(addr) {
+            Some(SegKind::Chan(c)) => Ok(c),
+            _ => Err(VmError::NotAChannel(addr)),
+        }
+    }
+
+    fn group(&self, addr: u64) -> Result<u32, VmError> {
+        match self.mem.kind(addr) {
+            Some(SegKind::Group(g)) => Ok(g),
+            _ => Err(VmError::NotAGroup(addr)),
+        }
+    }
+
+    /// Return true if the queue entry still belongs to a blocked thread.
+    fn valid(&self, w: Waiter) -> bool {
+        waiting(&self.threads, w)
+    }
+
+    /// Drop stale entries from the front of a queue and return the first valid one.
+    fn first(&mut self, c: u32, send: bool) -> Option<Waiter> {
+        loop {
+            let ch = &self.chans[c as usize];
+            let w = *if send { &ch.sendq } else { &ch.recvq }.front()?;
+            if self.valid(w) {
+                return Some(w);
+            }
+            let ch = &mut self.chans[c as usize];
+            if send { &mut ch.sendq } else { &mut ch.recvq }.pop_front();
+        }
+    }
+
+    /// Complete arm `arm` of the wait of thread `tid`, delivering `value` if it was a receive,
+    /// and return the value if it was a send.
+    fn complete(
+        &mut self,
+        (tid, _, arm): Waiter,
+        value: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>, VmError> {
+        let t = self.threads[tid].as_mut().unwrap();
+        let Some(Wait::Comm(mut arms)) = t.wait.take() else {
+            unreachable!("completing a thread that is not communicating");
+        };
+        let arm = arms.swap_remove(arm);
+        let frame = t.frames.last_mut().unwrap();
+        if let Some(target) = arm.target {
+            frame.pc = target;
+        }
+        if let Some(v) = &value {
+            put(&mut self.mem, frame, arm.dest, v)?;
+        }
+        self.ready.push_back(tid);
+        Ok(arm.value.unwrap_or_default())
+    }
+
+    /// Send `value` on `c` if it can be done without blocking.
+    fn try_send(&mut self, c: u32, value: &[u8]) -> Result<bool, VmError> {
+        if let Some(w) = self.first(c, false) {
+            self.chans[c as usize].recvq.pop_front();
+            self.complete(w, Some(value.to_vec()))?;
+            return Ok(true);
+        }
+        let ch = &mut self.chans[c as usize];
+        if ch.buf.len() < ch.cap {
+            ch.buf.push_back(value.to_vec());
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Receive from `c` if it can be done without blocking.
+    fn try_recv(&mut self, c: u32) -> Result<Option<Vec<u8>>, VmError> {
+        let sender = self.first(c, true);
+        if sender.is_some() {
+            self.chans[c as usize].sendq.pop_front();
+        }
+        let buffered = self.chans[c as usize].buf.pop_front();
+        match (buffered, sender) {
+            (Some(v), Some(w)) => {
+                // A slot was freed: the first blocked sender can now buffer its value.
+                let sent = self.complete(w, None)?;
+                self.chans[c as usize].buf.push_back(sent);
+                Ok(Some(v))
+            }
+            (Some(v), None) => Ok(Some(v)),
+            (None, Some(w)) => Ok(Some(self.complete(w, None)?)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    /// Try the arms in order and complete the first that can proceed; block on all of them if
+    /// none can.
+    fn communicate(&mut self, tid: Tid, t: &mut Thread, arms: Vec<Arm>) -> Result<Flow, VmError> {
+        for arm in &arms {
+            let done = match &arm.value {
+                Some(v) => self.try_send(arm.chan, v)?,
+                None => match self.try_recv(arm.chan)? {
+                    Some(v) => {
+                        put(&mut self.mem, t.frames.last_mut().unwrap(), arm.dest, &v)?;
+                        true
+                    }
+                    None => false,
+                },
+            };
+            if done {
+                if let Some(target) = arm.target {
+                    t.frames.last_mut().unwrap().pc = target;
+                }
+                return Ok(Flow::Next);
+            }
+        }
+
+        t.serial += 1;
+        for (i, arm) in arms.iter().enumerate() {
+            let ch = &mut self.chans[arm.chan as usize];
+            let q = if arm.value.is_some() {
+                &mut ch.sendq
+            } else {
+                &mut ch.recvq
+            };
+            // Entries of alts completed on other channels would otherwise pile up.
+            let threads = &self.threads;
+            q.retain(|w| waiting(threads, *w));
+            q.push_back((tid, t.serial, i));
+        }
+        t.wait = Some(Wait::Comm(arms));
+        Ok(Flow::Block)
+    }
+
+    /// Call `callee`, pushing a frame or running a host function.
+    fn call(
+        &mut self,
+        t: &mut Thread,
+        callee: u64,
+        args: &[(Kind, u64)],
+        dst: Reg,
+    ) -> Result<Flow, VmError> {
+        let (seg, index) = (callee >> 32, (callee & 0xffff_ffff) as usize);
+        if seg != FUNC_SEG {
+            return Err(VmError::NotAFunction(callee));
+        }
+        if index < self.prog.funcs.len() {
+            if t.frames.len() >= MAX_FRAMES {
+                return Err(VmError::StackOverflow);
+            }
+            let frame = self.frame(index as u32, args, dst)?;
+            t.frames.push(frame);
+            return Ok(Flow::Next);
+        }
+
+        let name = self
+            .prog
+            .externs
+            .get(index - self.prog.funcs.len())
+            .ok_or(VmError::NotAFunction(callee))?;
+        match host::call(name, args, &mut self.mem, self.out)? {
+            Outcome::Value(v) => {
+                if dst != NO_REG {
+                    t.frames.last_mut().unwrap().regs[dst as usize] = v;
+                }
+                Ok(Flow::Next)
+            }
+            Outcome::Exit(status) => Ok(Flow::Exit(status)),
+        }
+    }
+
+    /// Start a new thread running `callee`.
+    fn spawn(
+        &mut self,
+        callee: u64,
+        args: &[(Kind, u64)],
+        group: Option<u32>,
+    ) -> Result<(), VmError> {
+        let (seg, index) = (callee >> 32, (callee & 0xffff_ffff) as usize);
+        if seg != FUNC_SEG || index >= self.prog.funcs.len() + self.prog.externs.len() {
+            return Err(VmError::NotAFunction(callee));
+        }
+        if index >= self.prog.funcs.len() {
+            // A host function never blocks, so it can run to completion right away.
+            let name = &self.prog.externs[index - self.prog.funcs.len()];
+            host::call(name, args, &mut self.mem, self.out)?;
+            return Ok(());
+        }
+
+        if let Some(g) = group {
+            self.groups[g as usize].live += 1;
+        }
+        let frame = self.frame(index as u32, args, NO_REG)?;
+        self.start(frame, group);
+        Ok(())
+    }
+
+    /// Execute one instruction of thread `tid`.
+    fn step(&mut self, tid: Tid, t: &mut Thread) -> Result<Flow, VmError> {
+        let prog = self.prog;
+        let frame = t.frames.last_mut().unwrap();
+        let f: &Function = &prog.funcs[frame.func as usize];
+        let op = f.code[frame.pc as usize];
+        frame.pc += 1;
+        let r = |frame: &Frame, reg: Reg| frame.regs[reg as usize];
+
+        match op {
+            Op::Mov { dst, src } => frame.regs[dst as usize] = r(frame, src),
+            Op::Bin {
+                op,
+                ty,
+                dst,
+                lhs,
+                rhs,
+            } => frame.regs[dst as usize] = bin(op, ty, r(frame, lhs), r(frame, rhs))?,
+            Op::Cmp {
+                op,
+                ty,
+                dst,
+                lhs,
+                rhs,
+            } => frame.regs[dst as usize] = cmp(op, ty, r(frame, lhs), r(frame, rhs)) as u64,
+            Op::Un { op, ty, dst, arg } => {
+                let a = r(frame, arg);
+                frame.regs[dst as usize] = match (op, ty) {
+                    (UnOp::Neg, Ty::F64) => (-f64::from_bits(a)).to_bits(),
+                    (UnOp::Neg, ty) => ty.norm(a.wrapping_neg()),
+                    (UnOp::Not, ty) => ty.norm(!a),
+                };
+            }
+            Op::Conv {
+                op,
+                from,
+                to,
+                dst,
+                arg,
+            } => frame.regs[dst as usize] = conv(op, from, to, r(frame, arg)),
+            Op::Frame { dst, offset } => frame.regs[dst as usize] = frame.stack + offset as u64,
+            Op::Load { ty, dst, addr } => {
+                frame.regs[dst as usize] = self.mem.load(ty, r(frame, addr))?;
+            }
+            Op::Store { ty, src, addr } => self.mem.store(ty, r(frame, addr), r(frame, src))?,
+            Op::VaStart { ap } => self.mem.store(Ty::I64, r(frame, ap), frame.varargs)?,
+            Op::VaArg { ty, dst, ap } => {
+                let ap = r(frame, ap);
+                let next = self.mem.load(Ty::I64, ap)?;
+                frame.regs[dst as usize] = self.mem.load(ty, next)?;
+                self.mem.store(Ty::I64, ap, next + 8)?;
+            }
+            Op::Offset { dst, base, offset } => {
+                frame.regs[dst as usize] = r(frame, base).wrapping_add(offset as u64);
+            }
+            Op::Index {
+                dst,
+                base,
+                index,
+                size,
+            } => {
+                let off = r(frame, index).wrapping_mul(size as u64);
+                frame.regs[dst as usize] = r(frame, base).wrapping_add(off);
+            }
+            Op::Blit { dst, src, size } => {
+                self.mem.copy(r(frame, dst), r(frame, src), size as u64)?;
+            }
+            Op::Call { dst, callee, site } => {
+                let callee = r(frame, callee);
+                let args = args(frame, &f.sites[site as usize]);
+                return self.call(t, callee, &args, dst);
+            }
+            Op::Ret { src } => {
+                let v = if src == NO_REG { 0 } else { r(frame, src) };
+                let done = t.frames.pop().unwrap();
+                if let Some(caller) = t.frames.last_mut() {
+                    if done.dst != NO_REG {
+                        match f.ret {
+                            Kind::Mem(size) => {
+                                let to = caller.regs[done.dst as usize];
+                                self.mem.copy(to, v, size as u64)?;
+                            }
+                            _ => caller.regs[done.dst as usize] = v,
+                        }
+                    }
+                }
+                if done.stack != 0 {
+                    self.mem.free(done.stack, SegKind::Stack)?;
+                }
+                if done.varargs != 0 {
+                    self.mem.free(done.varargs, SegKind::Stack)?;
+                }
+                if t.frames.is_empty() {
+                    return Ok(Flow::Done(v));
+                }
+            }
+            Op::Jmp { target } => frame.pc = target,
+            Op::Br { cond, then, else_ } => {
+                frame.pc = if r(frame, cond) as u32 != 0 {
+                    then
+                } else {
+                    else_
+                };
+            }
+            Op::Switch { ty, value, table } => {
+                let v = ty.norm(r(frame, value)) as i64;
+                let table = &f.switches[table as usize];
+                frame.pc = table
+                    .cases
+                    .iter()
+                    .find(|(n, _)| *n == v)
+                    .map_or(table.default, |(_, t)| *t);
+            }
+            Op::Hlt => return Err(VmError::Halted),
+            Op::Alloc { dst, size } => {
+                frame.regs[dst as usize] = self.mem.alloc(size, SegKind::Heap);
+            }
+            Op::Unalloc { ptr } => self.mem.free(r(frame, ptr), SegKind::Heap)?,
+            Op::Chan { dst, cap, .. } => {
+                let cap = (r(frame, cap) as i64).max(0) as usize;
+                self.chans.push(Chan {
+                    cap,
+                    buf: VecDeque::new(),
+                    sendq: VecDeque::new(),
+                    recvq: VecDeque::new(),
+                });
+                let c = (self.chans.len() - 1) as u32;
+                frame.regs[dst as usize] = self.mem.alloc(0, SegKind::Chan(c));
+            }
+            Op::Send { elem, chan, src } => {
+                let c = self.chan(r(frame, chan))?;
+                let value = self.encode(elem, r(frame, src))?;
+                let arm = Arm {
+                    chan: c,
+                    value: Some(value),
+                    dest: Dest::Drop,
+                    target: None,
+                };
+                return self.communicate(tid, t, vec![arm]);
+            }
+            Op::Recv { elem, dst, chan } => {
+                let c = self.chan(r(frame, chan))?;
+                let dest = match elem {
+                    Kind::Mem(_) => Dest::Mem(r(frame, dst)),
+                    Kind::Scalar(ty) => Dest::Reg(dst, ty),
+                    Kind::Void => Dest::Drop,
+                };
+                let arm = Arm {
+                    chan: c,
+                    value: None,
+                    dest,
+                    target: None,
+                };
+                return self.communicate(tid, t, vec![arm]);
+            }
+            Op::CanSend { dst, chan } => {
+                let c = self.chan(r(frame, chan))?;
+                let ch = &self.chans[c as usize];
+                let room = ch.buf.len() < ch.cap;
+                let ok = room || self.first(c, false).is_some();
+                t.frames.last_mut().unwrap().regs[dst as usize] = ok as u64;
+            }
+            Op::CanRecv { dst, chan } => {
+                let c = self.chan(r(frame, chan))?;
+                let ok = !self.chans[c as usize].buf.is_empty() || self.first(c, true).is_some();
+                t.frames.last_mut().unwrap().regs[dst as usize] = ok as u64;
+            }
+            Op::Spawn {
+                how,
+                group,
+                callee,
+                site,
+            } => {
+                let group = match how {
+                    Spawn::Par => Some(self.group(r(frame, group))?),
+                    Spawn::Proc | Spawn::Task => None,
+                };
+                let callee = r(frame, callee);
+                let args = args(frame, &f.sites[site as usize]);
+                self.spawn(callee, &args, group)?;
+            }
+            Op::Par { dst } => {
+                self.groups.push(Group::default());
+                let g = (self.groups.len() - 1) as u32;
+                frame.regs[dst as usize] = self.mem.alloc(0, SegKind::Group(g));
+            }
+            Op::Join { group } => {
+                let g = self.group(r(frame, group))?;
+                let group = &mut self.groups[g as usize];
+                if group.live > 0 {
+                    group.joiners.push(tid);
+                    t.serial += 1;
+                    t.wait = Some(Wait::Join);
+                    return Ok(Flow::Block);
+                }
+            }
+            Op::Rescue { target } => frame.handlers.push(target),
+            Op::Unrescue => {
+                frame.handlers.pop();
+            }
+            Op::Raise => match frame.handlers.pop() {
+                Some(target) => frame.pc = target,
+                None => return Err(VmError::Unhandled),
+            },
+            Op::Alt { table } => {
+                let mut arms = vec![];
+                for a in &f.alts[table as usize] {
+                    let chan = self.chan(r(frame, a.chan))?;
+                    let (value, dest) = if a.send {
+                        (Some(self.encode(a.elem, r(frame, a.reg))?), Dest::Drop)
+                    } else if a.reg == NO_REG {
+                        (None, Dest::Drop)
+                    } else {
+                        (None, Dest::Mem(r(frame, a.reg)))
+                    };
+                    arms.push(Arm {
+                        chan,
+                        value,
+                        dest,
+                        target: Some(a.target),
+                    });
+                }
+                return self.communicate(tid, t, arms);
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// The bytes of an element sent on a channel.
+    fn encode(&self, elem: Kind, v: u64) -> Result<Vec<u8>, VmError> {
+        Ok(match elem {
+            Kind::Mem(size) => self.mem.read(v, size as u64)?.to_vec(),
+            kind => v.to_le_bytes()[..kind.size() as usize].to_vec(),
+        })
+    }
+}
+
+/// Return true if the queue entry `w` belongs to the current wait of a blocked thread.
+fn waiting(threads: &[Option<Thread>], (tid, serial, _): Waiter) -> bool {
+    matches!(&threads[tid], Some(t) if t.wait.is_some() && t.serial == serial)
+}
+
+/// Store a received element.
+fn put(mem: &mut Memory, frame: &mut Frame, dest: Dest, v: &[u8]) -> Result<(), VmError> {
+    match dest {
+        Dest::Drop => {}
+        Dest::Reg(reg, ty) => frame.regs[reg as usize] = decode(ty, v),
+        Dest::Mem(addr) => mem.write(addr, v)?,
+    }
+    Ok(())
+}
+
+/// The arguments of a call site.
+fn args(frame: &Frame, site: &Site) -> Vec<(Kind, u64)> {
+    site.args
+        .iter()
+        .map(|(k, reg)| (*k, frame.regs[*reg as usize]))
+        .collect()
+}
+
+fn bin(op: BinOp, ty: Ty, a: u64, b: u64) -> Result<u64, VmError> {
+    if ty == Ty::F64 {
+        let (x, y) = (f64::from_bits(a), f64::from_bits(b));
+        let r = match op {
+            BinOp::Add => x + y,
+            BinOp::Sub => x - y,
+            BinOp::Mul => x * y,
+            BinOp::Div | BinOp::UDiv => x / y,
+            BinOp::Rem | BinOp::URem => x % y,
+            _ => return bin(op, Ty::I64, a, b),
+        };
+        return Ok(r.to_bits());
+    }
+
+    let bits = ty.size() as u64 * 8;
+    let (sa, sb) = (a as i64, b as i64);
+    let (ua, ub) = (ty.mask(a), ty.mask(b));
+    let r = match op {
+        BinOp::Add => a.wrapping_add(b),
+        BinOp::Sub => a.wrapping_sub(b),
+        BinOp::Mul => a.wrapping_mul(b),
+        BinOp::Div | BinOp::Rem | BinOp::UDiv | BinOp::URem if ty.mask(b) == 0 => {
+            return Err(VmError::DivisionByZero)
+        }
+        BinOp::Div => sa.wrapping_div(sb) as u64,
+        BinOp::Rem => sa.wrapping_rem(sb) as u64,
+        BinOp::UDiv => ua / ub,
+        BinOp::URem => ua % ub,
+        BinOp::And => a & b,
+        BinOp::Or => a | b,
+        BinOp::Xor => a ^ b,
+        BinOp::Shl => a << (b & (bits - 1)),
+        BinOp::Shr => (sa >> (b & (bits - 1))) as u64,
+        BinOp::UShr => ua >> (b & (bits - 1)),
+    };
+    Ok(ty.norm(r))
+}
+
+fn cmp(op: CmpOp, ty: Ty, a: u64, b: u64) -> bool {
+    if ty == Ty::F64 {
+        let (x, y) = (f64::from_bits(a), f64::from_bits(b));
+        return match op {
+            CmpOp::Eq => x == y,
+            CmpOp::Ne => x != y,
+            CmpOp::Lt | CmpOp::ULt => x < y,
+            CmpOp::Le | CmpOp::ULe => x <= y,
+            CmpOp::Gt | CmpOp::UGt => x > y,
+            CmpOp::Ge | CmpOp::UGe => x >= y,
+        };
+    }
+
+    let (sa, sb) = (a as i64, b as i64);
+    let (ua, ub) = (ty.mask(a), ty.mask(b));
+    match op {
+        CmpOp::Eq => a == b,
+        CmpOp::Ne => a != b,
+        CmpOp::Lt => sa < sb,
+        CmpOp::Le => sa <= sb,
+        CmpOp::Gt => sa > sb,
+        CmpOp::Ge => sa >= sb,
+        CmpOp::ULt => ua < ub,
+        CmpOp::ULe => ua <= ub,
+        CmpOp::UGt => ua > ub,
+        CmpOp::UGe => ua >= ub,
+    }
+}
+
+fn conv(op: ConvOp, from: Ty, to: Ty, v: u64) -> u64 {
+    match op {
+        ConvOp::Sext | ConvOp::Trunc => to.norm(v),
+        ConvOp::Zext => to.norm(from.mask(v)),
+        ConvOp::SiToF => (v as i64 as f64).to_bits(),
+        ConvOp::UiToF => (from.mask(v) as f64).to_bits(),
+        ConvOp::FToSi => to.norm(f64::from_bits(v) as i64 as u64),
+        ConvOp::FToUi => to.norm(f64::from_bits(v) as u64),
+        ConvOp::Bitcast => v,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arithmetic() {
+        let n = |v: i64| v as u64;
+        assert_eq!(bin(BinOp::Add, Ty::I8, n(127), 1), Ok(n(-128)));
+        assert_eq!(bin(BinOp::UDiv, Ty::I8, n(-1), 85), Ok(3));
+        assert_eq!(bin(BinOp::UShr, Ty::I16, n(-1), 8), Ok(255));
+        assert_eq!(bin(BinOp::Shl, Ty::I32, 1, 33), Ok(2));
+        assert_eq!(
+            bin(BinOp::Div, Ty::I32, n(i32::MIN as i64), n(-1)),
+            Ok(n(i32::MIN as i64))
+        );
+        assert_eq!(bin(BinOp::Rem, Ty::I64, 5, 0), Err(VmError::DivisionByZero));
+        assert!(cmp(CmpOp::ULt, Ty::I32, 5, n(-1)));
+        assert!(!cmp(CmpOp::Lt, Ty::I32, 5, n(-1)));
+        assert_eq!(conv(ConvOp::Zext, Ty::I8, Ty::I64, n(-56)), 200);
+        assert_eq!(conv(ConvOp::Trunc, Ty::I32, Ty::I8, 263), 7);
+        assert_eq!(
+            f64::from_bits(conv(ConvOp::UiToF, Ty::I64, Ty::F64, u64::MAX)),
+            1.8446744073709552e19
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Segmented memory.
+//!
+//! Every allocation gets a segment of its own and segment numbers are never reused, so that a
+//! pointer to released storage stays invalid forever. Channels and `par` groups are segments of
+//! size 0 tagged with the index of the object, which gives them addresses without making their
+//! state reachable through loads and stores.
+
+use crate::bytecode::{Ty, FUNC_SEG};
+use crate::err::VmError;
+
+/// What a segment holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegKind {
+    /// A data definition.
+    Data,
+
+    /// A stack frame.
+    Stack,
+
+    /// Storage from `alloc`, `box` or `malloc`.
+    Heap,
+
+    /// A channel, with its index in the machine.
+    Chan(u32),
+
+    /// A `par` group, with its index in the machine.
+    Group(u32),
+}
+
+#[derive(Debug)]
+struct Segment {
+    kind: SegKind,
+    bytes: Vec<u8>,
+}
+
+/// The memory of the machine.
+#[derive(Debug)]
+pub struct Memory {
+    /// The segments by number; segment 0 is never allocated, so null is never valid.
+    segs: Vec<Option<Segment>>,
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
+impl Memory {
+    /// Create an empty memory.
+    pub fn new() -> Memory {
+        Memory { segs: vec![None] }
+    }
+
+    /// Allocate a zeroed segment of `size` bytes and return its address.
+    pub fn alloc(&mut self, size: u32, kind: SegKind) -> u64 {
+        self.segs.push(Some(Segment {
+            kind,
+            bytes: vec![0; size as usize],
+        }));
+        assert!((self.segs.len() as u64) < FUNC_SEG, "out of segments");
+        ((self.segs.len() - 1) as u64) << 32
+    }
+
+    /// Allocate a segment holding `bytes`.
+    pub fn alloc_bytes(&mut self, bytes: Vec<u8>, kind: SegKind) -> u64 {
+        let addr = self.alloc(0, kind);
+        self.segs[(addr >> 32) as usize].as_mut().unwrap().bytes = bytes;
+        addr
+    }
+
+    /// Release the segment starting at `addr`, which must be of kind `kind`.
+    pub fn free(&mut self, addr: u64, kind: SegKind) -> Result<(), VmError> {
+        let seg = self.segs.get_mut((addr >> 32) as usize);
+        match seg {
+            Some(s) if addr & 0xffff_ffff == 0 && s.as_ref().map(|s| s.kind) == Some(kind) => {
+                *s = None;
+                Ok(())
+            }
+            _ => Err(VmError::BadFree(addr)),
+        }
+    }
+
+    /// The kind of the segment starting at `addr`, if it is alive.
+    pub fn kind(&self, addr: u64) -> Option<SegKind> {
+        if addr & 0xffff_ffff != 0 {
+            return None;
+        }
+        self.segs
+            .get((addr >> 32) as usize)?
+            .as_ref()
+            .map(|s| s.kind)
+    }
+
+    fn range(&self, addr: u64, len: u64) -> Option<(usize, std::ops::Range<usize>)> {
+        let seg = (addr >> 32) as usize;
+        let off = addr & 0xffff_ffff;
+        let s = self.segs.get(seg)?.as_ref()?;
+        let end = off.checked_add(len)?;
+        if end > s.bytes.len() as u64 {
+            return None;
+        }
+        Some((seg, off as usize..end as usize))
+    }
+
+    /// The `len` bytes at `addr`.
+    pub fn read(&self, addr: u64, len: u64) -> Result<&[u8], VmError> {
+        let (seg, r) = self.range(addr, len).ok_or(VmError::Fault { addr, len })?;
+        Ok(&self.segs[seg].as_ref().unwrap().bytes[r])
+    }
+
+    /// Overwrite the bytes at `addr`.
+    pub fn write(&mut self, addr: u64, bytes: &[u8]) -> Result<(), VmError> {
+        let len = bytes.len() as u64;
+        let (seg, r) = self.range(addr, len).ok_or(VmError::Fault { addr, len })?;
+        self.segs[seg].as_mut().unwrap().bytes[r].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Copy `len` bytes from `src` to `dst`; the areas may overlap.
+    pub fn copy(&mut self, dst: u64, src: u64, len: u64) -> Result<(), VmError> {
+        let bytes = self.read(src, len)?.to_vec();
+        self.write(dst, &bytes)
+    }
+
+    /// Load a scalar of type `ty`, in the canonical form of registers.
+    pub fn load(&self, ty: Ty, addr: u64) -> Result<u64, VmError> {
+        Ok(decode(ty, self.read(addr, ty.size() as u64)?))
+    }
+
+    /// Store the low bytes of `v` as a scalar of type `ty`.
+    pub fn store(&mut self, ty: Ty, addr: u64, v: u64) -> Result<(), VmError> {
+        self.write(addr, &v.to_le_bytes()[..ty.size() as usize])
+    }
+
+    /// The NUL-terminated string at `addr`, without the terminator.
+    pub fn cstr(&self, addr: u64) -> Result<&[u8], VmError> {
+        let (seg, r) = self.range(addr, 0).ok_or(VmError::Fault { addr, len: 1 })?;
+        let bytes = &self.segs[seg].as_ref().unwrap().bytes[r.start..];
+        match bytes.iter().position(|b| *b == 0) {
+            Some(n) => Ok(&bytes[..n]),
+            None => Err(VmError::Fault {
+                addr: addr + bytes.len() as u64,
+                len: 1,
+            }),
+        }
+    }
+}
+
+/// Decode a little-endian scalar of type `ty` into the canonical form of registers.
+pub fn decode(ty: Ty, bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf[..bytes.len()].copy_from_slice(bytes);
+    ty.norm(u64::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds() {
+        let mut m = Memory::new();
+        let a = m.alloc(8, SegKind::Heap);
+        m.store(Ty::I32, a + 4, 0xdead_beef).unwrap();
+        assert_eq!(
+            m.load(Ty::I32, a + 4).unwrap(),
+            0xdead_beefu32 as i32 as u64
+        );
+        assert_eq!(
+            m.load(Ty::I64, a + 4),
+            Err(VmError::Fault {
+                addr: a + 4,
+                len: 8
+            })
+        );
+        assert!(m.load(Ty::I8, 0).is_err());
+    }
+
+    #[test]
+    fn release() {
+        let mut m = Memory::new();
+        let a = m.alloc(4, SegKind::Heap);
+        let s = m.alloc(4, SegKind::Stack);
+        assert_eq!(m.free(s, SegKind::Heap), Err(VmError::BadFree(s)));
+        assert_eq!(m.free(a + 1, SegKind::Heap), Err(VmError::BadFree(a + 1)));
+        m.free(a, SegKind::Heap).unwrap();
+        assert!(m.load(Ty::I8, a).is_err());
+        assert_eq!(m.free(a, SegKind::Heap), Err(VmError::BadFree(a)));
+    }
+
+    #[test]
+    fn strings() {
+        let mut m = Memory::new();
+        let a = m.alloc_bytes(b"abc\0".to_vec(), SegKind::Data);
+        assert_eq!(m.cstr(a).unwrap(), b"abc");
+        assert_eq!(m.cstr(a + 3).unwrap(), b"");
+        let b = m.alloc_bytes(b"xy".to_vec(), SegKind::Data);
+        assert!(m.cstr(b).is_err());
+    }
+}
@@ -0,0 +1,46 @@
+// Each test crate only uses some of the helpers.
+#![allow(dead_code)]
+
+use alef_ir::module::Module;
+use alef_test_support::tests_dir;
+use alef_vm::{bytecode::Program, compile::compile, err::VmError, machine};
+use std::path::{Path, PathBuf};
+
+/// The directory of the test programs of the machine.
+pub fn programs() -> PathBuf {
+    tests_dir!("programs")
+}
+
+/// The directory of the test programs shared by the backends, which the machine runs too.
+pub fn backend_programs() -> PathBuf {
+    tests_dir!("backend", "programs")
+}
+
+/// Read and verify a module.
+pub fn parse(text: &str) -> Module {
+    alef_test_support::module(text, "module")
+}
+
+/// Read, verify and compile every program in `dir`, returning their names and programs.
+pub fn load_programs(dir: &Path) -> Vec<(String, Program)> {
+    alef_test_support::load_modules(dir)
+        .into_iter()
+        .map(|(name, module)| {
+            let program =
+                compile(&module).unwrap_or_else(|e| panic!("cannot compile {}: {}", name, e));
+            (name, program)
+        })
+        .collect()
+}
+
+/// Run a program, returning its exit status and output.
+pub fn run(program: &Program) -> Result<(i32, String), VmError> {
+    let mut out = vec![];
+    let status = machine::run(program, &mut out)?;
+    Ok((status, String::from_utf8(out).unwrap()))
+}
+
+/// Compile and run the module in `text`.
+pub fn run_text(text: &str) -> Result<(i32, String), VmError> {
+    run(&compile(&parse(text)).unwrap())
+}
@@ -0,0 +1,10 @@
+mod common;
+
+use alef_test_support::{check_golden, tests_dir};
+
+#[test]
+fn test_disasm() {
+    for (name, program) in common::load_programs(&common::programs()) {
+        check_golden(&tests_dir!("disasm"), &name, "txt", &program.to_string());
+    }
+}
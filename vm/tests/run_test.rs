@@ -0,0 +1,105 @@
+mod common;
+
+use alef_vm::err::VmError;
+use std::fs;
+use std::path::Path;
+
+/// Run every program of `dir` that has an expected output, except those with a C half.
+fn run_dir(dir: &Path) {
+    let mut ran = 0;
+    for (name, program) in common::load_programs(dir) {
+        let Ok(expected) = fs::read_to_string(dir.join(format!("{}.out", name))) else {
+            continue;
+        };
+        if dir.join(format!("{}.c", name)).exists() {
+            continue;
+        }
+        let (status, out) =
+            common::run(&program).unwrap_or_else(|e| panic!("{} failed: {}", name, e));
+        assert_eq!(status, 0, "{} exited with {}", name, status);
+        assert_eq!(out, expected, "output of {} differs", name);
+        ran += 1;
+    }
+    assert!(ran > 0, "no programs in {}", dir.display());
+}
+
+#[test]
+fn test_programs() {
+    run_dir(&common::programs());
+}
+
+#[test]
+fn test_backend_programs() {
+    run_dir(&common::backend_programs());
+}
+
+#[test]
+fn test_deterministic() {
+    for (name, program) in common::load_programs(&common::programs()) {
+        if name != "deadlock" {
+            assert_eq!(common::run(&program), common::run(&program), "{}", name);
+        }
+    }
+}
+
+#[test]
+fn test_deadlock() {
+    let path = common::programs().join("deadlock.air");
+    let module = common::parse(&fs::read_to_string(path).unwrap());
+    let program = alef_vm::compile::compile(&module).unwrap();
+    match common::run(&program) {
+        Err(VmError::Deadlock(blocked)) => {
+            assert_eq!(blocked.len(), 2);
+            assert!(blocked[0].contains("recv in $main"), "{}", blocked[0]);
+            assert!(blocked[1].contains("recv in $wait"), "{}", blocked[1]);
+        }
+        r => panic!("expected a deadlock, got {:?}", r),
+    }
+}
+
+/// The error raised at the first instruction of `$main` that fails.
+fn error(body: &str) -> VmError {
+    let text = format!(
+        "extern fn $exit(i32) -> void\nextern fn $nope() -> void\n\
+         export fn $main() -> i32 {{\n@start:\n{}\n}}",
+        body
+    );
+    match common::run_text(&text) {
+        Err(VmError::At { func, err, .. }) => {
+            assert_eq!(func, "main");
+            *err
+        }
+        r => panic!("expected an error, got {:?}", r),
+    }
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(error("    raise"), VmError::Unhandled);
+    assert_eq!(error("    hlt"), VmError::Halted);
+    assert_eq!(
+        error("    %x = div i32 1, 0\n    ret i32 %x"),
+        VmError::DivisionByZero
+    );
+    assert_eq!(
+        error("    call void $nope()\n    ret i32 0"),
+        VmError::UnknownExtern("nope".into())
+    );
+    assert!(matches!(
+        error("    %p = alloc i64\n    unalloc %p\n    %x = load i32 %p\n    ret i32 %x"),
+        VmError::Fault { len: 4, .. }
+    ));
+    assert!(matches!(
+        error("    %x = load i32 0\n    ret i32 %x"),
+        VmError::Fault { addr: 0, .. }
+    ));
+}
+
+#[test]
+fn test_exit_status() {
+    let ret = "export fn $main() -> i32 {\n@start:\n    ret i32 7\n}";
+    assert_eq!(common::run_text(ret), Ok((7, String::new())));
+    let exit = "extern fn $exit(i32) -> void\nexport fn $main() -> i32 {\n\
+                @start:\n    call void $exit(i32 3)\n    ret i32 0\n}";
+    assert_eq!(common::run_text(exit), Ok((3, String::new())));
+}